@@ -332,6 +332,41 @@ impl MlsGroup {
         self.public_group().leaf(self.own_leaf_index())
     }
 
+    // ALG: expose application sender ratchet generations (author: torln)
+    /// Returns the generation of the application message sender ratchet of
+    /// the member at `leaf_index` in the current epoch, i.e. the number of
+    /// application messages sent (for the own leaf) or received (for other
+    /// leaves) so far. Returns `None` if `leaf_index` is outside the tree.
+    pub fn application_generation(&self, leaf_index: LeafNodeIndex) -> Option<u32> {
+        self.message_secrets().secret_tree().generation_opt(
+            leaf_index,
+            crate::tree::secret_tree::SecretType::ApplicationSecret,
+        )
+    }
+
+    // ALG: expose the retained keys of application sender ratchets (author: torln)
+    /// Returns the number of application message keys of past generations
+    /// the group retains for the member at `leaf_index` in the current
+    /// epoch, for messages received out of order. Returns `None` if
+    /// `leaf_index` is outside the tree.
+    pub fn application_retained_keys(&self, leaf_index: LeafNodeIndex) -> Option<usize> {
+        self.message_secrets().secret_tree().retained_key_count_opt(
+            leaf_index,
+            crate::tree::secret_tree::SecretType::ApplicationSecret,
+        )
+    }
+
+    // ALG: expose the epochs of the retained past message secrets (author: torln)
+    /// Returns the past epochs the group still holds message secrets for,
+    /// oldest first. Messages of these epochs and of the current epoch can
+    /// be decrypted. At most `max_past_epochs` epochs are retained.
+    pub fn retained_past_epochs(&self) -> Vec<GroupEpoch> {
+        self.message_secrets_store
+            .past_epochs()
+            .map(GroupEpoch::from)
+            .collect()
+    }
+
     /// Returns the group ID.
     pub fn group_id(&self) -> &GroupId {
         self.public_group.group_id()
@@ -460,6 +495,69 @@ impl MlsGroup {
         Ok(build())
     }
 
+    // ALG: report the storage entries missing to load a group (author: torln)
+    /// Returns the entries of the group with the given id that are missing
+    /// from storage, in the order [`MlsGroup::load`] reads them, followed by
+    /// the encryption key pair of the own leaf. A group with none of the
+    /// former missing loads, unless an entry can't be read.
+    ///
+    /// Entries are named after their labels in the memory storage, e.g.
+    /// `"EpochSecrets"` or `"EncryptionKeyPair"`. Entries that are stored but
+    /// can't be read count as present; [`MlsGroup::load`] returns their
+    /// error.
+    pub fn missing_storage_entries<Storage: crate::storage::StorageProvider>(
+        storage: &Storage,
+        group_id: &GroupId,
+    ) -> Vec<&'static str> {
+        fn missing<T, E>(entry: Result<Option<T>, E>) -> bool {
+            matches!(entry, Ok(None))
+        }
+
+        let tree: Result<Option<crate::treesync::TreeSync>, _> = storage.tree(group_id);
+        let group_context: Result<Option<GroupContext>, _> = storage.group_context(group_id);
+        let interim_transcript_hash: Result<Option<crate::group::InterimTranscriptHash>, _> =
+            storage.interim_transcript_hash(group_id);
+        let confirmation_tag: Result<Option<ConfirmationTag>, _> =
+            storage.confirmation_tag(group_id);
+        let group_epoch_secrets: Result<Option<GroupEpochSecrets>, _> =
+            storage.group_epoch_secrets(group_id);
+        let own_leaf_index: Result<Option<LeafNodeIndex>, _> = storage.own_leaf_index(group_id);
+        let message_secrets_store: Result<Option<MessageSecretsStore>, _> =
+            storage.message_secrets(group_id);
+        let resumption_psk_store: Result<Option<ResumptionPskStore>, _> =
+            storage.resumption_psk_store(group_id);
+        let mls_group_config: Result<Option<MlsGroupJoinConfig>, _> =
+            storage.mls_group_join_config(group_id);
+        let group_state: Result<Option<MlsGroupState>, _> = storage.group_state(group_id);
+
+        // Only checked if the own leaf can be found in the tree.
+        let own_leaf_key_pair = match (&tree, &own_leaf_index) {
+            (Ok(Some(tree)), Ok(Some(own_leaf_index))) => {
+                tree.leaf(*own_leaf_index).map(|leaf_node| {
+                    storage.encryption_key_pair::<EncryptionKeyPair, _>(leaf_node.encryption_key())
+                })
+            }
+            _ => None,
+        };
+
+        [
+            ("Tree", missing(tree)),
+            ("GroupContext", missing(group_context)),
+            ("InterimTranscriptHash", missing(interim_transcript_hash)),
+            ("ConfirmationTag", missing(confirmation_tag)),
+            ("EpochSecrets", missing(group_epoch_secrets)),
+            ("OwnLeafNodeIndex", missing(own_leaf_index)),
+            ("MessageSecrets", missing(message_secrets_store)),
+            ("ResumptionPsk", missing(resumption_psk_store)),
+            ("MlsGroupJoinConfig", missing(mls_group_config)),
+            ("GroupState", missing(group_state)),
+            ("EncryptionKeyPair", own_leaf_key_pair.is_some_and(missing)),
+        ]
+        .into_iter()
+        .filter_map(|(entry, missing)| missing.then_some(entry))
+        .collect()
+    }
+
     /// Remove the persisted state of this group from storage. Note that
     /// signature key material is not managed by OpenMLS and has to be removed
     /// from the storage provider separately (if desired).
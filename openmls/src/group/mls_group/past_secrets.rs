@@ -156,6 +156,15 @@ impl MessageSecretsStore {
         HashMap::new()
     }
 
+    // ALG: list the epochs of the stored past secrets (author: torln)
+    /// Returns the epochs of the past message secrets in the store, oldest
+    /// first.
+    pub(crate) fn past_epochs(&self) -> impl Iterator<Item = u64> + '_ {
+        self.past_epoch_trees
+            .iter()
+            .map(|epoch_tree| epoch_tree.epoch)
+    }
+
     /// Check if the provided epoch contains a leaf index.
     pub(crate) fn epoch_has_leaf(
         &self,
@@ -66,6 +66,12 @@ impl MessageSecrets {
         self.serialized_context.as_ref()
     }
 
+    // ALG: read-only access to the secret tree (author: torln)
+    /// Get a reference to the message secrets's secret tree.
+    pub(crate) fn secret_tree(&self) -> &SecretTree {
+        &self.secret_tree
+    }
+
     /// Get a mutable reference to the message secrets's secret tree.
     pub(crate) fn secret_tree_mut(&mut self) -> &mut SecretTree {
         &mut self.secret_tree
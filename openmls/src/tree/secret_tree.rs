@@ -194,6 +194,32 @@ impl SecretTree {
         }
     }
 
+    // ALG: read sender ratchet generations without panicking (author: torln)
+    /// Get the current generation for a specific SenderRatchet, or `None` if
+    /// `index` is out of bounds.
+    pub(crate) fn generation_opt(
+        &self,
+        index: LeafNodeIndex,
+        secret_type: SecretType,
+    ) -> Option<u32> {
+        self.ratchet_opt(index, secret_type)
+            .ok()
+            .map(|sender_ratchet| sender_ratchet.map_or(0, |ratchet| ratchet.generation()))
+    }
+
+    // ALG: count the retained keys of a sender ratchet (author: torln)
+    /// Get the number of keys of past generations a specific SenderRatchet
+    /// retains, or `None` if `index` is out of bounds.
+    pub(crate) fn retained_key_count_opt(
+        &self,
+        index: LeafNodeIndex,
+        secret_type: SecretType,
+    ) -> Option<usize> {
+        self.ratchet_opt(index, secret_type)
+            .ok()
+            .map(|sender_ratchet| sender_ratchet.map_or(0, SenderRatchet::retained_key_count))
+    }
+
     /// Initializes a specific SenderRatchet pair for a given index by
     /// calculating and deleting the appropriate values in the SecretTree
     fn initialize_sender_ratchets(
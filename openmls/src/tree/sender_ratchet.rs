@@ -79,13 +79,27 @@ pub(crate) enum SenderRatchet {
 }
 
 impl SenderRatchet {
-    #[cfg(test)]
+    // ALG: make the ratchet generation available outside of tests (author: torln)
     pub(crate) fn generation(&self) -> Generation {
         match self {
             SenderRatchet::EncryptionRatchet(enc_ratchet) => enc_ratchet.generation(),
             SenderRatchet::DecryptionRatchet(dec_ratchet) => dec_ratchet.generation(),
         }
     }
+
+    // ALG: count the retained keys of skipped generations (author: torln)
+    /// The number of keys of past generations this ratchet still holds for
+    /// out-of-order messages. Always 0 for an encryption ratchet.
+    pub(crate) fn retained_key_count(&self) -> usize {
+        match self {
+            SenderRatchet::EncryptionRatchet(_) => 0,
+            SenderRatchet::DecryptionRatchet(dec_ratchet) => dec_ratchet
+                .past_secrets
+                .iter()
+                .filter(|secret| secret.is_some())
+                .count(),
+        }
+    }
 }
 
 /// The core of both types of [`SenderRatchet`]. It contains the current head of
@@ -0,0 +1,32 @@
+//! Creating application messages into the reused buffer of
+//! `createMessageInto`, compared with a new buffer per message as
+//! `createMessage` returns:
+//!
+//! ```sh
+//! cargo bench --bench messages
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use torln_openmls_wasm::{Group, Identity, Provider};
+
+fn messages_benchmark(c: &mut Criterion) {
+    let provider = Provider::create(None).unwrap();
+    let alice = Identity::create(&provider, "alice", None).unwrap();
+    let mut group = Group::create_new(&provider, &alice, "chess club");
+
+    c.bench_function("create message", |b| {
+        b.iter(|| group.create_message(&provider, &alice, &[0; 1024]).unwrap())
+    });
+
+    c.bench_function("create message into buffer", |b| {
+        b.iter(|| {
+            group
+                .create_message_buffered(&provider, &alice, &[0; 1024])
+                .unwrap()
+                .len()
+        })
+    });
+}
+
+criterion_group!(benches, messages_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+//! Reading the content type of a message for routing, compared with
+//! deserializing the whole message:
+//!
+//! ```sh
+//! cargo bench --bench routing
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use openmls::framing::MlsMessageIn;
+use tls_codec::Deserialize;
+use torln_openmls_wasm::{message_content_type, Group, Identity, Provider};
+
+fn routing_benchmark(c: &mut Criterion) {
+    let provider = Provider::create(None).unwrap();
+    let alice = Identity::create(&provider, "alice", None).unwrap();
+    let mut group = Group::create_new(&provider, &alice, "chess club");
+    let message = group.create_message(&provider, &alice, &[0; 1024]).unwrap();
+
+    c.bench_function("message content type", |b| {
+        b.iter(|| message_content_type(&message).unwrap())
+    });
+
+    c.bench_function("deserialize message", |b| {
+        b.iter(|| MlsMessageIn::tls_deserialize_exact(&message).unwrap())
+    });
+}
+
+criterion_group!(benches, routing_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+//! Storage-heavy operations, for comparing the default storage with the
+//! `unsync-storage` feature:
+//!
+//! ```sh
+//! cargo bench --bench storage
+//! cargo bench --bench storage --features unsync-storage
+//! ```
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use torln_openmls_wasm::{Group, Identity, Provider};
+
+/// Number of key packages in the storage of the populated provider.
+const KEY_PACKAGES: usize = 100;
+
+fn populated_provider() -> (Provider, Identity) {
+    let provider = Provider::create(None).unwrap();
+    let identity = Identity::create(&provider, "alice", None).unwrap();
+    for _ in 0..KEY_PACKAGES {
+        identity.get_key_package(&provider);
+    }
+
+    (provider, identity)
+}
+
+fn storage_benchmark(c: &mut Criterion) {
+    let (provider, identity) = populated_provider();
+
+    c.bench_function("create key package", |b| {
+        b.iter(|| identity.get_key_package(&provider))
+    });
+
+    c.bench_function("create group", |b| {
+        let mut i = 0;
+        b.iter(|| {
+            i += 1;
+            Group::create_new(&provider, &identity, &format!("group {i}"))
+        })
+    });
+
+    let exported = provider.export_storage().unwrap();
+    c.bench_function("export storage", |b| {
+        b.iter(|| provider.export_storage().unwrap())
+    });
+
+    c.bench_function("import storage", |b| {
+        b.iter_with_setup(
+            || Provider::create(None).unwrap(),
+            |provider| provider.import_storage(&exported).unwrap(),
+        )
+    });
+}
+
+criterion_group!(benches, storage_benchmark);
+criterion_main!(benches);
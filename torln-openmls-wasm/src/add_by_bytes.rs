@@ -0,0 +1,150 @@
+//! Adding members by the serialized key packages the delivery service hands
+//! out, without going through a `KeyPackage` object in JS.
+//!
+//! The key package is validated before it is proposed: its signatures must
+//! check out and it must be for the ciphersuite of the group.
+
+use openmls::{
+    framing::MlsMessageOut,
+    group::ProposeAddMemberError,
+    key_packages::{errors::KeyPackageVerifyError, KeyPackage, KeyPackageIn},
+    versions::ProtocolVersion,
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{types::Ciphersuite, OpenMlsProvider};
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    capacity::GroupFull, enrollment::EnrollmentError,
+    key_package_lifetime::KeyPackageLifetimeTooLong, message_size::MessageTooLarge,
+    mls_message_to_uint8array, signer::MissingSignerError, AddMessages, Group, Identity, Provider,
+};
+
+/// Errors when adding a member by the bytes of their key package.
+#[derive(Debug)]
+pub(crate) enum AddByBytesError {
+    TooLarge(MessageTooLarge),
+    Malformed(tls_codec::Error),
+    InvalidKeyPackage(KeyPackageVerifyError),
+    /// The key package is for another ciphersuite than the group.
+    CiphersuiteMismatch {
+        group: Ciphersuite,
+        key_package: Ciphersuite,
+    },
+    LifetimeTooLong(KeyPackageLifetimeTooLong),
+    GroupFull(GroupFull),
+    Signer(MissingSignerError),
+    Propose(ProposeAddMemberError<TorlnStorageError>),
+    Commit(EnrollmentError),
+    NoWelcome,
+}
+
+impl std::fmt::Display for AddByBytesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed key package: {e}"),
+            Self::InvalidKeyPackage(e) => write!(f, "invalid key package: {e}"),
+            Self::CiphersuiteMismatch { group, key_package } => write!(
+                f,
+                "key package is for ciphersuite {key_package:?}, but the group uses {group:?}"
+            ),
+            Self::LifetimeTooLong(e) => write!(f, "{e}"),
+            Self::GroupFull(e) => write!(f, "{e}"),
+            Self::Signer(e) => write!(f, "{e}"),
+            Self::Propose(e) => write!(f, "failed to propose add: {e}"),
+            Self::Commit(e) => write!(f, "failed to commit add: {e}"),
+            Self::NoWelcome => write!(f, "no welcome"),
+        }
+    }
+}
+
+impl std::error::Error for AddByBytesError {}
+
+/// Validate the parsed `key_package` for adding its owner to a group with
+/// `ciphersuite`: its signatures, its ciphersuite and its lifetime.
+pub(crate) fn validate_key_package(
+    provider: &Provider,
+    ciphersuite: Ciphersuite,
+    key_package: KeyPackageIn,
+) -> Result<KeyPackage, AddByBytesError> {
+    let key_package = key_package
+        .validate(provider.0.crypto(), ProtocolVersion::Mls10)
+        .map_err(AddByBytesError::InvalidKeyPackage)?;
+    if key_package.ciphersuite() != ciphersuite {
+        return Err(AddByBytesError::CiphersuiteMismatch {
+            group: ciphersuite,
+            key_package: key_package.ciphersuite(),
+        });
+    }
+    provider
+        .check_key_package_lifetime(&key_package)
+        .map_err(AddByBytesError::LifetimeTooLong)?;
+
+    Ok(key_package)
+}
+
+impl Group {
+    /// Propose and commit adding the owner of the key package in
+    /// `key_package`, see `addMemberByBytes`. Returns the proposal, the
+    /// commit and the welcome.
+    pub(crate) fn add_member_by_bytes_native(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        mut key_package: &[u8],
+    ) -> Result<(MlsMessageOut, MlsMessageOut, MlsMessageOut), AddByBytesError> {
+        provider
+            .check_message_size(key_package)
+            .map_err(AddByBytesError::TooLarge)?;
+        let key_package =
+            KeyPackageIn::tls_deserialize(&mut key_package).map_err(AddByBytesError::Malformed)?;
+        let key_package =
+            validate_key_package(provider, self.mls_group.ciphersuite(), key_package)?;
+        self.ensure_capacity(1)
+            .map_err(AddByBytesError::GroupFull)?;
+        provider
+            .check_committed_key_packages(self.mls_group.pending_proposals())
+            .map_err(AddByBytesError::LifetimeTooLong)?;
+
+        let signer = self
+            .signer(provider, sender)
+            .map_err(AddByBytesError::Signer)?;
+        let (proposal, _proposal_ref) = self
+            .mls_group
+            .propose_add_member(provider.as_ref(), &signer, &key_package)
+            .map_err(AddByBytesError::Propose)?;
+        let (commit, welcome) = self
+            .commit_adds(provider, sender)
+            .map_err(AddByBytesError::Commit)?;
+
+        Ok((proposal, commit, welcome.ok_or(AddByBytesError::NoWelcome)?))
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `proposeAndCommitAdd`, but takes the serialized key package as
+    /// received from the delivery service.
+    ///
+    /// Fails without changing the group if the key package can't be parsed,
+    /// isn't validly signed, is for another ciphersuite than the group or
+    /// is valid for longer than `setKeyPackageMaxLifetime` allows.
+    #[wasm_bindgen(js_name = addMemberByBytes)]
+    pub fn add_member_by_bytes(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        key_package_bytes: &[u8],
+    ) -> Result<AddMessages, JsError> {
+        let (proposal, commit, welcome) =
+            self.add_member_by_bytes_native(provider, sender, key_package_bytes)?;
+
+        Ok(AddMessages {
+            proposal: mls_message_to_uint8array(&proposal),
+            commit: mls_message_to_uint8array(&commit),
+            welcome: mls_message_to_uint8array(&welcome),
+        })
+    }
+}
@@ -0,0 +1,137 @@
+//! Audit records of commits, for append-only membership logs.
+
+use openmls::{
+    credentials::Credential,
+    framing::Sender,
+    group::{MlsGroup, StagedCommit},
+    messages::proposals::Proposal,
+};
+use tls_codec::{Deserialize, Serialize, TlsDeserialize, TlsSerialize, TlsSize};
+use wasm_bindgen::prelude::*;
+
+/// One change made by a commit.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq, TlsSerialize, TlsDeserialize, TlsSize)]
+pub struct AuditAction {
+    proposal_type: u16,
+    subject: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl AuditAction {
+    /// The MLS proposal type of the change, e.g. 1 for an add or 3 for a
+    /// remove, or an application-defined type.
+    #[wasm_bindgen(getter, js_name = proposalType)]
+    pub fn proposal_type(&self) -> u16 {
+        self.proposal_type
+    }
+    /// The serialized credential of the affected member, or an empty array
+    /// if the change doesn't affect a single member.
+    #[wasm_bindgen(getter)]
+    pub fn subject(&self) -> Vec<u8> {
+        self.subject.clone()
+    }
+}
+
+/// Who changed what in a commit, see `ProcessedMessage.auditRecord`.
+///
+/// The record serializes to a stable binary format with `toBytes`, for
+/// persisting it as is.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq, TlsSerialize, TlsDeserialize, TlsSize)]
+pub struct AuditRecord {
+    actor: Vec<u8>,
+    actions: Vec<AuditAction>,
+    from_epoch: u64,
+    to_epoch: u64,
+    timestamp: u64,
+}
+
+#[wasm_bindgen]
+impl AuditRecord {
+    /// The serialized credential of the committer.
+    #[wasm_bindgen(getter)]
+    pub fn actor(&self) -> Vec<u8> {
+        self.actor.clone()
+    }
+    /// The proposals the commit applied, in commit order.
+    #[wasm_bindgen(getter)]
+    pub fn actions(&self) -> Vec<AuditAction> {
+        self.actions.clone()
+    }
+    #[wasm_bindgen(getter, js_name = fromEpoch)]
+    pub fn from_epoch(&self) -> u64 {
+        self.from_epoch
+    }
+    #[wasm_bindgen(getter, js_name = toEpoch)]
+    pub fn to_epoch(&self) -> u64 {
+        self.to_epoch
+    }
+    /// A timestamp attached by the application, 0 if none was set.
+    #[wasm_bindgen(getter)]
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+    #[wasm_bindgen(setter)]
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.timestamp = timestamp;
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsError> {
+        Ok(self.tls_serialize_detached()?)
+    }
+
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<AuditRecord, JsError> {
+        Ok(AuditRecord::tls_deserialize(&mut bytes)?)
+    }
+}
+
+fn credential_bytes(credential: Option<&Credential>) -> Result<Vec<u8>, tls_codec::Error> {
+    credential
+        .map(|credential| credential.tls_serialize_detached())
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// The audit record of `staged_commit` by `actor`, before it is merged into
+/// `group`, so that removed members can still be looked up.
+pub(crate) fn audit_record(
+    group: &MlsGroup,
+    actor: &Credential,
+    staged_commit: &StagedCommit,
+) -> Result<AuditRecord, tls_codec::Error> {
+    let sender_credential = |sender: &Sender| match sender {
+        Sender::Member(index) => group.member(*index),
+        _ => None,
+    };
+
+    let actions = staged_commit
+        .queued_proposals()
+        .map(|queued_proposal| {
+            let proposal = queued_proposal.proposal();
+            let subject = match proposal {
+                Proposal::Add(add) => Some(add.key_package().leaf_node().credential()),
+                Proposal::Update(update) => Some(update.leaf_node().credential()),
+                Proposal::Remove(remove) => group.member(remove.removed()),
+                Proposal::SelfRemove => sender_credential(queued_proposal.sender()),
+                _ => None,
+            };
+
+            Ok(AuditAction {
+                proposal_type: proposal.proposal_type().into(),
+                subject: credential_bytes(subject)?,
+            })
+        })
+        .collect::<Result<_, tls_codec::Error>>()?;
+
+    let from_epoch = group.epoch().as_u64();
+    Ok(AuditRecord {
+        actor: actor.tls_serialize_detached()?,
+        actions,
+        from_epoch,
+        to_epoch: from_epoch + 1,
+        timestamp: 0,
+    })
+}
@@ -0,0 +1,193 @@
+//! Creating a group with many initial members whose key packages arrive one
+//! at a time.
+//!
+//! `Group.createWith` takes every `KeyPackage` object at once. For
+//! thousands of members, the app would rather hand over the serialized key
+//! packages as it fetches them and drop each right after. The accumulator
+//! validates each key package as it arrives and keeps only the validated
+//! ones; the group is created with all of them in a single commit at the
+//! end, as with `createWith`.
+
+use openmls::{
+    group::{MergePendingCommitError, ProposeAddMemberError},
+    key_packages::{KeyPackage, KeyPackageIn},
+};
+use openmls_torln_storage::TorlnStorageError;
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    add_by_bytes::{self, AddByBytesError},
+    capacity::GroupFull,
+    enrollment::EnrollmentError,
+    initial_members::GroupWithMembers,
+    mls_message_to_u8vec,
+    transaction::{self, Aborted},
+    Group, Identity, Provider, CIPHERSUITE,
+};
+
+/// Errors when collecting or committing a batch of members.
+#[derive(Debug)]
+pub(crate) enum BatchAddError {
+    Empty,
+    /// The key package added at `index` can't be added.
+    KeyPackage {
+        index: usize,
+        error: AddByBytesError,
+    },
+    GroupFull(GroupFull),
+    Propose(ProposeAddMemberError<TorlnStorageError>),
+    Commit(EnrollmentError),
+    NoWelcome,
+    Merge(MergePendingCommitError<TorlnStorageError>),
+}
+
+impl std::fmt::Display for BatchAddError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "no key packages to add"),
+            Self::KeyPackage { index, error } => write!(f, "key package {index}: {error}"),
+            Self::GroupFull(e) => e.fmt(f),
+            Self::Propose(e) => write!(f, "failed to propose add: {e}"),
+            Self::Commit(e) => write!(f, "failed to add members: {e}"),
+            Self::NoWelcome => write!(f, "no welcome"),
+            Self::Merge(e) => write!(f, "failed to merge commit: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BatchAddError {}
+
+/// The initial members of a new group, see `Group.startBatchAdd`.
+#[wasm_bindgen]
+pub struct BatchAdd {
+    group_id: String,
+    key_packages: Vec<KeyPackage>,
+}
+
+impl BatchAdd {
+    /// Parse, validate and queue a serialized key package, see
+    /// `addKeyPackage`.
+    pub(crate) fn push_key_package(
+        &mut self,
+        provider: &Provider,
+        mut bytes: &[u8],
+    ) -> Result<(), BatchAddError> {
+        let index = self.key_packages.len();
+        let key_package = provider
+            .check_message_size(bytes)
+            .map_err(AddByBytesError::TooLarge)
+            .and_then(|()| {
+                KeyPackageIn::tls_deserialize(&mut bytes).map_err(AddByBytesError::Malformed)
+            })
+            .and_then(|key_package| {
+                add_by_bytes::validate_key_package(provider, CIPHERSUITE, key_package)
+            })
+            .map_err(|error| BatchAddError::KeyPackage { index, error })?;
+        self.key_packages.push(key_package);
+
+        Ok(())
+    }
+
+    /// Create the group and add the queued members in one commit, see
+    /// `commit`. Nothing is stored if this fails.
+    pub(crate) fn commit_native(
+        self,
+        provider: &Provider,
+        founder: &Identity,
+    ) -> Result<GroupWithMembers, Aborted<BatchAddError>> {
+        let BatchAdd {
+            group_id,
+            key_packages,
+        } = self;
+        if key_packages.is_empty() {
+            return Err(Aborted {
+                error: BatchAddError::Empty,
+                rolled_back: false,
+            });
+        }
+
+        transaction::with_rollback(provider, || {
+            let mut group = Group::create_new(provider, founder, &group_id);
+            group
+                .ensure_capacity(key_packages.len())
+                .map_err(BatchAddError::GroupFull)?;
+            for key_package in &key_packages {
+                group
+                    .mls_group
+                    .propose_add_member(provider.as_ref(), &founder.keypair, key_package)
+                    .map_err(BatchAddError::Propose)?;
+            }
+            let (_commit, welcome) = group
+                .commit_adds(provider, founder)
+                .map_err(BatchAddError::Commit)?;
+            let welcome = welcome.ok_or(BatchAddError::NoWelcome)?;
+            group
+                .mls_group
+                .merge_pending_commit(&provider.0)
+                .map_err(BatchAddError::Merge)?;
+
+            Ok(GroupWithMembers {
+                group,
+                welcome: mls_message_to_u8vec(&welcome),
+            })
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl BatchAdd {
+    /// Queue the serialized key package of a member to add, as received
+    /// from the delivery service.
+    ///
+    /// Fails without queueing it if it is larger than `setMaxMessageBytes`
+    /// allows, can't be parsed, isn't validly signed, is for another
+    /// ciphersuite than the group or is valid for longer than
+    /// `setKeyPackageMaxLifetime` allows; the error names its position.
+    #[wasm_bindgen(js_name = addKeyPackage)]
+    pub fn add_key_package(
+        &mut self,
+        provider: &Provider,
+        key_package_bytes: &[u8],
+    ) -> Result<(), JsError> {
+        Ok(self.push_key_package(provider, key_package_bytes)?)
+    }
+
+    /// The number of key packages queued so far.
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> u32 {
+        self.key_packages.len() as u32
+    }
+
+    /// Create the group and add all queued members in a single commit,
+    /// which is merged right away, and return the group with the welcome
+    /// for them. `sender` is the founder. Consumes this object.
+    ///
+    /// If this fails, e.g. because two key packages of the same member were
+    /// queued, no group is created and the storage is left as it was.
+    pub fn commit(
+        self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<GroupWithMembers, JsError> {
+        Ok(self.commit_native(provider, sender)?)
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Start collecting the initial members of a new group, one serialized
+    /// key package at a time with `addKeyPackage`, to create the group with
+    /// all of them with `commit`.
+    ///
+    /// Like `createWith`, but without holding every `KeyPackage` object in
+    /// JS. Nothing is stored until `commit`, whose sender founds the
+    /// group.
+    #[wasm_bindgen(js_name = startBatchAdd)]
+    pub fn start_batch_add(group_id: &str) -> BatchAdd {
+        BatchAdd {
+            group_id: group_id.to_owned(),
+            key_packages: Vec::new(),
+        }
+    }
+}
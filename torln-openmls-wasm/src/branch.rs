@@ -0,0 +1,239 @@
+//! Branching a subgroup off an existing group.
+//!
+//! A subgroup is a new, independent group whose key schedule is bound to the
+//! parent group by injecting the parent's resumption PSK into its first
+//! epoch. Only clients that are members of the parent in the branching epoch
+//! know that secret, so the subgroup inherits the trust of the parent.
+//!
+//! MLS defines a dedicated "branch" resumption PSK for this, but openmls only
+//! resolves resumption PSKs against the history of the group being joined,
+//! which a new group doesn't have. We therefore inject the parent's
+//! resumption secret as an external PSK whose id names the parent group and
+//! epoch. Joiners store the same PSK from their copy of the parent group
+//! before processing the welcome. The PSK is deleted again once the subgroup
+//! is created or joined, so the parent's secret doesn't outlive its epoch in
+//! storage.
+
+use openmls::{
+    group::{GroupId, MlsGroup},
+    key_packages::KeyPackage as OpenMlsKeyPackage,
+    prelude::{PreSharedKeyProposal, Proposal},
+    schedule::{ExternalPsk, PreSharedKeyId, Psk},
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{storage::StorageProvider, OpenMlsProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    extensions, mls_message_to_u8vec, Group, Identity, KeyPackage, NoWelcomeError, Provider,
+    RatchetTree, CIPHERSUITE, WIRE_FORMAT_POLICY,
+};
+
+/// Prefix of the ids of the PSKs binding a subgroup to its parent.
+const BRANCH_PSK_LABEL: &[u8] = b"torln branch";
+
+/// Errors when branching a subgroup.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum BranchError {
+    UnknownMember(u32),
+    MissingKeyPackage(u32),
+}
+
+impl std::fmt::Display for BranchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownMember(leaf_index) => {
+                write!(f, "no member at leaf index {leaf_index}")
+            }
+            Self::MissingKeyPackage(leaf_index) => {
+                write!(
+                    f,
+                    "no key package for the member at leaf index {leaf_index}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BranchError {}
+
+/// The id of the PSK binding a subgroup to `parent` in its current epoch:
+/// the label, the parent group id and the big-endian epoch.
+pub(crate) fn branch_psk_id(parent: &MlsGroup) -> Vec<u8> {
+    let mut psk_id = BRANCH_PSK_LABEL.to_vec();
+    psk_id.extend_from_slice(parent.group_id().as_slice());
+    psk_id.extend_from_slice(&parent.epoch().as_u64().to_be_bytes());
+    psk_id
+}
+
+/// Store the parent's resumption secret for the current epoch under the
+/// branch PSK id and return a fresh `PreSharedKeyId` for it.
+fn store_branch_psk(provider: &Provider, parent: &MlsGroup) -> Result<PreSharedKeyId, JsError> {
+    let psk = Psk::External(ExternalPsk::new(branch_psk_id(parent)));
+    let psk_id = PreSharedKeyId::new(CIPHERSUITE, provider.0.rand(), psk)?;
+    psk_id.store(&provider.0, parent.resumption_psk_secret().as_slice())?;
+
+    Ok(psk_id)
+}
+
+/// Delete the PSK stored by `store_branch_psk`.
+fn delete_branch_psk(
+    provider: &Provider,
+    psk_id: &PreSharedKeyId,
+) -> Result<(), TorlnStorageError> {
+    provider.0.storage().delete_psk(psk_id.psk())
+}
+
+/// Pick the key package of each selected member of `parent`.
+///
+/// A key package is matched to a member by its signature key. The sender's
+/// own leaf may be listed but needs no key package, since the sender creates
+/// the subgroup.
+fn select_key_packages(
+    parent: &MlsGroup,
+    member_leaf_indices: &[u32],
+    key_packages: Vec<KeyPackage>,
+) -> Result<Vec<OpenMlsKeyPackage>, BranchError> {
+    let own_index = parent.own_leaf_index().u32();
+    let mut key_packages = key_packages.into_iter().map(|kp| kp.0).collect::<Vec<_>>();
+    let mut selected = Vec::new();
+
+    for &leaf_index in member_leaf_indices {
+        if leaf_index == own_index {
+            continue;
+        }
+
+        let member = parent
+            .members()
+            .find(|member| member.index.u32() == leaf_index)
+            .ok_or(BranchError::UnknownMember(leaf_index))?;
+
+        let position = key_packages
+            .iter()
+            .position(|kp| kp.leaf_node().signature_key().as_slice() == member.signature_key)
+            .ok_or(BranchError::MissingKeyPackage(leaf_index))?;
+
+        selected.push(key_packages.swap_remove(position));
+    }
+
+    Ok(selected)
+}
+
+/// A subgroup branched off a parent group, see `Group.branchSubgroup`.
+#[wasm_bindgen]
+pub struct Subgroup {
+    group: Group,
+    welcome: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Subgroup {
+    /// The welcome for the members added to the subgroup. They join with
+    /// `Group.joinBranch`.
+    #[wasm_bindgen(getter)]
+    pub fn welcome(&self) -> Vec<u8> {
+        self.welcome.clone()
+    }
+
+    #[wasm_bindgen(js_name = exportRatchetTree)]
+    pub fn export_ratchet_tree(&self) -> RatchetTree {
+        self.group.export_ratchet_tree()
+    }
+
+    /// The subgroup itself. Consumes this object.
+    #[wasm_bindgen(js_name = intoGroup)]
+    pub fn into_group(self) -> Group {
+        self.group
+    }
+}
+
+/// Create the subgroup `new_group_id` of `sender` and the members of
+/// `key_packages`, with its first epoch bound to the branch PSK `psk_id`.
+fn create_subgroup(
+    provider: &Provider,
+    sender: &Identity,
+    new_group_id: &str,
+    key_packages: Vec<OpenMlsKeyPackage>,
+    psk_id: &PreSharedKeyId,
+) -> Result<Subgroup, JsError> {
+    let mut subgroup = MlsGroup::builder()
+        .ciphersuite(CIPHERSUITE)
+        .with_capabilities(extensions::capabilities())
+        .with_wire_format_policy(WIRE_FORMAT_POLICY)
+        .with_group_id(GroupId::from_slice(new_group_id.as_bytes()))
+        .build(&provider.0, &sender.keypair, sender.credential_with_key())?;
+
+    let bundle = subgroup
+        .commit_builder()
+        .propose_adds(key_packages)
+        .add_proposal(Proposal::PreSharedKey(Box::new(PreSharedKeyProposal::new(
+            psk_id.clone(),
+        ))))
+        .load_psks(provider.0.storage())?
+        .build(
+            provider.0.rand(),
+            provider.0.crypto(),
+            &sender.keypair,
+            |_| true,
+        )?
+        .stage_commit(&provider.0)?;
+
+    subgroup.merge_pending_commit(&provider.0)?;
+
+    let welcome = bundle.into_welcome_msg().ok_or(NoWelcomeError)?;
+
+    Ok(Subgroup {
+        group: subgroup.into(),
+        welcome: mls_message_to_u8vec(&welcome),
+    })
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Create a new group `new_group_id` with a subset of the members of this
+    /// group, e.g. a breakout room.
+    ///
+    /// `member_leaf_indices` selects the members of this group to add, and
+    /// `key_packages` must contain a fresh key package for each of them. The
+    /// sender is always part of the subgroup. The first epoch of the subgroup
+    /// is bound to the current epoch of this group, and the subgroup advances
+    /// independently afterwards.
+    ///
+    /// The subgroup's first commit is merged right away.
+    #[wasm_bindgen(js_name = branchSubgroup)]
+    pub fn branch_subgroup(
+        &self,
+        provider: &Provider,
+        sender: &Identity,
+        new_group_id: &str,
+        member_leaf_indices: Vec<u32>,
+        key_packages: Vec<KeyPackage>,
+    ) -> Result<Subgroup, JsError> {
+        let key_packages =
+            select_key_packages(&self.mls_group, &member_leaf_indices, key_packages)?;
+        let psk_id = store_branch_psk(provider, &self.mls_group)?;
+
+        let subgroup = create_subgroup(provider, sender, new_group_id, key_packages, &psk_id);
+        delete_branch_psk(provider, &psk_id)?;
+
+        subgroup
+    }
+
+    /// Join a subgroup branched off `parent` with `branchSubgroup`.
+    ///
+    /// `parent` must be in the epoch the subgroup was branched in.
+    #[wasm_bindgen(js_name = joinBranch)]
+    pub fn join_branch(
+        provider: &Provider,
+        parent: &Group,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<Group, JsError> {
+        let psk_id = store_branch_psk(provider, &parent.mls_group)?;
+
+        let group = Group::join(provider, welcome, ratchet_tree);
+        delete_branch_psk(provider, &psk_id)?;
+
+        group
+    }
+}
@@ -0,0 +1,213 @@
+//! Capping the number of members of a group.
+//!
+//! The cap is set when the group is created and stored in an
+//! application-defined group context extension, so that all members see the
+//! same limit.
+
+use openmls::{
+    extensions::Extensions,
+    group::{GroupContext, QueuedProposal, StagedCommit},
+    messages::proposals::Proposal,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    enrollment::{self, EnrollmentPsk},
+    extensions, utils, Group, Identity, Provider,
+};
+
+/// Adding members would exceed the maximum size of the group.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct GroupFull {
+    pub(crate) max_members: u32,
+    pub(crate) members: u32,
+    pub(crate) adding: u32,
+}
+
+impl std::fmt::Display for GroupFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "group is full: adding {} to {} members exceeds the maximum of {}",
+            self.adding, self.members, self.max_members
+        )
+    }
+}
+
+impl std::error::Error for GroupFull {}
+
+/// Options for `Group.createNewWithConfig`.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct GroupConfig {
+    max_members: Option<u32>,
+    pub(crate) enrollment_psk: Option<EnrollmentPsk>,
+    record_founder: bool,
+}
+
+#[wasm_bindgen]
+impl GroupConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GroupConfig {
+        GroupConfig::default()
+    }
+
+    /// The maximum number of members, or `undefined` for no limit.
+    #[wasm_bindgen(getter, js_name = maxMembers)]
+    pub fn max_members(&self) -> Option<u32> {
+        self.max_members
+    }
+    #[wasm_bindgen(setter, js_name = maxMembers)]
+    pub fn set_max_members(&mut self, max_members: Option<u32>) {
+        self.max_members = max_members;
+    }
+
+    /// Whether to record the founder and the creation time in the group
+    /// context, see `Group.founderInfo`. Defaults to `false`.
+    #[wasm_bindgen(getter, js_name = recordFounder)]
+    pub fn record_founder(&self) -> bool {
+        self.record_founder
+    }
+    #[wasm_bindgen(setter, js_name = recordFounder)]
+    pub fn set_record_founder(&mut self, record_founder: bool) {
+        self.record_founder = record_founder;
+    }
+}
+
+impl Group {
+    /// Check that `adding` more members fit into the group, counting the
+    /// adds that are already pending. Every commit includes the pending
+    /// proposals, so this applies to commits without new adds as well, with
+    /// `adding` 0.
+    pub(crate) fn ensure_capacity(&self, adding: usize) -> Result<(), GroupFull> {
+        self.ensure_capacity_for(self.mls_group.pending_proposals(), adding)
+    }
+
+    /// Like `ensure_capacity`, for a commit of only the `committed` pending
+    /// proposals.
+    pub(crate) fn ensure_capacity_for<'a>(
+        &self,
+        committed: impl IntoIterator<Item = &'a QueuedProposal>,
+        adding: usize,
+    ) -> Result<(), GroupFull> {
+        let Some(max_members) = max_members_of(self.mls_group.extensions()) else {
+            return Ok(());
+        };
+
+        self.check_capacity(max_members, committed, adding)
+    }
+
+    /// Check that a received commit leaves the group within the maximum
+    /// size of the epoch it starts.
+    pub(crate) fn ensure_capacity_after(
+        &self,
+        staged_commit: &StagedCommit,
+    ) -> Result<(), GroupFull> {
+        let Some(max_members) = max_members_of(staged_commit.group_context().extensions()) else {
+            return Ok(());
+        };
+
+        self.check_capacity(max_members, staged_commit.queued_proposals(), 0)
+    }
+
+    /// Check that `adding` more members fit into `max_members` after the
+    /// `committed` proposals, counting the members they add and remove.
+    fn check_capacity<'a>(
+        &self,
+        max_members: u32,
+        committed: impl IntoIterator<Item = &'a QueuedProposal>,
+        adding: usize,
+    ) -> Result<(), GroupFull> {
+        let (adds, removes) =
+            committed
+                .into_iter()
+                .fold((0, 0), |(adds, removes), queued_proposal| {
+                    match queued_proposal.proposal() {
+                        // An external commit adds its committer.
+                        Proposal::Add(_) | Proposal::ExternalInit(_) => (adds + 1, removes),
+                        Proposal::Remove(_) | Proposal::SelfRemove => (adds, removes + 1),
+                        _ => (adds, removes),
+                    }
+                });
+        let members = (self.mls_group.members().count() + adds).saturating_sub(removes) as u32;
+        let adding = adding as u32;
+
+        if members + adding > max_members {
+            return Err(GroupFull {
+                max_members,
+                members,
+                adding,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `createNew`, with the options in `config`.
+    ///
+    /// With `maxMembers` set, adding members fails with a "group is full"
+    /// error once the group has reached that size, as does any commit of
+    /// pending Add proposals that would exceed it; Removes in the same
+    /// commit make room. A received commit beyond the limit fails to process
+    /// and isn't merged. With an enrollment
+    /// PSK set, members can only join with `joinWithPsk` and the same
+    /// secret. Each option that is set adds its extension type to the
+    /// capabilities every member must support.
+    #[wasm_bindgen(js_name = createNewWithConfig)]
+    pub fn create_new_with_config(
+        provider: &Provider,
+        founder: &Identity,
+        group_id: &str,
+        config: &GroupConfig,
+    ) -> Result<Group, JsError> {
+        let mut group_context_extensions = if config.record_founder {
+            extensions::founding_extensions(&founder.credential, utils::unix_time_secs())?
+        } else {
+            Extensions::empty()
+        };
+        if let Some(max_members) = config.max_members {
+            group_context_extensions = extensions::with_app_extension(
+                &group_context_extensions,
+                extensions::MAX_MEMBERS_EXTENSION_TYPE,
+                max_members.to_be_bytes().to_vec(),
+            )?;
+        }
+        if let Some(enrollment_psk) = &config.enrollment_psk {
+            enrollment::store_enrollment_psk(
+                provider,
+                &enrollment_psk.psk_id,
+                &enrollment_psk.secret,
+            )?;
+            group_context_extensions = extensions::with_app_extension(
+                &group_context_extensions,
+                extensions::ENROLLMENT_PSK_EXTENSION_TYPE,
+                enrollment_psk.psk_id.clone(),
+            )?;
+        }
+
+        Ok(Group::build_new(
+            provider,
+            founder,
+            group_id.as_bytes(),
+            group_context_extensions,
+        )?)
+    }
+
+    /// The maximum number of members of this group, or `undefined` if there
+    /// is no limit.
+    #[wasm_bindgen(js_name = maxMembers)]
+    pub fn max_members(&self) -> Option<u32> {
+        max_members_of(self.mls_group.extensions())
+    }
+}
+
+/// The maximum number of members in the group context extensions
+/// `extensions`, if any.
+fn max_members_of(extensions: &Extensions<GroupContext>) -> Option<u32> {
+    let data = extensions::app_extension(extensions, extensions::MAX_MEMBERS_EXTENSION_TYPE)?;
+
+    Some(u32::from_be_bytes(data.try_into().ok()?))
+}
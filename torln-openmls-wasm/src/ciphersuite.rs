@@ -0,0 +1,150 @@
+//! Parameters of the ciphersuite, for applications doing their own
+//! cryptography with secrets exported from a group, and picking a
+//! ciphersuite both sides support.
+
+use openmls::{
+    key_packages::{errors::KeyPackageVerifyError, KeyPackageIn},
+    versions::ProtocolVersion,
+};
+use openmls_rust_crypto::RustCrypto;
+use openmls_traits::types::Ciphersuite;
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    message_size::{MaxMessageBytes, MessageTooLarge},
+    CIPHERSUITE,
+};
+
+/// The sizes and algorithms of a ciphersuite, see `ciphersuiteParams`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CiphersuiteParams {
+    ciphersuite: u16,
+    hash_length: u32,
+    aead_key_length: u32,
+    aead_nonce_length: u32,
+    signature_scheme: u16,
+}
+
+impl From<Ciphersuite> for CiphersuiteParams {
+    fn from(ciphersuite: Ciphersuite) -> Self {
+        Self {
+            ciphersuite: ciphersuite.into(),
+            hash_length: ciphersuite.hash_length() as u32,
+            aead_key_length: ciphersuite.aead_key_length() as u32,
+            aead_nonce_length: ciphersuite.aead_nonce_length() as u32,
+            signature_scheme: ciphersuite.signature_algorithm() as u16,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl CiphersuiteParams {
+    /// The IANA value of the ciphersuite.
+    #[wasm_bindgen(getter)]
+    pub fn ciphersuite(&self) -> u16 {
+        self.ciphersuite
+    }
+    /// The output length of the hash function, in bytes.
+    #[wasm_bindgen(getter, js_name = hashLength)]
+    pub fn hash_length(&self) -> u32 {
+        self.hash_length
+    }
+    /// The key length of the AEAD, in bytes.
+    #[wasm_bindgen(getter, js_name = aeadKeyLength)]
+    pub fn aead_key_length(&self) -> u32 {
+        self.aead_key_length
+    }
+    /// The nonce length of the AEAD, in bytes.
+    #[wasm_bindgen(getter, js_name = aeadNonceLength)]
+    pub fn aead_nonce_length(&self) -> u32 {
+        self.aead_nonce_length
+    }
+    /// The IANA value of the signature scheme, e.g. `0x0807` for Ed25519.
+    #[wasm_bindgen(getter, js_name = signatureScheme)]
+    pub fn signature_scheme(&self) -> u16 {
+        self.signature_scheme
+    }
+}
+
+/// The parameters of `suite`, or of the ciphersuite used by our groups if
+/// `suite` is `None`.
+pub(crate) fn params_of(suite: Option<u16>) -> Result<CiphersuiteParams, tls_codec::Error> {
+    let ciphersuite = match suite {
+        Some(suite) => Ciphersuite::try_from(suite)?,
+        None => CIPHERSUITE,
+    };
+
+    Ok(ciphersuite.into())
+}
+
+/// The hash length, AEAD key and nonce lengths and signature scheme of the
+/// ciphersuite with the IANA value `suite`, or of the ciphersuite used by our
+/// groups if `suite` is omitted.
+///
+/// Use this to size keys and nonces derived with `exportSecret` instead of
+/// hardcoding them.
+#[wasm_bindgen(js_name = ciphersuiteParams)]
+pub fn ciphersuite_params(suite: Option<u16>) -> Result<CiphersuiteParams, JsError> {
+    Ok(params_of(suite)?)
+}
+
+/// Errors when reading the capabilities of a key package.
+#[derive(Debug)]
+pub(crate) enum NegotiationError {
+    TooLarge(MessageTooLarge),
+    Malformed(tls_codec::Error),
+    InvalidKeyPackage(KeyPackageVerifyError),
+}
+
+impl std::fmt::Display for NegotiationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed key package: {e}"),
+            Self::InvalidKeyPackage(e) => write!(f, "invalid key package: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for NegotiationError {}
+
+/// The first ciphersuite in `our_supported` that the key package in
+/// `key_package` lists in its capabilities, see `negotiateCiphersuite`.
+/// There is no provider, so `key_package` is checked against the default
+/// size limit.
+pub(crate) fn negotiate(
+    our_supported: &[u16],
+    mut key_package: &[u8],
+) -> Result<Option<u16>, NegotiationError> {
+    MaxMessageBytes::default()
+        .check(key_package)
+        .map_err(NegotiationError::TooLarge)?;
+    let key_package =
+        KeyPackageIn::tls_deserialize(&mut key_package).map_err(NegotiationError::Malformed)?;
+    let key_package = key_package
+        .validate(&RustCrypto::default(), ProtocolVersion::Mls10)
+        .map_err(NegotiationError::InvalidKeyPackage)?;
+    let theirs = key_package.leaf_node().capabilities().ciphersuites();
+
+    Ok(our_supported
+        .iter()
+        .copied()
+        .find(|&suite| theirs.iter().any(|their| their.value() == suite)))
+}
+
+/// Pick the ciphersuite to use with the owner of a key package.
+///
+/// `our_supported` lists the IANA values of the ciphersuites we support, in
+/// order of preference. Returns the first of them that the key package
+/// advertises in its capabilities, or `undefined` if there is none in
+/// common. Fails if the key package can't be parsed or isn't validly
+/// signed, or is larger than the default of `Provider.setMaxMessageBytes`.
+#[wasm_bindgen(js_name = negotiateCiphersuite)]
+pub fn negotiate_ciphersuite(
+    our_supported: Vec<u16>,
+    key_package_bytes: &[u8],
+) -> Result<Option<u16>, JsError> {
+    Ok(negotiate(&our_supported, key_package_bytes)?)
+}
@@ -0,0 +1,56 @@
+//! The messages of an add as a single blob, for delivery services that
+//! store and relay the commit and welcome together.
+//!
+//! Serialized with the TLS presentation language, each message with its
+//! length in front:
+//!
+//! ```text
+//! struct {
+//!     opaque proposal<V>;
+//!     opaque commit<V>;
+//!     opaque welcome<V>;
+//! } CombinedAddMessages;
+//! ```
+
+use tls_codec::{Deserialize, Serialize, TlsDeserialize, TlsSerialize, TlsSize};
+use wasm_bindgen::prelude::*;
+
+use crate::AddMessages;
+
+/// The serialized messages of an `AddMessages`.
+#[derive(Debug, Clone, PartialEq, Eq, TlsSerialize, TlsDeserialize, TlsSize)]
+pub(crate) struct CombinedAddMessages {
+    pub(crate) proposal: Vec<u8>,
+    pub(crate) commit: Vec<u8>,
+    pub(crate) welcome: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl AddMessages {
+    /// The proposal, commit and welcome in one blob, in that order, each
+    /// prefixed with its length. `parseCombined` splits it again.
+    #[wasm_bindgen(js_name = toCombinedBytes)]
+    pub fn to_combined_bytes(&self) -> Result<Vec<u8>, JsError> {
+        let combined = CombinedAddMessages {
+            proposal: self.proposal.to_vec(),
+            commit: self.commit.to_vec(),
+            welcome: self.welcome.to_vec(),
+        };
+
+        Ok(combined.tls_serialize_detached()?)
+    }
+
+    /// Split a blob from `toCombinedBytes` into its messages. Fails if the
+    /// blob is truncated or has trailing bytes; the messages themselves are
+    /// only checked when they are processed.
+    #[wasm_bindgen(js_name = parseCombined)]
+    pub fn parse_combined(bytes: &[u8]) -> Result<AddMessages, JsError> {
+        let combined = CombinedAddMessages::tls_deserialize_exact(bytes)?;
+
+        Ok(AddMessages {
+            proposal: combined.proposal.as_slice().into(),
+            commit: combined.commit.as_slice().into(),
+            welcome: combined.welcome.as_slice().into(),
+        })
+    }
+}
@@ -0,0 +1,129 @@
+//! Checking that a fetched backlog of commits continues the group's epochs.
+//!
+//! Each commit ends the epoch in its unencrypted header. A backlog that
+//! starts at the current epoch and has exactly one commit per epoch can be
+//! applied in order; one with a gap is missing commits, and two commits for
+//! the same epoch mean the delivery service forked the group. Only headers
+//! are read, so nothing is decrypted, verified or stored.
+
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use crate::{routing, Group};
+
+/// The first way a backlog of commits doesn't continue the group's epochs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ChainIssue {
+    /// The message has no readable header.
+    Unreadable(routing::RoutingError),
+    OtherGroup,
+    NotACommit,
+    /// The commits for the epochs from `expected` up to `found` are missing.
+    Gap {
+        expected: u64,
+        found: u64,
+    },
+    /// A second commit for an epoch that already had one, or a commit for
+    /// an epoch the group has left.
+    Fork {
+        epoch: u64,
+    },
+}
+
+impl std::fmt::Display for ChainIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreadable(e) => write!(f, "unreadable message: {e}"),
+            Self::OtherGroup => write!(f, "message is for another group"),
+            Self::NotACommit => write!(f, "message is not a commit"),
+            Self::Gap { expected, found } => write!(
+                f,
+                "missing commits: expected a commit for epoch {expected}, found epoch {found}"
+            ),
+            Self::Fork { epoch } => write!(f, "fork: another commit for epoch {epoch}"),
+        }
+    }
+}
+
+/// The result of `Group.validateCommitChain`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitChainCheck {
+    /// The index of the first inconsistent commit, and what's wrong with it.
+    issue: Option<(u32, ChainIssue)>,
+}
+
+#[wasm_bindgen]
+impl CommitChainCheck {
+    /// Whether the commits continue the group from its current epoch, one
+    /// per epoch.
+    #[wasm_bindgen(getter)]
+    pub fn contiguous(&self) -> bool {
+        self.issue.is_none()
+    }
+    /// The index of the first commit that doesn't continue the chain.
+    #[wasm_bindgen(getter, js_name = firstInconsistency)]
+    pub fn first_inconsistency(&self) -> Option<u32> {
+        self.issue.as_ref().map(|(index, _)| *index)
+    }
+    /// What's wrong with that commit, e.g. which epochs are missing.
+    #[wasm_bindgen(getter)]
+    pub fn reason(&self) -> Option<String> {
+        self.issue.as_ref().map(|(_, issue)| issue.to_string())
+    }
+}
+
+impl Group {
+    /// Check that `commits` continue the epochs of this group, see
+    /// `validateCommitChain`.
+    pub(crate) fn check_commit_chain(&self, commits: &[Vec<u8>]) -> CommitChainCheck {
+        let group_id = self.mls_group.group_id().as_slice();
+        let current_epoch = self.mls_group.epoch().as_u64();
+
+        let issue = commits.iter().zip(current_epoch..).enumerate().find_map(
+            |(index, (commit, expected))| {
+                let issue = match (routing::group_id_of(commit), routing::header_of(commit)) {
+                    (Err(e), _) | (_, Err(e)) => ChainIssue::Unreadable(e),
+                    (Ok(id), _) if id != group_id => ChainIssue::OtherGroup,
+                    (_, Ok(header)) if header.content_type != routing::CONTENT_TYPE_COMMIT => {
+                        ChainIssue::NotACommit
+                    }
+                    (_, Ok(header)) if header.epoch > expected => ChainIssue::Gap {
+                        expected,
+                        found: header.epoch,
+                    },
+                    (_, Ok(header)) if header.epoch < expected => ChainIssue::Fork {
+                        epoch: header.epoch,
+                    },
+                    _ => return None,
+                };
+
+                Some((index as u32, issue))
+            },
+        );
+
+        CommitChainCheck { issue }
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Check, without changing the group, that `commit_messages` can be
+    /// applied in order from the current epoch: that they are commits of
+    /// this group, for the current epoch and the ones after it, one each.
+    ///
+    /// The result tells whether the chain is contiguous, and otherwise the
+    /// index of the first commit that breaks it and why, e.g. the epochs
+    /// whose commits are missing, so they can be fetched before processing
+    /// anything. Only the unencrypted headers are read, so a commit that
+    /// passes can still fail to process, e.g. if it was tampered with.
+    #[wasm_bindgen(js_name = validateCommitChain)]
+    pub fn validate_commit_chain(&self, commit_messages: Vec<Uint8Array>) -> CommitChainCheck {
+        let commits = commit_messages
+            .iter()
+            .map(Uint8Array::to_vec)
+            .collect::<Vec<_>>();
+
+        self.check_commit_chain(&commits)
+    }
+}
@@ -0,0 +1,80 @@
+//! Commits that come with the group info of the new epoch.
+//!
+//! Whether a group info carries the ratchet tree is usually a property of
+//! the group. For a single commit, e.g. one after which a new device joins
+//! externally, the tree can be embedded in the group info for just that
+//! epoch, which saves a separate `exportRatchetTree` round trip.
+
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{mls_message_to_u8vec, Group, Identity, Provider};
+
+/// The messages of a commit, see
+/// `Group.commitPendingProposalsWithGroupInfo`.
+#[wasm_bindgen]
+pub struct CommitWithGroupInfo {
+    commit: Vec<u8>,
+    welcome: Option<Vec<u8>>,
+    group_info: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl CommitWithGroupInfo {
+    #[wasm_bindgen(getter)]
+    pub fn commit(&self) -> Vec<u8> {
+        self.commit.clone()
+    }
+    /// The welcome, if the commit adds members.
+    #[wasm_bindgen(getter)]
+    pub fn welcome(&self) -> Option<Vec<u8>> {
+        self.welcome.clone()
+    }
+    /// The signed group info of the epoch the commit starts.
+    #[wasm_bindgen(getter, js_name = groupInfo)]
+    pub fn group_info(&self) -> Vec<u8> {
+        self.group_info.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `commitPendingProposals`, but also returns the group info of the
+    /// new epoch, e.g. for an external joiner.
+    ///
+    /// With `include_ratchet_tree`, the group info embeds the ratchet tree,
+    /// so that the joiner needs nothing else; the welcome then carries it
+    /// as well. Without it, the joiner needs the tree from
+    /// `exportRatchetTree`. The flag only applies to this commit.
+    #[wasm_bindgen(js_name = commitPendingProposalsWithGroupInfo)]
+    pub fn commit_pending_proposals_with_group_info(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        include_ratchet_tree: bool,
+    ) -> Result<CommitWithGroupInfo, JsError> {
+        self.ensure_capacity(0)?;
+        provider.check_committed_key_packages(self.mls_group.pending_proposals())?;
+        let signer = self.signer(provider, sender)?;
+        let bundle = self
+            .mls_group
+            .commit_builder()
+            .consume_proposal_store(true)
+            .load_psks(provider.0.storage())?
+            .create_group_info(true)
+            .use_ratchet_tree_extension(include_ratchet_tree)
+            .build(provider.0.rand(), provider.0.crypto(), &signer, |_| true)?
+            .stage_commit(&provider.0)?;
+        let (commit, welcome, group_info) = bundle.into_messages();
+
+        Ok(CommitWithGroupInfo {
+            commit: mls_message_to_u8vec(&commit),
+            welcome: welcome.as_ref().map(mls_message_to_u8vec),
+            // Always present, as we asked for it with `create_group_info`.
+            group_info: group_info
+                .as_ref()
+                .map(mls_message_to_u8vec)
+                .unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,135 @@
+//! Checking the confirmation tag of a received commit without applying it.
+//!
+//! The confirmation tag is a MAC over the transcript of the new epoch, keyed
+//! with its confirmation key, so only the epoch secrets the commit leads to
+//! can verify it. openmls checks it when staging a commit, so the check
+//! stages the commit against a snapshot of the storage and throws the
+//! result away.
+
+use openmls::{
+    framing::{MlsMessageIn, ProcessedMessageContent},
+    group::MlsGroup,
+};
+use openmls_torln_storage::TorlnStorageError;
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{message_size::MessageTooLarge, routing, transaction, Group, Provider};
+
+/// Errors when checking the confirmation tag of a commit.
+#[derive(Debug)]
+pub(crate) enum ConfirmationCheckError {
+    TooLarge(MessageTooLarge),
+    Unreadable(routing::RoutingError),
+    Malformed(tls_codec::Error),
+    OtherGroup,
+    NotACommit,
+    WrongEpoch {
+        message_epoch: u64,
+        current_epoch: u64,
+    },
+    Storage(TorlnStorageError),
+    GroupNotFound,
+}
+
+impl std::fmt::Display for ConfirmationCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::Unreadable(e) => write!(f, "unreadable message: {e}"),
+            Self::Malformed(e) => write!(f, "malformed commit: {e}"),
+            Self::OtherGroup => write!(f, "message is for another group"),
+            Self::NotACommit => write!(f, "message is not a commit"),
+            Self::WrongEpoch {
+                message_epoch,
+                current_epoch,
+            } => write!(
+                f,
+                "commit is for epoch {message_epoch}, but the group is in epoch {current_epoch}"
+            ),
+            Self::Storage(e) => write!(f, "failed to reload group: {e}"),
+            Self::GroupNotFound => write!(f, "failed to reload group: not found in storage"),
+        }
+    }
+}
+
+impl std::error::Error for ConfirmationCheckError {}
+
+impl Group {
+    /// Whether the serialized `commit` has a valid confirmation tag, see
+    /// `verifyCommitConfirmationTag`.
+    pub(crate) fn check_confirmation_tag(
+        &mut self,
+        provider: &Provider,
+        commit: &[u8],
+    ) -> Result<bool, ConfirmationCheckError> {
+        provider
+            .check_message_size(commit)
+            .map_err(ConfirmationCheckError::TooLarge)?;
+        let group_id = routing::group_id_of(commit).map_err(ConfirmationCheckError::Unreadable)?;
+        if group_id != self.mls_group.group_id().as_slice() {
+            return Err(ConfirmationCheckError::OtherGroup);
+        }
+        let header = routing::header_of(commit).map_err(ConfirmationCheckError::Unreadable)?;
+        if header.content_type != routing::CONTENT_TYPE_COMMIT {
+            return Err(ConfirmationCheckError::NotACommit);
+        }
+        let current_epoch = self.mls_group.epoch().as_u64();
+        if header.epoch != current_epoch {
+            return Err(ConfirmationCheckError::WrongEpoch {
+                message_epoch: header.epoch,
+                current_epoch,
+            });
+        }
+
+        let message = MlsMessageIn::tls_deserialize_exact(commit)
+            .map_err(ConfirmationCheckError::Malformed)?
+            .try_into_protocol_message()
+            .map_err(|_| ConfirmationCheckError::NotACommit)?;
+
+        let valid = transaction::discarding_writes(provider, || {
+            // Any failure to stage, e.g. a mismatching tag or a commit that
+            // doesn't decrypt, means the tag can't be confirmed.
+            self.mls_group
+                .process_message(provider.as_ref(), message)
+                .is_ok_and(|processed| {
+                    matches!(
+                        processed.into_content(),
+                        ProcessedMessageContent::StagedCommitMessage(_)
+                    )
+                })
+        });
+
+        // Decrypting advanced the secret tree in memory as well. The
+        // restored storage has the group as it was.
+        self.mls_group = MlsGroup::load(provider.0.storage(), self.mls_group.group_id())
+            .map_err(ConfirmationCheckError::Storage)?
+            .ok_or(ConfirmationCheckError::GroupNotFound)?;
+
+        Ok(valid)
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Whether the serialized commit `commit_bytes`, received for the
+    /// current epoch, carries a valid confirmation tag for the epoch it
+    /// starts, as a check before processing it, e.g. with `setAutoMerge`
+    /// off.
+    ///
+    /// The commit is staged against a snapshot of the storage, which is
+    /// restored afterwards, so the group stays as it was and the commit can
+    /// still be processed. Returns `false` if the tag doesn't match or the
+    /// commit fails to decrypt or verify, since the tag can't be confirmed
+    /// then. Fails if the message isn't a commit of this group for the
+    /// current epoch. Our own commits can't be checked, as we can't decrypt
+    /// them.
+    #[wasm_bindgen(js_name = verifyCommitConfirmationTag)]
+    pub fn verify_commit_confirmation_tag(
+        &mut self,
+        provider: &Provider,
+        commit_bytes: &[u8],
+    ) -> Result<bool, JsError> {
+        Ok(self.check_confirmation_tag(provider, commit_bytes)?)
+    }
+}
@@ -0,0 +1,127 @@
+//! Refusing members whose credential type we don't trust, e.g. basic
+//! credentials in a deployment that requires X.509.
+//!
+//! openmls accepts every credential type the leaves of a group list in
+//! their capabilities. The policy here is checked on the members of a group
+//! when joining, and on the credentials a message brings into the group:
+//! the sender's, those of added members, of updated leaves and of the
+//! committer's new leaf.
+
+use openmls::{
+    credentials::Credential, framing::ProcessedMessageContent, messages::proposals::Proposal,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{welcome, Group, Provider, RatchetTree};
+
+/// A credential of a type outside the accepted ones.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct UnacceptedCredentialType(pub(crate) u16);
+
+impl std::fmt::Display for UnacceptedCredentialType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unaccepted credential type {:#06x}", self.0)
+    }
+}
+
+impl std::error::Error for UnacceptedCredentialType {}
+
+/// Check that every credential in `credentials` has a type in `accepted`.
+pub(crate) fn check_credential_types<'a>(
+    accepted: &[u16],
+    credentials: impl IntoIterator<Item = &'a Credential>,
+) -> Result<(), UnacceptedCredentialType> {
+    match credentials
+        .into_iter()
+        .map(|credential| u16::from(credential.credential_type()))
+        .find(|credential_type| !accepted.contains(credential_type))
+    {
+        Some(credential_type) => Err(UnacceptedCredentialType(credential_type)),
+        None => Ok(()),
+    }
+}
+
+/// The credential `proposal` brings into the group, if any.
+fn proposed_credential(proposal: &Proposal) -> Option<&Credential> {
+    match proposal {
+        Proposal::Add(add) => Some(add.key_package().leaf_node().credential()),
+        Proposal::Update(update) => Some(update.leaf_node().credential()),
+        _ => None,
+    }
+}
+
+/// The credentials a message with `content` brings into the group, besides
+/// the one of its sender.
+pub(crate) fn new_credentials(content: &ProcessedMessageContent) -> Vec<&Credential> {
+    match content {
+        ProcessedMessageContent::ProposalMessage(proposal) => {
+            proposed_credential(proposal.proposal())
+                .into_iter()
+                .collect()
+        }
+        ProcessedMessageContent::StagedCommitMessage(staged_commit) => staged_commit
+            .queued_proposals()
+            .filter_map(|queued_proposal| proposed_credential(queued_proposal.proposal()))
+            .chain(
+                staged_commit
+                    .update_path_leaf_node()
+                    .map(|leaf_node| leaf_node.credential()),
+            )
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl Group {
+    /// Check `credentials` against the policy set with
+    /// `setAcceptedCredentialTypes`.
+    pub(crate) fn check_accepted_credentials<'a>(
+        &self,
+        credentials: impl IntoIterator<Item = &'a Credential>,
+    ) -> Result<(), UnacceptedCredentialType> {
+        match &self.accepted_credential_types {
+            Some(accepted) => check_credential_types(accepted, credentials),
+            None => Ok(()),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Only accept members with one of the credential types `types`, e.g.
+    /// `[2]` for X.509 only.
+    ///
+    /// `processMessage` then fails with an "unaccepted credential type"
+    /// error for messages whose sender has another credential type, or
+    /// that add or update a member to one. Such commits are not merged and
+    /// such proposals are not stored. Members already in the group are not
+    /// checked; to refuse a group on joining, use
+    /// `joinWithAcceptedCredentialTypes`. An empty list accepts all types,
+    /// which is the default.
+    #[wasm_bindgen(js_name = setAcceptedCredentialTypes)]
+    pub fn set_accepted_credential_types(&mut self, types: Vec<u16>) {
+        self.accepted_credential_types = (!types.is_empty()).then_some(types);
+    }
+
+    /// Like `join`, but refuses the group if one of its members has a
+    /// credential type other than `types`, with an "unaccepted credential
+    /// type" error. The storage is left as it was, so the welcome can still
+    /// be joined with another policy.
+    ///
+    /// The joined group keeps `types` as its policy for `processMessage`,
+    /// see `setAcceptedCredentialTypes`. An empty list accepts all types.
+    #[wasm_bindgen(js_name = joinWithAcceptedCredentialTypes)]
+    pub fn join_with_accepted_credential_types(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+        types: Vec<u16>,
+    ) -> Result<Group, JsError> {
+        let accepted = (!types.is_empty()).then_some(types.as_slice());
+        let joined = welcome::join_group_accepting(provider, welcome, ratchet_tree, accepted)?;
+
+        let mut group = Group::from(joined.mls_group);
+        group.set_accepted_credential_types(types);
+        Ok(group)
+    }
+}
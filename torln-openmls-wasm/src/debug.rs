@@ -0,0 +1,243 @@
+//! Diagnostics for debugging groups, only built with the `debug-tools`
+//! feature.
+
+use openmls::{group::ExportSecretError, treesync::Node};
+use openmls_traits::OpenMlsProvider;
+use tls_codec::{Deserialize, Serialize, TlsDeserialize, VLBytes};
+use wasm_bindgen::prelude::*;
+
+use crate::{Group, Provider, CIPHERSUITE};
+
+/// Exporter label of the secret tree fingerprint.
+const SECRET_TREE_FINGERPRINT_LABEL: &str = "torln secret tree fingerprint";
+
+/// The fields of a parent node, which openmls doesn't expose. Read from the
+/// node's TLS encoding, as defined in RFC 9420.
+#[derive(TlsDeserialize)]
+struct ParentNodeFields {
+    _encryption_key: VLBytes,
+    parent_hash: VLBytes,
+    _unmerged_leaves: Vec<u32>,
+}
+
+/// Errors when dumping the ratchet tree.
+#[derive(Debug)]
+pub(crate) enum TreeDumpError {
+    Nodes(serde_json::Error),
+    Encoding(tls_codec::Error),
+}
+
+impl std::fmt::Display for TreeDumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nodes(e) => write!(f, "failed to read tree nodes: {e}"),
+            Self::Encoding(e) => write!(f, "failed to encode tree node: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TreeDumpError {}
+
+/// A node of the ratchet tree, see `Group.debugTreeDump`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct TreeNodeDump {
+    node_index: u32,
+    occupied: bool,
+    credential: Option<Vec<u8>>,
+    parent_hash: Option<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl TreeNodeDump {
+    /// The index of the node in the array representation of the tree.
+    /// Leaves have even indices.
+    #[wasm_bindgen(getter, js_name = nodeIndex)]
+    pub fn node_index(&self) -> u32 {
+        self.node_index
+    }
+    /// The leaf index, if this is a leaf.
+    #[wasm_bindgen(getter, js_name = leafIndex)]
+    pub fn leaf_index(&self) -> Option<u32> {
+        (self.node_index % 2 == 0).then_some(self.node_index / 2)
+    }
+    /// Whether the node is occupied, i.e. not blank.
+    #[wasm_bindgen(getter)]
+    pub fn occupied(&self) -> bool {
+        self.occupied
+    }
+    /// The TLS-serialized credential, if this is an occupied leaf.
+    #[wasm_bindgen(getter)]
+    pub fn credential(&self) -> Option<Vec<u8>> {
+        self.credential.clone()
+    }
+    /// The parent hash, if this is an occupied parent node.
+    #[wasm_bindgen(getter, js_name = parentHash)]
+    pub fn parent_hash(&self) -> Option<Vec<u8>> {
+        self.parent_hash.clone()
+    }
+}
+
+impl TreeNodeDump {
+    fn new(node_index: u32, node: Option<Node>) -> Result<Self, TreeDumpError> {
+        let (credential, parent_hash) = match &node {
+            None => (None, None),
+            Some(Node::LeafNode(leaf)) => (
+                Some(
+                    leaf.credential()
+                        .tls_serialize_detached()
+                        .map_err(TreeDumpError::Encoding)?,
+                ),
+                None,
+            ),
+            Some(Node::ParentNode(parent)) => {
+                let bytes = parent
+                    .tls_serialize_detached()
+                    .map_err(TreeDumpError::Encoding)?;
+                let fields = ParentNodeFields::tls_deserialize_exact(bytes)
+                    .map_err(TreeDumpError::Encoding)?;
+                (None, Some(fields.parent_hash.into()))
+            }
+        };
+
+        Ok(TreeNodeDump {
+            node_index,
+            occupied: node.is_some(),
+            credential,
+            parent_hash,
+        })
+    }
+}
+
+/// The application message ratchet of a member, see
+/// `Group.debugRatchetState`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenderRatchetState {
+    leaf_index: u32,
+    own: bool,
+    generation: u32,
+    retained_keys: u32,
+}
+
+#[wasm_bindgen]
+impl SenderRatchetState {
+    #[wasm_bindgen(getter, js_name = leafIndex)]
+    pub fn leaf_index(&self) -> u32 {
+        self.leaf_index
+    }
+    /// Whether this is our own leaf, whose ratchet counts the messages we
+    /// sent rather than received.
+    #[wasm_bindgen(getter)]
+    pub fn own(&self) -> bool {
+        self.own
+    }
+    /// The next generation the ratchet derives a key for.
+    #[wasm_bindgen(getter)]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+    /// The number of keys of skipped generations kept for messages that
+    /// arrive out of order. 0 if none are kept.
+    #[wasm_bindgen(getter, js_name = retainedKeys)]
+    pub fn retained_keys(&self) -> u32 {
+        self.retained_keys
+    }
+}
+
+impl Group {
+    /// The application message ratchets of the members, see
+    /// `debugRatchetState`.
+    pub(crate) fn ratchet_state(&self) -> Vec<SenderRatchetState> {
+        let own_leaf_index = self.mls_group.own_leaf_index();
+        let mut states = self
+            .mls_group
+            .members()
+            .map(|member| SenderRatchetState {
+                leaf_index: member.index.u32(),
+                own: member.index == own_leaf_index,
+                generation: self
+                    .mls_group
+                    .application_generation(member.index)
+                    .unwrap_or_default(),
+                retained_keys: self
+                    .mls_group
+                    .application_retained_keys(member.index)
+                    .unwrap_or_default() as u32,
+            })
+            .collect::<Vec<_>>();
+        states.sort_by_key(|state| state.leaf_index);
+
+        states
+    }
+
+    /// The nodes of the ratchet tree, see `debugTreeDump`.
+    pub(crate) fn tree_dump(&self) -> Result<Vec<TreeNodeDump>, TreeDumpError> {
+        // openmls doesn't give access to the nodes of an exported tree, but
+        // serializes it as the plain list of nodes.
+        let tree = serde_json::to_value(self.mls_group.export_ratchet_tree())
+            .map_err(TreeDumpError::Nodes)?;
+        let nodes =
+            serde_json::from_value::<Vec<Option<Node>>>(tree).map_err(TreeDumpError::Nodes)?;
+
+        nodes
+            .into_iter()
+            .enumerate()
+            .map(|(node_index, node)| TreeNodeDump::new(node_index as u32, node))
+            .collect()
+    }
+
+    pub(crate) fn secret_tree_fingerprint(
+        &self,
+        provider: &Provider,
+    ) -> Result<Vec<u8>, ExportSecretError> {
+        self.mls_group.export_secret(
+            provider.0.crypto(),
+            SECRET_TREE_FINGERPRINT_LABEL,
+            &[],
+            CIPHERSUITE.hash_length(),
+        )
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// A fingerprint of the secret tree of the current epoch, for checking
+    /// whether two members derive the same message keys when they can't
+    /// decrypt each other's messages.
+    ///
+    /// openmls deletes the secrets of the tree as it derives message keys,
+    /// so the tree itself can't be compared. Instead, the fingerprint is
+    /// exported from the epoch secret that the root of the secret tree is
+    /// derived from: two members with the same fingerprint have the same
+    /// secret tree. The fingerprint is a one-way derivation under a
+    /// dedicated label and reveals nothing about the message keys.
+    #[wasm_bindgen(js_name = debugSecretTreeFingerprint)]
+    pub fn debug_secret_tree_fingerprint(&self, provider: &Provider) -> Result<Vec<u8>, JsError> {
+        Ok(self.secret_tree_fingerprint(provider)?)
+    }
+
+    /// The nodes of the ratchet tree, including blank ones, for comparing
+    /// the trees of two members node by node when they diverged.
+    ///
+    /// Only public data is included: the credential of each leaf and the
+    /// parent hash of each parent node. Trailing blank nodes are omitted,
+    /// as in `exportRatchetTree`.
+    #[wasm_bindgen(js_name = debugTreeDump)]
+    pub fn debug_tree_dump(&self) -> Result<Vec<TreeNodeDump>, JsError> {
+        Ok(self.tree_dump()?)
+    }
+
+    /// The application message ratchet of each member in the current
+    /// epoch, by leaf index, for finding where a sender's ratchet desynced
+    /// when their messages don't decrypt.
+    ///
+    /// Only the generations and the number of retained keys are included,
+    /// never the keys. A sender whose generation here is behind their own
+    /// count has messages that weren't received; one far ahead of it was
+    /// skipped past, e.g. by a forged or misrouted message.
+    #[wasm_bindgen(js_name = debugRatchetState)]
+    pub fn debug_ratchet_state(&self) -> Vec<SenderRatchetState> {
+        self.ratchet_state()
+    }
+}
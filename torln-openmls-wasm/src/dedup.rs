@@ -0,0 +1,167 @@
+//! Skipping messages the group already processed, for delivery services
+//! that deliver a message more than once.
+//!
+//! The group remembers the hashes of the last messages it processed
+//! successfully. Like the processing stats, they live in memory only and
+//! are forgotten when the group is loaded.
+
+use std::collections::VecDeque;
+
+use openmls_traits::{crypto::OpenMlsCrypto, types::CryptoError, OpenMlsProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    processing::{ProcessError, ProcessedMessage},
+    Group, Provider, CIPHERSUITE,
+};
+
+/// The number of message hashes kept by default, see `setDedupWindow`.
+pub(crate) const DEFAULT_DEDUP_WINDOW: usize = 1024;
+
+/// The hashes of the recently processed messages, least recently seen
+/// first.
+#[derive(Debug, Clone)]
+pub(crate) struct SeenMessages {
+    window: usize,
+    hashes: VecDeque<Vec<u8>>,
+}
+
+impl Default for SeenMessages {
+    fn default() -> Self {
+        SeenMessages {
+            window: DEFAULT_DEDUP_WINDOW,
+            hashes: VecDeque::new(),
+        }
+    }
+}
+
+impl SeenMessages {
+    /// Whether `hash` was seen, marking it as seen most recently if so.
+    fn touch(&mut self, hash: &[u8]) -> bool {
+        let Some(position) = self.hashes.iter().position(|seen| seen == hash) else {
+            return false;
+        };
+        if let Some(seen) = self.hashes.remove(position) {
+            self.hashes.push_back(seen);
+        }
+
+        true
+    }
+
+    fn insert(&mut self, hash: Vec<u8>) {
+        self.hashes.push_back(hash);
+        self.evict();
+    }
+
+    fn set_window(&mut self, window: usize) {
+        self.window = window;
+        self.evict();
+    }
+
+    /// Drop the least recently seen hashes beyond the window.
+    fn evict(&mut self) {
+        while self.hashes.len() > self.window {
+            self.hashes.pop_front();
+        }
+    }
+}
+
+/// Errors when processing a message with deduplication.
+#[derive(Debug)]
+pub(crate) enum DedupError {
+    Hash(CryptoError),
+    Process(ProcessError),
+}
+
+impl std::fmt::Display for DedupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hash(e) => write!(f, "failed to hash message: {e:?}"),
+            Self::Process(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DedupError {}
+
+/// The outcome of `Group.processMessageDedup`. `processed` is set unless
+/// the message is a `duplicate`.
+#[wasm_bindgen]
+pub struct DedupResult {
+    duplicate: bool,
+    processed: Option<ProcessedMessage>,
+}
+
+#[wasm_bindgen]
+impl DedupResult {
+    /// Whether the message was processed before and skipped.
+    #[wasm_bindgen(getter)]
+    pub fn duplicate(&self) -> bool {
+        self.duplicate
+    }
+    #[wasm_bindgen(getter)]
+    pub fn processed(&self) -> Option<ProcessedMessage> {
+        self.processed.clone()
+    }
+}
+
+impl Group {
+    /// Process a message unless it was processed before, see
+    /// `processMessageDedup`.
+    pub(crate) fn process_dedup(
+        &mut self,
+        provider: &Provider,
+        bytes: &[u8],
+    ) -> Result<DedupResult, DedupError> {
+        let hash = provider
+            .0
+            .crypto()
+            .hash(CIPHERSUITE.hash_algorithm(), bytes)
+            .map_err(DedupError::Hash)?;
+        if self.seen_messages.touch(&hash) {
+            return Ok(DedupResult {
+                duplicate: true,
+                processed: None,
+            });
+        }
+
+        let processed = self.process(provider, bytes).map_err(DedupError::Process)?;
+        // Only recorded once processed, so that a message that failed, e.g.
+        // one that arrived before the commits it depends on, can be retried.
+        self.seen_messages.insert(hash);
+
+        Ok(DedupResult {
+            duplicate: false,
+            processed: Some(processed),
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `processMessageDetailed`, but skips a message identical to one
+    /// of the last messages processed successfully, returning it as a
+    /// `duplicate` without touching the group. Messages that fail are not
+    /// remembered and can be retried.
+    ///
+    /// Messages are compared by the hash of their bytes, so a message
+    /// re-encoded by the delivery service counts as new. The hashes are
+    /// kept in memory only, see `setDedupWindow`.
+    #[wasm_bindgen(js_name = processMessageDedup)]
+    pub fn process_message_dedup(
+        &mut self,
+        provider: &Provider,
+        bytes: &[u8],
+    ) -> Result<DedupResult, JsError> {
+        Ok(self.process_dedup(provider, bytes)?)
+    }
+
+    /// Set how many messages `processMessageDedup` remembers, 1024 by
+    /// default. The least recently seen are forgotten first; 0 turns
+    /// deduplication off. Not persisted: the window and the remembered
+    /// messages start over when the group is loaded.
+    #[wasm_bindgen(js_name = setDedupWindow)]
+    pub fn set_dedup_window(&mut self, size: u32) {
+        self.seen_messages.set_window(size as usize);
+    }
+}
@@ -0,0 +1,121 @@
+//! Users with several devices.
+//!
+//! Each device of a user is a separate member of the group, with its own
+//! leaf and signature key, but all devices share the credential of the user.
+//! A device is identified by its signature key: the key packages of a device
+//! are signed with it, and so are the messages the device sends.
+
+use openmls::{group::MlsGroup, prelude::SignatureScheme};
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{types::CryptoError, OpenMlsProvider};
+use std::collections::BTreeMap;
+use tls_codec::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{Group, Identity, Provider};
+
+/// Errors when adding a device to an identity.
+#[derive(Debug)]
+pub(crate) enum DeviceError {
+    KeyGeneration(CryptoError),
+    Storage(TorlnStorageError),
+}
+
+impl std::fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KeyGeneration(e) => write!(f, "failed to generate signature key: {e}"),
+            Self::Storage(e) => write!(f, "failed to store signature key: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {}
+
+/// The members of a group belonging to one user, see `Group.membersByUser`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct UserMembers {
+    credential: Vec<u8>,
+    leaf_indices: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl UserMembers {
+    /// The TLS-serialized credential shared by the devices of the user.
+    #[wasm_bindgen(getter)]
+    pub fn credential(&self) -> Vec<u8> {
+        self.credential.clone()
+    }
+    /// The leaf indices of the devices of the user, in ascending order.
+    #[wasm_bindgen(getter, js_name = leafIndices)]
+    pub fn leaf_indices(&self) -> Vec<u32> {
+        self.leaf_indices.clone()
+    }
+}
+
+/// The members of `group` grouped by credential, ordered by the first leaf
+/// index of each user.
+pub(crate) fn members_by_user(group: &MlsGroup) -> Result<Vec<UserMembers>, tls_codec::Error> {
+    let mut members = group.members().collect::<Vec<_>>();
+    members.sort_by_key(|member| member.index);
+
+    let mut users = Vec::<UserMembers>::new();
+    let mut positions = BTreeMap::new();
+    for member in members {
+        let credential = member.credential.tls_serialize_detached()?;
+        let position = *positions.entry(credential.clone()).or_insert_with(|| {
+            users.push(UserMembers {
+                credential,
+                leaf_indices: Vec::new(),
+            });
+            users.len() - 1
+        });
+        users[position].leaf_indices.push(member.index.u32());
+    }
+
+    Ok(users)
+}
+
+impl Identity {
+    /// A new device of this user, see `addDevice`.
+    pub(crate) fn new_device(&self, provider: &Provider) -> Result<Identity, DeviceError> {
+        let keypair =
+            SignatureKeyPair::new(SignatureScheme::ED25519).map_err(DeviceError::KeyGeneration)?;
+        keypair
+            .store(provider.0.storage())
+            .map_err(DeviceError::Storage)?;
+
+        Ok(Identity {
+            credential: self.credential.clone(),
+            keypair,
+            group_key_packages: BTreeMap::new(),
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Identity {
+    /// Create an identity for another device of the same user.
+    ///
+    /// The new identity has the same credential but its own signature key,
+    /// which is stored in `provider`, the provider of the new device. Its key
+    /// packages are signed with that key, so each device joins a group as a
+    /// separate member. Export the keypair with `exportKeypairBytes` to move
+    /// it to the device.
+    #[wasm_bindgen(js_name = addDevice)]
+    pub fn add_device(&self, provider: &Provider) -> Result<Identity, JsError> {
+        Ok(self.new_device(provider)?)
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// The members of the group grouped by user, i.e. by credential, so that
+    /// the devices of a user can be shown as one participant.
+    #[wasm_bindgen(js_name = membersByUser)]
+    pub fn members_by_user(&self) -> Result<Vec<UserMembers>, JsError> {
+        Ok(members_by_user(&self.mls_group)?)
+    }
+}
@@ -0,0 +1,154 @@
+//! Computing a commit without applying it.
+//!
+//! openmls stages a commit in the group and in the provider storage as it
+//! creates it. For a dry run, we create the commit as usual, then restore
+//! the storage and reload the group from it, so that neither keeps a trace
+//! of the commit.
+
+use js_sys::Uint8Array;
+use openmls::group::{CommitBuilderStageError, CreateCommitError, MlsGroup};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    mls_message_to_u8vec, signer::MissingSignerError, transaction, Group, Identity, Provider,
+};
+
+/// Errors when computing a commit in a dry run.
+#[derive(Debug)]
+pub(crate) enum DryRunError {
+    /// No pending proposal has the reference at this position.
+    UnknownProposal(usize),
+    Signer(MissingSignerError),
+    Commit(CreateCommitError),
+    Stage(CommitBuilderStageError<TorlnStorageError>),
+    Storage(TorlnStorageError),
+    /// The group was missing from the restored storage.
+    GroupNotFound,
+}
+
+impl std::fmt::Display for DryRunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownProposal(position) => {
+                write!(f, "no pending proposal with reference {position}")
+            }
+            Self::Signer(e) => write!(f, "{e}"),
+            Self::Commit(e) => write!(f, "failed to create commit: {e}"),
+            Self::Stage(e) => write!(f, "failed to create commit: {e}"),
+            Self::Storage(e) => write!(f, "failed to reload group: {e}"),
+            Self::GroupNotFound => write!(f, "failed to reload group: not found in storage"),
+        }
+    }
+}
+
+impl std::error::Error for DryRunError {}
+
+/// The messages a commit would produce, see `Group.dryRunCommit`.
+#[wasm_bindgen]
+pub struct DryRunCommit {
+    commit: Vec<u8>,
+    welcome: Option<Vec<u8>>,
+    group_info: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl DryRunCommit {
+    #[wasm_bindgen(getter)]
+    pub fn commit(&self) -> Vec<u8> {
+        self.commit.clone()
+    }
+    /// The welcome, if the commit adds members.
+    #[wasm_bindgen(getter)]
+    pub fn welcome(&self) -> Option<Vec<u8>> {
+        self.welcome.clone()
+    }
+    /// The group info of the epoch the commit would start.
+    #[wasm_bindgen(getter, js_name = groupInfo)]
+    pub fn group_info(&self) -> Vec<u8> {
+        self.group_info.clone()
+    }
+}
+
+impl Group {
+    /// Compute the commit of the pending proposals with the given
+    /// references without applying it, see `dryRunCommit`.
+    pub(crate) fn dry_run(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        proposal_refs: &[Vec<u8>],
+    ) -> Result<DryRunCommit, DryRunError> {
+        if let Some(position) = self.unknown_proposal(proposal_refs) {
+            return Err(DryRunError::UnknownProposal(position));
+        }
+        let signer = self.signer(provider, sender).map_err(DryRunError::Signer)?;
+
+        let messages = transaction::discarding_writes(provider, || {
+            self.mls_group
+                .commit_builder()
+                .consume_proposal_store(true)
+                .create_group_info(true)
+                .load_psks(provider.0.storage())
+                .map_err(DryRunError::Commit)?
+                .build(provider.0.rand(), provider.0.crypto(), &signer, |queued| {
+                    proposal_refs.iter().any(|selected| {
+                        selected.as_slice() == queued.proposal_reference_ref().as_slice()
+                    })
+                })
+                .map_err(DryRunError::Commit)?
+                .stage_commit(&provider.0)
+                .map_err(DryRunError::Stage)
+        });
+
+        // Staging changed the group in memory as well, and creating the
+        // commit may have failed half-way. Either way, the restored storage
+        // has the group as it was.
+        self.mls_group = MlsGroup::load(provider.0.storage(), self.mls_group.group_id())
+            .map_err(DryRunError::Storage)?
+            .ok_or(DryRunError::GroupNotFound)?;
+
+        let (commit, welcome, group_info) = messages?.into_messages();
+
+        Ok(DryRunCommit {
+            commit: mls_message_to_u8vec(&commit),
+            welcome: welcome.as_ref().map(mls_message_to_u8vec),
+            // Always present, as we asked for it with `create_group_info`.
+            group_info: group_info
+                .as_ref()
+                .map(mls_message_to_u8vec)
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Compute the commit of the pending proposals with the given
+    /// references, as returned in `proposalRef` by `processMessageDetailed`,
+    /// without applying it.
+    ///
+    /// Returns the commit, the welcome if the commit adds members, and the
+    /// group info of the resulting epoch, e.g. to check them with the
+    /// delivery service before committing. The group and the provider
+    /// storage are left unchanged, and no commit is pending afterwards.
+    ///
+    /// The messages must not be sent. They are encrypted with the same keys
+    /// as the next real commit, which is created with `commitProposals` once
+    /// the dry run checks out, and has different secrets.
+    #[wasm_bindgen(js_name = dryRunCommit)]
+    pub fn dry_run_commit(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        proposal_refs: Vec<Uint8Array>,
+    ) -> Result<DryRunCommit, JsError> {
+        let proposal_refs = proposal_refs
+            .iter()
+            .map(Uint8Array::to_vec)
+            .collect::<Vec<_>>();
+
+        Ok(self.dry_run(provider, sender, &proposal_refs)?)
+    }
+}
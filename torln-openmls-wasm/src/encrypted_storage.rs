@@ -0,0 +1,372 @@
+//! Passphrase-encrypted storage exports, and changing their passphrase.
+//!
+//! The storage is encrypted with a random storage key, which is itself
+//! encrypted ("wrapped") with a key derived from the passphrase with
+//! Argon2id. Changing the passphrase re-encrypts the storage under a new
+//! storage key; the storage entries are only ever decrypted in Rust, never in
+//! JS.
+//!
+//! Format (little endian):
+//! `[magic "TMLE"][u16 format_version][u32 m_cost][u32 t_cost][u32 p_cost]`
+//! `[16 salt][12 key_nonce][48 wrapped_key][12 storage_nonce]`
+//! then the storage, in the `exportStorage` format, encrypted with the
+//! storage key. Both are encrypted with ChaCha20-Poly1305; the wrapped key
+//! authenticates the header before it, the storage the magic and version.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use openmls_rust_crypto::RandError;
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{
+    crypto::OpenMlsCrypto,
+    random::OpenMlsRand,
+    types::{AeadType, CryptoError},
+    OpenMlsProvider,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    storage::{self, StorageFormatError},
+    Provider,
+};
+
+/// Marks a passphrase-encrypted storage blob.
+const MAGIC: &[u8; 4] = b"TMLE";
+/// Current version of the encrypted storage format.
+const FORMAT_VERSION: u16 = 1;
+const AEAD: AeadType = AeadType::ChaCha20Poly1305;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const SALT_LEN: usize = 16;
+/// Size of the magic and version, authenticated with the storage.
+const VERSION_LEN: usize = 6;
+/// Size of the header up to the key nonce, authenticated with the wrapped
+/// key.
+const KDF_HEADER_LEN: usize = VERSION_LEN + 12 + SALT_LEN;
+/// Size of the header up to the storage nonce.
+const WRAPPED_HEADER_LEN: usize = KDF_HEADER_LEN + NONCE_LEN + KEY_LEN + TAG_LEN;
+/// Size of the whole header.
+const HEADER_LEN: usize = WRAPPED_HEADER_LEN + NONCE_LEN;
+
+/// Errors when encrypting or decrypting a storage blob.
+#[derive(Debug)]
+pub(crate) enum EncryptedStorageError {
+    TooShort,
+    NotEncrypted,
+    UnsupportedVersion(u16),
+    Kdf(argon2::Error),
+    /// The key derivation parameters of the blob are above the defaults.
+    KdfParamsTooHigh,
+    /// The passphrase doesn't decrypt the storage key.
+    WrongPassphrase,
+    Rand(RandError),
+    Crypto(CryptoError),
+    Format(StorageFormatError),
+    Storage(TorlnStorageError),
+}
+
+impl std::fmt::Display for EncryptedStorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "Encrypted storage data too short"),
+            Self::NotEncrypted => write!(f, "Storage data is not encrypted"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported encrypted storage format version {version}")
+            }
+            Self::Kdf(e) => write!(f, "failed to derive key from passphrase: {e}"),
+            Self::KdfParamsTooHigh => {
+                write!(f, "key derivation parameters of encrypted storage too high")
+            }
+            Self::WrongPassphrase => write!(f, "wrong passphrase for encrypted storage"),
+            Self::Rand(e) => write!(f, "failed to create storage key: {e}"),
+            Self::Crypto(e) => write!(f, "failed to encrypt storage: {e}"),
+            Self::Format(e) => write!(f, "{e}"),
+            Self::Storage(e) => write!(f, "failed to import storage: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptedStorageError {}
+
+/// The header of an encrypted storage blob, with the storage key wrapped.
+struct Header {
+    params: Params,
+    salt: [u8; SALT_LEN],
+    key_nonce: [u8; NONCE_LEN],
+    wrapped_key: [u8; KEY_LEN + TAG_LEN],
+    storage_nonce: [u8; NONCE_LEN],
+}
+
+impl Header {
+    fn read(bytes: &[u8]) -> Result<Self, EncryptedStorageError> {
+        let read_u16 = |data: &[u8]| -> u16 { u16::from_le_bytes(data.try_into().unwrap()) };
+        let read_u32 = |data: &[u8]| -> u32 { u32::from_le_bytes(data.try_into().unwrap()) };
+
+        if !bytes.starts_with(MAGIC) {
+            return Err(EncryptedStorageError::NotEncrypted);
+        }
+        if bytes.len() < HEADER_LEN + TAG_LEN {
+            return Err(EncryptedStorageError::TooShort);
+        }
+
+        let version = read_u16(&bytes[4..6]);
+        if version != FORMAT_VERSION {
+            return Err(EncryptedStorageError::UnsupportedVersion(version));
+        }
+
+        // We only ever write the default parameters. Higher ones in a blob
+        // would make deriving the key arbitrarily slow or large.
+        let (m_cost, t_cost, p_cost) = (
+            read_u32(&bytes[6..10]),
+            read_u32(&bytes[10..14]),
+            read_u32(&bytes[14..18]),
+        );
+        let max = Params::default();
+        if m_cost > max.m_cost() || t_cost > max.t_cost() || p_cost > max.p_cost() {
+            return Err(EncryptedStorageError::KdfParamsTooHigh);
+        }
+        let params = Params::new(m_cost, t_cost, p_cost, Some(KEY_LEN))
+            .map_err(EncryptedStorageError::Kdf)?;
+
+        Ok(Header {
+            params,
+            salt: bytes[18..KDF_HEADER_LEN].try_into().unwrap(),
+            key_nonce: bytes[KDF_HEADER_LEN..KDF_HEADER_LEN + NONCE_LEN]
+                .try_into()
+                .unwrap(),
+            wrapped_key: bytes[KDF_HEADER_LEN + NONCE_LEN..WRAPPED_HEADER_LEN]
+                .try_into()
+                .unwrap(),
+            storage_nonce: bytes[WRAPPED_HEADER_LEN..HEADER_LEN].try_into().unwrap(),
+        })
+    }
+
+    /// The header up to the key nonce, which the wrapped key authenticates.
+    fn kdf_header(params: &Params, salt: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(KDF_HEADER_LEN);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&params.m_cost().to_le_bytes());
+        out.extend_from_slice(&params.t_cost().to_le_bytes());
+        out.extend_from_slice(&params.p_cost().to_le_bytes());
+        out.extend_from_slice(salt);
+        out
+    }
+
+    fn write(&self) -> Vec<u8> {
+        let mut out = Self::kdf_header(&self.params, &self.salt);
+        out.extend_from_slice(&self.key_nonce);
+        out.extend_from_slice(&self.wrapped_key);
+        out.extend_from_slice(&self.storage_nonce);
+        out
+    }
+}
+
+impl Provider {
+    /// The key derived from `passphrase` with `params` and `salt`.
+    fn passphrase_key(
+        passphrase: &str,
+        params: Params,
+        salt: &[u8],
+    ) -> Result<[u8; KEY_LEN], EncryptedStorageError> {
+        let mut key = [0; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(EncryptedStorageError::Kdf)?;
+        Ok(key)
+    }
+
+    /// Wrap `storage_key` with a key derived from `passphrase` and a fresh
+    /// salt, returning the header without the storage nonce.
+    fn wrap_storage_key(
+        &self,
+        passphrase: &str,
+        storage_key: &[u8],
+        storage_nonce: [u8; NONCE_LEN],
+    ) -> Result<Header, EncryptedStorageError> {
+        let rand = self.0.rand();
+        let salt = rand.random_array().map_err(EncryptedStorageError::Rand)?;
+        let key_nonce = rand.random_array().map_err(EncryptedStorageError::Rand)?;
+        let params = Params::default();
+
+        let passphrase_key = Self::passphrase_key(passphrase, params.clone(), &salt)?;
+        let wrapped_key = self
+            .0
+            .crypto()
+            .aead_encrypt(
+                AEAD,
+                &passphrase_key,
+                storage_key,
+                &key_nonce,
+                &Header::kdf_header(&params, &salt),
+            )
+            .map_err(EncryptedStorageError::Crypto)?;
+
+        Ok(Header {
+            params,
+            salt,
+            key_nonce,
+            // The storage key and the tag.
+            wrapped_key: wrapped_key.try_into().unwrap(),
+            storage_nonce,
+        })
+    }
+
+    /// The storage key in `header`, unwrapped with `passphrase`.
+    fn unwrap_storage_key(
+        &self,
+        header: &Header,
+        passphrase: &str,
+    ) -> Result<Vec<u8>, EncryptedStorageError> {
+        let passphrase_key = Self::passphrase_key(passphrase, header.params.clone(), &header.salt)?;
+        self.0
+            .crypto()
+            .aead_decrypt(
+                AEAD,
+                &passphrase_key,
+                &header.wrapped_key,
+                &header.key_nonce,
+                &Header::kdf_header(&header.params, &header.salt),
+            )
+            .map_err(|_| EncryptedStorageError::WrongPassphrase)
+    }
+
+    /// `plaintext` encrypted under a fresh storage key, which is wrapped
+    /// under `passphrase`.
+    fn seal(&self, passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>, EncryptedStorageError> {
+        let rand = self.0.rand();
+        let storage_key: [u8; KEY_LEN] =
+            rand.random_array().map_err(EncryptedStorageError::Rand)?;
+        let storage_nonce = rand.random_array().map_err(EncryptedStorageError::Rand)?;
+        let header = self.wrap_storage_key(passphrase, &storage_key, storage_nonce)?;
+
+        let ciphertext = self
+            .0
+            .crypto()
+            .aead_encrypt(
+                AEAD,
+                &storage_key,
+                plaintext,
+                &storage_nonce,
+                &header.write()[..VERSION_LEN],
+            )
+            .map_err(EncryptedStorageError::Crypto)?;
+
+        let mut out = header.write();
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// The plaintext of `encrypted`, whose storage key is wrapped under
+    /// `passphrase`.
+    fn open(&self, encrypted: &[u8], passphrase: &str) -> Result<Vec<u8>, EncryptedStorageError> {
+        let header = Header::read(encrypted)?;
+        let storage_key = self.unwrap_storage_key(&header, passphrase)?;
+        self.0
+            .crypto()
+            .aead_decrypt(
+                AEAD,
+                &storage_key,
+                &encrypted[HEADER_LEN..],
+                &header.storage_nonce,
+                &encrypted[..VERSION_LEN],
+            )
+            .map_err(EncryptedStorageError::Crypto)
+    }
+
+    /// The storage encrypted under `passphrase`, see
+    /// `exportStorageEncrypted`.
+    pub(crate) fn export_storage_encrypted_native(
+        &self,
+        passphrase: &str,
+    ) -> Result<Vec<u8>, EncryptedStorageError> {
+        let entries = {
+            // A poisoned lock still holds consistent data, see `transaction`.
+            let values = self
+                .0
+                .storage()
+                .values()
+                .read()
+                .unwrap_or_else(|e| e.into_inner());
+            storage::encode_entries(
+                values.len(),
+                values
+                    .iter()
+                    .map(|(key, value)| (key.as_slice(), value.as_slice())),
+            )
+        };
+
+        self.seal(passphrase, &entries)
+    }
+
+    /// Import the storage `encrypted` under `passphrase`, see
+    /// `importStorageEncrypted`.
+    pub(crate) fn import_storage_encrypted_native(
+        &self,
+        encrypted: &[u8],
+        passphrase: &str,
+    ) -> Result<(), EncryptedStorageError> {
+        let entries = self.open(encrypted, passphrase)?;
+        let entries = storage::decode_entries(&entries).map_err(EncryptedStorageError::Format)?;
+
+        self.0
+            .storage()
+            .write_entries(entries.into_iter().map(|(key, value)| (key, Some(value))))
+            .map_err(EncryptedStorageError::Storage)
+    }
+
+    /// `encrypted` re-encrypted under a new storage key wrapped under
+    /// `new_passphrase`, see `rekeyStorage`.
+    pub(crate) fn rekey_storage_native(
+        &self,
+        encrypted: &[u8],
+        old_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<Vec<u8>, EncryptedStorageError> {
+        let entries = self.open(encrypted, old_passphrase)?;
+        self.seal(new_passphrase, &entries)
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Export the entire provider storage like `exportStorage`, encrypted
+    /// under `passphrase`.
+    ///
+    /// The storage is encrypted in wasm, so its entries never reach JS in
+    /// plaintext. Import it with `importStorageEncrypted`.
+    #[wasm_bindgen(js_name = exportStorageEncrypted)]
+    pub fn export_storage_encrypted(&self, passphrase: &str) -> Result<Vec<u8>, JsError> {
+        Ok(self.export_storage_encrypted_native(passphrase)?)
+    }
+
+    /// Import storage from a blob produced by `exportStorageEncrypted`.
+    ///
+    /// Fails without importing anything if `passphrase` is wrong.
+    #[wasm_bindgen(js_name = importStorageEncrypted)]
+    pub fn import_storage_encrypted(
+        &self,
+        encrypted: &[u8],
+        passphrase: &str,
+    ) -> Result<(), JsError> {
+        Ok(self.import_storage_encrypted_native(encrypted, passphrase)?)
+    }
+
+    /// Change the passphrase of a blob produced by `exportStorageEncrypted`
+    /// from `old_passphrase` to `new_passphrase`.
+    ///
+    /// The storage is decrypted and encrypted again under a new storage key
+    /// inside wasm, so no secrets of the storage reach JS memory. Since the
+    /// storage key changes too, neither `old_passphrase` nor the storage key
+    /// of an older export decrypts the returned blob. The storage of this
+    /// provider is left untouched.
+    #[wasm_bindgen(js_name = rekeyStorage)]
+    pub fn rekey_storage(
+        &self,
+        encrypted: &[u8],
+        old_passphrase: &str,
+        new_passphrase: &str,
+    ) -> Result<Vec<u8>, JsError> {
+        Ok(self.rekey_storage_native(encrypted, old_passphrase, new_passphrase)?)
+    }
+}
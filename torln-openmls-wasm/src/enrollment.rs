@@ -0,0 +1,163 @@
+//! Groups bound to an enrollment secret shared out of band.
+//!
+//! MLS can't inject a PSK into the first epoch of a group, which only has
+//! the founder in it. Instead, the id of the enrollment PSK is recorded in
+//! the group context when the group is created, and every commit that adds
+//! members with `proposeAndCommitAdd` or `addMemberByBytes` includes a PSK
+//! proposal for it. The epoch a new member joins in is thus bound to the
+//! secret, and a welcome can only be processed by someone who stored the
+//! same PSK beforehand, see `Group.joinWithPsk`.
+
+use openmls::{
+    framing::MlsMessageOut,
+    group::{CommitBuilderStageError, CommitToPendingProposalsError, CreateCommitError},
+    prelude::{PreSharedKeyProposal, Proposal},
+    schedule::{errors::PskError, ExternalPsk, PreSharedKeyId, Psk},
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{types::CryptoError, OpenMlsProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    capacity::GroupConfig, extensions, signer::MissingSignerError, Group, Identity, Provider,
+    RatchetTree, CIPHERSUITE,
+};
+
+/// Errors when storing or applying the enrollment PSK.
+#[derive(Debug)]
+pub(crate) enum EnrollmentError {
+    Nonce(CryptoError),
+    Store(PskError),
+    Signer(MissingSignerError),
+    Commit(CommitToPendingProposalsError<TorlnStorageError>),
+    CommitWithPsk(CreateCommitError),
+    Stage(CommitBuilderStageError<TorlnStorageError>),
+}
+
+impl std::fmt::Display for EnrollmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nonce(e) => write!(f, "failed to create PSK nonce: {e}"),
+            Self::Store(e) => write!(f, "failed to store enrollment PSK: {e}"),
+            Self::Signer(e) => write!(f, "{e}"),
+            Self::Commit(e) => write!(f, "failed to commit: {e}"),
+            Self::CommitWithPsk(e) => write!(f, "failed to commit with enrollment PSK: {e}"),
+            Self::Stage(e) => write!(f, "failed to commit with enrollment PSK: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for EnrollmentError {}
+
+/// The id and secret of an enrollment PSK, see
+/// `GroupConfig.setEnrollmentPsk`.
+#[derive(Debug, Clone)]
+pub(crate) struct EnrollmentPsk {
+    pub(crate) psk_id: Vec<u8>,
+    pub(crate) secret: Vec<u8>,
+}
+
+/// Store the external PSK `psk_id` with `secret` in the storage of
+/// `provider`.
+pub(crate) fn store_enrollment_psk(
+    provider: &Provider,
+    psk_id: &[u8],
+    secret: &[u8],
+) -> Result<(), EnrollmentError> {
+    let psk = Psk::External(ExternalPsk::new(psk_id.to_vec()));
+    PreSharedKeyId::new(CIPHERSUITE, provider.0.rand(), psk)
+        .map_err(EnrollmentError::Nonce)?
+        .store(&provider.0, secret)
+        .map_err(EnrollmentError::Store)
+}
+
+impl Group {
+    /// The id of the enrollment PSK of this group, if it has one.
+    pub(crate) fn enrollment_psk_id(&self) -> Option<&[u8]> {
+        extensions::app_extension(
+            self.mls_group.extensions(),
+            extensions::ENROLLMENT_PSK_EXTENSION_TYPE,
+        )
+    }
+
+    /// Commit the pending proposals, which include adds, together with the
+    /// enrollment PSK if the group has one. Returns the commit and the
+    /// welcome.
+    pub(crate) fn commit_adds(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<(MlsMessageOut, Option<MlsMessageOut>), EnrollmentError> {
+        let signer = self
+            .signer(provider, sender)
+            .map_err(EnrollmentError::Signer)?;
+        let Some(psk_id) = self.enrollment_psk_id().map(<[u8]>::to_vec) else {
+            let (commit, welcome, _group_info) = self
+                .mls_group
+                .commit_to_pending_proposals(provider.as_ref(), &signer)
+                .map_err(EnrollmentError::Commit)?;
+            return Ok((commit, welcome));
+        };
+
+        // The PSK is in the storage of every member: the founder stored it
+        // when creating the group, the others when joining.
+        let psk_id = PreSharedKeyId::new(
+            CIPHERSUITE,
+            provider.0.rand(),
+            Psk::External(ExternalPsk::new(psk_id)),
+        )
+        .map_err(EnrollmentError::Nonce)?;
+        let (commit, welcome, _group_info) = self
+            .mls_group
+            .commit_builder()
+            .consume_proposal_store(true)
+            .add_proposal(Proposal::PreSharedKey(Box::new(PreSharedKeyProposal::new(
+                psk_id,
+            ))))
+            .load_psks(provider.0.storage())
+            .map_err(EnrollmentError::CommitWithPsk)?
+            .build(provider.0.rand(), provider.0.crypto(), &signer, |_| true)
+            .map_err(EnrollmentError::CommitWithPsk)?
+            .stage_commit(&provider.0)
+            .map_err(EnrollmentError::Stage)?
+            .into_messages();
+
+        Ok((commit, welcome))
+    }
+}
+
+#[wasm_bindgen]
+impl GroupConfig {
+    /// Bind the group to the enrollment secret `secret`, shared out of band
+    /// under the id `psk_id`.
+    ///
+    /// Every commit adding members with `proposeAndCommitAdd` or
+    /// `addMemberByBytes` then injects the secret as an external PSK, so
+    /// that only those who know it can join, with `Group.joinWithPsk`.
+    #[wasm_bindgen(js_name = setEnrollmentPsk)]
+    pub fn set_enrollment_psk(&mut self, psk_id: Vec<u8>, secret: Vec<u8>) {
+        self.enrollment_psk = Some(EnrollmentPsk { psk_id, secret });
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Join a group bound to an enrollment secret, see
+    /// `GroupConfig.setEnrollmentPsk`.
+    ///
+    /// Stores the secret under `psk_id` before processing the welcome, and
+    /// keeps it for adding members later. Joining fails if the secret
+    /// doesn't match the one of the group.
+    #[wasm_bindgen(js_name = joinWithPsk)]
+    pub fn join_with_psk(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+        psk_id: &[u8],
+        secret: &[u8],
+    ) -> Result<Group, JsError> {
+        store_enrollment_psk(provider, psk_id, secret)?;
+
+        Group::join(provider, welcome, ratchet_tree)
+    }
+}
@@ -0,0 +1,58 @@
+//! Ephemeral messages, which recipients delete after a time to live.
+//!
+//! The time to live travels in the authenticated data of the message, so
+//! the delivery service can see it, e.g. to expire the message in the
+//! mailbox as well, but can't change or strip it: the authenticated data is
+//! covered by the AEAD of the ciphertext.
+//!
+//! Layout of the authenticated data: the label "torln ttl" followed by the
+//! time to live in seconds as a big-endian `u32`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{mls_message_to_u8vec, Group, Identity, Provider};
+
+/// Prefix of the authenticated data of ephemeral messages.
+const EPHEMERAL_AAD_LABEL: &[u8] = b"torln ttl";
+
+/// The authenticated data for a message with a time to live of
+/// `ttl_seconds`.
+fn ephemeral_aad(ttl_seconds: u32) -> Vec<u8> {
+    [EPHEMERAL_AAD_LABEL, &ttl_seconds.to_be_bytes()].concat()
+}
+
+/// The time to live in the authenticated data `aad`, if it is the one of an
+/// ephemeral message.
+pub(crate) fn ttl_of(aad: &[u8]) -> Option<u32> {
+    let ttl_seconds = aad.strip_prefix(EPHEMERAL_AAD_LABEL)?;
+
+    Some(u32::from_be_bytes(ttl_seconds.try_into().ok()?))
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `createMessage`, but marks the message to be deleted
+    /// `ttl_seconds` after it was received.
+    ///
+    /// Recipients find the time to live in `ttlSeconds` of the result of
+    /// `processMessageDetailed`. It is sent unencrypted but authenticated,
+    /// so a relay that alters it makes the message fail to decrypt.
+    #[wasm_bindgen(js_name = createEphemeralMessage)]
+    pub fn create_ephemeral_message(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        msg: &[u8],
+        ttl_seconds: u32,
+    ) -> Result<Vec<u8>, JsError> {
+        let signer = self.signer(provider, sender)?;
+        self.mls_group.set_aad(ephemeral_aad(ttl_seconds));
+        let message = self
+            .mls_group
+            .create_message(provider.as_ref(), &signer, msg);
+        // The time to live is only meant for this message.
+        self.mls_group.set_aad(Vec::new());
+
+        Ok(mls_message_to_u8vec(&message?))
+    }
+}
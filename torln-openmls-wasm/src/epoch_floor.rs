@@ -0,0 +1,107 @@
+//! A per-group minimum epoch for decrypting application messages.
+//!
+//! How many past epochs a group keeps secrets for is a matter of message
+//! ordering. Whether old messages may still be read is a matter of policy,
+//! e.g. "no reading the backlog from before the device was verified". The
+//! floor is kept in the provider storage, so that it survives reloading the
+//! group, and is checked on the epoch in the header of a message before
+//! anything is decrypted.
+
+use openmls::group::GroupId;
+use openmls_torln_storage::TorlnStorageError;
+use wasm_bindgen::prelude::*;
+
+use crate::{processing::ProcessError, routing, Group, Provider};
+
+/// Prefix of the storage keys of the minimum decryption epochs.
+pub(crate) const MIN_DECRYPT_EPOCH_STORAGE_LABEL: &[u8] = b"TorlnMinDecryptEpoch";
+
+/// The storage key of the minimum decryption epoch of the group `group_id`.
+fn min_decrypt_epoch_key(group_id: &GroupId) -> Vec<u8> {
+    let group_id = group_id.as_slice();
+
+    let mut key = MIN_DECRYPT_EPOCH_STORAGE_LABEL.to_vec();
+    key.extend_from_slice(&(group_id.len() as u32).to_be_bytes());
+    key.extend_from_slice(group_id);
+    key
+}
+
+impl Provider {
+    /// The minimum decryption epoch of the group `group_id`, see
+    /// `setMinDecryptEpoch`.
+    pub(crate) fn min_decrypt_epoch(&self, group_id: &GroupId) -> u64 {
+        // A poisoned lock still holds consistent data, see `transaction`.
+        let values = self
+            .0
+            .storage()
+            .values()
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+
+        values
+            .get(&min_decrypt_epoch_key(group_id))
+            .and_then(|value| value.as_slice().try_into().ok())
+            .map(u64::from_be_bytes)
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn set_min_decrypt_epoch_native(
+        &self,
+        group_id: &GroupId,
+        epoch: u64,
+    ) -> Result<(), TorlnStorageError> {
+        let value = (epoch != 0).then(|| epoch.to_be_bytes().to_vec());
+        self.0
+            .storage()
+            .write_entries([(min_decrypt_epoch_key(group_id), value)])
+    }
+}
+
+impl Group {
+    /// Refuse the application message `message` if it is from an epoch below
+    /// the minimum decryption epoch of this group.
+    pub(crate) fn check_decrypt_epoch(
+        &self,
+        provider: &Provider,
+        message: &[u8],
+    ) -> Result<(), ProcessError> {
+        // Messages without a readable header fail when they are parsed.
+        let Ok(header) = routing::header_of(message) else {
+            return Ok(());
+        };
+        let min_epoch = provider.min_decrypt_epoch(self.mls_group.group_id());
+        if header.content_type == routing::CONTENT_TYPE_APPLICATION && header.epoch < min_epoch {
+            return Err(ProcessError::BelowMinEpoch {
+                message_epoch: header.epoch,
+                min_epoch,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Refuse to decrypt application messages of the group `group_id` from
+    /// epochs below `epoch`, even if the group still has the secrets for
+    /// them.
+    ///
+    /// `processMessage` then fails with a "below minimum epoch" error for
+    /// such messages, which is distinct from the error for messages whose
+    /// epoch the group no longer has secrets for. Handshake messages are not
+    /// affected. The floor is stored with the groups; an epoch of `0`
+    /// removes it.
+    #[wasm_bindgen(js_name = setMinDecryptEpoch)]
+    pub fn set_min_decrypt_epoch(&self, group_id: &str, epoch: u32) -> Result<(), JsError> {
+        self.set_min_decrypt_epoch_bytes(group_id.as_bytes(), epoch)
+    }
+
+    /// Like `setMinDecryptEpoch`, with the group id given as bytes, for ids
+    /// that aren't valid UTF-8.
+    #[wasm_bindgen(js_name = setMinDecryptEpochBytes)]
+    pub fn set_min_decrypt_epoch_bytes(&self, group_id: &[u8], epoch: u32) -> Result<(), JsError> {
+        self.set_min_decrypt_epoch_native(&GroupId::from_slice(group_id), epoch.into())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! Application-defined group context extensions.
+//!
+//! MLS lets applications carry their own data in the group context through
+//! extensions of an "unknown" type. Every extension type we put there must be
+//! advertised in the capabilities of all leaves and listed in the group's
+//! required capabilities, otherwise the GroupContextExtensions proposal is
+//! rejected by the other members.
+
+use openmls::{
+    credentials::Credential,
+    extensions::{
+        errors::InvalidExtensionError, Extension, ExtensionType, Extensions,
+        RequiredCapabilitiesExtension, UnknownExtension,
+    },
+    group::GroupContext,
+    messages::proposals::ProposalType,
+    prelude::Capabilities,
+    treesync::errors::LeafNodeValidationError,
+};
+use tls_codec::{Serialize, TlsDeserialize, TlsSerialize, TlsSize};
+
+/// Extension type carrying the human-readable group name (UTF-8).
+pub(crate) const GROUP_NAME_EXTENSION_TYPE: u16 = 0xf100;
+
+/// Extension type carrying the [`FounderInfo`] set when the group is created.
+pub(crate) const FOUNDER_INFO_EXTENSION_TYPE: u16 = 0xf101;
+
+/// Extension type carrying the maximum number of members (big-endian u32).
+pub(crate) const MAX_MEMBERS_EXTENSION_TYPE: u16 = 0xf102;
+
+/// Extension type carrying the id of the enrollment PSK, see `enrollment`.
+pub(crate) const ENROLLMENT_PSK_EXTENSION_TYPE: u16 = 0xf103;
+
+/// All application-defined extension types understood by this crate.
+const APP_EXTENSION_TYPES: &[u16] = &[
+    GROUP_NAME_EXTENSION_TYPE,
+    FOUNDER_INFO_EXTENSION_TYPE,
+    MAX_MEMBERS_EXTENSION_TYPE,
+    ENROLLMENT_PSK_EXTENSION_TYPE,
+];
+
+/// Custom proposal type promoting a member to admin. The payload is
+/// interpreted by the application.
+pub(crate) const PROMOTE_TO_ADMIN_PROPOSAL_TYPE: u16 = 0xf000;
+
+/// All application-defined proposal types understood by this crate.
+pub(crate) const APP_PROPOSAL_TYPES: &[u16] = &[PROMOTE_TO_ADMIN_PROPOSAL_TYPE];
+
+/// Who created the group and when.
+#[derive(Debug, Clone, PartialEq, TlsSerialize, TlsDeserialize, TlsSize)]
+pub(crate) struct FounderInfo {
+    pub(crate) credential: Credential,
+    /// Unix time in seconds.
+    pub(crate) created_at: u64,
+}
+
+fn app_extension_types() -> Vec<ExtensionType> {
+    APP_EXTENSION_TYPES
+        .iter()
+        .map(|&t| ExtensionType::Unknown(t))
+        .collect()
+}
+
+/// The leaf node capabilities used for group creation and key packages.
+pub(crate) fn capabilities() -> Capabilities {
+    Capabilities::builder()
+        .extensions(app_extension_types())
+        .proposals(
+            std::iter::once(ProposalType::SelfRemove)
+                .chain(APP_PROPOSAL_TYPES.iter().map(|&t| ProposalType::Custom(t)))
+                .collect(),
+        )
+        .build()
+}
+
+/// Check that `capabilities` cover the extension, proposal and credential
+/// types in `required`, as openmls does for the leaves of new members.
+///
+/// Extension and proposal types every MLS client implements count as
+/// supported without being listed.
+pub(crate) fn supports_required_capabilities(
+    capabilities: &Capabilities,
+    required: &RequiredCapabilitiesExtension,
+) -> Result<(), LeafNodeValidationError> {
+    let is_default_extension = |extension_type: &ExtensionType| {
+        matches!(
+            extension_type,
+            ExtensionType::ApplicationId
+                | ExtensionType::RatchetTree
+                | ExtensionType::RequiredCapabilities
+                | ExtensionType::ExternalPub
+                | ExtensionType::ExternalSenders
+        )
+    };
+    let is_default_proposal = |proposal_type: &ProposalType| {
+        matches!(
+            proposal_type,
+            ProposalType::Add
+                | ProposalType::Update
+                | ProposalType::Remove
+                | ProposalType::PreSharedKey
+                | ProposalType::Reinit
+                | ProposalType::ExternalInit
+                | ProposalType::GroupContextExtensions
+        )
+    };
+
+    if !required.extension_types().iter().all(|extension_type| {
+        is_default_extension(extension_type) || capabilities.extensions().contains(extension_type)
+    }) {
+        return Err(LeafNodeValidationError::UnsupportedExtensions);
+    }
+    if !required.proposal_types().iter().all(|proposal_type| {
+        is_default_proposal(proposal_type) || capabilities.proposals().contains(proposal_type)
+    }) {
+        return Err(LeafNodeValidationError::UnsupportedProposals);
+    }
+    if !required
+        .credential_types()
+        .iter()
+        .all(|credential_type| capabilities.credentials().contains(credential_type))
+    {
+        return Err(LeafNodeValidationError::UnsupportedCredentials);
+    }
+
+    Ok(())
+}
+
+/// Read an application-defined extension from the group context.
+pub(crate) fn app_extension(
+    extensions: &Extensions<GroupContext>,
+    extension_type: u16,
+) -> Option<&[u8]> {
+    extensions
+        .unknown(extension_type)
+        .map(|extension| extension.0.as_slice())
+}
+
+/// Returns a copy of `extensions` with the application-defined extension set
+/// to `data`, and the required capabilities extended to cover its type.
+pub(crate) fn with_app_extension(
+    extensions: &Extensions<GroupContext>,
+    extension_type: u16,
+    data: Vec<u8>,
+) -> Result<Extensions<GroupContext>, InvalidExtensionError> {
+    let mut extensions = extensions.clone();
+
+    let required_type = ExtensionType::Unknown(extension_type);
+    let required = match extensions.required_capabilities() {
+        Some(required) => {
+            let mut extension_types = required.extension_types().to_vec();
+            if !extension_types.contains(&required_type) {
+                extension_types.push(required_type);
+            }
+            RequiredCapabilitiesExtension::new(
+                &extension_types,
+                required.proposal_types(),
+                required.credential_types(),
+            )
+        }
+        None => RequiredCapabilitiesExtension::new(&[required_type], &[], &[]),
+    };
+
+    extensions.add_or_replace(Extension::RequiredCapabilities(required))?;
+    extensions.add_or_replace(Extension::Unknown(extension_type, UnknownExtension(data)))?;
+
+    Ok(extensions)
+}
+
+/// The group context extensions of a new group founded by `founder`.
+pub(crate) fn founding_extensions(
+    founder: &Credential,
+    created_at: u64,
+) -> Result<Extensions<GroupContext>, tls_codec::Error> {
+    let founder_info = FounderInfo {
+        credential: founder.clone(),
+        created_at,
+    }
+    .tls_serialize_detached()?;
+
+    // The extensions are empty, so they are always valid.
+    Ok(with_app_extension(
+        &Extensions::empty(),
+        FOUNDER_INFO_EXTENSION_TYPE,
+        founder_info,
+    )
+    .expect("founder info is a valid group context extension"))
+}
@@ -0,0 +1,81 @@
+//! The external senders of a group, who may send proposals without being
+//! members.
+//!
+//! They are listed in the ExternalSenders group context extension, which
+//! every member has. openmls only uses them to verify external proposals,
+//! so their fields are read from the TLS encoding of each entry.
+
+use openmls::credentials::Credential;
+use tls_codec::{Deserialize, Serialize, TlsDeserialize, VLBytes};
+use wasm_bindgen::prelude::*;
+
+use crate::Group;
+
+/// The fields of an external sender, which openmls doesn't expose. Read
+/// from its TLS encoding, as defined in RFC 9420.
+#[derive(TlsDeserialize)]
+struct ExternalSenderFields {
+    signature_key: VLBytes,
+    credential: Credential,
+}
+
+/// A sender the group accepts proposals from without being a member, see
+/// `Group.externalSenders`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalSenderInfo {
+    signature_key: Vec<u8>,
+    credential: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl ExternalSenderInfo {
+    /// The signature public key its proposals are verified with.
+    #[wasm_bindgen(getter, js_name = signatureKey)]
+    pub fn signature_key(&self) -> Vec<u8> {
+        self.signature_key.clone()
+    }
+    /// The TLS-serialized credential.
+    #[wasm_bindgen(getter)]
+    pub fn credential(&self) -> Vec<u8> {
+        self.credential.clone()
+    }
+}
+
+impl Group {
+    /// The external senders of the group, see `externalSenders`.
+    pub(crate) fn external_senders_native(
+        &self,
+    ) -> Result<Vec<ExternalSenderInfo>, tls_codec::Error> {
+        let Some(external_senders) = self.mls_group.extensions().external_senders() else {
+            return Ok(Vec::new());
+        };
+
+        external_senders
+            .iter()
+            .map(|external_sender| {
+                let bytes = external_sender.tls_serialize_detached()?;
+                let fields = ExternalSenderFields::tls_deserialize_exact(bytes)?;
+                Ok(ExternalSenderInfo {
+                    signature_key: fields.signature_key.into(),
+                    credential: fields.credential.tls_serialize_detached()?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// The senders that may send proposals to the group without being
+    /// members, e.g. servers, in the order of the ExternalSenders extension.
+    /// Empty if the group has none.
+    ///
+    /// External proposals name their sender by position in this list, and
+    /// are only accepted if signed with its `signatureKey`. Set with
+    /// `GroupConfigBuilder.externalSender` when creating the group.
+    #[wasm_bindgen(js_name = externalSenders)]
+    pub fn external_senders(&self) -> Result<Vec<ExternalSenderInfo>, JsError> {
+        Ok(self.external_senders_native()?)
+    }
+}
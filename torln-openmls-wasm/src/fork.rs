@@ -0,0 +1,124 @@
+//! Detecting that the local state of a group diverged from the group.
+//!
+//! If two members commit concurrently and the delivery service accepts
+//! both, clients that merged different commits end up in the same epoch
+//! with different trees and transcripts. They can't read each other's
+//! messages, and nothing tells them why. Comparing the local group context
+//! with the one in a group info the delivery service vouches for, e.g. the
+//! latest one it accepted or one a quorum of members agrees on, shows which
+//! side of the fork a client is on.
+
+use std::cmp::Ordering;
+
+use openmls::group::GroupId;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    message_size::MessageTooLarge,
+    routing::{self, RoutingError},
+    Group, Provider,
+};
+
+/// How the local state of a group compares to a trusted group info, see
+/// `Group.detectFork`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkStatus {
+    /// Same epoch, tree and transcript.
+    InSync,
+    /// Same epoch, but a different tree or transcript.
+    Forked,
+    /// The group info is of a later epoch.
+    Behind,
+    /// The group info is of an earlier epoch.
+    Ahead,
+}
+
+/// Errors when comparing the group with a group info.
+#[derive(Debug)]
+pub(crate) enum ForkCheckError {
+    TooLarge(MessageTooLarge),
+    GroupInfo(RoutingError),
+    OtherGroup(GroupId),
+}
+
+impl std::fmt::Display for ForkCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::GroupInfo(e) => write!(f, "invalid group info: {e}"),
+            Self::OtherGroup(group_id) => write!(
+                f,
+                "group info is for group {}",
+                String::from_utf8_lossy(group_id.as_slice())
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ForkCheckError {}
+
+impl Group {
+    /// Compare this group with the serialized `group_info`, see
+    /// `detectFork`.
+    pub(crate) fn fork_status(
+        &self,
+        provider: &Provider,
+        group_info: &[u8],
+    ) -> Result<ForkStatus, ForkCheckError> {
+        provider
+            .check_message_size(group_info)
+            .map_err(ForkCheckError::TooLarge)?;
+        let trusted =
+            routing::group_info_context_of(group_info).map_err(ForkCheckError::GroupInfo)?;
+        let local = self.mls_group.export_group_context();
+        if trusted.group_id() != local.group_id() {
+            return Err(ForkCheckError::OtherGroup(trusted.group_id().clone()));
+        }
+
+        Ok(match trusted.epoch().cmp(&local.epoch()) {
+            Ordering::Greater => ForkStatus::Behind,
+            Ordering::Less => ForkStatus::Ahead,
+            Ordering::Equal
+                if trusted.tree_hash() == local.tree_hash()
+                    && trusted.confirmed_transcript_hash() == local.confirmed_transcript_hash() =>
+            {
+                ForkStatus::InSync
+            }
+            Ordering::Equal => ForkStatus::Forked,
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Check whether this client is on a fork of the group, by comparing
+    /// the current epoch with a serialized `group_info` from a source the
+    /// app trusts to follow the group, such as the delivery service.
+    ///
+    /// The signature of the group info is not checked: on a fork, the
+    /// local tree may not have the key of the member who signed it. Only
+    /// pass group infos from the trusted source.
+    ///
+    /// - `InSync`: the group info is of the current epoch, and the tree
+    ///   hash and confirmed transcript hash match.
+    /// - `Forked`: same epoch, but a different tree hash or transcript
+    ///   hash. This client merged a commit the rest of the group didn't.
+    ///   It can't rejoin the group by processing messages; discard the
+    ///   local group and rejoin with `Group.recoverFromGroupInfo`, using
+    ///   the trusted group info and its ratchet tree.
+    /// - `Behind`: the group info is of a later epoch. Process the missing
+    ///   commits and check again; if they can't be processed, e.g. because
+    ///   they build on a forked epoch, recover as for `Forked`.
+    /// - `Ahead`: the group info is of an earlier epoch; get a newer one.
+    ///
+    /// Fails if the group info is for another group or can't be parsed.
+    #[wasm_bindgen(js_name = detectFork)]
+    pub fn detect_fork(
+        &self,
+        provider: &Provider,
+        group_info: &[u8],
+    ) -> Result<ForkStatus, JsError> {
+        Ok(self.fork_status(provider, group_info)?)
+    }
+}
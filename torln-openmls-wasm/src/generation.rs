@@ -0,0 +1,46 @@
+//! Sender ratchet generations, for rotating keys before a ratchet runs out.
+//!
+//! Every application message advances the sender's ratchet by one
+//! generation. A self update starts a new epoch with fresh ratchets, so apps
+//! should trigger one when the own generation reaches a watermark.
+
+use openmls::prelude::LeafNodeIndex;
+use wasm_bindgen::prelude::*;
+
+use crate::Group;
+
+/// The generation at which `generationWatermarkReached` turns `true`, unless
+/// configured otherwise with `setGenerationWatermark`.
+pub(crate) const DEFAULT_GENERATION_WATERMARK: u32 = 10_000;
+
+#[wasm_bindgen]
+impl Group {
+    /// The generation of the application message ratchet of the member at
+    /// `sender_leaf_index` in the current epoch, or `undefined` if there is no
+    /// such leaf.
+    ///
+    /// For the own leaf this is the number of messages sent in this epoch.
+    /// For other members it only reflects the messages received so far.
+    #[wasm_bindgen(js_name = currentGeneration)]
+    pub fn current_generation(&self, sender_leaf_index: u32) -> Option<u32> {
+        self.mls_group
+            .application_generation(LeafNodeIndex::new(sender_leaf_index))
+    }
+
+    /// Set the own generation at which `generationWatermarkReached` turns
+    /// `true`. The watermark isn't persisted and must be set again after
+    /// loading the group.
+    #[wasm_bindgen(js_name = setGenerationWatermark)]
+    pub fn set_generation_watermark(&mut self, watermark: u32) {
+        self.generation_watermark = watermark;
+    }
+
+    /// Whether the own generation has reached the watermark, i.e. the app
+    /// should send a self update.
+    #[wasm_bindgen(js_name = generationWatermarkReached)]
+    pub fn generation_watermark_reached(&self) -> bool {
+        let own_index = self.mls_group.own_leaf_index().u32();
+        self.current_generation(own_index)
+            .is_some_and(|generation| generation >= self.generation_watermark)
+    }
+}
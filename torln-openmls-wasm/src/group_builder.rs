@@ -0,0 +1,404 @@
+//! Creating groups with the openmls group configuration exposed.
+//!
+//! `createNew` fixes the configuration of the groups it creates. The
+//! builder collects the options to change, checks them in `build`, and
+//! `createNewWithBuilder` applies them on top of that same default
+//! configuration, so that new options don't need another `createNewWithX`.
+
+use openmls::{
+    credentials::{Credential, CredentialType},
+    extensions::{
+        Extension, ExtensionType, Extensions, ExternalSender, RequiredCapabilitiesExtension,
+    },
+    group::{
+        GroupContext, GroupId, IncomingWireFormatPolicy, MlsGroup, NewGroupError,
+        OutgoingWireFormatPolicy, WireFormatPolicy, WIRE_FORMAT_POLICIES,
+    },
+    messages::proposals::ProposalType,
+};
+use openmls_torln_storage::TorlnStorageError;
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    extensions, utils, wire_format::WireFormat, Group, Identity, Provider, CIPHERSUITE,
+    WIRE_FORMAT_POLICY,
+};
+
+/// Errors in the options of a `GroupConfigBuilder`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum GroupConfigError {
+    UnsupportedCiphersuite(u16),
+    InvalidWireFormatPolicy {
+        outgoing: WireFormat,
+        incoming: WireFormat,
+    },
+    /// The credential of the external sender at this position can't be
+    /// parsed.
+    InvalidExternalSender(usize),
+}
+
+impl std::fmt::Display for GroupConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedCiphersuite(ciphersuite) => {
+                write!(f, "unsupported ciphersuite {ciphersuite:#06x}")
+            }
+            Self::InvalidWireFormatPolicy { outgoing, incoming } => write!(
+                f,
+                "invalid wire format policy: outgoing {outgoing:?}, incoming {incoming:?}"
+            ),
+            Self::InvalidExternalSender(index) => {
+                write!(f, "external sender {index} has an invalid credential")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GroupConfigError {}
+
+/// The openmls wire format policy sending `outgoing` and accepting
+/// `incoming`, if openmls has one.
+fn wire_format_policy(
+    outgoing: WireFormat,
+    incoming: WireFormat,
+) -> Result<WireFormatPolicy, GroupConfigError> {
+    let outgoing_policy = match outgoing {
+        WireFormat::Ciphertext => Some(OutgoingWireFormatPolicy::AlwaysCiphertext),
+        WireFormat::Plaintext => Some(OutgoingWireFormatPolicy::AlwaysPlaintext),
+        WireFormat::Mixed => None,
+    };
+    let incoming_policy = match incoming {
+        WireFormat::Ciphertext => IncomingWireFormatPolicy::AlwaysCiphertext,
+        WireFormat::Plaintext => IncomingWireFormatPolicy::AlwaysPlaintext,
+        WireFormat::Mixed => IncomingWireFormatPolicy::Mixed,
+    };
+
+    WIRE_FORMAT_POLICIES
+        .into_iter()
+        .find(|policy| {
+            Some(policy.outgoing()) == outgoing_policy && policy.incoming() == incoming_policy
+        })
+        .ok_or(GroupConfigError::InvalidWireFormatPolicy { outgoing, incoming })
+}
+
+/// Options for `Group.createNewWithBuilder`, set with chainable setters.
+///
+/// Options that aren't set keep the configuration of `createNew`.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone)]
+pub struct GroupConfigBuilder {
+    ciphersuite: Option<u16>,
+    wire_format_policy: Option<(WireFormat, WireFormat)>,
+    max_past_epochs: Option<u32>,
+    padding_size: Option<u32>,
+    use_ratchet_tree_extension: Option<bool>,
+    required_capabilities: Option<(Vec<u16>, Vec<u16>, Vec<u16>)>,
+    extensions: Vec<(u16, Vec<u8>)>,
+    external_senders: Vec<(Vec<u8>, Vec<u8>)>,
+    record_founder: bool,
+}
+
+#[wasm_bindgen]
+impl GroupConfigBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GroupConfigBuilder {
+        GroupConfigBuilder::default()
+    }
+
+    /// The ciphersuite of the group. Only the ciphersuite of this client,
+    /// see `ciphersuiteParams`, is supported; `build` fails for others.
+    pub fn ciphersuite(mut self, ciphersuite: u16) -> GroupConfigBuilder {
+        self.ciphersuite = Some(ciphersuite);
+        self
+    }
+
+    /// How handshake messages are sent and accepted. `outgoing` is either
+    /// `Ciphertext` or `Plaintext`, and `incoming` either the same or
+    /// `Mixed`. The default is `Ciphertext` for both; public SelfRemove
+    /// proposals are accepted under any policy, see `proposeSelfRemove`.
+    #[wasm_bindgen(js_name = wireFormatPolicy)]
+    pub fn wire_format_policy(
+        mut self,
+        outgoing: WireFormat,
+        incoming: WireFormat,
+    ) -> GroupConfigBuilder {
+        self.wire_format_policy = Some((outgoing, incoming));
+        self
+    }
+
+    /// How many past epochs to keep the message secrets of, for messages
+    /// that arrive after a commit. Defaults to `0`.
+    #[wasm_bindgen(js_name = maxPastEpochs)]
+    pub fn max_past_epochs(mut self, max_past_epochs: u32) -> GroupConfigBuilder {
+        self.max_past_epochs = Some(max_past_epochs);
+        self
+    }
+
+    /// Pad encrypted messages to a multiple of `padding_size` bytes, to
+    /// hide their exact length. Defaults to `0`, no padding.
+    #[wasm_bindgen(js_name = paddingSize)]
+    pub fn padding_size(mut self, padding_size: u32) -> GroupConfigBuilder {
+        self.padding_size = Some(padding_size);
+        self
+    }
+
+    /// Whether welcomes and group infos of commits carry the ratchet tree,
+    /// so that joiners don't need `exportRatchetTree`. Defaults to `false`.
+    #[wasm_bindgen(js_name = useRatchetTreeExtension)]
+    pub fn use_ratchet_tree_extension(
+        mut self,
+        use_ratchet_tree_extension: bool,
+    ) -> GroupConfigBuilder {
+        self.use_ratchet_tree_extension = Some(use_ratchet_tree_extension);
+        self
+    }
+
+    /// Extension, proposal and credential types every member must support,
+    /// in addition to the extension types of this app. Members can only be
+    /// added if their capabilities list all of them, including the founder.
+    #[wasm_bindgen(js_name = requiredCapabilities)]
+    pub fn required_capabilities(
+        mut self,
+        extension_types: Vec<u16>,
+        proposal_types: Vec<u16>,
+        credential_types: Vec<u16>,
+    ) -> GroupConfigBuilder {
+        self.required_capabilities = Some((extension_types, proposal_types, credential_types));
+        self
+    }
+
+    /// Add the application-defined group context extension
+    /// `extension_type` with `data`. Can be called once per type; a later
+    /// call for the same type replaces the data.
+    pub fn extension(mut self, extension_type: u16, data: Vec<u8>) -> GroupConfigBuilder {
+        self.extensions.retain(|(t, _)| *t != extension_type);
+        self.extensions.push((extension_type, data));
+        self
+    }
+
+    /// Allow the holder of `signature_key`, e.g. a server, to send
+    /// proposals to the group without being a member. `credential` is its
+    /// TLS-serialized credential. Can be called once per external sender;
+    /// members read them with `Group.externalSenders`.
+    #[wasm_bindgen(js_name = externalSender)]
+    pub fn external_sender(
+        mut self,
+        signature_key: Vec<u8>,
+        credential: Vec<u8>,
+    ) -> GroupConfigBuilder {
+        self.external_senders.push((signature_key, credential));
+        self
+    }
+
+    /// Record the founder and the creation time in the group context, see
+    /// `Group.founderInfo`. Members must then support the founder info
+    /// extension type. Defaults to `false`.
+    #[wasm_bindgen(js_name = recordFounder)]
+    pub fn record_founder(mut self, record_founder: bool) -> GroupConfigBuilder {
+        self.record_founder = record_founder;
+        self
+    }
+
+    /// Check the options and return the configuration for
+    /// `Group.createNewWithBuilder`.
+    pub fn build(self) -> Result<GroupCreationConfig, JsError> {
+        Ok(self.build_native()?)
+    }
+}
+
+impl GroupConfigBuilder {
+    pub(crate) fn build_native(self) -> Result<GroupCreationConfig, GroupConfigError> {
+        if let Some(ciphersuite) = self.ciphersuite {
+            if ciphersuite != u16::from(CIPHERSUITE) {
+                return Err(GroupConfigError::UnsupportedCiphersuite(ciphersuite));
+            }
+        }
+        let wire_format_policy = match self.wire_format_policy {
+            Some((outgoing, incoming)) => wire_format_policy(outgoing, incoming)?,
+            None => WIRE_FORMAT_POLICY,
+        };
+        let external_senders = self
+            .external_senders
+            .iter()
+            .enumerate()
+            .map(|(index, (signature_key, credential))| {
+                let credential = Credential::tls_deserialize_exact(credential)
+                    .map_err(|_| GroupConfigError::InvalidExternalSender(index))?;
+                Ok(ExternalSender::new(
+                    signature_key.clone().into(),
+                    credential,
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(GroupCreationConfig {
+            wire_format_policy,
+            max_past_epochs: self.max_past_epochs.map(|n| n as usize),
+            padding_size: self.padding_size.map(|n| n as usize),
+            use_ratchet_tree_extension: self.use_ratchet_tree_extension,
+            required_capabilities: self.required_capabilities,
+            extensions: self.extensions,
+            external_senders,
+            record_founder: self.record_founder,
+        })
+    }
+}
+
+/// The checked configuration of a new group, see `GroupConfigBuilder`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct GroupCreationConfig {
+    wire_format_policy: WireFormatPolicy,
+    max_past_epochs: Option<usize>,
+    padding_size: Option<usize>,
+    use_ratchet_tree_extension: Option<bool>,
+    required_capabilities: Option<(Vec<u16>, Vec<u16>, Vec<u16>)>,
+    extensions: Vec<(u16, Vec<u8>)>,
+    external_senders: Vec<ExternalSender>,
+    record_founder: bool,
+}
+
+impl Default for GroupCreationConfig {
+    /// The configuration of `createNew`.
+    fn default() -> Self {
+        Self {
+            wire_format_policy: WIRE_FORMAT_POLICY,
+            max_past_epochs: None,
+            padding_size: None,
+            use_ratchet_tree_extension: None,
+            required_capabilities: None,
+            extensions: Vec::new(),
+            external_senders: Vec::new(),
+            record_founder: false,
+        }
+    }
+}
+
+impl GroupCreationConfig {
+    /// `group_context_extensions` with the extensions and required
+    /// capabilities of this configuration added.
+    fn group_context_extensions(
+        &self,
+        mut group_context_extensions: Extensions<GroupContext>,
+    ) -> Result<Extensions<GroupContext>, JsError> {
+        if !self.external_senders.is_empty() {
+            group_context_extensions
+                .add_or_replace(Extension::ExternalSenders(self.external_senders.clone()))?;
+        }
+        for (extension_type, data) in &self.extensions {
+            group_context_extensions = extensions::with_app_extension(
+                &group_context_extensions,
+                *extension_type,
+                data.clone(),
+            )?;
+        }
+
+        if let Some((extension_types, proposal_types, credential_types)) =
+            &self.required_capabilities
+        {
+            let current = group_context_extensions.required_capabilities();
+            let mut required_extension_types = current
+                .map(|required| required.extension_types().to_vec())
+                .unwrap_or_default();
+            let mut required_proposal_types = current
+                .map(|required| required.proposal_types().to_vec())
+                .unwrap_or_default();
+            let mut required_credential_types = current
+                .map(|required| required.credential_types().to_vec())
+                .unwrap_or_default();
+            for &t in extension_types {
+                let t = ExtensionType::from(t);
+                if !required_extension_types.contains(&t) {
+                    required_extension_types.push(t);
+                }
+            }
+            for &t in proposal_types {
+                let t = ProposalType::from(t);
+                if !required_proposal_types.contains(&t) {
+                    required_proposal_types.push(t);
+                }
+            }
+            for &t in credential_types {
+                let t = CredentialType::from(t);
+                if !required_credential_types.contains(&t) {
+                    required_credential_types.push(t);
+                }
+            }
+
+            group_context_extensions.add_or_replace(Extension::RequiredCapabilities(
+                RequiredCapabilitiesExtension::new(
+                    &required_extension_types,
+                    &required_proposal_types,
+                    &required_credential_types,
+                ),
+            ))?;
+        }
+
+        Ok(group_context_extensions)
+    }
+}
+
+impl Group {
+    /// Create a group with the configuration `config`. The group context
+    /// extensions of `config` are added to `group_context_extensions`
+    /// separately, see `createNewWithBuilder`.
+    pub(crate) fn build_configured(
+        provider: &Provider,
+        founder: &Identity,
+        group_id: &[u8],
+        group_context_extensions: Extensions<GroupContext>,
+        config: &GroupCreationConfig,
+    ) -> Result<Group, NewGroupError<TorlnStorageError>> {
+        let mut builder = MlsGroup::builder()
+            .ciphersuite(CIPHERSUITE)
+            .with_capabilities(extensions::capabilities())
+            .with_wire_format_policy(config.wire_format_policy)
+            .with_group_context_extensions(group_context_extensions)
+            .with_group_id(GroupId::from_slice(group_id));
+        if let Some(max_past_epochs) = config.max_past_epochs {
+            builder = builder.max_past_epochs(max_past_epochs);
+        }
+        if let Some(padding_size) = config.padding_size {
+            builder = builder.padding_size(padding_size);
+        }
+        if let Some(use_ratchet_tree_extension) = config.use_ratchet_tree_extension {
+            builder = builder.use_ratchet_tree_extension(use_ratchet_tree_extension);
+        }
+
+        let mls_group =
+            builder.build(&provider.0, &founder.keypair, founder.credential_with_key())?;
+
+        Ok(mls_group.into())
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `createNew`, with the options of a `GroupConfigBuilder`.
+    ///
+    /// Fails if the founder doesn't support the required capabilities or
+    /// the extensions.
+    #[wasm_bindgen(js_name = createNewWithBuilder)]
+    pub fn create_new_with_builder(
+        provider: &Provider,
+        founder: &Identity,
+        group_id: &str,
+        config: &GroupCreationConfig,
+    ) -> Result<Group, JsError> {
+        let founding_extensions = if config.record_founder {
+            extensions::founding_extensions(&founder.credential, utils::unix_time_secs())?
+        } else {
+            Extensions::empty()
+        };
+        let group_context_extensions = config.group_context_extensions(founding_extensions)?;
+
+        Ok(Group::build_configured(
+            provider,
+            founder,
+            group_id.as_bytes(),
+            group_context_extensions,
+            config,
+        )?)
+    }
+}
@@ -0,0 +1,65 @@
+//! Group ids derived from application data.
+//!
+//! Some groups are identified by what they are for rather than by who
+//! created them, e.g. the direct messages between two users. If every
+//! member derives the group id from the same application data, they all
+//! arrive at the same group without agreeing on an id first. The id is the
+//! hash of the data, separated by a label and a namespace so that ids for
+//! different purposes never collide.
+
+use openmls::extensions::Extensions;
+use openmls_rust_crypto::RustCrypto;
+use openmls_traits::{
+    crypto::OpenMlsCrypto,
+    types::{CryptoError, HashType},
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{Group, Identity, Provider};
+
+/// Prefix of the hash input of derived group ids.
+const GROUP_ID_LABEL: &[u8] = b"torln group id";
+
+/// The group id for `input` in `namespace`, see `deriveGroupId`.
+pub(crate) fn group_id_for(namespace: &str, input: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    // The namespace is length-prefixed, so that no namespace and input pair
+    // hashes the same as another one.
+    let mut data = GROUP_ID_LABEL.to_vec();
+    data.extend_from_slice(&(namespace.len() as u32).to_be_bytes());
+    data.extend_from_slice(namespace.as_bytes());
+    data.extend_from_slice(input);
+
+    RustCrypto::default().hash(HashType::Sha2_256, &data)
+}
+
+/// Derive a 32 byte group id from `input`, e.g. the sorted ids of the users
+/// of a direct message group, for use with `Group.createNewFromBytes`.
+///
+/// The id is the SHA-256 hash of `input`, separated by `namespace`: the same
+/// `namespace` and `input` always give the same id, on every client, and
+/// different ones give different ids. Use a `namespace` per kind of group,
+/// e.g. `"dm"`, and encode `input` unambiguously, since only the bytes are
+/// hashed.
+#[wasm_bindgen(js_name = deriveGroupId)]
+pub fn derive_group_id(namespace: &str, input: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(group_id_for(namespace, input)?)
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `createNew`, with the group id given as bytes, e.g. from
+    /// `deriveGroupId`. The id doesn't need to be valid UTF-8.
+    #[wasm_bindgen(js_name = createNewFromBytes)]
+    pub fn create_new_from_bytes(
+        provider: &Provider,
+        founder: &Identity,
+        group_id: &[u8],
+    ) -> Result<Group, JsError> {
+        Ok(Group::build_new(
+            provider,
+            founder,
+            group_id,
+            Extensions::empty(),
+        )?)
+    }
+}
@@ -0,0 +1,74 @@
+//! The identity in basic credentials, i.e. the application's user id.
+//!
+//! Credentials are handed to JS TLS-serialized, so that other credential
+//! types can be added later. For basic credentials, the only kind this
+//! crate creates, the identity is all callers want.
+
+use openmls::{
+    credentials::{errors::BasicCredentialError, BasicCredential, Credential},
+    prelude::LeafNodeIndex,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{BlankLeaf, Group, Identity};
+
+/// Errors when reading the identity of a member.
+#[derive(Debug)]
+pub(crate) enum MemberIdentityError {
+    BlankLeaf(BlankLeaf),
+    Credential(BasicCredentialError),
+}
+
+impl std::fmt::Display for MemberIdentityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BlankLeaf(e) => write!(f, "{e}"),
+            Self::Credential(e) => write!(f, "not a basic credential: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MemberIdentityError {}
+
+/// The identity of the basic credential `credential`.
+fn basic_identity(credential: Credential) -> Result<Vec<u8>, BasicCredentialError> {
+    Ok(BasicCredential::try_from(credential)?.identity().to_vec())
+}
+
+impl Group {
+    /// The identity of the member at `leaf_index`, see `memberIdentity`.
+    pub(crate) fn member_identity_native(
+        &self,
+        leaf_index: u32,
+    ) -> Result<Vec<u8>, MemberIdentityError> {
+        let member = self
+            .mls_group
+            .member_at(LeafNodeIndex::new(leaf_index))
+            .ok_or(MemberIdentityError::BlankLeaf(BlankLeaf(leaf_index)))?;
+
+        basic_identity(member.credential).map_err(MemberIdentityError::Credential)
+    }
+}
+
+#[wasm_bindgen]
+impl Identity {
+    /// The identity in the basic credential, i.e. the name passed to the
+    /// constructor, as bytes.
+    #[wasm_bindgen(js_name = identityBytes)]
+    pub fn identity_bytes(&self) -> Result<Vec<u8>, JsError> {
+        Ok(basic_identity(self.credential.clone())?)
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// The identity in the basic credential of the member at `leaf_index`,
+    /// as bytes, without parsing the TLS-serialized credential in JS.
+    ///
+    /// Fails for blank leaves, indices outside the tree, and members with
+    /// other credential types.
+    #[wasm_bindgen(js_name = memberIdentity)]
+    pub fn member_identity(&self, leaf_index: u32) -> Result<Vec<u8>, JsError> {
+        Ok(self.member_identity_native(leaf_index)?)
+    }
+}
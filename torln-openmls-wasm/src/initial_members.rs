@@ -0,0 +1,66 @@
+//! Creating a group together with its first members.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{mls_message_to_u8vec, Group, Identity, KeyPackage, Provider, RatchetTree};
+
+/// A group created with its initial members, see `Group.createWith`.
+#[wasm_bindgen]
+pub struct GroupWithMembers {
+    pub(crate) group: Group,
+    pub(crate) welcome: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl GroupWithMembers {
+    /// The welcome for the initial members. They join with `Group.join`.
+    #[wasm_bindgen(getter)]
+    pub fn welcome(&self) -> Vec<u8> {
+        self.welcome.clone()
+    }
+
+    #[wasm_bindgen(js_name = exportRatchetTree)]
+    pub fn export_ratchet_tree(&self) -> RatchetTree {
+        self.group.export_ratchet_tree()
+    }
+
+    /// The group itself. Consumes this object.
+    #[wasm_bindgen(js_name = intoGroup)]
+    pub fn into_group(self) -> Group {
+        self.group
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Create a new group that already contains the owners of
+    /// `key_packages`, instead of calling `createNew` and adding them one by
+    /// one.
+    ///
+    /// All members are added in a single commit, which is merged right away,
+    /// so the group starts out in epoch 1 with everyone in it.
+    #[wasm_bindgen(js_name = createWith)]
+    pub fn create_with(
+        provider: &Provider,
+        founder: &Identity,
+        group_id: &str,
+        key_packages: Vec<KeyPackage>,
+    ) -> Result<GroupWithMembers, JsError> {
+        let key_packages = key_packages.into_iter().map(|kp| kp.0).collect::<Vec<_>>();
+        for key_package in &key_packages {
+            provider.check_key_package_lifetime(key_package)?;
+        }
+        let mut group = Group::create_new(provider, founder, group_id);
+
+        let (_commit, welcome, _group_info) =
+            group
+                .mls_group
+                .add_members(&provider.0, &founder.keypair, &key_packages)?;
+        group.mls_group.merge_pending_commit(&provider.0)?;
+
+        Ok(GroupWithMembers {
+            group,
+            welcome: mls_message_to_u8vec(&welcome),
+        })
+    }
+}
@@ -0,0 +1,93 @@
+//! Asking to join a group with an external Add proposal.
+//!
+//! Unlike an external commit, which lets a non-member add themselves, a
+//! join request only proposes the add: a member has to commit it, so the
+//! members decide who gets in. The requester signs an Add of a fresh key
+//! package of theirs for the group and epoch of a group info, and sends it
+//! to the group. Members store the proposal when processing it and commit
+//! it with the other pending proposals; the welcome of that commit lets
+//! the requester join.
+
+use openmls::{
+    group::ProposeAddMemberError, key_packages::errors::KeyPackageNewError,
+    messages::external_proposals::JoinProposal,
+};
+use openmls_torln_storage::{TorlnStorage, TorlnStorageError};
+use wasm_bindgen::prelude::*;
+
+use crate::{message_size::MessageTooLarge, mls_message_to_u8vec, routing, Identity, Provider};
+
+/// Errors when creating a join request.
+#[derive(Debug)]
+pub(crate) enum JoinRequestError {
+    TooLarge(MessageTooLarge),
+    InvalidGroupInfo(routing::RoutingError),
+    KeyPackage(KeyPackageNewError),
+    Propose(ProposeAddMemberError<TorlnStorageError>),
+}
+
+impl std::fmt::Display for JoinRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::InvalidGroupInfo(e) => write!(f, "invalid group info: {e}"),
+            Self::KeyPackage(e) => write!(f, "failed to create key package: {e}"),
+            Self::Propose(e) => write!(f, "failed to create join proposal: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for JoinRequestError {}
+
+impl Identity {
+    /// An external Add proposal of a fresh key package for the group of
+    /// `group_info`, see `createExternalJoinProposal`.
+    pub(crate) fn external_join_proposal(
+        &self,
+        provider: &Provider,
+        group_info: &[u8],
+    ) -> Result<Vec<u8>, JoinRequestError> {
+        provider
+            .check_message_size(group_info)
+            .map_err(JoinRequestError::TooLarge)?;
+        let group_context = routing::group_info_context_of(group_info)
+            .map_err(JoinRequestError::InvalidGroupInfo)?;
+        let key_package = self
+            .build_key_package(provider)
+            .map_err(JoinRequestError::KeyPackage)?;
+
+        let proposal = JoinProposal::new::<TorlnStorage>(
+            key_package,
+            group_context.group_id().clone(),
+            group_context.epoch(),
+            &self.keypair,
+        )
+        .map_err(JoinRequestError::Propose)?;
+
+        Ok(mls_message_to_u8vec(&proposal))
+    }
+}
+
+#[wasm_bindgen]
+impl Identity {
+    /// Ask to join the group of the serialized `group_info_bytes`, e.g. one
+    /// from `commitPendingProposalsWithGroupInfo`, without being invited.
+    ///
+    /// Returns an external Add proposal of a fresh key package, to send to
+    /// the group. Members see it as an `ExternalJoinProposal` when
+    /// processing it, and a member who approves commits it with
+    /// `commitPendingProposalsWithGroupInfo`, whose welcome lets this
+    /// identity `join`. The proposal is only valid in the epoch of the
+    /// group info, so it has to be sent again after a commit.
+    ///
+    /// The group info is only read, not verified: a forged one makes the
+    /// proposal useless, but reveals nothing but the key package.
+    #[wasm_bindgen(js_name = createExternalJoinProposal)]
+    pub fn create_external_join_proposal(
+        &self,
+        provider: &Provider,
+        group_info_bytes: &[u8],
+    ) -> Result<Vec<u8>, JsError> {
+        Ok(self.external_join_proposal(provider, group_info_bytes)?)
+    }
+}
@@ -0,0 +1,227 @@
+//! A single blob for uploading many key packages at once.
+//!
+//! Format (little endian, like the storage format):
+//! `[magic "TKPB"][u16 format_version][u32 count]`
+//! then for each key package: `[u32 len][TLS-serialized key package]`
+
+use openmls::{
+    error::LibraryError,
+    key_packages::{
+        errors::{KeyPackageNewError, KeyPackageVerifyError},
+        KeyPackageIn,
+    },
+    versions::ProtocolVersion,
+};
+use openmls_rust_crypto::RustCrypto;
+use tls_codec::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    message_size::{MaxMessageBytes, MessageTooLarge},
+    Identity, Provider,
+};
+
+/// Marks a key package bundle.
+const MAGIC: &[u8; 4] = b"TKPB";
+/// Current version of the bundle format.
+const FORMAT_VERSION: u16 = 1;
+/// Size of the header, i.e. the marker and the key package count.
+const HEADER_LEN: usize = 10;
+
+/// Errors when creating or parsing a key package bundle.
+#[derive(Debug)]
+pub(crate) enum KeyPackageBundleError {
+    KeyPackage(KeyPackageNewError),
+    Encoding(tls_codec::Error),
+    NotABundle,
+    UnsupportedVersion(u16),
+    Truncated,
+    TooLarge {
+        index: usize,
+        error: MessageTooLarge,
+    },
+    /// Bytes after the last key package.
+    TrailingBytes,
+    Malformed {
+        index: usize,
+        error: tls_codec::Error,
+    },
+    Invalid {
+        index: usize,
+        error: KeyPackageVerifyError,
+    },
+    Reference(LibraryError),
+}
+
+impl std::fmt::Display for KeyPackageBundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KeyPackage(e) => write!(f, "failed to create key package: {e}"),
+            Self::Encoding(e) => write!(f, "failed to encode key package: {e}"),
+            Self::NotABundle => write!(f, "not a key package bundle"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported key package bundle version {version}")
+            }
+            Self::Truncated => write!(f, "truncated key package bundle"),
+            Self::TooLarge { index, error } => write!(f, "key package {index}: {error}"),
+            Self::TrailingBytes => write!(f, "trailing bytes after the last key package"),
+            Self::Malformed { index, error } => write!(f, "malformed key package {index}: {error}"),
+            Self::Invalid { index, error } => write!(f, "invalid key package {index}: {error}"),
+            Self::Reference(e) => write!(f, "failed to compute key package reference: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyPackageBundleError {}
+
+/// Encode serialized key packages into a bundle.
+pub(crate) fn encode_bundle(key_packages: &[Vec<u8>]) -> Vec<u8> {
+    let len = HEADER_LEN + key_packages.iter().map(|kp| 4 + kp.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(len);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&(key_packages.len() as u32).to_le_bytes());
+    for key_package in key_packages {
+        out.extend_from_slice(&(key_package.len() as u32).to_le_bytes());
+        out.extend_from_slice(key_package);
+    }
+
+    out
+}
+
+/// Split a bundle into the serialized key packages it holds, without
+/// parsing them.
+pub(crate) fn decode_bundle(bundle: &[u8]) -> Result<Vec<&[u8]>, KeyPackageBundleError> {
+    let read_u32 = |data: &[u8]| -> usize { u32::from_le_bytes(data.try_into().unwrap()) as usize };
+
+    if bundle.len() < HEADER_LEN || !bundle.starts_with(MAGIC) {
+        return Err(KeyPackageBundleError::NotABundle);
+    }
+    let version = u16::from_le_bytes([bundle[4], bundle[5]]);
+    if version != FORMAT_VERSION {
+        return Err(KeyPackageBundleError::UnsupportedVersion(version));
+    }
+    let count = read_u32(&bundle[6..HEADER_LEN]);
+
+    let mut rest = &bundle[HEADER_LEN..];
+    let mut key_packages = Vec::new();
+    for _ in 0..count {
+        if rest.len() < 4 {
+            return Err(KeyPackageBundleError::Truncated);
+        }
+        let len = read_u32(&rest[..4]);
+        rest = &rest[4..];
+        if rest.len() < len {
+            return Err(KeyPackageBundleError::Truncated);
+        }
+        let (key_package, tail) = rest.split_at(len);
+        key_packages.push(key_package);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        return Err(KeyPackageBundleError::TrailingBytes);
+    }
+
+    Ok(key_packages)
+}
+
+/// A key package from a bundle, see `parseKeyPackageBundle`.
+#[wasm_bindgen]
+pub struct BundledKeyPackage {
+    key_package: Vec<u8>,
+    reference: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl BundledKeyPackage {
+    /// The TLS-serialized key package.
+    #[wasm_bindgen(getter, js_name = keyPackage)]
+    pub fn key_package(&self) -> Vec<u8> {
+        self.key_package.clone()
+    }
+    /// The key package reference, see `KeyPackage.reference`.
+    #[wasm_bindgen(getter)]
+    pub fn reference(&self) -> Vec<u8> {
+        self.reference.clone()
+    }
+}
+
+/// Split and validate the key packages in `bundle`, see
+/// `parseKeyPackageBundle`. There is no provider, so each key package is
+/// checked against the default size limit.
+pub(crate) fn parse_bundle(bundle: &[u8]) -> Result<Vec<BundledKeyPackage>, KeyPackageBundleError> {
+    let crypto = RustCrypto::default();
+
+    decode_bundle(bundle)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, bytes)| {
+            MaxMessageBytes::default()
+                .check(bytes)
+                .map_err(|error| KeyPackageBundleError::TooLarge { index, error })?;
+            let key_package = KeyPackageIn::tls_deserialize_exact(bytes)
+                .map_err(|error| KeyPackageBundleError::Malformed { index, error })?
+                .validate(&crypto, ProtocolVersion::Mls10)
+                .map_err(|error| KeyPackageBundleError::Invalid { index, error })?;
+            let reference = key_package
+                .hash_ref(&crypto)
+                .map_err(KeyPackageBundleError::Reference)?;
+
+            Ok(BundledKeyPackage {
+                key_package: bytes.to_vec(),
+                reference: reference.as_slice().to_vec(),
+            })
+        })
+        .collect()
+}
+
+impl Identity {
+    /// A bundle of `count` new key packages, see `getKeyPackageBundle`.
+    pub(crate) fn key_package_bundle(
+        &self,
+        provider: &Provider,
+        count: usize,
+    ) -> Result<Vec<u8>, KeyPackageBundleError> {
+        let key_packages = (0..count)
+            .map(|_| {
+                self.build_key_package(provider)
+                    .map_err(KeyPackageBundleError::KeyPackage)?
+                    .tls_serialize_detached()
+                    .map_err(KeyPackageBundleError::Encoding)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(encode_bundle(&key_packages))
+    }
+}
+
+#[wasm_bindgen]
+impl Identity {
+    /// Create `count` new key packages, like `getKeyPackage`, and return
+    /// them as a single bundle for upload.
+    ///
+    /// The bundle is the list of the serialized key packages, each prefixed
+    /// with its length; see the `key_package_bundle` module for the layout.
+    /// The server splits it with `parseKeyPackageBundle`.
+    #[wasm_bindgen(js_name = getKeyPackageBundle)]
+    pub fn get_key_package_bundle(
+        &self,
+        provider: &Provider,
+        count: u32,
+    ) -> Result<Vec<u8>, JsError> {
+        Ok(self.key_package_bundle(provider, count as usize)?)
+    }
+}
+
+/// Split a bundle from `Identity.getKeyPackageBundle` into its key packages,
+/// in the order they were bundled, each with its reference.
+///
+/// Every key package is parsed and its signature checked, so a bundle with
+/// a single bad key package is rejected as a whole, with the position of
+/// that key package in the error. Needs no provider, so it can run on the
+/// server; key packages larger than the default of
+/// `Provider.setMaxMessageBytes` are rejected before parsing.
+#[wasm_bindgen(js_name = parseKeyPackageBundle)]
+pub fn parse_key_package_bundle(bundle: &[u8]) -> Result<Vec<BundledKeyPackage>, JsError> {
+    Ok(parse_bundle(bundle)?)
+}
@@ -0,0 +1,98 @@
+//! A limit on the lifetime of the key packages we add to groups.
+//!
+//! openmls only checks that a key package is valid now. A key package that
+//! stays valid for years lets its owner skip rotating their init keys, and
+//! can be replayed long after it was published. With a limit set on the
+//! provider, adding members refuses key packages whose validity period is
+//! longer than that.
+
+use openmls::{group::QueuedProposal, key_packages::KeyPackage, messages::proposals::Proposal};
+use wasm_bindgen::prelude::*;
+
+use crate::Provider;
+
+/// The maximum lifetime of key packages to add, in seconds, see
+/// `Provider.setKeyPackageMaxLifetime`. No limit by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MaxKeyPackageLifetime(Option<u64>);
+
+/// A key package is valid for longer than the limit of the provider.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct KeyPackageLifetimeTooLong {
+    pub(crate) lifetime: u64,
+    pub(crate) max: u64,
+}
+
+impl std::fmt::Display for KeyPackageLifetimeTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "key package lifetime of {} seconds exceeds the limit of {} seconds",
+            self.lifetime, self.max
+        )
+    }
+}
+
+impl std::error::Error for KeyPackageLifetimeTooLong {}
+
+impl Provider {
+    /// Check the lifetime of `key_package` against the limit before adding
+    /// its owner.
+    pub(crate) fn check_key_package_lifetime(
+        &self,
+        key_package: &KeyPackage,
+    ) -> Result<(), KeyPackageLifetimeTooLong> {
+        let Some(max) = self.3 .0 else {
+            return Ok(());
+        };
+        let life_time = key_package.life_time();
+        let lifetime = life_time.not_after().saturating_sub(life_time.not_before());
+        if lifetime > max {
+            return Err(KeyPackageLifetimeTooLong { lifetime, max });
+        }
+
+        Ok(())
+    }
+
+    /// Check the key packages of the Add proposals among `committed`, the
+    /// pending proposals a commit includes. Every commit includes the
+    /// pending proposals, so this applies to commits without new adds as
+    /// well.
+    pub(crate) fn check_committed_key_packages<'a>(
+        &self,
+        committed: impl IntoIterator<Item = &'a QueuedProposal>,
+    ) -> Result<(), KeyPackageLifetimeTooLong> {
+        committed
+            .into_iter()
+            .try_for_each(|queued_proposal| match queued_proposal.proposal() {
+                Proposal::Add(add_proposal) => {
+                    self.check_key_package_lifetime(add_proposal.key_package())
+                }
+                _ => Ok(()),
+            })
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Refuse to add members whose key package is valid for longer than
+    /// `max_seconds`, from its `notBefore` to its `notAfter`, with a "key
+    /// package lifetime exceeds the limit" error. `undefined` removes the
+    /// limit, which is the default.
+    ///
+    /// Applies to the members added by `proposeAndCommitAdd`,
+    /// `addMemberByBytes`, `createWith`, `BatchAdd.commit` and
+    /// `reAddMember`, and to pending Add proposals, e.g. join proposals of
+    /// external senders, in every commit that includes them. Key packages
+    /// created by this crate are valid for 12 weeks and an hour.
+    #[wasm_bindgen(js_name = setKeyPackageMaxLifetime)]
+    pub fn set_key_package_max_lifetime(&mut self, max_seconds: Option<u64>) {
+        self.3 = MaxKeyPackageLifetime(max_seconds);
+    }
+
+    /// The current limit of `setKeyPackageMaxLifetime`.
+    #[wasm_bindgen(getter, js_name = keyPackageMaxLifetime)]
+    pub fn key_package_max_lifetime(&self) -> Option<u64> {
+        self.3 .0
+    }
+}
@@ -0,0 +1,226 @@
+//! Managing the key packages in the provider storage.
+//!
+//! Key packages are uploaded ahead of time and carry the credential they
+//! were created with. When the credential changes, e.g. because the identity
+//! was re-created from its exported keypair under a new name, the uploaded
+//! key packages would still admit the member under the old credential. They
+//! are replaced by deleting their private state, so that welcomes to them
+//! can no longer be processed, and publishing fresh ones.
+//!
+//! The private state of key packages that were never used stays in the
+//! storage until it is deleted, so expired key packages are pruned as well.
+
+use js_sys::Uint8Array;
+use openmls::{
+    credentials::Credential,
+    error::LibraryError,
+    key_packages::{
+        errors::KeyPackageNewError, KeyPackage as OpenMlsKeyPackage, KeyPackageBundle, KeyPackageIn,
+    },
+    versions::ProtocolVersion,
+};
+use openmls_rust_crypto::RustCrypto;
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{storage::StorageProvider, OpenMlsProvider};
+use tls_codec::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{Identity, Provider};
+
+/// Label of the key package entries in the storage.
+const KEY_PACKAGE_LABEL: &[u8] = b"KeyPackage";
+
+/// Errors when replacing or pruning stored key packages.
+#[derive(Debug)]
+pub(crate) enum KeyPackageStorageError {
+    StorageUnavailable,
+    HashRef(LibraryError),
+    Storage(TorlnStorageError),
+    KeyPackage(KeyPackageNewError),
+}
+
+impl std::fmt::Display for KeyPackageStorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StorageUnavailable => write!(f, "failed to read storage"),
+            Self::HashRef(e) => write!(f, "failed to compute key package reference: {e}"),
+            Self::Storage(e) => write!(f, "failed to delete key package: {e}"),
+            Self::KeyPackage(e) => write!(f, "failed to create key package: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyPackageStorageError {}
+
+/// All key packages in the storage of `provider`.
+fn stored_key_packages(
+    provider: &Provider,
+) -> Result<Vec<OpenMlsKeyPackage>, KeyPackageStorageError> {
+    let values = provider
+        .0
+        .storage()
+        .values()
+        .read()
+        .map_err(|_| KeyPackageStorageError::StorageUnavailable)?;
+
+    Ok(values
+        .iter()
+        .filter(|(key, _)| key.starts_with(KEY_PACKAGE_LABEL))
+        .filter_map(|(_, value)| serde_json::from_slice::<KeyPackageBundle>(value).ok())
+        .map(|bundle| bundle.key_package().clone())
+        .collect())
+}
+
+/// Delete `key_package` and its private keys from the storage of `provider`.
+fn delete_key_package(
+    provider: &Provider,
+    key_package: &OpenMlsKeyPackage,
+) -> Result<(), KeyPackageStorageError> {
+    let hash_ref = key_package
+        .hash_ref(provider.0.crypto())
+        .map_err(KeyPackageStorageError::HashRef)?;
+
+    provider
+        .0
+        .storage()
+        .delete_key_package(&hash_ref)
+        .map_err(KeyPackageStorageError::Storage)
+}
+
+impl Provider {
+    /// Delete the key packages that expired before `now`, see
+    /// `pruneExpiredKeyPackages`.
+    pub(crate) fn prune_expired_key_packages_native(
+        &self,
+        now: u64,
+    ) -> Result<usize, KeyPackageStorageError> {
+        let mut pruned = 0;
+        for key_package in stored_key_packages(self)? {
+            if key_package.life_time().not_after() < now {
+                delete_key_package(self, &key_package)?;
+                pruned += 1;
+            }
+        }
+
+        Ok(pruned)
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Delete the private keys of all stored key packages that expired
+    /// before `now_unix_seconds`, and return how many were deleted.
+    ///
+    /// Call this regularly when replenishing key packages, so that the
+    /// storage doesn't grow with key packages that can't be used anymore.
+    #[wasm_bindgen(js_name = pruneExpiredKeyPackages)]
+    pub fn prune_expired_key_packages(&self, now_unix_seconds: u64) -> Result<u32, JsError> {
+        Ok(self.prune_expired_key_packages_native(now_unix_seconds)? as u32)
+    }
+}
+
+impl Identity {
+    /// Delete all stored key packages of this identity and create `count`
+    /// new ones, see `refreshKeyPackages`.
+    pub(crate) fn refresh_key_packages_native(
+        &mut self,
+        provider: &Provider,
+        count: usize,
+    ) -> Result<Vec<OpenMlsKeyPackage>, KeyPackageStorageError> {
+        let own_key_packages = stored_key_packages(provider)?
+            .into_iter()
+            .filter(|kp| kp.leaf_node().signature_key().as_slice() == self.keypair.public());
+        for key_package in own_key_packages {
+            delete_key_package(provider, &key_package)?;
+        }
+        self.group_key_packages.clear();
+
+        (0..count)
+            .map(|_| {
+                self.build_key_package(provider)
+                    .map_err(KeyPackageStorageError::KeyPackage)
+            })
+            .collect()
+    }
+
+    /// A key package of this identity whose private keys are thrown away,
+    /// see `getKeyPackagePublicOnly`.
+    pub(crate) fn public_only_key_package(&self) -> Result<OpenMlsKeyPackage, KeyPackageNewError> {
+        // openmls stores the private keys as it builds the key package, so
+        // build it against a scratch provider that is dropped afterwards.
+        self.build_key_package(&Provider::default())
+    }
+}
+
+#[wasm_bindgen]
+impl Identity {
+    /// A serialized key package of this identity, without storing its
+    /// private init and encryption keys anywhere.
+    ///
+    /// For tests and for identities that only verify, e.g. to publish the
+    /// capabilities and credential of a client that never joins. Such a key
+    /// package can't be used to join a group: the welcome to it can't be
+    /// decrypted. Use `getKeyPackage` for key packages to upload.
+    #[wasm_bindgen(js_name = getKeyPackagePublicOnly)]
+    pub fn get_key_package_public_only(&self) -> Result<Vec<u8>, JsError> {
+        Ok(self.public_only_key_package()?.tls_serialize_detached()?)
+    }
+
+    /// Replace the key packages of this identity after its credential
+    /// changed.
+    ///
+    /// Deletes the private state of all key packages signed by this identity
+    /// from the provider storage, so that welcomes to previously uploaded
+    /// key packages can't be joined anymore, and forgets the key packages of
+    /// `deriveGroupKeyPackage`. Returns `count` new serialized key packages
+    /// bound to the current credential, for re-upload.
+    #[wasm_bindgen(js_name = refreshKeyPackages)]
+    pub fn refresh_key_packages(
+        &mut self,
+        provider: &Provider,
+        count: u32,
+    ) -> Result<Vec<Uint8Array>, JsError> {
+        let mut key_packages = Vec::new();
+        for key_package in self.refresh_key_packages_native(provider, count as usize)? {
+            key_packages.push(key_package.tls_serialize_detached()?.as_slice().into());
+        }
+
+        Ok(key_packages)
+    }
+}
+
+/// Whether the key package in `key_package` has a valid signature and is
+/// bound to the credential in `expected_credential`.
+pub(crate) fn key_package_has_credential(
+    mut key_package: &[u8],
+    mut expected_credential: &[u8],
+) -> Result<bool, tls_codec::Error> {
+    let key_package = KeyPackageIn::tls_deserialize(&mut key_package)?;
+    let expected_credential = Credential::tls_deserialize(&mut expected_credential)?;
+
+    let Ok(key_package) = key_package.validate(&RustCrypto::default(), ProtocolVersion::Mls10)
+    else {
+        return Ok(false);
+    };
+
+    Ok(key_package.leaf_node().credential() == &expected_credential)
+}
+
+/// Check a key package fetched from the server against the credential the
+/// directory lists for its owner, before adding it to a group.
+///
+/// Returns `true` if the key package is validly signed and its leaf carries
+/// exactly `expected_credential_bytes`, as returned by
+/// `Identity.getCredentialBytes`. A `false` result means the key package was
+/// tampered with or belongs to someone else, e.g. because a malicious server
+/// swapped the credential. Fails if either input can't be parsed.
+#[wasm_bindgen(js_name = verifyKeyPackageCredential)]
+pub fn verify_key_package_credential(
+    key_package_bytes: &[u8],
+    expected_credential_bytes: &[u8],
+) -> Result<bool, JsError> {
+    Ok(key_package_has_credential(
+        key_package_bytes,
+        expected_credential_bytes,
+    )?)
+}
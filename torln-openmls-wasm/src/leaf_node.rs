@@ -0,0 +1,65 @@
+//! The full leaf node of a member, for verifying it against an external
+//! registry: credential, capabilities, encryption key and signature.
+
+use openmls::treesync::Node;
+use tls_codec::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{BlankLeaf, Group};
+
+/// Errors when reading the leaf node of a member.
+#[derive(Debug)]
+pub(crate) enum LeafNodeError {
+    Blank(BlankLeaf),
+    Nodes(serde_json::Error),
+    Encoding(tls_codec::Error),
+}
+
+impl std::fmt::Display for LeafNodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Blank(e) => write!(f, "{e}"),
+            Self::Nodes(e) => write!(f, "failed to read tree nodes: {e}"),
+            Self::Encoding(e) => write!(f, "failed to encode leaf node: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LeafNodeError {}
+
+impl Group {
+    /// The TLS-serialized leaf node in the leaf `leaf_index`, see
+    /// `memberLeafNode`.
+    pub(crate) fn leaf_node_bytes(&self, leaf_index: u32) -> Result<Vec<u8>, LeafNodeError> {
+        // openmls only gives access to the leaf nodes of the tree through the
+        // serialized list of nodes, see `debugTreeDump`.
+        let tree = serde_json::to_value(self.mls_group.export_ratchet_tree())
+            .map_err(LeafNodeError::Nodes)?;
+        let nodes =
+            serde_json::from_value::<Vec<Option<Node>>>(tree).map_err(LeafNodeError::Nodes)?;
+
+        match nodes.into_iter().nth(2 * leaf_index as usize).flatten() {
+            Some(Node::LeafNode(leaf)) => leaf
+                .tls_serialize_detached()
+                .map_err(LeafNodeError::Encoding),
+            _ => Err(LeafNodeError::Blank(BlankLeaf(leaf_index))),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// The TLS-serialized `LeafNode` in the leaf `leaf_index` of the tree in
+    /// the current epoch, as defined in RFC 9420.
+    ///
+    /// Unlike `members`, this has everything a verifier needs to check the
+    /// leaf independently, e.g. against the credential an external registry
+    /// lists for the member: the credential, capabilities, encryption key,
+    /// extensions and the signature. Pending commits are not reflected
+    /// until they are merged. Fails for blank leaves and indices outside the
+    /// tree.
+    #[wasm_bindgen(js_name = memberLeafNode)]
+    pub fn member_leaf_node(&self, leaf_index: u32) -> Result<Vec<u8>, JsError> {
+        Ok(self.leaf_node_bytes(leaf_index)?)
+    }
+}
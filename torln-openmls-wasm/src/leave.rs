@@ -0,0 +1,123 @@
+//! Leaving a group.
+//!
+//! A member can't commit its own removal, so leaving always takes a proposal
+//! that another member commits. The preferred way is a SelfRemove proposal:
+//! unlike a Remove proposal it stays valid when the leaf changes concurrently,
+//! and it can be committed together with other proposals by anyone.
+//!
+//! SelfRemove proposals are always sent as public messages, while our groups
+//! accept encrypted handshake messages only. Public SelfRemove proposals are
+//! the one exception: they're processed with public handshake messages
+//! accepted for that message only.
+
+use openmls::{
+    framing::{ProcessedMessage, PublicMessageIn},
+    group::{
+        IncomingWireFormatPolicy, MlsGroup, MlsGroupJoinConfig, ProcessMessageError,
+        WireFormatPolicy, MIXED_CIPHERTEXT_WIRE_FORMAT_POLICY, MIXED_PLAINTEXT_WIRE_FORMAT_POLICY,
+    },
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{mls_message_to_u8vec, routing, Group, Identity, Provider};
+
+/// `ProposalType::SelfRemove`
+const PROPOSAL_TYPE_SELF_REMOVE: u16 = 0x000a;
+
+/// Whether the serialized message is a public SelfRemove proposal.
+pub(crate) fn is_public_self_remove(bytes: &[u8]) -> bool {
+    routing::public_proposal_type_of(bytes) == Some(PROPOSAL_TYPE_SELF_REMOVE)
+}
+
+impl Group {
+    /// Run `f` on the group with the wire format policy set to `policy`,
+    /// restoring the configuration afterwards.
+    fn with_wire_format_policy<T>(
+        &mut self,
+        provider: &Provider,
+        policy: WireFormatPolicy,
+        f: impl FnOnce(&mut MlsGroup) -> T,
+    ) -> Result<T, TorlnStorageError> {
+        let config = self.mls_group.configuration().clone();
+        let switched_config = MlsGroupJoinConfig::builder()
+            .wire_format_policy(policy)
+            .padding_size(config.padding_size())
+            .sender_ratchet_configuration(*config.sender_ratchet_configuration())
+            .build();
+
+        self.mls_group
+            .set_configuration(provider.0.storage(), &switched_config)?;
+        let result = f(&mut self.mls_group);
+        self.mls_group
+            .set_configuration(provider.0.storage(), &config)?;
+
+        Ok(result)
+    }
+
+    /// Process a public SelfRemove proposal, accepting it even though the
+    /// group only accepts encrypted handshake messages.
+    pub(crate) fn process_public_self_remove(
+        &mut self,
+        provider: &Provider,
+        message: PublicMessageIn,
+    ) -> Result<ProcessedMessage, ProcessMessageError<TorlnStorageError>> {
+        let policy = self.mls_group.configuration().wire_format_policy();
+        if policy.incoming() != IncomingWireFormatPolicy::AlwaysCiphertext {
+            return self.mls_group.process_message(provider.as_ref(), message);
+        }
+
+        // A pure-ciphertext group sends encrypted messages only, as does the
+        // mixed one accepting public messages too.
+        self.with_wire_format_policy(provider, MIXED_CIPHERTEXT_WIRE_FORMAT_POLICY, |mls_group| {
+            mls_group.process_message(provider.as_ref(), message)
+        })
+        .map_err(ProcessMessageError::StorageError)?
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Propose to remove ourselves from the group with a SelfRemove proposal.
+    ///
+    /// Returns the serialized proposal, which any other member can commit,
+    /// e.g. with `commitPendingProposals`. SelfRemove proposals are always
+    /// sent as public messages. Members accept them regardless of the wire
+    /// format policy of the group, unless they run a version without
+    /// SelfRemove support; use `leaveGroup` for groups with such members.
+    #[wasm_bindgen(js_name = proposeSelfRemove)]
+    pub fn propose_self_remove(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<Vec<u8>, JsError> {
+        // Handshake messages are sent encrypted by default, which openmls
+        // refuses for SelfRemove proposals. Switch to public handshake
+        // messages for this proposal only.
+        let signer = self.signer(provider, sender)?;
+        let proposal = self.with_wire_format_policy(
+            provider,
+            MIXED_PLAINTEXT_WIRE_FORMAT_POLICY,
+            |mls_group| mls_group.leave_group_via_self_remove(provider.as_ref(), &signer),
+        )?;
+
+        Ok(mls_message_to_u8vec(&proposal?))
+    }
+
+    /// Propose to remove ourselves from the group with a Remove proposal.
+    ///
+    /// Returns the serialized proposal. Prefer `proposeSelfRemove`; this is
+    /// the fallback for groups that don't accept SelfRemove proposals.
+    #[wasm_bindgen(js_name = leaveGroup)]
+    pub fn leave_group(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<Vec<u8>, JsError> {
+        let signer = self.signer(provider, sender)?;
+        let proposal = self.mls_group.leave_group(provider.as_ref(), &signer)?;
+
+        Ok(mls_message_to_u8vec(&proposal))
+    }
+}
@@ -1,24 +1,126 @@
+mod add_by_bytes;
+mod audit;
+mod batch_add;
+mod branch;
+mod capacity;
+mod ciphersuite;
+mod combined_messages;
+mod commit_chain;
+mod commit_group_info;
+mod confirmation_tag;
+mod credential_policy;
+#[cfg(feature = "debug-tools")]
+mod debug;
+mod dedup;
+mod devices;
+mod dry_run;
+mod encrypted_storage;
+mod enrollment;
+mod ephemeral;
+mod epoch_floor;
+mod extensions;
+mod external_senders;
+mod fork;
+mod generation;
+mod group_builder;
+mod group_id;
+mod identity_bytes;
+mod initial_members;
+mod join_request;
+mod key_package_bundle;
+mod key_package_lifetime;
+mod key_packages;
+mod leaf_node;
+mod leave;
+mod message_size;
+mod migrate;
+mod orphaned_keys;
+mod pending_state;
+mod processing;
+mod proposals;
+mod provider;
+mod public_state;
+mod readd;
+mod recovery;
+mod rekey;
+mod reserialize;
+mod roster;
+mod routing;
+mod self_update;
+mod signer;
+mod stable_secret;
+mod staged_join;
+mod stats;
+mod storage;
+mod storage_delta;
+mod stored_groups;
+mod sync_check;
+mod transaction;
+mod tree_diff;
 mod utils;
+mod welcome;
+mod wire_format;
 
 #[cfg(test)]
 mod tests;
 
 use js_sys::Uint8Array;
 use openmls::{
-    credentials::{BasicCredential, CredentialWithKey},
-    framing::{MlsMessageBodyIn, MlsMessageIn, MlsMessageOut},
-    group::{GroupId, MlsGroup, MlsGroupJoinConfig, StagedWelcome},
-    key_packages::KeyPackage as OpenMlsKeyPackage,
-    prelude::SignatureScheme,
-    treesync::RatchetTreeIn,
+    credentials::{BasicCredential, Credential, CredentialWithKey, NewSignerBundle},
+    extensions::Extensions,
+    framing::MlsMessageOut,
+    group::{
+        GroupContext, GroupId, Member, MlsGroup, MlsGroupJoinConfig, NewGroupError, StagedCommit,
+        WireFormatPolicy, PURE_CIPHERTEXT_WIRE_FORMAT_POLICY,
+    },
+    key_packages::{errors::KeyPackageNewError, KeyPackage as OpenMlsKeyPackage},
+    prelude::{LeafNodeIndex, SignatureScheme},
+    treesync::{LeafNodeParameters, RatchetTreeIn},
 };
 use openmls_basic_credential::SignatureKeyPair;
-use openmls_rust_crypto::OpenMlsRustCrypto;
+use openmls_rust_crypto::RustCrypto;
+use openmls_torln_storage::TorlnStorageError;
 use openmls_traits::{types::Ciphersuite, OpenMlsProvider};
-use std::convert::TryInto;
-use tls_codec::{Deserialize, Serialize};
+use signer::PendingRotation;
+use std::collections::BTreeMap;
+use tls_codec::{Deserialize, Serialize, Size};
 use wasm_bindgen::prelude::*;
 
+pub use audit::{AuditAction, AuditRecord};
+pub use batch_add::BatchAdd;
+pub use branch::Subgroup;
+pub use capacity::GroupConfig;
+pub use ciphersuite::{ciphersuite_params, negotiate_ciphersuite, CiphersuiteParams};
+pub use commit_chain::CommitChainCheck;
+pub use commit_group_info::CommitWithGroupInfo;
+#[cfg(feature = "debug-tools")]
+pub use debug::{SenderRatchetState, TreeNodeDump};
+pub use dedup::DedupResult;
+pub use devices::UserMembers;
+pub use dry_run::DryRunCommit;
+pub use external_senders::ExternalSenderInfo;
+pub use fork::ForkStatus;
+pub use group_builder::{GroupConfigBuilder, GroupCreationConfig};
+pub use group_id::derive_group_id;
+pub use initial_members::GroupWithMembers;
+pub use key_package_bundle::{parse_key_package_bundle, BundledKeyPackage};
+pub use key_packages::verify_key_package_credential;
+pub use processing::{AppProposal, LeafIndexChange, MessageKind, MessageResult, ProcessedMessage};
+pub use proposals::ProposalCounts;
+pub use public_state::GroupPublicState;
+pub use readd::ReAddMessages;
+pub use recovery::Recovery;
+pub use reserialize::reserialize_message;
+pub use roster::{verify_roster, SignedRoster};
+pub use routing::{message_content_type, message_group_id, MessageContentType};
+pub use staged_join::StagedJoin;
+pub use stats::ProcessingStats;
+pub use storage::StorageExportChunks;
+pub use storage_delta::StorageDelta;
+pub use stored_groups::GroupHealth;
+pub use welcome::{JoinInfo, WelcomeInfo};
+pub use wire_format::{GroupWireFormatPolicy, WireFormat};
+
 #[wasm_bindgen]
 extern "C" {
     fn alert(s: &str);
@@ -30,18 +132,35 @@ extern "C" {
 /// The ciphersuite used here. Fixed in order to reduce the binary size.
 static CIPHERSUITE: Ciphersuite = Ciphersuite::MLS_128_DHKEMX25519_CHACHA20POLY1305_SHA256_Ed25519;
 
+/// The wire format policy of our groups: handshake messages are sent and
+/// accepted encrypted only. Public SelfRemove proposals are accepted
+/// nonetheless, see `leave`.
+static WIRE_FORMAT_POLICY: WireFormatPolicy = PURE_CIPHERTEXT_WIRE_FORMAT_POLICY;
+
+/// The configuration used when joining a group.
+pub(crate) fn join_config() -> MlsGroupJoinConfig {
+    MlsGroupJoinConfig::builder()
+        .wire_format_policy(WIRE_FORMAT_POLICY)
+        .build()
+}
+
 #[wasm_bindgen]
 #[derive(Default)]
-pub struct Provider(OpenMlsRustCrypto);
-
-impl AsRef<OpenMlsRustCrypto> for Provider {
-    fn as_ref(&self) -> &OpenMlsRustCrypto {
+pub struct Provider(
+    provider::TorlnProvider,
+    message_size::MaxMessageBytes,
+    storage_delta::StorageVersions,
+    key_package_lifetime::MaxKeyPackageLifetime,
+);
+
+impl AsRef<provider::TorlnProvider> for Provider {
+    fn as_ref(&self) -> &provider::TorlnProvider {
         &self.0
     }
 }
 
-impl AsMut<OpenMlsRustCrypto> for Provider {
-    fn as_mut(&mut self) -> &mut OpenMlsRustCrypto {
+impl AsMut<provider::TorlnProvider> for Provider {
+    fn as_mut(&mut self) -> &mut provider::TorlnProvider {
         &mut self.0
     }
 }
@@ -54,81 +173,46 @@ impl Provider {
             if seed_vec.len() != 32 {
                 return Err(JsError::new("Seed must be exactly 32 bytes"));
             }
-            let provider = OpenMlsRustCrypto::with_seed(&seed_vec);
-            Ok(Self(provider))
+            let provider = provider::TorlnProvider::with_seed(&seed_vec);
+            Ok(Self(
+                provider,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            ))
         } else {
             Ok(Self::default())
         }
     }
 
     /// Export the entire provider storage as a compact binary blob for backup
+    ///
+    /// Keys and values are written as raw bytes with length prefixes, without
+    /// any text encoding, so this is also the format to use for syncing
+    /// between devices. See the `storage` module for the layout.
     #[wasm_bindgen(js_name = exportStorage)]
     pub fn export_storage(&self) -> Result<Vec<u8>, JsError> {
         let storage = self.0.storage();
         let values = storage
-            .values
+            .values()
             .read()
             .map_err(|e| JsError::new(&format!("Failed to read storage: {}", e)))?;
 
-        // Binary format (little endian):
-        // [u32 entry_count] then for each entry: [u32 key_len][u32 val_len][key bytes][val bytes]
-        let mut out = Vec::with_capacity(4 + values.len() * 8);
-        out.extend_from_slice(&(values.len() as u32).to_le_bytes());
-
-        for (key, value) in values.iter() {
-            out.extend_from_slice(&(key.len() as u32).to_le_bytes());
-            out.extend_from_slice(&(value.len() as u32).to_le_bytes());
-            out.extend_from_slice(key);
-            out.extend_from_slice(value);
-        }
-
-        Ok(out)
+        Ok(storage::encode_entries(
+            values.len(),
+            values
+                .iter()
+                .map(|(key, value)| (key.as_slice(), value.as_slice())),
+        ))
     }
 
     /// Import storage from a previously exported binary blob
     #[wasm_bindgen(js_name = importStorage)]
     pub fn import_storage(&self, storage_bytes: &[u8]) -> Result<(), JsError> {
-        let storage = self.0.storage();
-        let mut values = storage
-            .values
-            .write()
-            .map_err(|e| JsError::new(&format!("Failed to write to storage: {}", e)))?;
-
-        // Parse the binary format described in `export_storage`.
-        let mut cursor = 0usize;
-        let len = storage_bytes.len();
-
-        // Need at least 4 bytes for the entry count
-        if len < 4 {
-            return Err(JsError::new("Storage data too short"));
-        }
-
-        let read_u32 = |data: &[u8]| -> u32 { u32::from_le_bytes(data.try_into().unwrap()) };
-
-        let entry_count = read_u32(&storage_bytes[cursor..cursor + 4]) as usize;
-        cursor += 4;
-
-        for _ in 0..entry_count {
-            if cursor + 8 > len {
-                return Err(JsError::new("Corrupted storage: truncated lengths"));
-            }
-
-            let key_len = read_u32(&storage_bytes[cursor..cursor + 4]) as usize;
-            cursor += 4;
-            let val_len = read_u32(&storage_bytes[cursor..cursor + 4]) as usize;
-            cursor += 4;
-
-            if cursor + key_len + val_len > len {
-                return Err(JsError::new("Corrupted storage: truncated key/value"));
-            }
-
-            let key = storage_bytes[cursor..cursor + key_len].to_vec();
-            cursor += key_len;
-            let value = storage_bytes[cursor..cursor + val_len].to_vec();
-            cursor += val_len;
-
-            values.insert(key, value);
-        }
+        let entries = storage::decode_entries(storage_bytes)?;
+        self.0
+            .storage()
+            .write_entries(entries.into_iter().map(|(key, value)| (key, Some(value))))?;
 
         Ok(())
     }
@@ -146,8 +230,11 @@ impl Provider {
 
 #[wasm_bindgen]
 pub struct Identity {
-    credential_with_key: CredentialWithKey,
-    keypair: openmls_basic_credential::SignatureKeyPair,
+    credential: Credential,
+    keypair: SignatureKeyPair,
+    /// Key packages handed out per group, see `deriveGroupKeyPackage`.
+    /// Only kept for the lifetime of this object, not in the storage.
+    group_key_packages: BTreeMap<String, OpenMlsKeyPackage>,
 }
 
 #[wasm_bindgen]
@@ -170,31 +257,52 @@ impl Identity {
 
         keypair.store(provider.0.storage())?;
 
-        let credential_with_key = CredentialWithKey {
-            credential: credential.into(),
-            signature_key: keypair.public().into(),
-        };
-
         Ok(Identity {
-            credential_with_key,
+            credential: credential.into(),
             keypair,
+            group_key_packages: BTreeMap::new(),
         })
     }
 
     #[wasm_bindgen(js_name = getKeyPackage)]
     pub fn get_key_package(&self, provider: &Provider) -> KeyPackage {
-        KeyPackage(
-            OpenMlsKeyPackage::builder()
-                .build(
-                    CIPHERSUITE,
-                    &provider.0,
-                    &self.keypair,
-                    self.credential_with_key.clone(),
-                )
-                .unwrap()
-                .key_package()
-                .clone(),
-        )
+        KeyPackage(self.build_key_package(provider).unwrap())
+    }
+
+    /// Get the key package for joining the group identified by
+    /// `group_context_id`, an application-chosen identifier such as an invite
+    /// or conversation id.
+    ///
+    /// The first call for a group generates a fresh key package; later calls
+    /// for the same group return that same key package. A key package is
+    /// therefore never handed out for two different groups, even when one
+    /// identity participates in many groups.
+    ///
+    /// The mapping only lives as long as this `Identity` object; it isn't
+    /// written to the provider storage. After a reload, the first call for
+    /// a group generates a new key package, so an app that needs the same
+    /// key package across sessions has to keep the one it uploaded.
+    #[wasm_bindgen(js_name = deriveGroupKeyPackage)]
+    pub fn derive_group_key_package(
+        &mut self,
+        provider: &Provider,
+        group_context_id: &str,
+    ) -> Result<KeyPackage, JsError> {
+        if let Some(key_package) = self.group_key_packages.get(group_context_id) {
+            return Ok(KeyPackage(key_package.clone()));
+        }
+
+        let key_package = self.build_key_package(provider)?;
+        self.group_key_packages
+            .insert(group_context_id.to_string(), key_package.clone());
+
+        Ok(KeyPackage(key_package))
+    }
+
+    /// The groups this identity has derived key packages for with
+    /// `deriveGroupKeyPackage` since it was created, in lexicographic order.
+    pub fn groups(&self) -> Vec<String> {
+        self.group_key_packages.keys().cloned().collect()
     }
 
     #[wasm_bindgen(js_name = getPublicKeyBytes)]
@@ -210,16 +318,178 @@ impl Identity {
 
     #[wasm_bindgen(js_name = getCredentialBytes)]
     pub fn get_credential_bytes(&self) -> Result<Vec<u8>, JsError> {
-        Ok(self
-            .credential_with_key
-            .credential
-            .tls_serialize_detached()?)
+        Ok(self.credential.tls_serialize_detached()?)
+    }
+}
+
+impl Identity {
+    /// The credential with the signature key of the identity.
+    pub(crate) fn credential_with_key(&self) -> CredentialWithKey {
+        CredentialWithKey {
+            credential: self.credential.clone(),
+            signature_key: self.keypair.public().into(),
+        }
+    }
+
+    fn build_key_package(
+        &self,
+        provider: &Provider,
+    ) -> Result<OpenMlsKeyPackage, KeyPackageNewError> {
+        Ok(OpenMlsKeyPackage::builder()
+            .leaf_node_capabilities(extensions::capabilities())
+            .build(
+                CIPHERSUITE,
+                &provider.0,
+                &self.keypair,
+                self.credential_with_key(),
+            )?
+            .key_package()
+            .clone())
     }
 }
 
 #[wasm_bindgen]
 pub struct Group {
     mls_group: MlsGroup,
+    /// The keys of a `rotateSignatureKey` commit that is still pending.
+    pending_rotation: Option<PendingRotation>,
+    /// See `setGenerationWatermark`.
+    generation_watermark: u32,
+    /// See `setAutoMerge`.
+    auto_merge: bool,
+    /// See `setIgnoreOwnMessages`.
+    ignore_own_messages: bool,
+    /// A received commit staged but not merged, see `setAutoMerge`.
+    staged_commit: Option<Box<StagedCommit>>,
+    /// See `processingStats`.
+    stats: ProcessingStats,
+    /// See `setAcceptedCredentialTypes`; `None` accepts all types.
+    accepted_credential_types: Option<Vec<u16>>,
+    /// See `processMessageDedup`.
+    seen_messages: dedup::SeenMessages,
+    /// The last message serialized by `createMessageInto`, kept to reuse
+    /// its allocation.
+    message_buffer: Vec<u8>,
+}
+
+impl From<MlsGroup> for Group {
+    fn from(mls_group: MlsGroup) -> Self {
+        Group {
+            mls_group,
+            pending_rotation: None,
+            generation_watermark: generation::DEFAULT_GENERATION_WATERMARK,
+            auto_merge: true,
+            ignore_own_messages: false,
+            staged_commit: None,
+            stats: ProcessingStats::default(),
+            accepted_credential_types: None,
+            seen_messages: dedup::SeenMessages::default(),
+            message_buffer: Vec::new(),
+        }
+    }
+}
+
+/// The result of rotating the own signature key in a group.
+#[wasm_bindgen]
+pub struct SignatureKeyRotation {
+    commit: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl SignatureKeyRotation {
+    #[wasm_bindgen(getter)]
+    pub fn commit(&self) -> Vec<u8> {
+        self.commit.clone()
+    }
+    #[wasm_bindgen(getter, js_name = publicKey)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.clone()
+    }
+}
+
+/// A member of a group, as seen in the current epoch.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct GroupMember {
+    leaf_index: u32,
+    credential: Vec<u8>,
+    signature_key: Vec<u8>,
+    encryption_key: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl GroupMember {
+    #[wasm_bindgen(getter, js_name = leafIndex)]
+    pub fn leaf_index(&self) -> u32 {
+        self.leaf_index
+    }
+    /// The TLS-serialized credential, as returned by `Identity::getCredentialBytes`.
+    #[wasm_bindgen(getter)]
+    pub fn credential(&self) -> Vec<u8> {
+        self.credential.clone()
+    }
+    #[wasm_bindgen(getter, js_name = signatureKey)]
+    pub fn signature_key(&self) -> Vec<u8> {
+        self.signature_key.clone()
+    }
+    #[wasm_bindgen(getter, js_name = encryptionKey)]
+    pub fn encryption_key(&self) -> Vec<u8> {
+        self.encryption_key.clone()
+    }
+}
+
+impl TryFrom<Member> for GroupMember {
+    type Error = tls_codec::Error;
+
+    fn try_from(member: Member) -> Result<Self, Self::Error> {
+        Ok(GroupMember {
+            leaf_index: member.index.u32(),
+            credential: member.credential.tls_serialize_detached()?,
+            signature_key: member.signature_key,
+            encryption_key: member.encryption_key,
+        })
+    }
+}
+
+/// The founder of a group, see `Group::founderInfo`.
+#[wasm_bindgen]
+pub struct FounderInfo(extensions::FounderInfo);
+
+#[wasm_bindgen]
+impl FounderInfo {
+    /// The TLS-serialized credential of the founder.
+    #[wasm_bindgen(getter)]
+    pub fn credential(&self) -> Result<Vec<u8>, JsError> {
+        Ok(self.0.credential.tls_serialize_detached()?)
+    }
+    /// When the group was created, in seconds since the Unix epoch.
+    #[wasm_bindgen(getter, js_name = createdAt)]
+    pub fn created_at(&self) -> u64 {
+        self.0.created_at
+    }
+}
+
+/// The outcome of `Group::createMessageInto`.
+#[wasm_bindgen]
+pub struct MessageWrite {
+    written: usize,
+    overflow: Option<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl MessageWrite {
+    /// The number of bytes written into the caller's buffer, or 0 if the
+    /// message didn't fit.
+    #[wasm_bindgen(getter)]
+    pub fn written(&self) -> usize {
+        self.written
+    }
+    /// The serialized message if it didn't fit into the caller's buffer.
+    #[wasm_bindgen(getter)]
+    pub fn overflow(&self) -> Option<Vec<u8>> {
+        self.overflow.clone()
+    }
 }
 
 #[wasm_bindgen]
@@ -229,6 +499,35 @@ pub struct AddMessages {
     welcome: Uint8Array,
 }
 
+/// The messages of a commit of pending proposals.
+#[wasm_bindgen]
+pub struct CommitMessages {
+    commit: Vec<u8>,
+    welcome: Option<Vec<u8>>,
+}
+
+impl CommitMessages {
+    pub(crate) fn new(commit: &MlsMessageOut, welcome: Option<&MlsMessageOut>) -> Self {
+        CommitMessages {
+            commit: mls_message_to_u8vec(commit),
+            welcome: welcome.map(mls_message_to_u8vec),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl CommitMessages {
+    #[wasm_bindgen(getter)]
+    pub fn commit(&self) -> Vec<u8> {
+        self.commit.clone()
+    }
+    /// The welcome for the members the commit adds, if it adds any.
+    #[wasm_bindgen(getter)]
+    pub fn welcome(&self) -> Option<Vec<u8>> {
+        self.welcome.clone()
+    }
+}
+
 #[cfg(test)]
 pub(crate) struct NativeAddMessages {
     pub(crate) proposal: Vec<u8>,
@@ -256,58 +555,62 @@ impl AddMessages {
 impl Group {
     #[wasm_bindgen(js_name = createNew)]
     pub fn create_new(provider: &Provider, founder: &Identity, group_id: &str) -> Group {
-        let group_id_bytes = group_id.bytes().collect::<Vec<_>>();
-
-        let mls_group = MlsGroup::builder()
-            .ciphersuite(CIPHERSUITE)
-            .with_group_id(GroupId::from_slice(&group_id_bytes))
-            .build(
-                &provider.0,
-                &founder.keypair,
-                founder.credential_with_key.clone(),
-            )
-            .unwrap();
-
-        Group { mls_group }
+        Group::build_new(provider, founder, group_id.as_bytes(), Extensions::empty()).unwrap()
     }
 
     /// Load an existing group from provider storage by group ID
     #[wasm_bindgen(js_name = loadFromStorage)]
     pub fn load_from_storage(provider: &Provider, group_id: &str) -> Result<Group, JsError> {
-        let group_id_bytes = group_id.bytes().collect::<Vec<_>>();
-        let group_id_obj = GroupId::from_slice(&group_id_bytes);
+        Group::load_from_storage_bytes(provider, group_id.as_bytes())
+    }
+
+    /// Like `loadFromStorage`, with the group id given as bytes, for ids
+    /// that aren't valid UTF-8.
+    #[wasm_bindgen(js_name = loadFromStorageBytes)]
+    pub fn load_from_storage_bytes(provider: &Provider, group_id: &[u8]) -> Result<Group, JsError> {
+        let group_id_obj = GroupId::from_slice(group_id);
 
         let mls_group = MlsGroup::load(provider.0.storage(), &group_id_obj)
             .map_err(|e| JsError::new(&format!("Failed to load group: {}", e)))?
             .ok_or_else(|| JsError::new("Group not found in storage"))?;
 
-        Ok(Group { mls_group })
+        Ok(mls_group.into())
     }
 
+    /// The group id as a string. Bytes that aren't valid UTF-8 are replaced,
+    /// so use `groupIdBytes` for binary ids.
     #[wasm_bindgen(js_name = groupId)]
     pub fn group_id(&self) -> String {
         String::from_utf8_lossy(self.mls_group.group_id().as_slice()).to_string()
     }
 
+    /// The group id as it is, e.g. as passed to `createNewFromBytes`.
+    #[wasm_bindgen(js_name = groupIdBytes)]
+    pub fn group_id_bytes(&self) -> Vec<u8> {
+        self.mls_group.group_id().as_slice().to_vec()
+    }
+
+    /// Join the group `welcome` invites to.
+    ///
+    /// Fails early if the group uses a different ciphersuite than this
+    /// client. If joining fails after the welcome was processed, the provider
+    /// storage is rolled back, so that no secrets of the failed join are left
+    /// in it; the error message then says so.
     pub fn join(
         provider: &Provider,
-        mut welcome: &[u8],
+        welcome: &[u8],
         ratchet_tree: RatchetTree,
     ) -> Result<Group, JsError> {
-        let welcome = match MlsMessageIn::tls_deserialize(&mut welcome)?.extract() {
-            MlsMessageBodyIn::Welcome(welcome) => Ok(welcome),
-            other => Err(openmls::error::ErrorString::from(format!(
-                "expected a message of type welcome, got {other:?}",
-            ))),
-        }?;
-        let config = MlsGroupJoinConfig::builder().build();
-        let mls_group =
-            StagedWelcome::new_from_welcome(&provider.0, &config, welcome, Some(ratchet_tree.0))?
-                .into_group(&provider.0)?;
-
-        Ok(Group { mls_group })
+        Ok(welcome::join_group(provider, welcome, ratchet_tree)?.into())
     }
 
+    /// The ratchet tree of the current epoch, for joiners whose welcome
+    /// doesn't carry it.
+    ///
+    /// Trailing blank leaves are already left out. The tree can't be pruned
+    /// further for a specific joiner: the tree hash a joiner checks covers
+    /// every node, and joining verifies the parent hashes of every
+    /// non-blank parent, so a joiner needs the full content of every node.
     #[wasm_bindgen(js_name = exportRatchetTree)]
     pub fn export_ratchet_tree(&self) -> RatchetTree {
         RatchetTree(self.mls_group.export_ratchet_tree().into())
@@ -320,13 +623,16 @@ impl Group {
         sender: &Identity,
         new_member: &KeyPackage,
     ) -> Result<AddMessages, JsError> {
+        self.ensure_capacity(1)?;
+        provider.check_key_package_lifetime(&new_member.0)?;
+        provider.check_committed_key_packages(self.mls_group.pending_proposals())?;
+
+        let signer = self.signer(provider, sender)?;
         let (proposal_msg, _proposal_ref) =
             self.mls_group
-                .propose_add_member(provider.as_ref(), &sender.keypair, &new_member.0)?;
+                .propose_add_member(provider.as_ref(), &signer, &new_member.0)?;
 
-        let (commit_msg, welcome_msg, _group_info) = self
-            .mls_group
-            .commit_to_pending_proposals(&provider.0, &sender.keypair)?;
+        let (commit_msg, welcome_msg) = self.commit_adds(provider, sender)?;
 
         let welcome_msg = welcome_msg.ok_or(NoWelcomeError)?;
 
@@ -341,11 +647,160 @@ impl Group {
         })
     }
 
+    /// Commit all proposals received so far, e.g. a member's SelfRemove.
+    ///
+    /// Returns the serialized commit, and the welcome if the proposals add
+    /// members, e.g. a join proposal of an external sender. The commit is
+    /// pending until `mergePendingCommit` is called.
+    #[wasm_bindgen(js_name = commitPendingProposals)]
+    pub fn commit_pending_proposals(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<CommitMessages, JsError> {
+        self.ensure_capacity(0)?;
+        provider.check_committed_key_packages(self.mls_group.pending_proposals())?;
+        let signer = self.signer(provider, sender)?;
+        let (commit_msg, welcome_msg, _group_info) = self
+            .mls_group
+            .commit_to_pending_proposals(provider.as_ref(), &signer)?;
+
+        Ok(CommitMessages::new(&commit_msg, welcome_msg.as_ref()))
+    }
+
+    /// Like `commitPendingProposals`, for commits without adds, such as
+    /// removes and updates.
+    ///
+    /// Fails without committing if a pending proposal adds a member, as its
+    /// welcome would be lost. Returns the serialized commit, which is
+    /// pending until `mergePendingCommit` is called.
+    #[wasm_bindgen(js_name = commitNoWelcome)]
+    pub fn commit_no_welcome(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<Vec<u8>, JsError> {
+        let adds = self.pending_proposal_counts().add();
+        if adds > 0 {
+            return Err(PendingAddsError(adds).into());
+        }
+        let signer = self.signer(provider, sender)?;
+        let (commit_msg, _welcome_msg, _group_info) = self
+            .mls_group
+            .commit_to_pending_proposals(provider.as_ref(), &signer)?;
+
+        Ok(mls_message_to_u8vec(&commit_msg))
+    }
+
+    /// Commit without any proposals, only to advance the epoch and rotate
+    /// the own path secrets, e.g. for periodic rekeying.
+    ///
+    /// Pending proposals aren't committed, and are discarded once the commit
+    /// is merged, as with every new epoch; commit them first with
+    /// `commitPendingProposals` to apply them. Returns the serialized
+    /// commit; there is no welcome since no one is added. The commit is
+    /// pending until `mergePendingCommit` is called.
+    #[wasm_bindgen(js_name = commitEmpty)]
+    pub fn commit_empty(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<Vec<u8>, JsError> {
+        let signer = self.signer(provider, sender)?;
+        let bundle = self
+            .mls_group
+            .commit_builder()
+            .consume_proposal_store(false)
+            .force_self_update(true)
+            .load_psks(provider.0.storage())?
+            .build(provider.0.rand(), provider.0.crypto(), &signer, |_| true)?
+            .stage_commit(&provider.0)?;
+
+        Ok(mls_message_to_u8vec(bundle.commit()))
+    }
+
     #[wasm_bindgen(js_name = mergePendingCommit)]
     pub fn merge_pending_commit(&mut self, provider: &mut Provider) -> Result<(), JsError> {
+        if let Some(staged_commit) = self.mls_group.pending_commit() {
+            self.record_removals(provider, staged_commit)?;
+        }
+        let update_encryption_keys = self.update_encryption_keys(provider)?;
+        self.mls_group.merge_pending_commit(provider.as_mut())?;
+        self.delete_update_key_pairs(provider, &update_encryption_keys)?;
+
+        self.retire_rotated_key(provider)?;
+
+        Ok(())
+    }
+
+    /// Replace the own signature key in this group with a freshly generated one.
+    ///
+    /// The credential stays the same. The new keypair is stored in the
+    /// provider right away. Once the returned commit is merged with
+    /// `mergePendingCommit`, this group signs with the new keypair, while
+    /// `sender` and its other groups keep the key they have. A keypair
+    /// replaced by an earlier rotation of this group is deleted from storage
+    /// then, unless another group still uses it; the keypair of `sender`
+    /// itself is kept. If the commit is dropped instead, e.g. with
+    /// `clearPendingCommit`, the new keypair is deleted again.
+    ///
+    /// Returns the commit to send to the group and the new public key.
+    #[wasm_bindgen(js_name = rotateSignatureKey)]
+    pub fn rotate_signature_key(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<SignatureKeyRotation, JsError> {
+        self.ensure_capacity(0)?;
+        provider.check_committed_key_packages(self.mls_group.pending_proposals())?;
+        let new_keypair = SignatureKeyPair::new(SignatureScheme::ED25519)?;
+        let credential_with_key = CredentialWithKey {
+            credential: sender.credential.clone(),
+            signature_key: new_keypair.public().into(),
+        };
+
+        let signer = self.signer(provider, sender)?;
+        let bundle = self.mls_group.self_update_with_new_signer(
+            provider.as_ref(),
+            &signer,
+            NewSignerBundle {
+                signer: &new_keypair,
+                credential_with_key: credential_with_key.clone(),
+            },
+            LeafNodeParameters::builder()
+                .with_credential_with_key(credential_with_key.clone())
+                .build(),
+        )?;
+
+        new_keypair.store(provider.0.storage())?;
+        let public_key = new_keypair.to_public_vec();
+        self.pending_rotation = Some(PendingRotation {
+            new_public_key: public_key.clone(),
+            retired_public_key: match signer {
+                signer::GroupSigner::Identity(_) => None,
+                signer::GroupSigner::Rotated(keypair) => Some(keypair.to_public_vec()),
+            },
+        });
+
+        Ok(SignatureKeyRotation {
+            commit: mls_message_to_u8vec(bundle.commit()),
+            public_key,
+        })
+    }
+
+    /// The signature public key in the own leaf of the current epoch.
+    ///
+    /// Messages we send carry our leaf index as `senderLeafIndex`, and this
+    /// key is what other members verify them with, so it can be used to tag
+    /// and recognize our own messages. After `rotateSignatureKey` or
+    /// `selfUpdate`, this changes once the commit is merged with
+    /// `mergePendingCommit`. Returns `undefined` if the own leaf isn't in
+    /// the tree, e.g. after we were removed.
+    #[wasm_bindgen(js_name = ownSignatureKey)]
+    pub fn own_signature_key(&self) -> Option<Vec<u8>> {
         self.mls_group
-            .merge_pending_commit(provider.as_mut())
-            .map_err(|e| e.into())
+            .own_leaf_node()
+            .map(|leaf| leaf.signature_key().as_slice().to_vec())
     }
 
     #[wasm_bindgen(js_name = createMessage)]
@@ -355,49 +810,62 @@ impl Group {
         sender: &Identity,
         msg: &[u8],
     ) -> Result<Vec<u8>, JsError> {
+        let signer = self.signer(provider, sender)?;
         let msg_out = &self
             .mls_group
-            .create_message(provider.as_ref(), &sender.keypair, msg)?;
+            .create_message(provider.as_ref(), &signer, msg)?;
         let mut serialized = vec![];
         msg_out.tls_serialize(&mut serialized)?;
         Ok(serialized)
     }
 
+    /// Like `createMessage`, but copies the message into `out` instead of
+    /// returning a new array per message.
+    ///
+    /// The message is serialized into a buffer the group reuses, and copied
+    /// once into `out`. If it doesn't fit into `out`, it is returned in a
+    /// new array in `overflow` instead and `out` is left untouched.
+    #[wasm_bindgen(js_name = createMessageInto)]
+    pub fn create_message_into(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        msg: &[u8],
+        out: &Uint8Array,
+    ) -> Result<MessageWrite, JsError> {
+        let serialized = self.create_message_buffered(provider, sender, msg)?;
+
+        let len = serialized.len();
+        if len > out.length() as usize {
+            return Ok(MessageWrite {
+                written: 0,
+                overflow: Some(serialized.to_vec()),
+            });
+        }
+
+        out.subarray(0, len as u32).copy_from(serialized);
+        Ok(MessageWrite {
+            written: len,
+            overflow: None,
+        })
+    }
+
+    /// Process a message and return the plaintext of an application message,
+    /// or an empty array for other messages.
+    ///
+    /// A message for another epoch than the current one fails with a "wrong
+    /// epoch" error naming both epochs: a message from a past epoch can be
+    /// dropped, one from a future epoch has to wait for the commits before
+    /// it.
     #[wasm_bindgen(js_name = processMessage)]
     pub fn process_message(
         &mut self,
         provider: &mut Provider,
-        mut msg: &[u8],
+        msg: &[u8],
     ) -> Result<Vec<u8>, JsError> {
-        let msg = MlsMessageIn::tls_deserialize(&mut msg).unwrap();
-
-        let msg = match msg.extract() {
-            openmls::framing::MlsMessageBodyIn::PublicMessage(msg) => {
-                self.mls_group.process_message(provider.as_ref(), msg)?
-            }
-
-            openmls::framing::MlsMessageBodyIn::PrivateMessage(msg) => {
-                self.mls_group.process_message(provider.as_ref(), msg)?
-            }
-            openmls::framing::MlsMessageBodyIn::Welcome(_) => todo!(),
-            openmls::framing::MlsMessageBodyIn::GroupInfo(_) => todo!(),
-            openmls::framing::MlsMessageBodyIn::KeyPackage(_) => todo!(),
-        };
+        let processed = self.process(provider, msg)?;
 
-        match msg.into_content() {
-            openmls::framing::ProcessedMessageContent::ApplicationMessage(app_msg) => {
-                Ok(app_msg.into_bytes())
-            }
-            openmls::framing::ProcessedMessageContent::ProposalMessage(_)
-            | openmls::framing::ProcessedMessageContent::ExternalJoinProposalMessage(_) => {
-                Ok(vec![])
-            }
-            openmls::framing::ProcessedMessageContent::StagedCommitMessage(staged_commit) => {
-                self.mls_group
-                    .merge_staged_commit(provider.as_mut(), *staged_commit)?;
-                Ok(vec![])
-            }
-        }
+        Ok(processed.application_data().unwrap_or_default())
     }
 
     #[wasm_bindgen(js_name = exportSecret)]
@@ -420,6 +888,158 @@ impl Group {
     pub fn get_epoch(&self) -> u32 {
         self.mls_group.epoch().as_u64() as u32
     }
+
+    /// The epochs this group can still decrypt application messages of,
+    /// oldest first, ending with the current epoch.
+    ///
+    /// These are the past epochs the group still holds the message secrets
+    /// for, at most `max_past_epochs` of them, plus the current one. A group
+    /// that was just joined has no secrets of earlier epochs, so the list
+    /// can be shorter than the configuration allows. Messages from
+    /// older epochs can't be decrypted and need a resync. A floor set with
+    /// `Provider.setMinDecryptEpoch` isn't applied here.
+    #[wasm_bindgen(js_name = retainedEpochs)]
+    pub fn retained_epochs(&self) -> Vec<u32> {
+        self.mls_group
+            .retained_past_epochs()
+            .into_iter()
+            .chain([self.mls_group.epoch()])
+            .map(|epoch| epoch.as_u64() as u32)
+            .collect()
+    }
+
+    /// Whether this client is a current member of the group and can still
+    /// encrypt messages. Becomes `false` once a commit removing this client
+    /// has been merged.
+    #[wasm_bindgen(js_name = isActive)]
+    pub fn is_active(&self) -> bool {
+        self.mls_group.is_active()
+    }
+
+    /// The members of the group in the current epoch.
+    ///
+    /// Members are always returned in ascending leaf index order, so that
+    /// values derived from the list (fingerprints, safety numbers) are
+    /// identical across all clients in the same epoch.
+    pub fn members(&self) -> Result<Vec<GroupMember>, JsError> {
+        let mut members = self.mls_group.members().collect::<Vec<_>>();
+        members.sort_by_key(|member| member.index);
+
+        Ok(members
+            .into_iter()
+            .map(GroupMember::try_from)
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// The HPKE public encryption key in the leaf `leaf_index` of the tree
+    /// in the current epoch, e.g. to encrypt directly to that member.
+    ///
+    /// The key changes with every commit that updates the leaf, so compare
+    /// it before and after a self-update to check that the update took
+    /// effect. Fails for blank leaves and indices outside the tree.
+    #[wasm_bindgen(js_name = memberEncryptionKey)]
+    pub fn member_encryption_key(&self, leaf_index: u32) -> Result<Vec<u8>, JsError> {
+        Ok(self.leaf_encryption_key(leaf_index)?)
+    }
+
+    /// Set the group name by committing a GroupContextExtensions proposal.
+    ///
+    /// Returns the serialized commit. Like the other commit methods, the
+    /// commit is pending until `mergePendingCommit` is called.
+    #[wasm_bindgen(js_name = setName)]
+    pub fn set_name(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        name: &str,
+    ) -> Result<Vec<u8>, JsError> {
+        self.ensure_capacity(0)?;
+        provider.check_committed_key_packages(self.mls_group.pending_proposals())?;
+        let extensions = extensions::with_app_extension(
+            self.mls_group.extensions(),
+            extensions::GROUP_NAME_EXTENSION_TYPE,
+            name.as_bytes().to_vec(),
+        )?;
+
+        let signer = self.signer(provider, sender)?;
+        let (commit_msg, _welcome_msg, _group_info) = self
+            .mls_group
+            .update_group_context_extensions(provider.as_ref(), extensions, &signer)?;
+
+        Ok(mls_message_to_u8vec(&commit_msg))
+    }
+
+    /// The group name from the group context, if one has been set.
+    pub fn name(&self) -> Option<String> {
+        extensions::app_extension(
+            self.mls_group.extensions(),
+            extensions::GROUP_NAME_EXTENSION_TYPE,
+        )
+        .map(|name| String::from_utf8_lossy(name).to_string())
+    }
+
+    /// Who created the group and when, as recorded in the group context at
+    /// creation with the `recordFounder` option of `GroupConfig` or
+    /// `GroupConfigBuilder`. `undefined` for groups created without it.
+    #[wasm_bindgen(js_name = founderInfo)]
+    pub fn founder_info(&self) -> Option<FounderInfo> {
+        let mut data = extensions::app_extension(
+            self.mls_group.extensions(),
+            extensions::FOUNDER_INFO_EXTENSION_TYPE,
+        )?;
+
+        extensions::FounderInfo::tls_deserialize(&mut data)
+            .ok()
+            .map(FounderInfo)
+    }
+}
+
+impl Group {
+    /// Create a group founded by `founder` with the given group context
+    /// extensions.
+    pub(crate) fn build_new(
+        provider: &Provider,
+        founder: &Identity,
+        group_id: &[u8],
+        group_context_extensions: Extensions<GroupContext>,
+    ) -> Result<Group, NewGroupError<TorlnStorageError>> {
+        Group::build_configured(
+            provider,
+            founder,
+            group_id,
+            group_context_extensions,
+            &GroupCreationConfig::default(),
+        )
+    }
+}
+
+impl Group {
+    /// Create an application message and serialize it into a buffer of the
+    /// group, which the next call reuses, see `createMessageInto`.
+    pub fn create_message_buffered(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        msg: &[u8],
+    ) -> Result<&[u8], JsError> {
+        let signer = self.signer(provider, sender)?;
+        let msg_out = self
+            .mls_group
+            .create_message(provider.as_ref(), &signer, msg)?;
+
+        self.message_buffer.clear();
+        msg_out.tls_serialize(&mut self.message_buffer)?;
+        Ok(&self.message_buffer)
+    }
+
+    /// The encryption key of the leaf `leaf_index`, see
+    /// `memberEncryptionKey`.
+    pub(crate) fn leaf_encryption_key(&self, leaf_index: u32) -> Result<Vec<u8>, BlankLeaf> {
+        self.mls_group
+            .member_at(LeafNodeIndex::new(leaf_index))
+            .map(|member| member.encryption_key)
+            .ok_or(BlankLeaf(leaf_index))
+    }
 }
 
 #[cfg(test)]
@@ -430,13 +1050,16 @@ impl Group {
         sender: &Identity,
         new_member: &KeyPackage,
     ) -> Result<NativeAddMessages, JsError> {
+        self.ensure_capacity(1)?;
+        provider.check_key_package_lifetime(&new_member.0)?;
+        provider.check_committed_key_packages(self.mls_group.pending_proposals())?;
+
+        let signer = self.signer(provider, sender)?;
         let (proposal_msg, _proposal_ref) =
             self.mls_group
-                .propose_add_member(provider.as_ref(), &sender.keypair, &new_member.0)?;
+                .propose_add_member(provider.as_ref(), &signer, &new_member.0)?;
 
-        let (commit_msg, welcome_msg, _group_info) = self
-            .mls_group
-            .commit_to_pending_proposals(provider.as_ref(), &sender.keypair)?;
+        let (commit_msg, welcome_msg) = self.commit_adds(provider, sender)?;
 
         let welcome_msg = welcome_msg.ok_or(NoWelcomeError)?;
 
@@ -453,28 +1076,12 @@ impl Group {
 
     pub(crate) fn native_join(
         provider: &Provider,
-        mut welcome: &[u8],
+        welcome: &[u8],
         ratchet_tree: RatchetTree,
     ) -> Group {
-        let welcome = match MlsMessageIn::tls_deserialize(&mut welcome)
+        welcome::join_group(provider, welcome, ratchet_tree)
             .unwrap()
-            .extract()
-        {
-            MlsMessageBodyIn::Welcome(welcome) => welcome,
-            _ => panic!("expected a message of type welcome"),
-        };
-        let config = MlsGroupJoinConfig::builder().build();
-        let mls_group = StagedWelcome::new_from_welcome(
-            provider.as_ref(),
-            &config,
-            welcome,
-            Some(ratchet_tree.0),
-        )
-        .unwrap()
-        .into_group(provider.as_ref())
-        .unwrap();
-
-        Group { mls_group }
+            .into()
     }
 }
 
@@ -490,6 +1097,34 @@ impl std::fmt::Display for NoWelcomeError {
 
 impl std::error::Error for NoWelcomeError {}
 
+/// Pending proposals add members, so committing them needs a welcome.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PendingAddsError(pub(crate) u32);
+
+impl std::fmt::Display for PendingAddsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pending proposals add members, commit them with commitPendingProposals",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for PendingAddsError {}
+
+/// There is no member in the leaf with the given index.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct BlankLeaf(pub(crate) u32);
+
+impl std::fmt::Display for BlankLeaf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no member at leaf index {}", self.0)
+    }
+}
+
+impl std::error::Error for BlankLeaf {}
+
 #[wasm_bindgen]
 pub struct KeyPackage(OpenMlsKeyPackage);
 
@@ -499,6 +1134,12 @@ impl KeyPackage {
     pub fn to_bytes(&self) -> Result<Vec<u8>, JsError> {
         Ok(self.0.tls_serialize_detached()?)
     }
+
+    /// The key package reference, which identifies the recipient of a
+    /// welcome, see `Group.welcomeForRef`.
+    pub fn reference(&self) -> Result<Vec<u8>, JsError> {
+        Ok(self.0.hash_ref(&RustCrypto::default())?.as_slice().to_vec())
+    }
 }
 
 #[wasm_bindgen]
@@ -513,7 +1154,6 @@ fn mls_message_to_uint8array(msg: &MlsMessageOut) -> Uint8Array {
     unsafe { Uint8Array::new(&Uint8Array::view(&serialized)) }
 }
 
-#[cfg(test)]
 pub(crate) fn mls_message_to_u8vec(msg: &MlsMessageOut) -> Vec<u8> {
     // see https://github.com/rustwasm/wasm-bindgen/issues/1619#issuecomment-505065294
 
@@ -0,0 +1,83 @@
+//! A limit on the size of the messages we parse.
+//!
+//! A hostile peer or relay can send a message that is small on the wire
+//! but declares huge vectors, or simply a huge message, to make the parser
+//! allocate until the browser tab runs out of memory. Inputs larger than
+//! the limit of the provider are rejected before they are parsed. Functions
+//! that parse without a provider use the default limit.
+
+use wasm_bindgen::prelude::*;
+
+use crate::Provider;
+
+/// The default limit, well above any message our groups produce: welcomes
+/// and commits carry the ratchet tree path, which stays far below this for
+/// groups of thousands of members.
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+/// The maximum size of an input to parse, see `Provider.setMaxMessageBytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MaxMessageBytes(usize);
+
+impl Default for MaxMessageBytes {
+    fn default() -> Self {
+        Self(DEFAULT_MAX_MESSAGE_BYTES)
+    }
+}
+
+impl MaxMessageBytes {
+    /// Check `bytes` against this limit before parsing them.
+    pub(crate) fn check(self, bytes: &[u8]) -> Result<(), MessageTooLarge> {
+        if bytes.len() > self.0 {
+            return Err(MessageTooLarge {
+                size: bytes.len(),
+                max: self.0,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// An input is larger than the limit of the provider.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct MessageTooLarge {
+    pub(crate) size: usize,
+    pub(crate) max: usize,
+}
+
+impl std::fmt::Display for MessageTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "message of {} bytes exceeds the limit of {} bytes",
+            self.size, self.max
+        )
+    }
+}
+
+impl std::error::Error for MessageTooLarge {}
+
+impl Provider {
+    /// Check `bytes` against the size limit before parsing them.
+    pub(crate) fn check_message_size(&self, bytes: &[u8]) -> Result<(), MessageTooLarge> {
+        self.1.check(bytes)
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Reject messages, welcomes, group infos and key packages larger than
+    /// `max_bytes` before parsing them, so that a hostile message can't
+    /// exhaust the memory of the tab. The default is 16 MiB.
+    #[wasm_bindgen(js_name = setMaxMessageBytes)]
+    pub fn set_max_message_bytes(&mut self, max_bytes: usize) {
+        self.1 = MaxMessageBytes(max_bytes);
+    }
+
+    /// The current limit of `setMaxMessageBytes`.
+    #[wasm_bindgen(getter, js_name = maxMessageBytes)]
+    pub fn max_message_bytes(&self) -> usize {
+        self.1 .0
+    }
+}
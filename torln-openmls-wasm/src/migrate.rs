@@ -0,0 +1,252 @@
+//! Moving a group from the storage of one provider to another, e.g. from an
+//! in-memory provider to one that is persisted.
+//!
+//! openmls keeps a group in many storage entries. Most are keyed by the JSON
+//! of the group id; queued proposals and the keypairs of past epochs by
+//! keys that start with it. Our own entries, like the minimum decryption
+//! epoch, are keyed by the length-prefixed group id. The private keys of our
+//! leaf are keyed by their public keys, so they are found through the own
+//! leaf nodes of the group, and external PSKs by their ids, which the group
+//! context names.
+
+use openmls::{
+    group::{GroupId, MlsGroup},
+    schedule::{ExternalPsk, Psk},
+};
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_torln_storage::{TorlnStorage, TorlnStorageError};
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    branch,
+    epoch_floor::MIN_DECRYPT_EPOCH_STORAGE_LABEL,
+    extensions,
+    orphaned_keys::SIGNATURE_KEY_PAIR_LABEL,
+    pending_state::{self, own_leaf_key_pair_keys},
+    readd::REMOVED_MEMBERS_STORAGE_LABEL,
+    stable_secret::STABLE_SECRET_STORAGE_LABEL,
+    Group, Provider,
+};
+
+/// The labels of our entries keyed by the length-prefixed group id.
+const TORLN_GROUP_LABELS: [&[u8]; 3] = [
+    MIN_DECRYPT_EPOCH_STORAGE_LABEL,
+    REMOVED_MEMBERS_STORAGE_LABEL,
+    STABLE_SECRET_STORAGE_LABEL,
+];
+
+/// Errors when migrating a group to another provider.
+#[derive(Debug)]
+pub(crate) enum MigrateError {
+    Encoding(serde_json::Error),
+    Storage(TorlnStorageError),
+    GroupNotFound,
+    /// The destination already has a group with this id.
+    AlreadyInDestination,
+}
+
+impl std::fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Encoding(e) => write!(f, "failed to read group state: {e}"),
+            Self::Storage(e) => write!(f, "failed to load group: {e}"),
+            Self::GroupNotFound => write!(f, "group not found in source storage"),
+            Self::AlreadyInDestination => {
+                write!(f, "group already exists in destination storage")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrateError {}
+
+/// The storage entries of a group.
+struct GroupEntries {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    /// How many of `entries`, at their end, are signature keypairs, which
+    /// other groups of the identity may use too.
+    signature_key_pairs: usize,
+}
+
+/// The storage entries of `mls_group` in `provider`, with the keypairs of
+/// our leaf.
+fn group_entries(provider: &Provider, mls_group: &MlsGroup) -> Result<GroupEntries, MigrateError> {
+    let group_id = mls_group.group_id();
+    let length_prefixed_id = [
+        &(group_id.as_slice().len() as u32).to_be_bytes()[..],
+        group_id.as_slice(),
+    ]
+    .concat();
+
+    let mut keys = TorlnStorage::group_keys(group_id).map_err(MigrateError::Storage)?;
+    keys.extend(
+        TORLN_GROUP_LABELS
+            .iter()
+            .map(|label| [*label, length_prefixed_id.as_slice()].concat()),
+    );
+    keys.push(pending_state::marker_key(group_id).map_err(MigrateError::Storage)?);
+
+    // The external PSKs of the group: the enrollment PSK, which later adds
+    // inject, and the PSK of a branch off the current epoch while the
+    // subgroup is created or joined.
+    let enrollment_psk_id = extensions::app_extension(
+        mls_group.extensions(),
+        extensions::ENROLLMENT_PSK_EXTENSION_TYPE,
+    );
+    for psk_id in enrollment_psk_id
+        .map(<[u8]>::to_vec)
+        .into_iter()
+        .chain([branch::branch_psk_id(mls_group)])
+    {
+        keys.push(
+            TorlnStorage::psk_key(&Psk::External(ExternalPsk::new(psk_id)))
+                .map_err(MigrateError::Storage)?,
+        );
+    }
+
+    // The own leaf, and the leaves of our update proposals and of a pending
+    // commit of ours once it's merged.
+    let own_leaf = mls_group.own_leaf_node();
+    let pending_leaf = mls_group
+        .pending_commit()
+        .and_then(|staged_commit| staged_commit.update_path_leaf_node());
+    let mut signature_keys = Vec::new();
+    for leaf_node in own_leaf.into_iter().chain(pending_leaf) {
+        keys.push(
+            TorlnStorage::encryption_key_pair_key(leaf_node.encryption_key())
+                .map_err(MigrateError::Storage)?,
+        );
+        signature_keys.push(leaf_node.signature_key().as_slice().to_vec());
+    }
+    keys.extend(
+        own_leaf_key_pair_keys(provider.0.storage(), mls_group.group_id())
+            .map_err(MigrateError::Storage)?,
+    );
+
+    // A poisoned lock still holds consistent data, see `transaction`.
+    let values = provider
+        .0
+        .storage()
+        .values()
+        .read()
+        .unwrap_or_else(|e| e.into_inner());
+
+    let mut entries = keys
+        .into_iter()
+        .filter_map(|key| Some((key.clone(), values.get(&key)?.clone())))
+        .chain(
+            values
+                .iter()
+                .filter(|(key, _)| {
+                    TorlnStorage::is_epoch_key_pairs_key(key, group_id).unwrap_or(false)
+                        || TorlnStorage::is_queued_proposal_key(key, group_id).unwrap_or(false)
+                })
+                .map(|(key, value)| (key.clone(), value.clone())),
+        )
+        .collect::<Vec<_>>();
+    let signature_key_pairs = values
+        .iter()
+        .filter(|(key, _)| key.starts_with(SIGNATURE_KEY_PAIR_LABEL))
+        .filter(|(_, value)| {
+            serde_json::from_slice::<SignatureKeyPair>(value)
+                .is_ok_and(|keypair| signature_keys.contains(&keypair.public().to_vec()))
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<Vec<_>>();
+    let signature_key_pair_count = signature_key_pairs.len();
+    entries.extend(signature_key_pairs);
+
+    Ok(GroupEntries {
+        entries,
+        signature_key_pairs: signature_key_pair_count,
+    })
+}
+
+impl Group {
+    /// Move the group `group_id` from `source` to `destination`, see
+    /// `migrateTo`.
+    pub(crate) fn migrate_native(
+        source: &Provider,
+        destination: &Provider,
+        group_id: &GroupId,
+        wipe_source: bool,
+    ) -> Result<Group, MigrateError> {
+        let mls_group = MlsGroup::load(source.0.storage(), group_id)
+            .map_err(MigrateError::Storage)?
+            .ok_or(MigrateError::GroupNotFound)?;
+        // Also refuses to migrate a group into the provider it's in.
+        if MlsGroup::load(destination.0.storage(), group_id)
+            .map_err(MigrateError::Storage)?
+            .is_some()
+        {
+            return Err(MigrateError::AlreadyInDestination);
+        }
+
+        let GroupEntries {
+            entries,
+            signature_key_pairs,
+        } = group_entries(source, &mls_group)?;
+        destination
+            .0
+            .storage()
+            .write_entries(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Some(value.clone()))),
+            )
+            .map_err(MigrateError::Storage)?;
+
+        let migrated = MlsGroup::load(destination.0.storage(), group_id)
+            .map_err(MigrateError::Storage)?
+            .ok_or(MigrateError::GroupNotFound)?;
+
+        if wipe_source {
+            source
+                .0
+                .storage()
+                .write_entries(
+                    entries[..entries.len() - signature_key_pairs]
+                        .iter()
+                        .map(|(key, _)| (key.clone(), None)),
+                )
+                .map_err(MigrateError::Storage)?;
+        }
+
+        Ok(migrated.into())
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Copy the group `group_id` from the storage of `source_provider` to
+    /// `dest_provider` and load it from there, e.g. to move a session from
+    /// an in-memory provider to a persisted one.
+    ///
+    /// Copies the state of the epoch, the tree, the secrets of past epochs,
+    /// the queued proposals and a pending commit, the encryption and
+    /// signature keypairs of our leaf, and the enrollment PSK of the group.
+    /// Settings of the `Group` object, like `setAutoMerge`, aren't stored
+    /// and start over. Fails if `dest_provider` already has the group.
+    ///
+    /// With `wipe_source`, the group is deleted from `source_provider`
+    /// afterwards. The signature keypair is kept there, as the identity may
+    /// use it in other groups; `pruneOrphanedKeypairs` removes it once
+    /// nothing uses it.
+    #[wasm_bindgen(js_name = migrateTo)]
+    pub fn migrate_to(
+        source_provider: &Provider,
+        dest_provider: &Provider,
+        group_id: &str,
+        wipe_source: bool,
+    ) -> Result<Group, JsError> {
+        let group_id = GroupId::from_slice(group_id.as_bytes());
+
+        Ok(Group::migrate_native(
+            source_provider,
+            dest_provider,
+            &group_id,
+            wipe_source,
+        )?)
+    }
+}
@@ -0,0 +1,173 @@
+//! Finding signature keypairs in the storage that nothing uses anymore.
+//!
+//! Keypairs are stored when an identity is created and when a signature key
+//! is rotated, and stay after the groups that used them are deleted or
+//! after the identity is replaced. A keypair is in use if it signs for our
+//! leaf in a stored group, now or once a pending commit of ours is merged.
+//! Identities that aren't in any group yet, e.g. with key packages out,
+//! look unused; the app passes their public keys as protected.
+
+use std::collections::BTreeSet;
+
+use js_sys::Uint8Array;
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{stored_groups::GroupLoadError, Provider};
+
+/// The label of the signature keypairs in the storage.
+pub(crate) const SIGNATURE_KEY_PAIR_LABEL: &[u8] = b"SignatureKeyPair";
+
+/// Errors when deleting the orphaned signature keypairs.
+#[derive(Debug)]
+pub(crate) enum PruneError {
+    Load(GroupLoadError),
+    Storage(TorlnStorageError),
+}
+
+impl std::fmt::Display for PruneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Load(e) => write!(f, "{e}"),
+            Self::Storage(e) => write!(f, "failed to delete keypairs: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PruneError {}
+
+/// A signature keypair in the storage.
+struct StoredKeyPair {
+    storage_key: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl Provider {
+    /// The public keys of the signature keypairs our leaves in the stored
+    /// groups use. Fails if a group can't be loaded, as its keys are
+    /// unknown then.
+    fn used_signature_keys(&self) -> Result<BTreeSet<Vec<u8>>, GroupLoadError> {
+        let mut used = BTreeSet::new();
+        for group in self.load_all_groups_native() {
+            let group = group?;
+            let own_leaf = group.mls_group.own_leaf_node();
+            let pending_leaf = group
+                .mls_group
+                .pending_commit()
+                .and_then(|staged_commit| staged_commit.update_path_leaf_node());
+            for leaf_node in own_leaf.into_iter().chain(pending_leaf) {
+                used.insert(leaf_node.signature_key().as_slice().to_vec());
+            }
+        }
+
+        Ok(used)
+    }
+
+    /// The signature keypairs no stored group uses and that aren't in
+    /// `protected`, see `listOrphanedKeypairs`.
+    fn orphaned_keypairs(
+        &self,
+        protected: &[Vec<u8>],
+    ) -> Result<Vec<StoredKeyPair>, GroupLoadError> {
+        let used = self.used_signature_keys()?;
+        // A poisoned lock still holds consistent data, see `transaction`.
+        let values = self
+            .0
+            .storage()
+            .values()
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+
+        Ok(values
+            .iter()
+            .filter(|(key, _)| key.starts_with(SIGNATURE_KEY_PAIR_LABEL))
+            .filter_map(|(key, value)| {
+                // Entries that aren't keypairs are left alone.
+                let keypair = serde_json::from_slice::<SignatureKeyPair>(value).ok()?;
+                Some(StoredKeyPair {
+                    storage_key: key.clone(),
+                    public_key: keypair.public().to_vec(),
+                })
+            })
+            .filter(|keypair| {
+                !used.contains(&keypair.public_key) && !protected.contains(&keypair.public_key)
+            })
+            .collect())
+    }
+
+    /// The public keys of the orphaned signature keypairs, see
+    /// `listOrphanedKeypairs`.
+    pub(crate) fn orphaned_public_keys(
+        &self,
+        protected: &[Vec<u8>],
+    ) -> Result<Vec<Vec<u8>>, GroupLoadError> {
+        let mut public_keys = self
+            .orphaned_keypairs(protected)?
+            .into_iter()
+            .map(|keypair| keypair.public_key)
+            .collect::<Vec<_>>();
+        public_keys.sort();
+
+        Ok(public_keys)
+    }
+
+    /// Delete the orphaned signature keypairs, see `pruneOrphanedKeypairs`.
+    pub(crate) fn prune_orphaned_keypairs_native(
+        &self,
+        protected: &[Vec<u8>],
+    ) -> Result<usize, PruneError> {
+        let orphaned = self
+            .orphaned_keypairs(protected)
+            .map_err(PruneError::Load)?;
+        self.0
+            .storage()
+            .write_entries(
+                orphaned
+                    .iter()
+                    .map(|keypair| (keypair.storage_key.clone(), None)),
+            )
+            .map_err(PruneError::Storage)?;
+
+        Ok(orphaned.len())
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// The public keys of the signature keypairs in the storage that our
+    /// leaf in no stored group uses, leaving out those in `protected`.
+    ///
+    /// A keypair counts as used if it signs for our leaf in a stored group,
+    /// or will once a pending commit of ours is merged. Keypairs of
+    /// identities that aren't in a group yet, e.g. that have key packages
+    /// out, are listed too, so pass the public keys of every identity the
+    /// app still uses in `protected`. Fails if a stored group can't be
+    /// loaded, since the keys it uses are unknown then.
+    #[wasm_bindgen(js_name = listOrphanedKeypairs)]
+    pub fn list_orphaned_keypairs(
+        &self,
+        protected: Vec<Uint8Array>,
+    ) -> Result<Vec<Uint8Array>, JsError> {
+        let protected = protected.iter().map(Uint8Array::to_vec).collect::<Vec<_>>();
+
+        Ok(self
+            .orphaned_public_keys(&protected)?
+            .iter()
+            .map(|public_key| public_key.as_slice().into())
+            .collect())
+    }
+
+    /// Delete the keypairs `listOrphanedKeypairs` lists for `protected`,
+    /// and return how many were deleted.
+    ///
+    /// Identities with a deleted keypair can't sign anymore, so this can't
+    /// be undone; check the list first if in doubt.
+    #[wasm_bindgen(js_name = pruneOrphanedKeypairs)]
+    pub fn prune_orphaned_keypairs(&self, protected: Vec<Uint8Array>) -> Result<u32, JsError> {
+        let protected = protected.iter().map(Uint8Array::to_vec).collect::<Vec<_>>();
+
+        Ok(self.prune_orphaned_keypairs_native(&protected)? as u32)
+    }
+}
@@ -0,0 +1,252 @@
+//! Checkpointing the uncommitted work of a group.
+//!
+//! Next to the state of the epoch, openmls keeps the pending proposals of a
+//! group, a pending commit of our own and the leaf nodes of our update
+//! proposals in the provider storage. An app that persists the storage only
+//! after a commit is merged loses all of that when it reloads. The pending
+//! state is the few storage entries holding it, in the `exportStorage`
+//! format, plus a marker entry with the group and epoch it belongs to, so
+//! that it can't be restored into another group or epoch.
+
+use openmls::{
+    ciphersuite::hash_ref::ProposalRef,
+    group::{GroupId, MlsGroup},
+    treesync::LeafNode,
+};
+use openmls_torln_storage::{TorlnStorage, TorlnStorageError};
+use openmls_traits::{
+    storage::{StorageProvider, CURRENT_VERSION},
+    OpenMlsProvider,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    storage::{self, StorageFormatError},
+    Group, Provider,
+};
+
+/// The label of the marker entry, whose value is the epoch.
+const PENDING_STATE_LABEL: &[u8] = b"TorlnPendingState";
+
+/// Errors when exporting or importing the pending state of a group.
+#[derive(Debug)]
+pub(crate) enum PendingStateError {
+    Format(StorageFormatError),
+    Encoding(TorlnStorageError),
+    /// The pending state has no marker for this group.
+    OtherGroup,
+    OtherEpoch {
+        expected: u64,
+        found: u64,
+    },
+    /// An entry that isn't pending state of this group.
+    UnexpectedEntry,
+    Storage(TorlnStorageError),
+    GroupNotFound,
+}
+
+impl std::fmt::Display for PendingStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Format(e) => write!(f, "invalid pending state: {e}"),
+            Self::Encoding(e) => write!(f, "failed to read pending state: {e}"),
+            Self::OtherGroup => write!(f, "pending state is not for this group"),
+            Self::OtherEpoch { expected, found } => write!(
+                f,
+                "pending state is for epoch {found}, but the group is in epoch {expected}"
+            ),
+            Self::UnexpectedEntry => {
+                write!(f, "pending state has entries that aren't pending state")
+            }
+            Self::Storage(e) => write!(f, "failed to restore pending state: {e}"),
+            Self::GroupNotFound => write!(f, "failed to reload group: not found in storage"),
+        }
+    }
+}
+
+impl std::error::Error for PendingStateError {}
+
+/// The storage keys of the pending state of a group.
+struct PendingKeys {
+    group_id: GroupId,
+    marker: Vec<u8>,
+    group_state: Vec<u8>,
+    proposal_queue_refs: Vec<u8>,
+    own_leaf_nodes: Vec<u8>,
+}
+
+/// The key of the marker entry of the pending state of `group_id`.
+pub(crate) fn marker_key(group_id: &GroupId) -> Result<Vec<u8>, TorlnStorageError> {
+    Ok([
+        PENDING_STATE_LABEL,
+        &serde_json::to_vec(group_id)?,
+        &CURRENT_VERSION.to_be_bytes(),
+    ]
+    .concat())
+}
+
+impl PendingKeys {
+    fn new(group_id: &GroupId) -> Result<Self, TorlnStorageError> {
+        Ok(Self {
+            group_id: group_id.clone(),
+            marker: marker_key(group_id)?,
+            group_state: TorlnStorage::group_state_key(group_id)?,
+            proposal_queue_refs: TorlnStorage::proposal_queue_refs_key(group_id)?,
+            own_leaf_nodes: TorlnStorage::own_leaf_nodes_key(group_id)?,
+        })
+    }
+
+    /// Whether `key` is one of the entries replaced on import, i.e. the
+    /// proposal queue or the own leaf nodes.
+    fn is_queue(&self, key: &[u8]) -> bool {
+        key == self.proposal_queue_refs
+            || key == self.own_leaf_nodes
+            || TorlnStorage::is_queued_proposal_key(key, &self.group_id).unwrap_or(false)
+    }
+}
+
+/// The storage keys of the encryption keypairs of the own leaf nodes of the
+/// group `group_id` in `storage`.
+pub(crate) fn own_leaf_key_pair_keys(
+    storage: &TorlnStorage,
+    group_id: &GroupId,
+) -> Result<Vec<Vec<u8>>, TorlnStorageError> {
+    storage
+        .own_leaf_nodes::<_, LeafNode>(group_id)?
+        .iter()
+        .map(|leaf_node| TorlnStorage::encryption_key_pair_key(leaf_node.encryption_key()))
+        .collect()
+}
+
+impl Group {
+    fn pending_keys(&self) -> Result<PendingKeys, PendingStateError> {
+        PendingKeys::new(self.mls_group.group_id()).map_err(PendingStateError::Encoding)
+    }
+
+    /// The pending state of this group, see `exportPendingState`.
+    pub(crate) fn pending_state(&self, provider: &Provider) -> Result<Vec<u8>, PendingStateError> {
+        let keys = self.pending_keys()?;
+        let key_pairs = own_leaf_key_pair_keys(provider.0.storage(), self.mls_group.group_id())
+            .map_err(PendingStateError::Encoding)?;
+        // A poisoned lock still holds consistent data, see `transaction`.
+        let values = provider
+            .0
+            .storage()
+            .values()
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+
+        let epoch = self.mls_group.epoch().as_u64().to_be_bytes();
+        let mut entries = vec![(keys.marker.as_slice(), epoch.as_slice())];
+        entries.extend(
+            values
+                .iter()
+                .filter(|(key, _)| *key == &keys.group_state || keys.is_queue(key))
+                .map(|(key, value)| (key.as_slice(), value.as_slice())),
+        );
+        for key in &key_pairs {
+            if let Some((key, value)) = values.get_key_value(key) {
+                entries.push((key.as_slice(), value.as_slice()));
+            }
+        }
+
+        Ok(storage::encode_entries(entries.len(), entries.into_iter()))
+    }
+
+    /// Replace the pending state of this group with `pending_state`, see
+    /// `importPendingState`.
+    pub(crate) fn restore_pending_state(
+        &mut self,
+        provider: &Provider,
+        pending_state: &[u8],
+    ) -> Result<(), PendingStateError> {
+        let keys = self.pending_keys()?;
+        let mut entries =
+            storage::decode_entries(pending_state).map_err(PendingStateError::Format)?;
+
+        let marker = entries
+            .iter()
+            .position(|(key, _)| *key == keys.marker)
+            .ok_or(PendingStateError::OtherGroup)?;
+        let (_, epoch) = entries.swap_remove(marker);
+        let found = epoch
+            .try_into()
+            .map(u64::from_be_bytes)
+            .map_err(|_| PendingStateError::OtherGroup)?;
+        let expected = self.mls_group.epoch().as_u64();
+        if found != expected {
+            return Err(PendingStateError::OtherEpoch { expected, found });
+        }
+
+        // The own leaf nodes of the pending state are read through a scratch
+        // storage, to find the keypairs that belong to them.
+        let pending_storage = TorlnStorage::default();
+        pending_storage
+            .values()
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(
+                entries
+                    .iter()
+                    .filter(|(key, _)| *key == keys.own_leaf_nodes)
+                    .cloned(),
+            );
+        let key_pairs = own_leaf_key_pair_keys(&pending_storage, self.mls_group.group_id())
+            .map_err(PendingStateError::Encoding)?;
+        if entries.iter().any(|(key, _)| {
+            *key != keys.group_state && !keys.is_queue(key) && !key_pairs.contains(key)
+        }) {
+            return Err(PendingStateError::UnexpectedEntry);
+        }
+
+        // Proposals that were queued since the export are dropped along with
+        // the rest of the queue.
+        let storage = provider.0.storage();
+        storage
+            .clear_proposal_queue::<_, ProposalRef>(self.mls_group.group_id())
+            .map_err(PendingStateError::Storage)?;
+        storage
+            .delete_own_leaf_nodes(self.mls_group.group_id())
+            .map_err(PendingStateError::Storage)?;
+        storage
+            .write_entries(entries.into_iter().map(|(key, value)| (key, Some(value))))
+            .map_err(PendingStateError::Storage)?;
+
+        self.mls_group = MlsGroup::load(storage, self.mls_group.group_id())
+            .map_err(PendingStateError::Storage)?
+            .ok_or(PendingStateError::GroupNotFound)?;
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Export the uncommitted work of this group: the pending proposals, a
+    /// pending commit of our own and the private keys they need.
+    ///
+    /// Unlike `exportStorage`, this leaves out the state of the epoch, so it
+    /// is small enough to checkpoint after every proposal or commit. A
+    /// received commit held back with `setAutoMerge` is not included.
+    #[wasm_bindgen(js_name = exportPendingState)]
+    pub fn export_pending_state(&self, provider: &Provider) -> Result<Vec<u8>, JsError> {
+        Ok(self.pending_state(provider)?)
+    }
+
+    /// Restore pending state exported with `exportPendingState`, e.g. after
+    /// reloading the group from storage persisted before the proposals or
+    /// commit were created.
+    ///
+    /// Replaces the pending proposals and the pending commit of the group.
+    /// Fails if the pending state is of another group or of another epoch,
+    /// e.g. because a commit was merged since it was exported; in that case
+    /// the group is left as it is.
+    #[wasm_bindgen(js_name = importPendingState)]
+    pub fn import_pending_state(
+        &mut self,
+        provider: &Provider,
+        pending_state: &[u8],
+    ) -> Result<(), JsError> {
+        Ok(self.restore_pending_state(provider, pending_state)?)
+    }
+}
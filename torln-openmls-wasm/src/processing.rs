@@ -0,0 +1,679 @@
+//! Processing incoming messages.
+
+use js_sys::Uint8Array;
+use openmls::{
+    framing::{
+        errors::{MessageDecryptionError, SecretTreeError},
+        MlsMessageBodyIn, MlsMessageIn, ProcessedMessageContent, Sender,
+    },
+    group::{MergeCommitError, ProcessMessageError, ValidationError},
+    messages::proposals::Proposal,
+};
+use openmls_torln_storage::TorlnStorageError;
+use tls_codec::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    audit::{self, AuditRecord},
+    capacity::GroupFull,
+    credential_policy::{self, UnacceptedCredentialType},
+    ephemeral, leave,
+    message_size::MessageTooLarge,
+    proposals::{self, UnsupportedProposalType},
+    readd::RemovedMembersError,
+    routing, stats, Group, Provider,
+};
+
+/// The kind of a processed message.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Application,
+    Proposal,
+    ExternalJoinProposal,
+    Commit,
+}
+
+/// A proposal of an application-defined type, see `Group::proposeCustom`.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct AppProposal {
+    proposal_type: u16,
+    payload: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl AppProposal {
+    #[wasm_bindgen(getter, js_name = proposalType)]
+    pub fn proposal_type(&self) -> u16 {
+        self.proposal_type
+    }
+    #[wasm_bindgen(getter)]
+    pub fn payload(&self) -> Vec<u8> {
+        self.payload.clone()
+    }
+}
+
+/// A member whose leaf index changed in a commit, see
+/// `ProcessedMessage.leafIndexChanges`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeafIndexChange {
+    from: u32,
+    to: u32,
+}
+
+#[wasm_bindgen]
+impl LeafIndexChange {
+    #[wasm_bindgen(getter)]
+    pub fn from(&self) -> u32 {
+        self.from
+    }
+    #[wasm_bindgen(getter)]
+    pub fn to(&self) -> u32 {
+        self.to
+    }
+}
+
+/// A successfully processed message.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ProcessedMessage {
+    kind: MessageKind,
+    epoch: u32,
+    sender_leaf_index: Option<u32>,
+    sender_credential: Vec<u8>,
+    application_data: Option<Vec<u8>>,
+    app_proposal: Option<AppProposal>,
+    proposal_ref: Option<Vec<u8>>,
+    audit_record: Option<AuditRecord>,
+    staged: bool,
+    has_path_update: bool,
+    ttl_seconds: Option<u32>,
+    unsupported_proposal_types: Vec<u16>,
+    own_message: bool,
+}
+
+#[wasm_bindgen]
+impl ProcessedMessage {
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> MessageKind {
+        self.kind
+    }
+    /// The epoch the message was sent in.
+    #[wasm_bindgen(getter)]
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+    /// The leaf index of the sender, or `undefined` if the sender isn't a
+    /// member.
+    #[wasm_bindgen(getter, js_name = senderLeafIndex)]
+    pub fn sender_leaf_index(&self) -> Option<u32> {
+        self.sender_leaf_index
+    }
+    /// The serialized credential of the sender, as of the epoch the message
+    /// was sent in.
+    ///
+    /// Unlike the leaf index, which is reused when a member is removed and
+    /// another one added, this identifies the sender, e.g. for tracking what
+    /// each member has read.
+    #[wasm_bindgen(getter, js_name = senderCredential)]
+    pub fn sender_credential(&self) -> Vec<u8> {
+        self.sender_credential.clone()
+    }
+    /// The plaintext of an application message.
+    #[wasm_bindgen(getter, js_name = applicationData)]
+    pub fn application_data(&self) -> Option<Vec<u8>> {
+        self.application_data.clone()
+    }
+    /// The proposal, if this is a proposal of an application-defined type.
+    #[wasm_bindgen(getter, js_name = appProposal)]
+    pub fn app_proposal(&self) -> Option<AppProposal> {
+        self.app_proposal.clone()
+    }
+    /// The reference of the proposal, if this is a proposal or an external
+    /// join proposal. It was queued and can be committed selectively with
+    /// `commitProposals`.
+    #[wasm_bindgen(getter, js_name = proposalRef)]
+    pub fn proposal_ref(&self) -> Option<Vec<u8>> {
+        self.proposal_ref.clone()
+    }
+    /// Who changed what, if this is a commit.
+    #[wasm_bindgen(getter, js_name = auditRecord)]
+    pub fn audit_record(&self) -> Option<AuditRecord> {
+        self.audit_record.clone()
+    }
+    /// Whether this is a commit that was staged instead of merged, because
+    /// auto-merging is off. See `Group.setAutoMerge`.
+    #[wasm_bindgen(getter)]
+    pub fn staged(&self) -> bool {
+        self.staged
+    }
+    /// Whether this is a commit with an update path, which gives the
+    /// committer new keys and so heals the group after a compromise.
+    ///
+    /// Commits with only adds, removes or PSKs can leave the path out,
+    /// e.g. when `commitPendingProposals` commits added members. A group
+    /// without path updates for a long time may want a `commitEmpty`.
+    #[wasm_bindgen(getter, js_name = hasPathUpdate)]
+    pub fn has_path_update(&self) -> bool {
+        self.has_path_update
+    }
+    /// The time to live of an ephemeral message, see
+    /// `createEphemeralMessage`.
+    #[wasm_bindgen(getter, js_name = ttlSeconds)]
+    pub fn ttl_seconds(&self) -> Option<u32> {
+        self.ttl_seconds
+    }
+    /// The custom proposal types in this proposal or commit that this client
+    /// doesn't know, e.g. because they were added in a later version of the
+    /// app.
+    ///
+    /// Proposals and commits with such types are still processed, as long
+    /// as the group doesn't require all members to support them; the app
+    /// may want to prompt for an update. If the group does require one of
+    /// them, processing fails with an "unsupported custom proposal type"
+    /// error instead, and the app has to update or leave the group.
+    #[wasm_bindgen(getter, js_name = unsupportedProposalTypes)]
+    pub fn unsupported_proposal_types(&self) -> Vec<u16> {
+        self.unsupported_proposal_types.clone()
+    }
+    /// The members that kept their place in the group but moved to another
+    /// leaf index, for state the app keys by index.
+    ///
+    /// Always empty: openmls blanks the leaves of removed members instead of
+    /// compacting the tree, and only drops blank leaves at its right end, so
+    /// the members that stay keep their index. Added members take the
+    /// leftmost blank leaves, so an index can be reused by a different
+    /// member; `senderCredential` tells them apart.
+    #[wasm_bindgen(getter, js_name = leafIndexChanges)]
+    pub fn leaf_index_changes(&self) -> Vec<LeafIndexChange> {
+        Vec::new()
+    }
+    /// Whether this is a message we sent ourselves, echoed back by the
+    /// delivery service, see `Group.setIgnoreOwnMessages`. Only the kind,
+    /// epoch and sender are set then; the group is unchanged.
+    #[wasm_bindgen(getter, js_name = ownMessage)]
+    pub fn own_message(&self) -> bool {
+        self.own_message
+    }
+}
+
+/// The outcome of processing one message of a batch, see
+/// `Group::tryProcessMessages`. Exactly one of `processed` and `error` is
+/// set.
+#[wasm_bindgen]
+pub struct MessageResult {
+    processed: Option<ProcessedMessage>,
+    error: Option<String>,
+}
+
+#[wasm_bindgen]
+impl MessageResult {
+    #[wasm_bindgen(getter)]
+    pub fn processed(&self) -> Option<ProcessedMessage> {
+        self.processed.clone()
+    }
+    #[wasm_bindgen(getter)]
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+}
+
+impl From<Result<ProcessedMessage, ProcessError>> for MessageResult {
+    fn from(result: Result<ProcessedMessage, ProcessError>) -> Self {
+        match result {
+            Ok(processed) => MessageResult {
+                processed: Some(processed),
+                error: None,
+            },
+            Err(e) => MessageResult {
+                processed: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Errors when processing a message.
+#[derive(Debug)]
+pub(crate) enum ProcessError {
+    TooLarge(MessageTooLarge),
+    Malformed(tls_codec::Error),
+    NotFramed(&'static str),
+    /// The message is for another epoch than the current one: an old message
+    /// that can no longer be processed, or one that has to wait for the
+    /// commits before it.
+    WrongEpoch {
+        message_epoch: u64,
+        current_epoch: u64,
+    },
+    /// The application message is from an epoch below the minimum set with
+    /// `setMinDecryptEpoch`.
+    BelowMinEpoch {
+        message_epoch: u64,
+        min_epoch: u64,
+    },
+    Process(ProcessMessageError<TorlnStorageError>),
+    /// A credential in the message has a type outside the ones set with
+    /// `setAcceptedCredentialTypes`.
+    UnacceptedCredentialType(UnacceptedCredentialType),
+    /// The message has a custom proposal of a type we don't know, but which
+    /// the group requires all members to support.
+    UnsupportedProposal(UnsupportedProposalType),
+    Merge(MergeCommitError<TorlnStorageError>),
+    Storage(TorlnStorageError),
+    /// Looking up the keypairs of our update leaves before a merge, or
+    /// deleting them after it, failed.
+    UpdateKeyPairs(TorlnStorageError),
+    /// Deleting the new keypair of our signature key rotation, whose commit
+    /// was dropped for the merged one, failed.
+    RotationKeyPair(TorlnStorageError),
+    Encoding(tls_codec::Error),
+    RemovedMembers(RemovedMembersError),
+    NoStagedCommit,
+    /// The commit would take the group beyond its maximum size.
+    GroupFull(GroupFull),
+}
+
+impl std::fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed message: {e}"),
+            Self::NotFramed(kind) => write!(f, "can't process a {kind} in a group"),
+            Self::WrongEpoch {
+                message_epoch,
+                current_epoch,
+            } => write!(
+                f,
+                "wrong epoch: message is for epoch {message_epoch}, group is in epoch {current_epoch}"
+            ),
+            Self::BelowMinEpoch {
+                message_epoch,
+                min_epoch,
+            } => write!(
+                f,
+                "below minimum epoch: message is for epoch {message_epoch}, minimum epoch is {min_epoch}"
+            ),
+            Self::Process(e) => write!(f, "failed to process message: {e}"),
+            Self::UnacceptedCredentialType(e) => write!(f, "{e}"),
+            Self::UnsupportedProposal(e) => write!(f, "group requires {e}"),
+            Self::Merge(e) => write!(f, "failed to merge commit: {e}"),
+            Self::Storage(e) => write!(f, "failed to store proposal: {e}"),
+            Self::UpdateKeyPairs(e) => write!(f, "failed to clean up update keypairs: {e}"),
+            Self::RotationKeyPair(e) => {
+                write!(f, "failed to delete the keypair of a dropped rotation: {e}")
+            }
+            Self::Encoding(e) => write!(f, "failed to encode credential: {e}"),
+            Self::RemovedMembers(e) => write!(f, "{e}"),
+            Self::NoStagedCommit => write!(f, "no staged commit to merge"),
+            Self::GroupFull(e) => write!(f, "received commit: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+/// The position of a message when processing a batch: by epoch, and within
+/// an epoch commits last, since they end the epoch. Messages without a
+/// readable header go last.
+fn processing_order(message: &[u8]) -> (u64, bool) {
+    match routing::header_of(message) {
+        Ok(header) => (
+            header.epoch,
+            header.content_type == routing::CONTENT_TYPE_COMMIT,
+        ),
+        Err(_) => (u64::MAX, true),
+    }
+}
+
+impl Group {
+    /// Process a single message. Received proposals are stored and commits
+    /// are merged.
+    pub(crate) fn process(
+        &mut self,
+        provider: &Provider,
+        bytes: &[u8],
+    ) -> Result<ProcessedMessage, ProcessError> {
+        #[cfg(feature = "processing-timing")]
+        let started = stats::now_ms();
+
+        let result = self.process_uncounted(provider, bytes);
+        self.stats.record(&result);
+        #[cfg(feature = "processing-timing")]
+        self.stats.record_time(stats::now_ms() - started);
+
+        result
+    }
+
+    /// `process` without updating the processing stats.
+    fn process_uncounted(
+        &mut self,
+        provider: &Provider,
+        bytes: &[u8],
+    ) -> Result<ProcessedMessage, ProcessError> {
+        provider
+            .check_message_size(bytes)
+            .map_err(ProcessError::TooLarge)?;
+        self.check_decrypt_epoch(provider, bytes)?;
+        let message =
+            MlsMessageIn::tls_deserialize(&mut &*bytes).map_err(ProcessError::Malformed)?;
+
+        let own_leaf = Sender::Member(self.mls_group.own_leaf_index());
+        let processed = match message.extract() {
+            MlsMessageBodyIn::PublicMessage(msg) => {
+                if self.ignore_own_messages && *msg.sender() == own_leaf {
+                    return self.own_message(bytes);
+                }
+                if leave::is_public_self_remove(bytes) {
+                    self.process_public_self_remove(provider, msg)
+                } else {
+                    self.mls_group.process_message(provider.as_ref(), msg)
+                }
+            }
+            MlsMessageBodyIn::PrivateMessage(msg) => {
+                self.mls_group.process_message(provider.as_ref(), msg)
+            }
+            MlsMessageBodyIn::Welcome(_) => return Err(ProcessError::NotFramed("welcome")),
+            MlsMessageBodyIn::GroupInfo(_) => return Err(ProcessError::NotFramed("group info")),
+            MlsMessageBodyIn::KeyPackage(_) => return Err(ProcessError::NotFramed("key package")),
+        };
+        let processed = match processed {
+            // The sender of a private message is encrypted separately from
+            // its content; openmls stops after decrypting it, before the
+            // content would be decrypted with a receiving ratchet.
+            Err(ProcessMessageError::ValidationError(ValidationError::CannotDecryptOwnMessage))
+                if self.ignore_own_messages =>
+            {
+                return self.own_message(bytes);
+            }
+            processed => processed.map_err(|e| self.process_error(bytes, e))?,
+        };
+
+        let epoch = processed.epoch().as_u64() as u32;
+        let sender_leaf_index = match processed.sender() {
+            Sender::Member(index) => Some(index.u32()),
+            _ => None,
+        };
+
+        let ttl_seconds = ephemeral::ttl_of(processed.aad());
+        let actor = processed.credential().clone();
+        self.check_accepted_credentials(
+            std::iter::once(&actor).chain(credential_policy::new_credentials(processed.content())),
+        )
+        .map_err(ProcessError::UnacceptedCredentialType)?;
+        let unsupported_proposal_types = proposals::unsupported_proposal_types(processed.content());
+        self.check_required_proposal_types(&unsupported_proposal_types)
+            .map_err(ProcessError::UnsupportedProposal)?;
+        let sender_credential = actor
+            .tls_serialize_detached()
+            .map_err(ProcessError::Encoding)?;
+        let mut app_proposal = None;
+        let mut proposal_ref = None;
+        let mut audit_record = None;
+        let mut staged = false;
+        let mut has_path_update = false;
+        let (kind, application_data) = match processed.into_content() {
+            ProcessedMessageContent::ApplicationMessage(app_msg) => {
+                (MessageKind::Application, Some(app_msg.into_bytes()))
+            }
+            ProcessedMessageContent::ProposalMessage(proposal) => {
+                if let Proposal::Custom(custom) = proposal.proposal() {
+                    app_proposal = Some(AppProposal {
+                        proposal_type: custom.proposal_type(),
+                        payload: custom.payload().to_vec(),
+                    });
+                }
+                proposal_ref = Some(proposal.proposal_reference_ref().as_slice().to_vec());
+                self.mls_group
+                    .store_pending_proposal(provider.0.storage(), *proposal)
+                    .map_err(ProcessError::Storage)?;
+                (MessageKind::Proposal, None)
+            }
+            ProcessedMessageContent::ExternalJoinProposalMessage(proposal) => {
+                // Queued like a member's proposal, so that committing the
+                // pending proposals admits the requester.
+                proposal_ref = Some(proposal.proposal_reference_ref().as_slice().to_vec());
+                self.mls_group
+                    .store_pending_proposal(provider.0.storage(), *proposal)
+                    .map_err(ProcessError::Storage)?;
+                (MessageKind::ExternalJoinProposal, None)
+            }
+            ProcessedMessageContent::StagedCommitMessage(staged_commit) => {
+                self.ensure_capacity_after(&staged_commit)
+                    .map_err(ProcessError::GroupFull)?;
+                has_path_update = staged_commit.update_path_leaf_node().is_some();
+                audit_record = Some(
+                    audit::audit_record(&self.mls_group, &actor, &staged_commit)
+                        .map_err(ProcessError::Encoding)?,
+                );
+                if self.auto_merge {
+                    self.record_removals(provider, &staged_commit)
+                        .map_err(ProcessError::RemovedMembers)?;
+                    let update_encryption_keys = self
+                        .update_encryption_keys(provider)
+                        .map_err(ProcessError::UpdateKeyPairs)?;
+                    self.mls_group
+                        .merge_staged_commit(provider.as_ref(), *staged_commit)
+                        .map_err(ProcessError::Merge)?;
+                    self.delete_update_key_pairs(provider, &update_encryption_keys)
+                        .map_err(ProcessError::UpdateKeyPairs)?;
+                    // Merging discards our pending commit, and with it a
+                    // signature key rotation.
+                    self.discard_pending_rotation(provider)
+                        .map_err(ProcessError::RotationKeyPair)?;
+                } else {
+                    self.staged_commit = Some(staged_commit);
+                    staged = true;
+                }
+                (MessageKind::Commit, None)
+            }
+        };
+
+        Ok(ProcessedMessage {
+            kind,
+            epoch,
+            sender_leaf_index,
+            sender_credential,
+            application_data,
+            app_proposal,
+            proposal_ref,
+            audit_record,
+            staged,
+            has_path_update,
+            ttl_seconds,
+            unsupported_proposal_types,
+            own_message: false,
+        })
+    }
+
+    /// The result for a message sent by our own leaf, see
+    /// `setIgnoreOwnMessages`.
+    fn own_message(&self, bytes: &[u8]) -> Result<ProcessedMessage, ProcessError> {
+        let header = routing::header_of(bytes).expect("the header of a parsed message is readable");
+        let kind = match header.content_type {
+            routing::CONTENT_TYPE_APPLICATION => MessageKind::Application,
+            routing::CONTENT_TYPE_COMMIT => MessageKind::Commit,
+            _ => MessageKind::Proposal,
+        };
+        let sender_credential = self
+            .mls_group
+            .own_leaf_node()
+            .map(|leaf_node| leaf_node.credential().tls_serialize_detached())
+            .transpose()
+            .map_err(ProcessError::Encoding)?
+            .unwrap_or_default();
+
+        Ok(ProcessedMessage {
+            kind,
+            epoch: header.epoch as u32,
+            sender_leaf_index: Some(self.mls_group.own_leaf_index().u32()),
+            sender_credential,
+            application_data: None,
+            app_proposal: None,
+            proposal_ref: None,
+            audit_record: None,
+            staged: false,
+            has_path_update: false,
+            ttl_seconds: None,
+            unsupported_proposal_types: Vec::new(),
+            own_message: true,
+        })
+    }
+
+    /// Merge the commit staged by `process`, see `mergeStaged`.
+    pub(crate) fn merge_staged(&mut self, provider: &Provider) -> Result<(), ProcessError> {
+        let staged_commit = self
+            .staged_commit
+            .take()
+            .ok_or(ProcessError::NoStagedCommit)?;
+
+        self.record_removals(provider, &staged_commit)
+            .map_err(ProcessError::RemovedMembers)?;
+        let update_encryption_keys = self
+            .update_encryption_keys(provider)
+            .map_err(ProcessError::UpdateKeyPairs)?;
+        self.mls_group
+            .merge_staged_commit(provider.as_ref(), *staged_commit)
+            .map_err(ProcessError::Merge)?;
+        self.delete_update_key_pairs(provider, &update_encryption_keys)
+            .map_err(ProcessError::UpdateKeyPairs)?;
+        self.discard_pending_rotation(provider)
+            .map_err(ProcessError::RotationKeyPair)?;
+        self.stats.record_merge();
+
+        Ok(())
+    }
+
+    /// The error for `error` of openmls when processing the serialized
+    /// `message`: `WrongEpoch` if openmls rejected the message for its
+    /// epoch.
+    fn process_error(
+        &self,
+        message: &[u8],
+        error: ProcessMessageError<TorlnStorageError>,
+    ) -> ProcessError {
+        match error {
+            ProcessMessageError::ValidationError(
+                ValidationError::WrongEpoch
+                | ValidationError::NoPastEpochData
+                | ValidationError::UnableToDecrypt(MessageDecryptionError::SecretTreeError(
+                    SecretTreeError::TooDistantInThePast,
+                )),
+            ) => self
+                .wrong_epoch(message)
+                .unwrap_or(ProcessError::Process(error)),
+            error => ProcessError::Process(error),
+        }
+    }
+
+    /// A `WrongEpoch` error if the serialized `message` is for another epoch
+    /// than the current one.
+    fn wrong_epoch(&self, message: &[u8]) -> Option<ProcessError> {
+        let message_epoch = routing::header_of(message).ok()?.epoch;
+        let current_epoch = self.mls_group.epoch().as_u64();
+
+        (message_epoch != current_epoch).then_some(ProcessError::WrongEpoch {
+            message_epoch,
+            current_epoch,
+        })
+    }
+
+    /// Process an unordered batch of messages, see `tryProcessMessages`.
+    pub(crate) fn try_process_all(
+        &mut self,
+        provider: &Provider,
+        messages: &[Vec<u8>],
+    ) -> Vec<MessageResult> {
+        let mut order = (0..messages.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| processing_order(&messages[i]));
+
+        let mut results = (0..messages.len()).map(|_| None).collect::<Vec<_>>();
+        for i in order {
+            results[i] = Some(self.process(provider, &messages[i]).into());
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.expect("every message is processed"))
+            .collect()
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Like `processMessage`, but returns the processed message with its
+    /// kind, sender and content.
+    #[wasm_bindgen(js_name = processMessageDetailed)]
+    pub fn process_message_detailed(
+        &mut self,
+        provider: &Provider,
+        msg: &[u8],
+    ) -> Result<ProcessedMessage, JsError> {
+        Ok(self.process(provider, msg)?)
+    }
+
+    /// Process a batch of messages in any order, e.g. a mailbox replayed
+    /// from storage.
+    ///
+    /// Messages are processed by epoch, with commits after the other
+    /// messages of their epoch, so that commits are merged in order. A
+    /// message that can't be processed doesn't stop the others: the returned
+    /// array holds one result per message, in the order of `messages`, each
+    /// with either the processed message or the error.
+    #[wasm_bindgen(js_name = tryProcessMessages)]
+    pub fn try_process_messages(
+        &mut self,
+        provider: &Provider,
+        messages: Vec<Uint8Array>,
+    ) -> Vec<MessageResult> {
+        let messages = messages.iter().map(Uint8Array::to_vec).collect::<Vec<_>>();
+
+        self.try_process_all(provider, &messages)
+    }
+
+    /// Turn merging of received commits on or off. It is on by default.
+    ///
+    /// When it is off, processing a commit only stages it: the result has
+    /// `staged` set, and the group stays in its epoch until `mergeStaged` is
+    /// called, so the app can validate or order commits first. Processing
+    /// another commit replaces the staged one. The setting and the staged
+    /// commit aren't persisted and are lost when the group is reloaded.
+    #[wasm_bindgen(js_name = setAutoMerge)]
+    pub fn set_auto_merge(&mut self, auto_merge: bool) {
+        self.auto_merge = auto_merge;
+    }
+
+    /// Turn recognizing our own messages on or off, for delivery services
+    /// that echo every message back to its sender. It is off by default.
+    ///
+    /// When it is on, processing a message sent by our own leaf returns a
+    /// result with `ownMessage` set, instead of failing because the message
+    /// can't be decrypted by its sender. The group is left as it is: own
+    /// commits are merged with `mergePendingCommit`, and own proposals are
+    /// already queued. The setting isn't persisted.
+    #[wasm_bindgen(js_name = setIgnoreOwnMessages)]
+    pub fn set_ignore_own_messages(&mut self, ignore_own_messages: bool) {
+        self.ignore_own_messages = ignore_own_messages;
+    }
+
+    /// Whether a received commit is staged and waiting for `mergeStaged`.
+    #[wasm_bindgen(js_name = hasStagedCommit)]
+    pub fn has_staged_commit(&self) -> bool {
+        self.staged_commit.is_some()
+    }
+
+    /// Merge the staged commit, advancing the group to the next epoch.
+    #[wasm_bindgen(js_name = mergeStaged)]
+    pub fn merge_staged_commit(&mut self, provider: &Provider) -> Result<(), JsError> {
+        Ok(self.merge_staged(provider)?)
+    }
+
+    /// Drop the staged commit without merging it, e.g. because it failed the
+    /// app's validation.
+    #[wasm_bindgen(js_name = discardStaged)]
+    pub fn discard_staged_commit(&mut self) {
+        self.staged_commit = None;
+    }
+}
@@ -0,0 +1,407 @@
+//! Proposals beyond the ones committed right away by the other methods.
+
+use js_sys::{Function, Uint8Array};
+use std::collections::BTreeSet;
+
+use openmls::{
+    framing::{ProcessedMessageContent, Sender},
+    group::{CommitToPendingProposalsError, RemoveProposalError},
+    messages::proposals::{CustomProposal, Proposal, ProposalType},
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    capacity::GroupFull, extensions, key_package_lifetime::KeyPackageLifetimeTooLong,
+    mls_message_to_u8vec, signer::MissingSignerError, CommitMessages, Group, Identity, Provider,
+};
+
+/// A custom proposal type that isn't advertised in our capabilities: the
+/// other members would reject it, or we don't know what it means.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct UnsupportedProposalType(pub(crate) u16);
+
+impl std::fmt::Display for UnsupportedProposalType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unsupported custom proposal type {:#06x}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedProposalType {}
+
+pub(crate) fn check_app_proposal_type(proposal_type: u16) -> Result<(), UnsupportedProposalType> {
+    if extensions::APP_PROPOSAL_TYPES.contains(&proposal_type) {
+        Ok(())
+    } else {
+        Err(UnsupportedProposalType(proposal_type))
+    }
+}
+
+/// The custom proposal types in a received proposal or commit that this
+/// client doesn't know, in ascending order.
+///
+/// openmls only accepts a commit if every member advertises the types of
+/// its custom proposals. A member can still advertise a type its app
+/// version doesn't handle, e.g. when the capabilities come from a newer
+/// version of the app on another device.
+pub(crate) fn unsupported_proposal_types(content: &ProcessedMessageContent) -> Vec<u16> {
+    let custom_type = |proposal: &Proposal| match proposal {
+        Proposal::Custom(custom) => Some(custom.proposal_type()),
+        _ => None,
+    };
+    let proposal_types: BTreeSet<u16> = match content {
+        ProcessedMessageContent::ProposalMessage(proposal) => {
+            custom_type(proposal.proposal()).into_iter().collect()
+        }
+        ProcessedMessageContent::StagedCommitMessage(staged_commit) => staged_commit
+            .queued_proposals()
+            .filter_map(|queued_proposal| custom_type(queued_proposal.proposal()))
+            .collect(),
+        _ => BTreeSet::new(),
+    };
+
+    proposal_types
+        .into_iter()
+        .filter(|proposal_type| !extensions::APP_PROPOSAL_TYPES.contains(proposal_type))
+        .collect()
+}
+
+/// Errors when committing selected proposals.
+#[derive(Debug)]
+pub(crate) enum CommitProposalsError {
+    /// No pending proposal has the reference at this position.
+    UnknownProposal(usize),
+    GroupFull(GroupFull),
+    LifetimeTooLong(KeyPackageLifetimeTooLong),
+    Signer(MissingSignerError),
+    Remove(RemoveProposalError<TorlnStorageError>),
+    Storage(TorlnStorageError),
+    Commit(CommitToPendingProposalsError<TorlnStorageError>),
+}
+
+impl std::fmt::Display for CommitProposalsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownProposal(position) => {
+                write!(f, "no pending proposal with reference {position}")
+            }
+            Self::GroupFull(e) => e.fmt(f),
+            Self::LifetimeTooLong(e) => e.fmt(f),
+            Self::Signer(e) => e.fmt(f),
+            Self::Remove(e) => write!(f, "failed to set aside proposal: {e}"),
+            Self::Storage(e) => write!(f, "failed to restore proposal: {e}"),
+            Self::Commit(e) => write!(f, "failed to commit proposals: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CommitProposalsError {}
+
+/// The number of pending proposals per type, see
+/// `Group::pendingProposalCounts`.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProposalCounts {
+    add: u32,
+    update: u32,
+    remove: u32,
+    self_remove: u32,
+    pre_shared_key: u32,
+    group_context_extensions: u32,
+    custom: u32,
+    other: u32,
+}
+
+#[wasm_bindgen]
+impl ProposalCounts {
+    #[wasm_bindgen(getter)]
+    pub fn add(&self) -> u32 {
+        self.add
+    }
+    #[wasm_bindgen(getter)]
+    pub fn update(&self) -> u32 {
+        self.update
+    }
+    #[wasm_bindgen(getter)]
+    pub fn remove(&self) -> u32 {
+        self.remove
+    }
+    #[wasm_bindgen(getter, js_name = selfRemove)]
+    pub fn self_remove(&self) -> u32 {
+        self.self_remove
+    }
+    #[wasm_bindgen(getter, js_name = preSharedKey)]
+    pub fn pre_shared_key(&self) -> u32 {
+        self.pre_shared_key
+    }
+    #[wasm_bindgen(getter, js_name = groupContextExtensions)]
+    pub fn group_context_extensions(&self) -> u32 {
+        self.group_context_extensions
+    }
+    /// Proposals of application-defined types, see `proposeCustom`.
+    #[wasm_bindgen(getter)]
+    pub fn custom(&self) -> u32 {
+        self.custom
+    }
+    /// Proposals of any other type, e.g. ReInit.
+    #[wasm_bindgen(getter)]
+    pub fn other(&self) -> u32 {
+        self.other
+    }
+    /// The number of pending proposals of all types.
+    #[wasm_bindgen(getter)]
+    pub fn total(&self) -> u32 {
+        self.add
+            + self.update
+            + self.remove
+            + self.self_remove
+            + self.pre_shared_key
+            + self.group_context_extensions
+            + self.custom
+            + self.other
+    }
+}
+
+impl Group {
+    /// The position of the first reference in `proposal_refs` that isn't
+    /// one of a pending proposal.
+    pub(crate) fn unknown_proposal(&self, proposal_refs: &[Vec<u8>]) -> Option<usize> {
+        proposal_refs.iter().position(|proposal_ref| {
+            !self
+                .mls_group
+                .pending_proposals()
+                .any(|queued| queued.proposal_reference_ref().as_slice() == proposal_ref)
+        })
+    }
+
+    /// Commit the pending proposals with the given references, see
+    /// `commitProposals`.
+    pub(crate) fn commit_selected_proposals(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        proposal_refs: &[Vec<u8>],
+    ) -> Result<CommitMessages, CommitProposalsError> {
+        if let Some(position) = self.unknown_proposal(proposal_refs) {
+            return Err(CommitProposalsError::UnknownProposal(position));
+        }
+        let is_selected = |proposal_ref: &[u8]| {
+            proposal_refs
+                .iter()
+                .any(|selected| selected.as_slice() == proposal_ref)
+        };
+        let selected = || {
+            self.mls_group
+                .pending_proposals()
+                .filter(|queued| is_selected(queued.proposal_reference_ref().as_slice()))
+        };
+        self.ensure_capacity_for(selected(), 0)
+            .map_err(CommitProposalsError::GroupFull)?;
+        provider
+            .check_committed_key_packages(selected())
+            .map_err(CommitProposalsError::LifetimeTooLong)?;
+        let signer = self
+            .signer(provider, sender)
+            .map_err(CommitProposalsError::Signer)?;
+        let pending = self
+            .mls_group
+            .pending_proposals()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // openmls commits all pending proposals, so set the others aside
+        // while committing and queue them again afterwards.
+        let set_aside = pending
+            .into_iter()
+            .filter(|queued| !is_selected(queued.proposal_reference_ref().as_slice()))
+            .collect::<Vec<_>>();
+        for queued in &set_aside {
+            self.mls_group
+                .remove_pending_proposal(provider.0.storage(), queued.proposal_reference_ref())
+                .map_err(CommitProposalsError::Remove)?;
+        }
+
+        let commit = self
+            .mls_group
+            .commit_to_pending_proposals(provider.as_ref(), &signer);
+
+        for queued in set_aside {
+            self.mls_group
+                .store_pending_proposal(provider.0.storage(), queued)
+                .map_err(CommitProposalsError::Storage)?;
+        }
+
+        let (commit_msg, welcome_msg, _group_info) =
+            commit.map_err(CommitProposalsError::Commit)?;
+
+        Ok(CommitMessages::new(&commit_msg, welcome_msg.as_ref()))
+    }
+
+    /// Commit the pending proposals that `include` accepts, given their type
+    /// and the leaf index of their sender, and drop the others, see
+    /// `commitReceivedProposalsFiltered`.
+    pub(crate) fn commit_filtered_proposals(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        mut include: impl FnMut(u16, Option<u32>) -> Result<bool, JsError>,
+    ) -> Result<CommitMessages, JsError> {
+        // Ask about every proposal before changing anything, so that a
+        // failing filter leaves the proposals as they are.
+        let mut dropped = Vec::new();
+        for queued in self.mls_group.pending_proposals() {
+            let actor = match queued.sender() {
+                Sender::Member(leaf_index) => Some(leaf_index.u32()),
+                _ => None,
+            };
+            if !include(queued.proposal().proposal_type().into(), actor)? {
+                dropped.push(queued.proposal_reference_ref().clone());
+            }
+        }
+
+        let included = || {
+            self.mls_group
+                .pending_proposals()
+                .filter(|queued| !dropped.contains(queued.proposal_reference_ref()))
+        };
+        self.ensure_capacity_for(included(), 0)?;
+        provider.check_committed_key_packages(included())?;
+        let signer = self.signer(provider, sender)?;
+
+        for proposal_ref in &dropped {
+            self.mls_group
+                .remove_pending_proposal(provider.0.storage(), proposal_ref)?;
+        }
+        let (commit_msg, welcome_msg, _group_info) = self
+            .mls_group
+            .commit_to_pending_proposals(provider.as_ref(), &signer)?;
+
+        Ok(CommitMessages::new(&commit_msg, welcome_msg.as_ref()))
+    }
+
+    /// Fail for the first of the unknown `proposal_types` that the required
+    /// capabilities of the group list: the group depends on it, so we can't
+    /// follow it without understanding it.
+    pub(crate) fn check_required_proposal_types(
+        &self,
+        proposal_types: &[u16],
+    ) -> Result<(), UnsupportedProposalType> {
+        let Some(required) = self.mls_group.extensions().required_capabilities() else {
+            return Ok(());
+        };
+        match proposal_types.iter().find(|&&proposal_type| {
+            required
+                .proposal_types()
+                .contains(&ProposalType::Custom(proposal_type))
+        }) {
+            Some(&proposal_type) => Err(UnsupportedProposalType(proposal_type)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Count the pending proposals by type: our own and the ones received
+    /// from other members that haven't been committed yet.
+    #[wasm_bindgen(js_name = pendingProposalCounts)]
+    pub fn pending_proposal_counts(&self) -> ProposalCounts {
+        let mut counts = ProposalCounts::default();
+        for queued_proposal in self.mls_group.pending_proposals() {
+            let count = match queued_proposal.proposal().proposal_type() {
+                ProposalType::Add => &mut counts.add,
+                ProposalType::Update => &mut counts.update,
+                ProposalType::Remove => &mut counts.remove,
+                ProposalType::SelfRemove => &mut counts.self_remove,
+                ProposalType::PreSharedKey => &mut counts.pre_shared_key,
+                ProposalType::GroupContextExtensions => &mut counts.group_context_extensions,
+                ProposalType::Custom(_) => &mut counts.custom,
+                _ => &mut counts.other,
+            };
+            *count += 1;
+        }
+
+        counts
+    }
+
+    /// Propose an application-defined proposal, e.g. promoting a member to
+    /// admin (type `0xf000`).
+    ///
+    /// Only the proposal types listed in our capabilities are accepted. The
+    /// other members see the type and payload in the result of
+    /// `processMessageDetailed`, and the proposal is committed like any other
+    /// with `commitPendingProposals`.
+    ///
+    /// Returns the serialized proposal.
+    #[wasm_bindgen(js_name = proposeCustom)]
+    pub fn propose_custom(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        proposal_type: u16,
+        payload: Vec<u8>,
+    ) -> Result<Vec<u8>, JsError> {
+        check_app_proposal_type(proposal_type)?;
+
+        let signer = self.signer(provider, sender)?;
+        let (proposal_msg, _proposal_ref) = self.mls_group.propose_custom_proposal_by_reference(
+            provider.as_ref(),
+            &signer,
+            CustomProposal::new(proposal_type, payload),
+        )?;
+
+        Ok(mls_message_to_u8vec(&proposal_msg))
+    }
+
+    /// Commit only the pending proposals with the given references, as
+    /// returned in `proposalRef` by `processMessageDetailed`.
+    ///
+    /// The other pending proposals stay queued. Returns the serialized
+    /// commit, and the welcome if the selected proposals add members, e.g.
+    /// a join proposal of an external sender. The commit is pending until
+    /// `mergePendingCommit` is called; merging it discards the proposals
+    /// that weren't committed, as they belong to the old epoch.
+    #[wasm_bindgen(js_name = commitProposals)]
+    pub fn commit_proposals(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        proposal_refs: Vec<Uint8Array>,
+    ) -> Result<CommitMessages, JsError> {
+        let proposal_refs = proposal_refs
+            .iter()
+            .map(Uint8Array::to_vec)
+            .collect::<Vec<_>>();
+
+        Ok(self.commit_selected_proposals(provider, sender, &proposal_refs)?)
+    }
+
+    /// Commit the pending proposals that pass an application policy, e.g.
+    /// to refuse a Remove proposed by a member who isn't an admin.
+    ///
+    /// `filter` is called for each pending proposal with its proposal type,
+    /// e.g. `3` for Remove or `0xf000` for a custom type, and the leaf index
+    /// of its sender, or `undefined` for external senders. The proposals for
+    /// which it returns a falsy value are dropped from the pending
+    /// proposals, not committed. If `filter` throws, nothing is dropped or
+    /// committed.
+    ///
+    /// Returns the serialized commit, and the welcome if the included
+    /// proposals add members. The commit is pending until
+    /// `mergePendingCommit` is called.
+    #[wasm_bindgen(js_name = commitReceivedProposalsFiltered)]
+    pub fn commit_received_proposals_filtered(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        filter: &Function,
+    ) -> Result<CommitMessages, JsError> {
+        self.commit_filtered_proposals(provider, sender, |proposal_type, actor| {
+            filter
+                .call2(&JsValue::NULL, &proposal_type.into(), &actor.into())
+                .map(|include| include.is_truthy())
+                .map_err(|e| JsError::new(&format!("proposal filter failed: {e:?}")))
+        })
+    }
+}
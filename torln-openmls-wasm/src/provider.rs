@@ -0,0 +1,42 @@
+//! The OpenMLS provider behind `Provider`.
+//!
+//! It is the provider of `openmls_rust_crypto` with the storage of
+//! `openmls_torln_storage`, which journals the writes of an operation so
+//! that they can be rolled back, see the `transaction` module.
+
+use openmls_rust_crypto::RustCrypto;
+use openmls_torln_storage::TorlnStorage;
+use openmls_traits::OpenMlsProvider;
+
+#[derive(Default, Debug)]
+pub struct TorlnProvider {
+    crypto: RustCrypto,
+    storage: TorlnStorage,
+}
+
+impl OpenMlsProvider for TorlnProvider {
+    type CryptoProvider = RustCrypto;
+    type RandProvider = RustCrypto;
+    type StorageProvider = TorlnStorage;
+
+    fn storage(&self) -> &Self::StorageProvider {
+        &self.storage
+    }
+
+    fn crypto(&self) -> &Self::CryptoProvider {
+        &self.crypto
+    }
+
+    fn rand(&self) -> &Self::RandProvider {
+        &self.crypto
+    }
+}
+
+impl TorlnProvider {
+    pub(crate) fn with_seed(seed: &[u8]) -> Self {
+        Self {
+            crypto: RustCrypto::with_seed(seed),
+            storage: TorlnStorage::default(),
+        }
+    }
+}
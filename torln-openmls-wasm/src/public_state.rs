@@ -0,0 +1,117 @@
+//! The public state of a group, for servers that relay for a group without
+//! being a member.
+//!
+//! Everything in it is in the group context or the ratchet tree, which every
+//! member and every external joiner knows; no secret or private key of the
+//! exporting member goes into it. Serialized with the TLS presentation
+//! language:
+//!
+//! ```text
+//! struct {
+//!     opaque group_id<V>;
+//!     uint64 epoch;
+//!     opaque tree_hash<V>;
+//!     opaque signature_keys<V><V>; // ascending byte order
+//!     opaque extensions<V>; // TLS-serialized group context extensions
+//! } GroupPublicState;
+//! ```
+
+use js_sys::Uint8Array;
+use tls_codec::{Deserialize, Serialize, TlsDeserialize, TlsSerialize, TlsSize};
+use wasm_bindgen::prelude::*;
+
+use crate::{roster::Roster, Group};
+
+/// The public state of a group in an epoch, see `Group.exportPublicState`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq, TlsSerialize, TlsDeserialize, TlsSize)]
+pub struct GroupPublicState {
+    pub(crate) group_id: Vec<u8>,
+    pub(crate) epoch: u64,
+    pub(crate) tree_hash: Vec<u8>,
+    pub(crate) signature_keys: Vec<Vec<u8>>,
+    pub(crate) extensions: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl GroupPublicState {
+    #[wasm_bindgen(getter, js_name = groupId)]
+    pub fn group_id(&self) -> Vec<u8> {
+        self.group_id.clone()
+    }
+    #[wasm_bindgen(getter)]
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+    /// The tree hash of the ratchet tree in this epoch.
+    #[wasm_bindgen(getter, js_name = treeHash)]
+    pub fn tree_hash(&self) -> Vec<u8> {
+        self.tree_hash.clone()
+    }
+    /// The signature keys of the members, in ascending byte order.
+    #[wasm_bindgen(getter, js_name = signatureKeys)]
+    pub fn signature_keys(&self) -> Vec<Uint8Array> {
+        self.signature_keys
+            .iter()
+            .map(|signature_key| signature_key.as_slice().into())
+            .collect()
+    }
+    /// The TLS-serialized group context extensions.
+    #[wasm_bindgen(getter)]
+    pub fn extensions(&self) -> Vec<u8> {
+        self.extensions.clone()
+    }
+
+    /// Whether the roster `attestation` of a `SignedRoster` claims exactly
+    /// the members of this state, in the same group and epoch. The roster
+    /// signature is checked separately with `verifyRoster`.
+    #[wasm_bindgen(js_name = matchesRoster)]
+    pub fn matches_roster(&self, attestation: &[u8]) -> bool {
+        Roster::tls_deserialize_exact(attestation).is_ok_and(|roster| {
+            roster.group_id == self.group_id
+                && roster.epoch == self.epoch
+                && roster.signature_keys == self.signature_keys
+        })
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsError> {
+        Ok(self.tls_serialize_detached()?)
+    }
+
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<GroupPublicState, JsError> {
+        Ok(GroupPublicState::tls_deserialize_exact(bytes)?)
+    }
+}
+
+impl Group {
+    /// The public state of the group in the current epoch, see
+    /// `exportPublicState`.
+    pub(crate) fn public_state(&self) -> Result<GroupPublicState, tls_codec::Error> {
+        let roster = self.roster();
+
+        Ok(GroupPublicState {
+            group_id: roster.group_id,
+            epoch: roster.epoch,
+            tree_hash: self.mls_group.tree_hash().to_vec(),
+            signature_keys: roster.signature_keys,
+            extensions: self.mls_group.extensions().tls_serialize_detached()?,
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// The public state of the group in the current epoch: the group id,
+    /// epoch, tree hash, signature keys of the members and group context
+    /// extensions.
+    ///
+    /// Meant for a server that isn't a member, e.g. to check a roster
+    /// claim with `matchesRoster`. Only public data goes into it, nothing
+    /// derived from the secrets of the group or the keys of this member.
+    #[wasm_bindgen(js_name = exportPublicState)]
+    pub fn export_public_state(&self) -> Result<GroupPublicState, JsError> {
+        Ok(self.public_state()?)
+    }
+}
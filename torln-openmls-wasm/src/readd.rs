@@ -0,0 +1,257 @@
+//! Re-adding members that were removed.
+//!
+//! Moderation often removes a member and invites them again later. To tell a
+//! re-add apart from adding someone new, every client keeps the credentials
+//! of the members removed from a group in the provider storage. They are
+//! recorded from each commit right before it is merged, while the removed
+//! leaves can still be looked up.
+
+use openmls::{
+    credentials::Credential,
+    framing::Sender,
+    group::{AddMembersError, MlsGroup, StagedCommit},
+    messages::proposals::Proposal,
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::OpenMlsProvider;
+use tls_codec::{Deserialize, Serialize, VLBytes};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    capacity::GroupFull, key_package_lifetime::KeyPackageLifetimeTooLong, mls_message_to_u8vec,
+    signer::MissingSignerError, Group, Identity, KeyPackage, Provider,
+};
+
+/// Prefix of the storage keys of the removed members of a group.
+pub(crate) const REMOVED_MEMBERS_STORAGE_LABEL: &[u8] = b"TorlnRemovedMembers";
+
+/// Errors when re-adding a member.
+#[derive(Debug)]
+pub(crate) enum ReAddError {
+    /// The credential of the key package isn't one of a removed member.
+    NotRemoved,
+    Encoding(tls_codec::Error),
+    GroupFull(GroupFull),
+    LifetimeTooLong(KeyPackageLifetimeTooLong),
+    Signer(MissingSignerError),
+    Add(AddMembersError<TorlnStorageError>),
+    RemovedMembers(RemovedMembersError),
+}
+
+impl std::fmt::Display for ReAddError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotRemoved => write!(f, "the key package is not of a removed member"),
+            Self::Encoding(e) => write!(f, "failed to encode credential: {e}"),
+            Self::GroupFull(e) => write!(f, "{e}"),
+            Self::LifetimeTooLong(e) => write!(f, "{e}"),
+            Self::Signer(e) => write!(f, "{e}"),
+            Self::Add(e) => write!(f, "failed to add member: {e}"),
+            Self::RemovedMembers(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReAddError {}
+
+/// Errors when recording the members removed from a group.
+#[derive(Debug)]
+pub(crate) enum RemovedMembersError {
+    Encoding(tls_codec::Error),
+    Storage(TorlnStorageError),
+}
+
+impl From<tls_codec::Error> for RemovedMembersError {
+    fn from(e: tls_codec::Error) -> Self {
+        Self::Encoding(e)
+    }
+}
+
+impl std::fmt::Display for RemovedMembersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Encoding(e) => write!(f, "failed to encode removed members: {e}"),
+            Self::Storage(e) => write!(f, "failed to store removed members: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RemovedMembersError {}
+
+/// The credentials of the members `staged_commit` removes from `group`.
+fn removed_by(group: &MlsGroup, staged_commit: &StagedCommit) -> Vec<Credential> {
+    staged_commit
+        .queued_proposals()
+        .filter_map(|queued_proposal| match queued_proposal.proposal() {
+            Proposal::Remove(remove) => group.member(remove.removed()),
+            Proposal::SelfRemove => match queued_proposal.sender() {
+                Sender::Member(index) => group.member(*index),
+                _ => None,
+            },
+            _ => None,
+        })
+        .cloned()
+        .collect()
+}
+
+impl Group {
+    /// The storage key of the removed members of this group.
+    fn removed_members_key(&self) -> Vec<u8> {
+        let group_id = self.mls_group.group_id().as_slice();
+
+        let mut key = REMOVED_MEMBERS_STORAGE_LABEL.to_vec();
+        key.extend_from_slice(&(group_id.len() as u32).to_be_bytes());
+        key.extend_from_slice(group_id);
+        key
+    }
+
+    /// The serialized credentials of the members removed from this group.
+    fn removed_members(&self, provider: &Provider) -> Result<Vec<VLBytes>, tls_codec::Error> {
+        // A poisoned lock still holds consistent data, see `transaction`.
+        let values = provider
+            .0
+            .storage()
+            .values()
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+
+        match values.get(&self.removed_members_key()) {
+            Some(value) => Vec::<VLBytes>::tls_deserialize_exact(value),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn store_removed_members(
+        &self,
+        provider: &Provider,
+        removed_members: Vec<VLBytes>,
+    ) -> Result<(), RemovedMembersError> {
+        let value = removed_members.tls_serialize_detached()?;
+
+        provider
+            .0
+            .storage()
+            .write_entries([(self.removed_members_key(), Some(value))])
+            .map_err(RemovedMembersError::Storage)
+    }
+
+    /// Record the members `staged_commit` removes. Must be called before the
+    /// commit is merged.
+    pub(crate) fn record_removals(
+        &self,
+        provider: &Provider,
+        staged_commit: &StagedCommit,
+    ) -> Result<(), RemovedMembersError> {
+        let removed = removed_by(&self.mls_group, staged_commit);
+        if removed.is_empty() {
+            return Ok(());
+        }
+
+        let mut removed_members = self.removed_members(provider)?;
+        for credential in removed {
+            let credential = VLBytes::from(credential.tls_serialize_detached()?);
+            if !removed_members.contains(&credential) {
+                removed_members.push(credential);
+            }
+        }
+
+        self.store_removed_members(provider, removed_members)
+    }
+
+    /// Re-add a removed member, see `reAddMember`. Returns the commit and the
+    /// welcome.
+    pub(crate) fn re_add(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        key_package: &KeyPackage,
+        note: Option<String>,
+    ) -> Result<(Vec<u8>, Vec<u8>), ReAddError> {
+        let credential = key_package
+            .0
+            .leaf_node()
+            .credential()
+            .tls_serialize_detached()
+            .map_err(ReAddError::Encoding)?;
+        let mut removed_members = self
+            .removed_members(provider)
+            .map_err(ReAddError::Encoding)?;
+        let position = removed_members
+            .iter()
+            .position(|removed| removed.as_slice() == credential)
+            .ok_or(ReAddError::NotRemoved)?;
+        self.ensure_capacity(1).map_err(ReAddError::GroupFull)?;
+        provider
+            .check_key_package_lifetime(&key_package.0)
+            .map_err(ReAddError::LifetimeTooLong)?;
+        provider
+            .check_committed_key_packages(self.mls_group.pending_proposals())
+            .map_err(ReAddError::LifetimeTooLong)?;
+        let signer = self.signer(provider, sender).map_err(ReAddError::Signer)?;
+
+        if let Some(note) = note {
+            self.mls_group.set_aad(note.into_bytes());
+        }
+        let added =
+            self.mls_group
+                .add_members(provider.as_ref(), &signer, &[key_package.0.clone()]);
+        // The note is only meant for this commit.
+        self.mls_group.set_aad(Vec::new());
+        let (commit, welcome, _group_info) = added.map_err(ReAddError::Add)?;
+
+        removed_members.remove(position);
+        self.store_removed_members(provider, removed_members)
+            .map_err(ReAddError::RemovedMembers)?;
+
+        Ok((
+            mls_message_to_u8vec(&commit),
+            mls_message_to_u8vec(&welcome),
+        ))
+    }
+}
+
+/// The commit and welcome re-adding a member, see `Group.reAddMember`.
+#[wasm_bindgen]
+pub struct ReAddMessages {
+    commit: Vec<u8>,
+    welcome: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl ReAddMessages {
+    #[wasm_bindgen(getter)]
+    pub fn commit(&self) -> Vec<u8> {
+        self.commit.clone()
+    }
+    #[wasm_bindgen(getter)]
+    pub fn welcome(&self) -> Vec<u8> {
+        self.welcome.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Add a member that was removed from this group before, with a fresh
+    /// key package.
+    ///
+    /// Fails unless the credential of `key_package` is one of a removed
+    /// member, so that a re-invite can't add someone new by mistake. The
+    /// member joins with the returned welcome like any new member, in the
+    /// leftmost free leaf, which may be their old one. `note`, e.g. the
+    /// reason for the re-add, is sent unencrypted in the authenticated data
+    /// of the commit.
+    ///
+    /// The commit is pending until `mergePendingCommit` is called.
+    #[wasm_bindgen(js_name = reAddMember)]
+    pub fn re_add_member(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+        key_package: &KeyPackage,
+        note: Option<String>,
+    ) -> Result<ReAddMessages, JsError> {
+        let (commit, welcome) = self.re_add(provider, sender, key_package, note)?;
+
+        Ok(ReAddMessages { commit, welcome })
+    }
+}
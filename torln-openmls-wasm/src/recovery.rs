@@ -0,0 +1,176 @@
+//! Rejoining a group after losing the local group state.
+//!
+//! A client that lost its storage but kept its signature keypair, e.g. from
+//! `Identity.exportKeypairBytes`, is still a member in the eyes of the other
+//! members. It can get back into the group with an external commit built
+//! from a current group info and ratchet tree. openmls adds a Remove
+//! proposal for the client's old leaf, identified by its signature key, to
+//! such a commit, so the client ends up with a fresh leaf in place of the
+//! stale one.
+
+use openmls::{
+    framing::{MlsMessageBodyIn, MlsMessageIn},
+    group::{
+        CreateCommitError, ExternalCommitBuilderError, ExternalCommitBuilderFinalizeError,
+        MlsGroup, ProposalStore, PublicGroup,
+    },
+    prelude::{CreationFromExternalError, LeafNodeParameters},
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::OpenMlsProvider;
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    extensions, join_config, message_size::MessageTooLarge, mls_message_to_u8vec,
+    provider::TorlnProvider, Group, Identity, Provider, RatchetTree,
+};
+
+/// Errors when rejoining a group from a group info.
+#[derive(Debug)]
+pub(crate) enum RecoveryError {
+    TooLarge(MessageTooLarge),
+    Malformed(tls_codec::Error),
+    NotAGroupInfo,
+    InvalidGroupInfo(CreationFromExternalError<TorlnStorageError>),
+    NotAMember,
+    ExternalCommit(ExternalCommitBuilderError<TorlnStorageError>),
+    Commit(CreateCommitError),
+    Finalize(ExternalCommitBuilderFinalizeError<TorlnStorageError>),
+}
+
+impl std::fmt::Display for RecoveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed group info: {e}"),
+            Self::NotAGroupInfo => write!(f, "expected a message of type group info"),
+            Self::InvalidGroupInfo(e) => write!(f, "invalid group info: {e}"),
+            Self::NotAMember => write!(
+                f,
+                "can't recover: the identity has no leaf in the group, join with a welcome instead"
+            ),
+            Self::ExternalCommit(e) => write!(f, "failed to rejoin the group: {e}"),
+            Self::Commit(e) => write!(f, "failed to create the external commit: {e}"),
+            Self::Finalize(e) => write!(f, "failed to create the external commit: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RecoveryError {}
+
+/// The rejoined group and the external commit, see
+/// `Group.recoverFromGroupInfo`.
+#[wasm_bindgen]
+pub struct Recovery {
+    group: Group,
+    commit: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Recovery {
+    /// The external commit, to be sent to the group.
+    #[wasm_bindgen(getter)]
+    pub fn commit(&self) -> Vec<u8> {
+        self.commit.clone()
+    }
+
+    /// The rejoined group. Consumes this object.
+    #[wasm_bindgen(js_name = intoGroup)]
+    pub fn into_group(self) -> Group {
+        self.group
+    }
+}
+
+impl Group {
+    pub(crate) fn recover(
+        provider: &Provider,
+        identity: &Identity,
+        mut group_info: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<Recovery, RecoveryError> {
+        provider
+            .check_message_size(group_info)
+            .map_err(RecoveryError::TooLarge)?;
+        let group_info = match MlsMessageIn::tls_deserialize(&mut group_info)
+            .map_err(RecoveryError::Malformed)?
+            .extract()
+        {
+            MlsMessageBodyIn::GroupInfo(group_info) => group_info,
+            _ => return Err(RecoveryError::NotAGroupInfo),
+        };
+
+        // Validate the group info and look for our leaf against a scratch
+        // storage, so that nothing is stored unless we can rejoin.
+        let scratch = TorlnProvider::default();
+        let (public_group, _) = PublicGroup::from_external(
+            scratch.crypto(),
+            scratch.storage(),
+            ratchet_tree.0.clone(),
+            group_info.clone(),
+            ProposalStore::new(),
+        )
+        .map_err(RecoveryError::InvalidGroupInfo)?;
+        if !public_group
+            .members()
+            .any(|member| member.signature_key == identity.keypair.public())
+        {
+            return Err(RecoveryError::NotAMember);
+        }
+
+        let (mls_group, bundle) = MlsGroup::external_commit_builder()
+            .with_ratchet_tree(ratchet_tree.0)
+            .with_config(join_config())
+            .build_group(&provider.0, group_info, identity.credential_with_key())
+            .map_err(RecoveryError::ExternalCommit)?
+            .leaf_node_parameters(
+                LeafNodeParameters::builder()
+                    .with_capabilities(extensions::capabilities())
+                    .build(),
+            )
+            .load_psks(provider.0.storage())
+            .map_err(RecoveryError::Commit)?
+            .build(
+                provider.0.rand(),
+                provider.0.crypto(),
+                &identity.keypair,
+                |_| true,
+            )
+            .map_err(RecoveryError::Commit)?
+            .finalize(&provider.0)
+            .map_err(RecoveryError::Finalize)?;
+
+        Ok(Recovery {
+            group: mls_group.into(),
+            commit: mls_message_to_u8vec(bundle.commit()),
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Rejoin a group after losing the local group state, e.g. when the
+    /// browser storage was cleared, using a current `group_info` and
+    /// `ratchet_tree` of the group.
+    ///
+    /// `identity` must have the signature keypair of a current member.
+    /// Returns the group in the next epoch and an external commit that
+    /// replaces the stale leaf of `identity`. The commit is already merged:
+    /// send it to the group, and if the delivery service rejects it, discard
+    /// the group and try again with a newer group info. Fails if the group
+    /// info is invalid or `identity` isn't a member.
+    #[wasm_bindgen(js_name = recoverFromGroupInfo)]
+    pub fn recover_from_group_info(
+        provider: &Provider,
+        identity: &Identity,
+        group_info: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<Recovery, JsError> {
+        Ok(Group::recover(
+            provider,
+            identity,
+            group_info,
+            ratchet_tree,
+        )?)
+    }
+}
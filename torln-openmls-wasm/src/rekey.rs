@@ -0,0 +1,70 @@
+//! Rekeying the group after a suspected compromise.
+//!
+//! A commit with a path replaces the secrets of every node on the direct
+//! path of the committer, up to the root, and the epoch secrets with them.
+//! Nodes off that path belong to other members and only change when those
+//! members update their leaves. So the most one commit can do is to apply
+//! every Update proposal the other members sent along with a fresh path of
+//! its own. For a full recovery, every member has to rekey in turn.
+
+use openmls::messages::proposals::Proposal;
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{mls_message_to_u8vec, Group, Identity, Provider};
+
+/// Proposals other than Updates are pending, which a rekey doesn't commit.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct OtherProposalsPending(pub(crate) u32);
+
+impl std::fmt::Display for OtherProposalsPending {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pending proposals aren't updates, commit them first",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for OtherProposalsPending {}
+
+#[wasm_bindgen]
+impl Group {
+    /// Commit fresh secrets for the own leaf and every node on its path to
+    /// the root, for post-compromise security.
+    ///
+    /// Unlike `commitEmpty`, which also forces a path, this includes the
+    /// pending Update proposals of other members, so that their leaves are
+    /// refreshed in the same commit. Fails without committing if other
+    /// proposals are pending, e.g. the Remove of a compromised member, which
+    /// would be discarded with the epoch; commit those first with
+    /// `commitPendingProposals`. Nodes that are neither on our path nor
+    /// updated by a proposal keep their secrets; ask the other members to
+    /// rekey as well to refresh the whole tree.
+    ///
+    /// Returns the serialized commit, which is pending until
+    /// `mergePendingCommit` is called.
+    pub fn rekey(&mut self, provider: &Provider, sender: &Identity) -> Result<Vec<u8>, JsError> {
+        let others = self
+            .mls_group
+            .pending_proposals()
+            .filter(|queued_proposal| !matches!(queued_proposal.proposal(), Proposal::Update(_)))
+            .count();
+        if others > 0 {
+            return Err(OtherProposalsPending(others as u32).into());
+        }
+
+        let signer = self.signer(provider, sender)?;
+        let bundle = self
+            .mls_group
+            .commit_builder()
+            .consume_proposal_store(true)
+            .force_self_update(true)
+            .load_psks(provider.0.storage())?
+            .build(provider.0.rand(), provider.0.crypto(), &signer, |_| true)?
+            .stage_commit(&provider.0)?;
+
+        Ok(mls_message_to_u8vec(bundle.commit()))
+    }
+}
@@ -0,0 +1,98 @@
+//! Re-encoding handshake messages for gateways between wire format policies.
+//!
+//! A public message can't be turned into a private message, nor the other
+//! way round, by anyone but the sender: the signature covers the wire
+//! format, and a private message can only be decrypted with the keys of
+//! the epoch. What a gateway can do without keys is check that a message
+//! already is in the target format and re-encode it canonically, e.g.
+//! before archiving it, and otherwise tell the sender to resend.
+
+use openmls::framing::{MlsMessageBodyIn, MlsMessageIn};
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    message_size::{MaxMessageBytes, MessageTooLarge},
+    wire_format::WireFormat,
+};
+
+/// Errors when re-encoding a message.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ReserializeError {
+    TooLarge(MessageTooLarge),
+    Malformed(tls_codec::Error),
+    NotFramed(&'static str),
+    /// `Mixed` isn't a representation a message can have.
+    InvalidTarget,
+    /// Converting between public and private messages needs the sender.
+    NotConvertible {
+        from: WireFormat,
+        to: WireFormat,
+    },
+}
+
+impl std::fmt::Display for ReserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed message: {e}"),
+            Self::NotFramed(kind) => write!(f, "a {kind} is not a framed protocol message"),
+            Self::InvalidTarget => {
+                write!(f, "the target must be either Ciphertext or Plaintext")
+            }
+            Self::NotConvertible { from, to } => write!(
+                f,
+                "can't convert a {from:?} message to {to:?}: only the sender can, since the \
+                 signature covers the wire format and private messages need the epoch keys"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReserializeError {}
+
+/// Re-encode `bytes` as a `target` message, see `reserializeMessage`.
+/// There is no provider, so `bytes` is checked against the default size
+/// limit.
+pub(crate) fn reserialize(bytes: &[u8], target: WireFormat) -> Result<Vec<u8>, ReserializeError> {
+    if target == WireFormat::Mixed {
+        return Err(ReserializeError::InvalidTarget);
+    }
+    MaxMessageBytes::default()
+        .check(bytes)
+        .map_err(ReserializeError::TooLarge)?;
+    // Parsing exactly accepts only the canonical encoding without trailing
+    // bytes, so the input is its own re-encoding.
+    let message =
+        MlsMessageIn::tls_deserialize_exact(bytes).map_err(ReserializeError::Malformed)?;
+
+    let from = match message.extract() {
+        MlsMessageBodyIn::PublicMessage(_) => WireFormat::Plaintext,
+        MlsMessageBodyIn::PrivateMessage(_) => WireFormat::Ciphertext,
+        MlsMessageBodyIn::Welcome(_) => return Err(ReserializeError::NotFramed("welcome")),
+        MlsMessageBodyIn::GroupInfo(_) => return Err(ReserializeError::NotFramed("group info")),
+        MlsMessageBodyIn::KeyPackage(_) => return Err(ReserializeError::NotFramed("key package")),
+    };
+    if from != target {
+        return Err(ReserializeError::NotConvertible { from, to: target });
+    }
+
+    Ok(bytes.to_vec())
+}
+
+/// Re-encode the serialized public or private message `bytes` as a
+/// `target` message, `Plaintext` for public and `Ciphertext` for private
+/// ones.
+///
+/// Only messages that already are in the target format can be re-encoded:
+/// the sender's signature covers the wire format, and private messages
+/// can't be opened without the keys of the epoch, so no one but the sender
+/// can convert between the two. For other messages this fails with a "can't
+/// convert" error, and the gateway has to ask the sender to resend in the
+/// format of the target group. Malformed messages, messages with trailing
+/// bytes, messages larger than the default of `Provider.setMaxMessageBytes`,
+/// and welcomes, group infos and key packages fail as well.
+#[wasm_bindgen(js_name = reserializeMessage)]
+pub fn reserialize_message(bytes: &[u8], target: WireFormat) -> Result<Vec<u8>, JsError> {
+    Ok(reserialize(bytes, target)?)
+}
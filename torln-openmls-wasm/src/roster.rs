@@ -0,0 +1,141 @@
+//! Signed statements of the membership of a group, for backends that
+//! enforce access control without being members themselves.
+
+use openmls::prelude::SignatureScheme;
+use openmls_rust_crypto::RustCrypto;
+use openmls_traits::{
+    crypto::OpenMlsCrypto,
+    signatures::{Signer, SignerError},
+};
+use tls_codec::{Serialize, TlsDeserialize, TlsSerialize, TlsSize};
+use wasm_bindgen::prelude::*;
+
+use crate::{Group, Identity};
+
+/// Prefix of the signed content, so that roster signatures can't be
+/// confused with MLS signatures by the same key.
+const ROSTER_SIGNATURE_LABEL: &[u8] = b"torln roster";
+
+/// The membership of a group in an epoch. Serialized with the TLS
+/// presentation language, this is the attestation that gets signed:
+///
+/// ```text
+/// struct {
+///     opaque group_id<V>;
+///     uint64 epoch;
+///     opaque signature_keys<V><V>; // ascending byte order
+/// } Roster;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, TlsSerialize, TlsDeserialize, TlsSize)]
+pub(crate) struct Roster {
+    pub(crate) group_id: Vec<u8>,
+    pub(crate) epoch: u64,
+    pub(crate) signature_keys: Vec<Vec<u8>>,
+}
+
+/// Errors when signing a roster.
+#[derive(Debug)]
+pub(crate) enum RosterError {
+    Encoding(tls_codec::Error),
+    Signing(SignerError),
+}
+
+impl std::fmt::Display for RosterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Encoding(e) => write!(f, "failed to encode roster: {e}"),
+            Self::Signing(e) => write!(f, "failed to sign roster: {e:?}"),
+        }
+    }
+}
+
+impl std::error::Error for RosterError {}
+
+fn signed_content(attestation: &[u8]) -> Vec<u8> {
+    [ROSTER_SIGNATURE_LABEL, attestation].concat()
+}
+
+/// A roster attestation and its signature, see `Group.signRoster`.
+#[wasm_bindgen]
+pub struct SignedRoster {
+    attestation: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl SignedRoster {
+    /// The serialized roster: group id, epoch and the sorted signature keys
+    /// of the members.
+    #[wasm_bindgen(getter)]
+    pub fn attestation(&self) -> Vec<u8> {
+        self.attestation.clone()
+    }
+    /// The Ed25519 signature of the signer over "torln roster" followed by
+    /// the attestation.
+    #[wasm_bindgen(getter)]
+    pub fn signature(&self) -> Vec<u8> {
+        self.signature.clone()
+    }
+}
+
+impl Group {
+    /// The membership of the group in the current epoch.
+    pub(crate) fn roster(&self) -> Roster {
+        let mut signature_keys = self
+            .mls_group
+            .members()
+            .map(|member| member.signature_key)
+            .collect::<Vec<_>>();
+        signature_keys.sort();
+
+        Roster {
+            group_id: self.mls_group.group_id().as_slice().to_vec(),
+            epoch: self.mls_group.epoch().as_u64(),
+            signature_keys,
+        }
+    }
+
+    pub(crate) fn sign_roster_native(
+        &self,
+        sender: &Identity,
+    ) -> Result<SignedRoster, RosterError> {
+        let attestation = self
+            .roster()
+            .tls_serialize_detached()
+            .map_err(RosterError::Encoding)?;
+        let signature = sender
+            .keypair
+            .sign(&signed_content(&attestation))
+            .map_err(RosterError::Signing)?;
+
+        Ok(SignedRoster {
+            attestation,
+            signature,
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Sign the current membership of the group with the keypair of
+    /// `sender`, so that a backend knowing the sender's public key can check
+    /// who is in the group in this epoch with `verifyRoster`.
+    #[wasm_bindgen(js_name = signRoster)]
+    pub fn sign_roster(&self, sender: &Identity) -> Result<SignedRoster, JsError> {
+        Ok(self.sign_roster_native(sender)?)
+    }
+}
+
+/// Whether `signature` is a valid roster signature over `attestation` by the
+/// Ed25519 key `public_key`, see `Group.signRoster`.
+#[wasm_bindgen(js_name = verifyRoster)]
+pub fn verify_roster(attestation: &[u8], signature: &[u8], public_key: &[u8]) -> bool {
+    RustCrypto::default()
+        .verify_signature(
+            SignatureScheme::ED25519,
+            &signed_content(attestation),
+            public_key,
+            signature,
+        )
+        .is_ok()
+}
@@ -0,0 +1,277 @@
+//! Cheap inspection of serialized messages, for routing them to the right
+//! group without deserializing the whole message.
+
+use openmls::{group::GroupContext, messages::EncryptedGroupSecrets};
+use tls_codec::{Deserialize, Serialize, TlsDeserialize, TlsSerialize, TlsSize, VLBytes};
+use wasm_bindgen::prelude::*;
+
+/// `ProtocolVersion::Mls10`
+const MLS10: u16 = 1;
+
+const WIRE_FORMAT_PUBLIC_MESSAGE: u16 = 1;
+const WIRE_FORMAT_PRIVATE_MESSAGE: u16 = 2;
+const WIRE_FORMAT_WELCOME: u16 = 3;
+const WIRE_FORMAT_GROUP_INFO: u16 = 4;
+const WIRE_FORMAT_KEY_PACKAGE: u16 = 5;
+
+/// Errors when inspecting a serialized message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RoutingError {
+    Malformed,
+    UnsupportedVersion(u16),
+    UnknownWireFormat(u16),
+    NoGroupId(&'static str),
+    NotFramed(&'static str),
+    NotAWelcome,
+    NotAGroupInfo,
+    UnknownContentType(u8),
+}
+
+impl std::fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed message"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported protocol version {version}")
+            }
+            Self::UnknownWireFormat(wire_format) => write!(f, "unknown wire format {wire_format}"),
+            Self::NoGroupId(kind) => write!(f, "a {kind} does not carry a readable group id"),
+            Self::NotFramed(kind) => write!(f, "a {kind} is not a framed protocol message"),
+            Self::NotAWelcome => write!(f, "expected a message of type welcome"),
+            Self::NotAGroupInfo => write!(f, "expected a message of type group info"),
+            Self::UnknownContentType(content_type) => {
+                write!(f, "unknown content type {content_type}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RoutingError {}
+
+fn read_u16(bytes: &mut &[u8]) -> Result<u16, RoutingError> {
+    u16::tls_deserialize(bytes).map_err(|_| RoutingError::Malformed)
+}
+
+/// Read the protocol version and wire format of a serialized `MLSMessage`,
+/// returning the wire format and the remaining bytes.
+fn read_header(mut bytes: &[u8]) -> Result<(u16, &[u8]), RoutingError> {
+    let version = read_u16(&mut bytes)?;
+    if version != MLS10 {
+        return Err(RoutingError::UnsupportedVersion(version));
+    }
+    let wire_format = read_u16(&mut bytes)?;
+
+    Ok((wire_format, bytes))
+}
+
+/// Extract the group id from a serialized `MLSMessage`.
+pub(crate) fn group_id_of(bytes: &[u8]) -> Result<Vec<u8>, RoutingError> {
+    let (wire_format, mut body) = read_header(bytes)?;
+
+    match wire_format {
+        // Both the framed content of a public message and the private
+        // message start with the group id.
+        WIRE_FORMAT_PUBLIC_MESSAGE | WIRE_FORMAT_PRIVATE_MESSAGE => {}
+        // The group context in the group info starts with the protocol
+        // version and the ciphersuite.
+        WIRE_FORMAT_GROUP_INFO => {
+            read_u16(&mut body)?;
+            read_u16(&mut body)?;
+        }
+        WIRE_FORMAT_WELCOME => return Err(RoutingError::NoGroupId("welcome")),
+        WIRE_FORMAT_KEY_PACKAGE => return Err(RoutingError::NoGroupId("key package")),
+        other => return Err(RoutingError::UnknownWireFormat(other)),
+    }
+
+    let group_id = VLBytes::tls_deserialize(&mut body).map_err(|_| RoutingError::Malformed)?;
+    Ok(group_id.as_slice().to_vec())
+}
+
+/// The unencrypted header of a public or private message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MessageHeader {
+    pub(crate) epoch: u64,
+    /// `ContentType`: 1 application, 2 proposal, 3 commit.
+    pub(crate) content_type: u8,
+}
+
+/// `ContentType::Application`
+pub(crate) const CONTENT_TYPE_APPLICATION: u8 = 1;
+const CONTENT_TYPE_PROPOSAL: u8 = 2;
+/// `ContentType::Commit`
+pub(crate) const CONTENT_TYPE_COMMIT: u8 = 3;
+
+/// Sender types of members and external senders, which are followed by an
+/// index.
+const SENDER_TYPES_WITH_INDEX: [u8; 2] = [1, 2];
+
+/// Read the epoch and content type from a serialized public or private
+/// message.
+pub(crate) fn header_of(bytes: &[u8]) -> Result<MessageHeader, RoutingError> {
+    read_message_header(bytes).map(|(_, header, _)| header)
+}
+
+/// The proposal type of a serialized public message carrying a proposal.
+/// `None` for other messages, whose content is encrypted or isn't a
+/// proposal.
+pub(crate) fn public_proposal_type_of(bytes: &[u8]) -> Option<u16> {
+    let (wire_format, header, mut content) = read_message_header(bytes).ok()?;
+    if wire_format != WIRE_FORMAT_PUBLIC_MESSAGE || header.content_type != CONTENT_TYPE_PROPOSAL {
+        return None;
+    }
+
+    read_u16(&mut content).ok()
+}
+
+/// Read the header of a serialized public or private message, returning the
+/// wire format, the header and the bytes following the content type.
+fn read_message_header(bytes: &[u8]) -> Result<(u16, MessageHeader, &[u8]), RoutingError> {
+    let (wire_format, mut body) = read_header(bytes)?;
+
+    let malformed = |_| RoutingError::Malformed;
+    match wire_format {
+        WIRE_FORMAT_PUBLIC_MESSAGE | WIRE_FORMAT_PRIVATE_MESSAGE => {}
+        WIRE_FORMAT_WELCOME => return Err(RoutingError::NotFramed("welcome")),
+        WIRE_FORMAT_GROUP_INFO => return Err(RoutingError::NotFramed("group info")),
+        WIRE_FORMAT_KEY_PACKAGE => return Err(RoutingError::NotFramed("key package")),
+        other => return Err(RoutingError::UnknownWireFormat(other)),
+    }
+
+    VLBytes::tls_deserialize(&mut body).map_err(malformed)?;
+    let epoch = u64::tls_deserialize(&mut body).map_err(malformed)?;
+
+    // In a public message, the sender and the authenticated data precede the
+    // content type. In a private message they are encrypted or come later.
+    if wire_format == WIRE_FORMAT_PUBLIC_MESSAGE {
+        let sender_type = u8::tls_deserialize(&mut body).map_err(malformed)?;
+        if SENDER_TYPES_WITH_INDEX.contains(&sender_type) {
+            u32::tls_deserialize(&mut body).map_err(malformed)?;
+        }
+        VLBytes::tls_deserialize(&mut body).map_err(malformed)?;
+    }
+    let content_type = u8::tls_deserialize(&mut body).map_err(malformed)?;
+
+    Ok((
+        wire_format,
+        MessageHeader {
+            epoch,
+            content_type,
+        },
+        body,
+    ))
+}
+
+/// Read the ciphersuite of a serialized welcome, which precedes the
+/// encrypted group secrets.
+pub(crate) fn welcome_ciphersuite_of(bytes: &[u8]) -> Result<u16, RoutingError> {
+    let (wire_format, mut body) = read_header(bytes)?;
+    if wire_format != WIRE_FORMAT_WELCOME {
+        return Err(RoutingError::NotAWelcome);
+    }
+
+    read_u16(&mut body)
+}
+
+/// Read the group context a serialized group info starts with, without
+/// verifying its signature.
+pub(crate) fn group_info_context_of(bytes: &[u8]) -> Result<GroupContext, RoutingError> {
+    let (wire_format, mut body) = read_header(bytes)?;
+    if wire_format != WIRE_FORMAT_GROUP_INFO {
+        return Err(RoutingError::NotAGroupInfo);
+    }
+
+    GroupContext::tls_deserialize(&mut body).map_err(|_| RoutingError::Malformed)
+}
+
+/// What a serialized message contains, see `messageContentType`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageContentType {
+    Application,
+    Proposal,
+    Commit,
+    Welcome,
+    GroupInfo,
+    KeyPackage,
+}
+
+/// Read what a serialized message contains from its wire format and, for
+/// public and private messages, its unencrypted header.
+pub(crate) fn content_type_of(bytes: &[u8]) -> Result<MessageContentType, RoutingError> {
+    let (wire_format, _) = read_header(bytes)?;
+
+    match wire_format {
+        WIRE_FORMAT_WELCOME => Ok(MessageContentType::Welcome),
+        WIRE_FORMAT_GROUP_INFO => Ok(MessageContentType::GroupInfo),
+        WIRE_FORMAT_KEY_PACKAGE => Ok(MessageContentType::KeyPackage),
+        _ => match header_of(bytes)?.content_type {
+            CONTENT_TYPE_APPLICATION => Ok(MessageContentType::Application),
+            CONTENT_TYPE_PROPOSAL => Ok(MessageContentType::Proposal),
+            CONTENT_TYPE_COMMIT => Ok(MessageContentType::Commit),
+            other => Err(RoutingError::UnknownContentType(other)),
+        },
+    }
+}
+
+/// The body of a welcome, as defined in RFC 9420.
+#[derive(TlsDeserialize, TlsSerialize, TlsSize)]
+struct WelcomeBody {
+    cipher_suite: u16,
+    secrets: Vec<EncryptedGroupSecrets>,
+    encrypted_group_info: VLBytes,
+}
+
+/// The welcome for the single recipient `key_package_ref` out of a
+/// serialized welcome for several recipients, or `None` if it isn't one of
+/// them.
+///
+/// The group info is encrypted once for all recipients, so only the list of
+/// encrypted group secrets shrinks.
+pub(crate) fn welcome_for_ref(
+    bytes: &[u8],
+    key_package_ref: &[u8],
+) -> Result<Option<Vec<u8>>, RoutingError> {
+    let (wire_format, body) = read_header(bytes)?;
+    if wire_format != WIRE_FORMAT_WELCOME {
+        return Err(RoutingError::NotAWelcome);
+    }
+    let mut welcome =
+        WelcomeBody::tls_deserialize_exact(body).map_err(|_| RoutingError::Malformed)?;
+
+    let Some(secrets) = welcome
+        .secrets
+        .into_iter()
+        .find(|secrets| secrets.new_member().as_slice() == key_package_ref)
+    else {
+        return Ok(None);
+    };
+    welcome.secrets = vec![secrets];
+
+    // Keep the protocol version and wire format.
+    let mut single = bytes[..bytes.len() - body.len()].to_vec();
+    welcome
+        .tls_serialize(&mut single)
+        .map_err(|_| RoutingError::Malformed)?;
+
+    Ok(Some(single))
+}
+
+/// Read the group id of a serialized message without loading the group.
+///
+/// Works for application messages, proposals, commits and group infos.
+/// Welcomes and key packages are rejected, since they carry no group id that
+/// can be read before decryption.
+#[wasm_bindgen(js_name = messageGroupId)]
+pub fn message_group_id(bytes: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(group_id_of(bytes)?)
+}
+
+/// What a serialized message contains, reading as little of it as possible.
+///
+/// Meant for fan-out on a delivery service, where only the type matters:
+/// nothing is decrypted or verified, and only the header of public and
+/// private messages is parsed.
+#[wasm_bindgen(js_name = messageContentType)]
+pub fn message_content_type(bytes: &[u8]) -> Result<MessageContentType, JsError> {
+    Ok(content_type_of(bytes)?)
+}
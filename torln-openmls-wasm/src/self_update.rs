@@ -0,0 +1,229 @@
+//! Cleaning up after self-updates that are never applied.
+//!
+//! Proposing an update of our leaf stores the private key of the new leaf
+//! right away, and openmls only deletes it once a commit applies that very
+//! proposal. If the proposal is cleared, or a commit leaves it out, the key
+//! stays in the storage for good. A self-update commit of our own keeps its
+//! new keys in the pending commit instead, so they go with it when it's
+//! cleared.
+
+use openmls::{
+    framing::Sender,
+    group::{MlsGroup, RemoveProposalError},
+    messages::proposals::Proposal,
+    treesync::{EncryptionKey, LeafNode, LeafNodeParameters},
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{storage::StorageProvider, OpenMlsProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{mls_message_to_u8vec, Group, Identity, Provider};
+
+/// Errors when abandoning pending self-updates.
+#[derive(Debug)]
+pub(crate) enum AbandonUpdateError {
+    Storage(TorlnStorageError),
+    RemoveProposal(RemoveProposalError<TorlnStorageError>),
+    GroupNotFound,
+}
+
+impl std::fmt::Display for AbandonUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Storage(e) => write!(f, "failed to update storage: {e}"),
+            Self::RemoveProposal(e) => write!(f, "failed to remove update proposal: {e}"),
+            Self::GroupNotFound => write!(f, "failed to reload group: not found in storage"),
+        }
+    }
+}
+
+impl std::error::Error for AbandonUpdateError {}
+
+impl Group {
+    /// The encryption keys of the leaves of our update proposals, applied
+    /// or not.
+    pub(crate) fn update_encryption_keys(
+        &self,
+        provider: &Provider,
+    ) -> Result<Vec<EncryptionKey>, TorlnStorageError> {
+        Ok(provider
+            .0
+            .storage()
+            .own_leaf_nodes::<_, LeafNode>(self.mls_group.group_id())?
+            .into_iter()
+            .map(|leaf_node| leaf_node.encryption_key().clone())
+            .collect())
+    }
+
+    /// Delete the encryption keypairs of `encryption_keys`, as returned by
+    /// `update_encryption_keys`, except the one of our current leaf.
+    ///
+    /// Called after merging a commit with the keys from before, as merging
+    /// forgets our update leaves but only deletes the keypair of the one it
+    /// applied.
+    pub(crate) fn delete_update_key_pairs(
+        &self,
+        provider: &Provider,
+        encryption_keys: &[EncryptionKey],
+    ) -> Result<(), TorlnStorageError> {
+        let own_leaf_key = self
+            .mls_group
+            .own_leaf_node()
+            .map(|leaf_node| leaf_node.encryption_key());
+        for encryption_key in encryption_keys {
+            if own_leaf_key != Some(encryption_key) {
+                provider
+                    .0
+                    .storage()
+                    .delete_encryption_key_pair(encryption_key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forget the leaves of our update proposals and delete their keypairs.
+    fn discard_update_leaves(&mut self, provider: &Provider) -> Result<(), AbandonUpdateError> {
+        let encryption_keys = self
+            .update_encryption_keys(provider)
+            .map_err(AbandonUpdateError::Storage)?;
+        if encryption_keys.is_empty() {
+            return Ok(());
+        }
+
+        self.delete_update_key_pairs(provider, &encryption_keys)
+            .map_err(AbandonUpdateError::Storage)?;
+        provider
+            .0
+            .storage()
+            .delete_own_leaf_nodes(self.mls_group.group_id())
+            .map_err(AbandonUpdateError::Storage)?;
+        // The group keeps its own copy of the leaves, and would look for
+        // their deleted keypairs when staging the next commit. openmls has
+        // no way to drop that copy short of loading the group again.
+        self.mls_group = MlsGroup::load(provider.0.storage(), self.mls_group.group_id())
+            .map_err(AbandonUpdateError::Storage)?
+            .ok_or(AbandonUpdateError::GroupNotFound)?;
+
+        Ok(())
+    }
+
+    /// Drop our pending commit, see `clearPendingCommit`.
+    pub(crate) fn clear_pending_commit_native(
+        &mut self,
+        provider: &Provider,
+    ) -> Result<(), AbandonUpdateError> {
+        self.mls_group
+            .clear_pending_commit(provider.0.storage())
+            .map_err(AbandonUpdateError::Storage)?;
+        // The group keeps signing with the old signature key.
+        self.discard_pending_rotation(provider)
+            .map_err(AbandonUpdateError::Storage)?;
+
+        Ok(())
+    }
+
+    /// Drop all pending proposals, see `clearPendingProposals`.
+    pub(crate) fn clear_pending_proposals_native(
+        &mut self,
+        provider: &Provider,
+    ) -> Result<(), AbandonUpdateError> {
+        self.mls_group
+            .clear_pending_proposals(provider.0.storage())
+            .map_err(AbandonUpdateError::Storage)?;
+
+        self.discard_update_leaves(provider)
+    }
+
+    /// Drop our pending self-updates, see `abandonSelfUpdate`.
+    pub(crate) fn abandon_self_update_native(
+        &mut self,
+        provider: &Provider,
+    ) -> Result<(), AbandonUpdateError> {
+        let updates_own_leaf = self
+            .mls_group
+            .pending_commit()
+            .is_some_and(|staged_commit| staged_commit.update_path_leaf_node().is_some());
+        if updates_own_leaf {
+            self.clear_pending_commit_native(provider)?;
+        }
+
+        let own_leaf_index = self.mls_group.own_leaf_index();
+        let own_updates = self
+            .mls_group
+            .pending_proposals()
+            .filter(|queued_proposal| {
+                matches!(queued_proposal.proposal(), Proposal::Update(_))
+                    && *queued_proposal.sender() == Sender::Member(own_leaf_index)
+            })
+            .map(|queued_proposal| queued_proposal.proposal_reference())
+            .collect::<Vec<_>>();
+        for proposal_ref in &own_updates {
+            self.mls_group
+                .remove_pending_proposal(provider.0.storage(), proposal_ref)
+                .map_err(AbandonUpdateError::RemoveProposal)?;
+        }
+
+        self.discard_update_leaves(provider)
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Propose to update the own leaf with a fresh encryption key, for a
+    /// later commit by any member.
+    ///
+    /// The private key of the new leaf is stored right away. It is deleted
+    /// when a commit is merged, whether or not it applied the proposal, or
+    /// with `abandonSelfUpdate` or `clearPendingProposals`.
+    ///
+    /// Returns the serialized proposal.
+    #[wasm_bindgen(js_name = proposeSelfUpdate)]
+    pub fn propose_self_update(
+        &mut self,
+        provider: &Provider,
+        sender: &Identity,
+    ) -> Result<Vec<u8>, JsError> {
+        let signer = self.signer(provider, sender)?;
+        let (proposal_msg, _proposal_ref) = self.mls_group.propose_self_update(
+            provider.as_ref(),
+            &signer,
+            LeafNodeParameters::default(),
+        )?;
+
+        Ok(mls_message_to_u8vec(&proposal_msg))
+    }
+
+    /// Drop our pending commit, e.g. because the delivery service rejected
+    /// it or another member's commit for the epoch came first.
+    ///
+    /// The new keys of a commit are kept in the pending commit and go with
+    /// it. After `rotateSignatureKey`, the new signature keypair is deleted
+    /// from storage as well, and the group keeps signing with the old one.
+    #[wasm_bindgen(js_name = clearPendingCommit)]
+    pub fn clear_pending_commit(&mut self, provider: &Provider) -> Result<(), JsError> {
+        Ok(self.clear_pending_commit_native(provider)?)
+    }
+
+    /// Drop all pending proposals, received or our own.
+    ///
+    /// Also deletes the private keys of our `proposeSelfUpdate` proposals.
+    /// A commit referencing a dropped proposal can't be processed anymore.
+    #[wasm_bindgen(js_name = clearPendingProposals)]
+    pub fn clear_pending_proposals(&mut self, provider: &Provider) -> Result<(), JsError> {
+        Ok(self.clear_pending_proposals_native(provider)?)
+    }
+
+    /// Give up on updating the own leaf: drop our pending commit if it has
+    /// a path, e.g. from `commitEmpty` or `rekey`, and our pending
+    /// `proposeSelfUpdate` proposals, and delete the private keys of their
+    /// new leaves.
+    ///
+    /// Proposals of other members are kept. Merging a commit already cleans
+    /// up update proposals it didn't apply, so this is for updates that are
+    /// abandoned within the epoch.
+    #[wasm_bindgen(js_name = abandonSelfUpdate)]
+    pub fn abandon_self_update(&mut self, provider: &Provider) -> Result<(), JsError> {
+        Ok(self.abandon_self_update_native(provider)?)
+    }
+}
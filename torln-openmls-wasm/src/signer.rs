@@ -0,0 +1,153 @@
+//! The signature keypair a group signs with.
+//!
+//! `rotateSignatureKey` replaces the signature key of the own leaf in one
+//! group only, so the keypair of the identity stays as it is and keeps
+//! signing in its other groups. A group whose own leaf carries another key
+//! signs with the keypair of that key instead, which is read from the
+//! storage by its public key. The storage holds the new keypair from the
+//! moment the rotation commit is created, and the old one is deleted on
+//! merge once no other group uses it.
+
+use openmls::group::{GroupId, MlsGroup};
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{
+    signatures::{Signer, SignerError},
+    types::SignatureScheme,
+    OpenMlsProvider,
+};
+
+use crate::{Group, Identity, Provider};
+
+/// The signature keypair of the own leaf of a group isn't in the storage.
+#[derive(Debug)]
+pub(crate) struct MissingSignerError;
+
+impl std::fmt::Display for MissingSignerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the signature keypair of the own leaf is not in the storage"
+        )
+    }
+}
+
+impl std::error::Error for MissingSignerError {}
+
+/// The keypair a group signs with, see `Group::signer`.
+pub(crate) enum GroupSigner<'a> {
+    /// The keypair of the identity, for a group that didn't rotate it.
+    Identity(&'a SignatureKeyPair),
+    /// A keypair from `rotateSignatureKey`.
+    Rotated(SignatureKeyPair),
+}
+
+impl Signer for GroupSigner<'_> {
+    fn sign(&self, payload: &[u8]) -> Result<Vec<u8>, SignerError> {
+        match self {
+            Self::Identity(keypair) => keypair.sign(payload),
+            Self::Rotated(keypair) => keypair.sign(payload),
+        }
+    }
+
+    fn signature_scheme(&self) -> SignatureScheme {
+        match self {
+            Self::Identity(keypair) => keypair.signature_scheme(),
+            Self::Rotated(keypair) => keypair.signature_scheme(),
+        }
+    }
+}
+
+/// A signature key rotation whose commit isn't merged yet, see
+/// `rotateSignatureKey`.
+pub(crate) struct PendingRotation {
+    /// The public key of the new keypair, which is already stored.
+    pub(crate) new_public_key: Vec<u8>,
+    /// The public key of the keypair the rotation replaces, unless it is the
+    /// keypair of the identity.
+    pub(crate) retired_public_key: Option<Vec<u8>>,
+}
+
+impl Group {
+    /// The keypair to sign with in this group as `sender`: the keypair of
+    /// the key in our own leaf, which is `sender`'s own unless a
+    /// `rotateSignatureKey` commit was merged.
+    pub(crate) fn signer<'a>(
+        &self,
+        provider: &Provider,
+        sender: &'a Identity,
+    ) -> Result<GroupSigner<'a>, MissingSignerError> {
+        let Some(own_leaf) = self.mls_group.own_leaf_node() else {
+            return Ok(GroupSigner::Identity(&sender.keypair));
+        };
+        let public_key = own_leaf.signature_key().as_slice();
+        if public_key == sender.keypair.public() {
+            return Ok(GroupSigner::Identity(&sender.keypair));
+        }
+
+        SignatureKeyPair::read(
+            provider.0.storage(),
+            public_key,
+            sender.keypair.signature_scheme(),
+        )
+        .map(GroupSigner::Rotated)
+        .ok_or(MissingSignerError)
+    }
+
+    /// Delete the new keypair of a rotation whose commit was dropped or
+    /// superseded by a received commit.
+    pub(crate) fn discard_pending_rotation(
+        &mut self,
+        provider: &Provider,
+    ) -> Result<(), TorlnStorageError> {
+        let Some(rotation) = self.pending_rotation.take() else {
+            return Ok(());
+        };
+
+        SignatureKeyPair::delete(
+            provider.0.storage(),
+            &rotation.new_public_key,
+            SignatureScheme::ED25519,
+        )
+    }
+
+    /// Delete the keypair a merged rotation replaced, unless the own leaf of
+    /// another stored group still carries it.
+    pub(crate) fn retire_rotated_key(
+        &mut self,
+        provider: &Provider,
+    ) -> Result<(), TorlnStorageError> {
+        let Some(public_key) = self
+            .pending_rotation
+            .take()
+            .and_then(|rotation| rotation.retired_public_key)
+        else {
+            return Ok(());
+        };
+        if signature_key_in_use(provider, &public_key, self.mls_group.group_id()) {
+            return Ok(());
+        }
+
+        SignatureKeyPair::delete(provider.0.storage(), &public_key, SignatureScheme::ED25519)
+    }
+}
+
+/// Whether the own leaf of a stored group other than `except` carries
+/// `public_key`. A group that fails to load counts as using it, so that its
+/// keypair isn't lost.
+fn signature_key_in_use(provider: &Provider, public_key: &[u8], except: &GroupId) -> bool {
+    provider
+        .0
+        .storage()
+        .group_ids::<GroupId>()
+        .iter()
+        .filter(|group_id| *group_id != except)
+        .any(
+            |group_id| match MlsGroup::load(provider.0.storage(), group_id) {
+                Ok(Some(mls_group)) => mls_group
+                    .own_leaf_node()
+                    .is_some_and(|leaf| leaf.signature_key().as_slice() == public_key),
+                _ => true,
+            },
+        )
+}
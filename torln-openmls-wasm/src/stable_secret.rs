@@ -0,0 +1,119 @@
+//! Secrets that stay the same across epochs.
+//!
+//! `exportSecret` derives from the exporter secret of the current epoch, so
+//! its output changes with every commit. Some features, such as the key of a
+//! local search index, need a key that lives as long as the group. Such a
+//! stable secret is derived once, from the resumption secret of the epoch it
+//! is first requested in, and kept in the provider storage from then on.
+
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{crypto::OpenMlsCrypto, types::CryptoError, OpenMlsProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{Group, Provider, CIPHERSUITE};
+
+/// Prefix of the storage keys of stable secrets.
+pub(crate) const STABLE_SECRET_STORAGE_LABEL: &[u8] = b"TorlnStableSecret";
+
+/// Prefix of the HKDF info of stable secrets.
+const STABLE_SECRET_LABEL: &[u8] = b"torln stable secret ";
+
+/// Errors when deriving a stable secret.
+#[derive(Debug)]
+pub(crate) enum StableSecretError {
+    StorageUnavailable,
+    Crypto(CryptoError),
+    Storage(TorlnStorageError),
+}
+
+impl std::fmt::Display for StableSecretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StorageUnavailable => write!(f, "failed to access storage"),
+            Self::Crypto(e) => write!(f, "failed to derive stable secret: {e}"),
+            Self::Storage(e) => write!(f, "failed to store stable secret: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StableSecretError {}
+
+impl Group {
+    /// The storage key of the stable secret for `label` and `length`.
+    fn stable_secret_key(&self, label: &str, length: usize) -> Vec<u8> {
+        let group_id = self.mls_group.group_id().as_slice();
+
+        let mut key = STABLE_SECRET_STORAGE_LABEL.to_vec();
+        key.extend_from_slice(&(group_id.len() as u32).to_be_bytes());
+        key.extend_from_slice(group_id);
+        key.extend_from_slice(&(length as u32).to_be_bytes());
+        key.extend_from_slice(label.as_bytes());
+        key
+    }
+
+    pub(crate) fn stable_secret(
+        &self,
+        provider: &Provider,
+        label: &str,
+        length: usize,
+    ) -> Result<Vec<u8>, StableSecretError> {
+        let key = self.stable_secret_key(label, length);
+        let storage = provider.0.storage();
+
+        let stored = storage
+            .values()
+            .read()
+            .map_err(|_| StableSecretError::StorageUnavailable)?
+            .get(&key)
+            .cloned();
+        if let Some(secret) = stored {
+            return Ok(secret);
+        }
+
+        let info = [STABLE_SECRET_LABEL, label.as_bytes()].concat();
+        let secret = provider
+            .0
+            .crypto()
+            .hkdf_expand(
+                CIPHERSUITE.hash_algorithm(),
+                self.mls_group.resumption_psk_secret().as_slice(),
+                &info,
+                length,
+            )
+            .map_err(StableSecretError::Crypto)?
+            .as_slice()
+            .to_vec();
+        storage
+            .write_entries([(key, Some(secret.clone()))])
+            .map_err(StableSecretError::Storage)?;
+
+        Ok(secret)
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Export a secret of `length` bytes for `label` that, unlike
+    /// `exportSecret`, doesn't change when the group moves to a new epoch.
+    ///
+    /// The secret is derived from the resumption secret of the epoch of the
+    /// first call for `label` and stored in the provider storage, so it
+    /// survives storage backups. It is meant for local use, e.g. to encrypt
+    /// a search index: members calling this in different epochs get
+    /// different secrets.
+    ///
+    /// Forward secrecy: commits don't rotate this secret. Whoever obtains it,
+    /// or the provider storage, can decrypt everything protected with it,
+    /// including data from before and after that point, and removed members
+    /// keep it. Use `exportSecret` for anything that should be protected by
+    /// the group's key rotation.
+    #[wasm_bindgen(js_name = exportStableSecret)]
+    pub fn export_stable_secret(
+        &self,
+        provider: &Provider,
+        label: &str,
+        length: usize,
+    ) -> Result<Vec<u8>, JsError> {
+        Ok(self.stable_secret(provider, label, length)?)
+    }
+}
@@ -0,0 +1,139 @@
+//! Joining a group in two steps, so that the host can yield in between.
+//!
+//! Staging a welcome decrypts the group secrets and verifies the ratchet
+//! tree, which takes long for large groups. `stageJoin` does that work and
+//! discards its writes to the storage, like `previewWelcome`, so that a
+//! staged join that is dropped leaves no trace. `finishJoin` then consumes
+//! the key package in the real storage and stores the group.
+
+use openmls::{
+    group::{StagedWelcome, WelcomeError},
+    key_packages::KeyPackageBundle,
+    prelude::KeyPackageRef,
+};
+use openmls_traits::{storage::StorageProvider, OpenMlsProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    join_config,
+    transaction::{self, Aborted},
+    welcome::{self, WelcomePreviewError},
+    Group, Provider, RatchetTree,
+};
+
+/// A welcome that was staged but whose group wasn't joined yet, see
+/// `Group.stageJoin`.
+///
+/// Dropping it, or freeing it from JS, cancels the join.
+#[wasm_bindgen]
+pub struct StagedJoin {
+    staged_welcome: StagedWelcome,
+    /// The key packages the welcome may be encrypted to.
+    key_package_refs: Vec<KeyPackageRef>,
+}
+
+#[wasm_bindgen]
+impl StagedJoin {
+    /// The epoch of the group the welcome invites to.
+    #[wasm_bindgen(getter)]
+    pub fn epoch(&self) -> u64 {
+        self.staged_welcome.group_context().epoch().as_u64()
+    }
+    /// The number of members of the group.
+    #[wasm_bindgen(getter, js_name = memberCount)]
+    pub fn member_count(&self) -> u32 {
+        self.staged_welcome.members().count() as u32
+    }
+}
+
+/// Stage `welcome` without writing to the storage of `provider`, see
+/// `stageJoin`.
+pub(crate) fn stage_join(
+    provider: &Provider,
+    welcome: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<StagedJoin, WelcomePreviewError> {
+    let welcome = welcome::deserialize_welcome(provider, welcome)?;
+    let key_package_refs = welcome
+        .secrets()
+        .iter()
+        .map(|secrets| secrets.new_member())
+        .collect();
+    let staged_welcome = transaction::discarding_writes(provider, || {
+        StagedWelcome::new_from_welcome(&provider.0, &join_config(), welcome, Some(ratchet_tree.0))
+    })
+    .map_err(WelcomePreviewError::Welcome)?;
+
+    Ok(StagedJoin {
+        staged_welcome,
+        key_package_refs,
+    })
+}
+
+/// Join the group of `staged`, see `finishJoin`.
+pub(crate) fn finish_join(
+    provider: &Provider,
+    staged: StagedJoin,
+) -> Result<Group, Aborted<WelcomePreviewError>> {
+    transaction::with_rollback(provider, || {
+        let storage = provider.0.storage();
+        // The key package may have been used by another join since the
+        // welcome was staged, then it's gone.
+        let (key_package_ref, bundle) = staged
+            .key_package_refs
+            .iter()
+            .find_map(|key_package_ref| {
+                storage
+                    .key_package::<_, KeyPackageBundle>(key_package_ref)
+                    .map(|bundle| Some((key_package_ref, bundle?)))
+                    .transpose()
+            })
+            .ok_or(WelcomePreviewError::Welcome(
+                WelcomeError::NoMatchingKeyPackage,
+            ))?
+            .map_err(|e| WelcomePreviewError::Welcome(WelcomeError::StorageError(e)))?;
+        // Like openmls, keep last resort key packages for further joins.
+        if !bundle.key_package().last_resort() {
+            storage
+                .delete_key_package(key_package_ref)
+                .map_err(|e| WelcomePreviewError::Welcome(WelcomeError::StorageError(e)))?;
+        }
+
+        let mls_group = staged
+            .staged_welcome
+            .into_group(&provider.0)
+            .map_err(WelcomePreviewError::Welcome)?;
+
+        Ok(mls_group.into())
+    })
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// The first step of joining the group `welcome` invites to: decrypt
+    /// the welcome and verify the ratchet tree, without storing anything.
+    ///
+    /// For large groups this takes a while, so the host can yield to the
+    /// event loop before calling `finishJoin` with the returned handle.
+    /// Dropping the handle instead cancels the join, and the key package
+    /// can still be used.
+    #[wasm_bindgen(js_name = stageJoin)]
+    pub fn stage_join(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<StagedJoin, JsError> {
+        Ok(stage_join(provider, welcome, ratchet_tree)?)
+    }
+
+    /// The second step of joining a group: consume the key package the
+    /// welcome was encrypted to and store the group staged by `stageJoin`.
+    ///
+    /// `provider` must be the one the join was staged with. Fails if the
+    /// key package was used since, e.g. by a `join` with the same welcome;
+    /// the storage is then left as it was.
+    #[wasm_bindgen(js_name = finishJoin)]
+    pub fn finish_join(provider: &Provider, staged: StagedJoin) -> Result<Group, JsError> {
+        Ok(finish_join(provider, staged)?)
+    }
+}
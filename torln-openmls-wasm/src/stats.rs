@@ -0,0 +1,127 @@
+//! Counters of the messages a group processed, for performance tuning.
+//!
+//! The counters live in memory only and start over when the group is
+//! loaded. With the `processing-timing` feature, the time spent in
+//! processing is measured too, with `performance.now()` in the browser.
+
+use openmls::group::{ProcessMessageError, ValidationError};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    processing::{MessageKind, ProcessError, ProcessedMessage},
+    Group,
+};
+
+#[cfg(all(feature = "processing-timing", target_arch = "wasm32"))]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// A timestamp in milliseconds, for measuring durations.
+#[cfg(feature = "processing-timing")]
+pub(crate) fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    let now = performance_now();
+    #[cfg(not(target_arch = "wasm32"))]
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64() * 1000.0)
+        .unwrap_or_default();
+
+    now
+}
+
+/// Counters of processed messages, see `Group.processingStats`.
+#[wasm_bindgen]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ProcessingStats {
+    messages_processed: u32,
+    commits_merged: u32,
+    proposals_queued: u32,
+    decryption_failures: u32,
+    #[cfg(feature = "processing-timing")]
+    time_ms: f64,
+}
+
+#[wasm_bindgen]
+impl ProcessingStats {
+    /// The messages processed successfully, of any kind.
+    #[wasm_bindgen(getter, js_name = messagesProcessed)]
+    pub fn messages_processed(&self) -> u32 {
+        self.messages_processed
+    }
+    /// The received commits merged, right away or with `mergeStaged`.
+    #[wasm_bindgen(getter, js_name = commitsMerged)]
+    pub fn commits_merged(&self) -> u32 {
+        self.commits_merged
+    }
+    /// The received proposals queued for a later commit.
+    #[wasm_bindgen(getter, js_name = proposalsQueued)]
+    pub fn proposals_queued(&self) -> u32 {
+        self.proposals_queued
+    }
+    /// The messages that couldn't be decrypted, including the ones for
+    /// another epoch.
+    #[wasm_bindgen(getter, js_name = decryptionFailures)]
+    pub fn decryption_failures(&self) -> u32 {
+        self.decryption_failures
+    }
+    /// The total time spent processing messages, successfully or not, in
+    /// milliseconds.
+    #[cfg(feature = "processing-timing")]
+    #[wasm_bindgen(getter, js_name = timeMs)]
+    pub fn time_ms(&self) -> f64 {
+        self.time_ms
+    }
+}
+
+impl ProcessingStats {
+    /// Count the outcome of processing one message.
+    pub(crate) fn record(&mut self, result: &Result<ProcessedMessage, ProcessError>) {
+        match result {
+            Ok(processed) => {
+                self.messages_processed += 1;
+                match processed.kind() {
+                    _ if processed.own_message() => {}
+                    MessageKind::Proposal => self.proposals_queued += 1,
+                    MessageKind::Commit if !processed.staged() => self.commits_merged += 1,
+                    _ => {}
+                }
+            }
+            Err(ProcessError::WrongEpoch { .. })
+            | Err(ProcessError::Process(ProcessMessageError::ValidationError(
+                ValidationError::UnableToDecrypt(_),
+            ))) => self.decryption_failures += 1,
+            Err(_) => {}
+        }
+    }
+
+    /// Count a staged commit that was merged.
+    pub(crate) fn record_merge(&mut self) {
+        self.commits_merged += 1;
+    }
+
+    #[cfg(feature = "processing-timing")]
+    pub(crate) fn record_time(&mut self, time_ms: f64) {
+        self.time_ms += time_ms;
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Counters of the messages this group processed since it was loaded or
+    /// the counters were reset, e.g. to find out why catching up on a
+    /// backlog is slow.
+    #[wasm_bindgen(js_name = processingStats)]
+    pub fn processing_stats(&self) -> ProcessingStats {
+        self.stats
+    }
+
+    /// Reset the counters of `processingStats` to zero.
+    #[wasm_bindgen(js_name = resetProcessingStats)]
+    pub fn reset_processing_stats(&mut self) {
+        self.stats = ProcessingStats::default();
+    }
+}
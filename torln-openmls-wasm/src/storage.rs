@@ -0,0 +1,246 @@
+//! Binary serialization of the provider storage.
+//!
+//! Format (little endian):
+//! `[magic "TMLS"][u16 format_version][u16 ciphersuite][u32 entry_count]`
+//! then for each entry: `[u32 key_len][u32 val_len][key bytes][val bytes]`
+//!
+//! Backups written before the marker was introduced start directly with the
+//! entry count. They are still accepted, but can't be checked for
+//! compatibility.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{utils::log_warning, Provider, CIPHERSUITE};
+
+/// Marks a storage blob that carries a format version and ciphersuite.
+const MAGIC: &[u8; 4] = b"TMLS";
+/// Current version of the storage format.
+const FORMAT_VERSION: u16 = 1;
+/// Size of the marker preceding the entry count.
+const MARKER_LEN: usize = 8;
+/// Size of the header, i.e. the marker and the entry count.
+const HEADER_LEN: usize = MARKER_LEN + 4;
+/// Size of the per-entry key and value lengths.
+const ENTRY_HEADER_LEN: usize = 8;
+
+pub(crate) fn encoded_entry_len(key: &[u8], value: &[u8]) -> usize {
+    ENTRY_HEADER_LEN + key.len() + value.len()
+}
+
+/// Encode storage entries into the binary storage format.
+pub(crate) fn encode_entries<'a>(
+    entry_count: usize,
+    entries: impl Iterator<Item = (&'a [u8], &'a [u8])>,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + entry_count * ENTRY_HEADER_LEN);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&u16::from(CIPHERSUITE).to_le_bytes());
+    out.extend_from_slice(&(entry_count as u32).to_le_bytes());
+
+    for (key, value) in entries {
+        out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(key);
+        out.extend_from_slice(value);
+    }
+
+    out
+}
+
+/// Errors when decoding a storage blob.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum StorageFormatError {
+    TooShort,
+    TruncatedLengths,
+    TruncatedEntry,
+    UnsupportedVersion(u16),
+    CiphersuiteMismatch { expected: u16, found: u16 },
+}
+
+impl std::fmt::Display for StorageFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooShort => write!(f, "Storage data too short"),
+            Self::TruncatedLengths => write!(f, "Corrupted storage: truncated lengths"),
+            Self::TruncatedEntry => write!(f, "Corrupted storage: truncated key/value"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported storage format version {version}")
+            }
+            Self::CiphersuiteMismatch { expected, found } => write!(
+                f,
+                "Storage was exported with ciphersuite {found:#06x}, expected {expected:#06x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StorageFormatError {}
+
+/// Decode storage entries from the binary storage format.
+pub(crate) fn decode_entries(
+    storage_bytes: &[u8],
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, StorageFormatError> {
+    let read_u16 = |data: &[u8]| -> u16 { u16::from_le_bytes(data.try_into().unwrap()) };
+    let read_u32 = |data: &[u8]| -> u32 { u32::from_le_bytes(data.try_into().unwrap()) };
+
+    let mut cursor = 0usize;
+    let len = storage_bytes.len();
+
+    if storage_bytes.starts_with(MAGIC) {
+        if len < HEADER_LEN {
+            return Err(StorageFormatError::TooShort);
+        }
+
+        let version = read_u16(&storage_bytes[4..6]);
+        if version != FORMAT_VERSION {
+            return Err(StorageFormatError::UnsupportedVersion(version));
+        }
+
+        let ciphersuite = read_u16(&storage_bytes[6..8]);
+        if ciphersuite != u16::from(CIPHERSUITE) {
+            return Err(StorageFormatError::CiphersuiteMismatch {
+                expected: CIPHERSUITE.into(),
+                found: ciphersuite,
+            });
+        }
+
+        cursor += MARKER_LEN;
+    } else {
+        log_warning("Importing storage without format marker; compatibility can't be checked");
+    }
+
+    // Need at least 4 bytes for the entry count
+    if len < cursor + 4 {
+        return Err(StorageFormatError::TooShort);
+    }
+
+    let entry_count = read_u32(&storage_bytes[cursor..cursor + 4]) as usize;
+    cursor += 4;
+
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+        if cursor + ENTRY_HEADER_LEN > len {
+            return Err(StorageFormatError::TruncatedLengths);
+        }
+
+        let key_len = read_u32(&storage_bytes[cursor..cursor + 4]) as usize;
+        cursor += 4;
+        let val_len = read_u32(&storage_bytes[cursor..cursor + 4]) as usize;
+        cursor += 4;
+
+        if cursor + key_len + val_len > len {
+            return Err(StorageFormatError::TruncatedEntry);
+        }
+
+        let key = storage_bytes[cursor..cursor + key_len].to_vec();
+        cursor += key_len;
+        let value = storage_bytes[cursor..cursor + val_len].to_vec();
+        cursor += val_len;
+
+        entries.push((key, value));
+    }
+
+    Ok(entries)
+}
+
+/// Incremental export of the provider storage.
+///
+/// Only the storage keys are captured when the export starts; values are read
+/// from the provider chunk by chunk. Every chunk is a complete storage blob
+/// in the `exportStorage` format, so the chunks can be imported one after the
+/// other with `importStorage` to reassemble the full storage.
+#[wasm_bindgen]
+pub struct StorageExportChunks {
+    keys: Vec<Vec<u8>>,
+    position: usize,
+    chunk_size: usize,
+}
+
+#[wasm_bindgen]
+impl StorageExportChunks {
+    /// Produce the next chunk, or `undefined` once all entries have been
+    /// exported.
+    ///
+    /// A chunk holds as many entries as fit into `chunk_size` bytes, but
+    /// always at least one entry. Entries removed from the storage since the
+    /// export started are skipped.
+    #[wasm_bindgen(js_name = nextChunk)]
+    pub fn next_chunk(&mut self, provider: &Provider) -> Result<Option<Vec<u8>>, JsError> {
+        let values = provider
+            .0
+            .storage()
+            .values()
+            .read()
+            .map_err(|e| JsError::new(&format!("Failed to read storage: {}", e)))?;
+
+        let mut chunk_entries = Vec::new();
+        let mut chunk_len = HEADER_LEN;
+
+        while let Some(key) = self.keys.get(self.position) {
+            let Some(value) = values.get(key) else {
+                self.position += 1;
+                continue;
+            };
+
+            let entry_len = encoded_entry_len(key, value);
+            if !chunk_entries.is_empty() && chunk_len + entry_len > self.chunk_size {
+                break;
+            }
+
+            chunk_entries.push((key.as_slice(), value.as_slice()));
+            chunk_len += entry_len;
+            self.position += 1;
+        }
+
+        if chunk_entries.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(encode_entries(
+            chunk_entries.len(),
+            chunk_entries.into_iter(),
+        )))
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Start an incremental export of the provider storage.
+    ///
+    /// Call `nextChunk` on the returned object until it returns `undefined`.
+    /// This avoids materializing the whole storage as a single buffer.
+    #[wasm_bindgen(js_name = exportStorageChunked)]
+    pub fn export_storage_chunked(
+        &self,
+        chunk_size: usize,
+    ) -> Result<StorageExportChunks, JsError> {
+        if chunk_size <= HEADER_LEN {
+            return Err(JsError::new("Chunk size too small"));
+        }
+
+        let values = self
+            .0
+            .storage()
+            .values()
+            .read()
+            .map_err(|e| JsError::new(&format!("Failed to read storage: {}", e)))?;
+
+        let mut keys = values.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+
+        Ok(StorageExportChunks {
+            keys,
+            position: 0,
+            chunk_size,
+        })
+    }
+
+    /// Import a single chunk produced by `exportStorageChunked`.
+    ///
+    /// Chunks are self-contained, so they may be imported in any order.
+    #[wasm_bindgen(js_name = importStorageChunk)]
+    pub fn import_storage_chunk(&self, chunk: &[u8]) -> Result<(), JsError> {
+        self.import_storage(chunk)
+    }
+}
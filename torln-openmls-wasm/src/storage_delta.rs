@@ -0,0 +1,192 @@
+//! Exporting only the storage entries that changed since the last sync.
+//!
+//! Continuous backup of an active client would otherwise upload the whole
+//! storage after every message. From the first delta export on, the storage
+//! gives every entry a new version when it is written or removed. A delta
+//! holds the entries with a version above the marker of the previous delta.
+//!
+//! Markers are only valid for the provider that issued them. A delta for a
+//! marker of another provider, e.g. one from before a reload, is a full
+//! export.
+
+use std::sync::Mutex;
+
+use openmls_rust_crypto::RandError;
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{random::OpenMlsRand, OpenMlsProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    storage::{self, StorageFormatError},
+    Provider,
+};
+
+/// Size of a marker, i.e. the provider's session id and a version.
+const MARKER_LEN: usize = 16;
+
+/// Random id of this provider, so that markers of another provider aren't
+/// mistaken for ours, see `Provider.exportStorageDelta`. Zero until the
+/// first delta export, which starts versioning the storage entries.
+#[derive(Default)]
+pub(crate) struct StorageVersions(Mutex<u64>);
+
+/// Errors when exporting or applying a storage delta.
+#[derive(Debug)]
+pub(crate) enum StorageDeltaError {
+    Rand(RandError),
+    Format(StorageFormatError),
+    Storage(TorlnStorageError),
+}
+
+impl std::fmt::Display for StorageDeltaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rand(e) => write!(f, "failed to create storage sync session: {e}"),
+            Self::Format(e) => write!(f, "invalid removed keys: {e}"),
+            Self::Storage(e) => write!(f, "failed to remove storage entries: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageDeltaError {}
+
+/// The storage entries written and removed since a marker, see
+/// `Provider.exportStorageDelta`.
+#[wasm_bindgen]
+pub struct StorageDelta {
+    entries: Vec<u8>,
+    removed: Vec<u8>,
+    marker: Vec<u8>,
+    full: bool,
+}
+
+#[wasm_bindgen]
+impl StorageDelta {
+    /// The written entries, in the `exportStorage` format.
+    #[wasm_bindgen(getter)]
+    pub fn entries(&self) -> Vec<u8> {
+        self.entries.clone()
+    }
+    /// The keys of the removed entries, for `Provider.removeStorageEntries`.
+    #[wasm_bindgen(getter, js_name = removedKeys)]
+    pub fn removed_keys(&self) -> Vec<u8> {
+        self.removed.clone()
+    }
+    /// The marker to pass to the next `exportStorageDelta`.
+    #[wasm_bindgen(getter)]
+    pub fn marker(&self) -> Vec<u8> {
+        self.marker.clone()
+    }
+    /// Whether `entries` is the whole storage, because the marker was
+    /// empty or not issued by this provider. The backup should then be
+    /// replaced rather than updated.
+    #[wasm_bindgen(getter)]
+    pub fn full(&self) -> bool {
+        self.full
+    }
+}
+
+impl Provider {
+    /// The storage delta since `marker`, see `exportStorageDelta`.
+    pub(crate) fn storage_delta(&self, marker: &[u8]) -> Result<StorageDelta, StorageDeltaError> {
+        let storage = self.0.storage();
+        let mut session = self.2 .0.lock().unwrap_or_else(|e| e.into_inner());
+        if *session == 0 {
+            *session = u64::from_be_bytes(
+                self.0
+                    .rand()
+                    .random_array()
+                    .map_err(StorageDeltaError::Rand)?,
+            )
+            .max(1);
+            storage.track_changes();
+        }
+
+        // A poisoned lock still holds consistent data, see `transaction`.
+        let values = storage.values().read().unwrap_or_else(|e| e.into_inner());
+        let version = storage.change_version();
+        let since = (marker.len() == MARKER_LEN
+            && marker[..MARKER_LEN / 2] == session.to_be_bytes())
+        .then(|| u64::from_be_bytes(marker[MARKER_LEN / 2..].try_into().unwrap()))
+        .filter(|since| *since <= version);
+
+        let (entries, removed) = match since {
+            Some(since) => {
+                let (written, removed) = storage
+                    .changed_since(since)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|key| values.contains_key(key));
+                let written = written
+                    .iter()
+                    .map(|key| (key.as_slice(), values[key].as_slice()))
+                    .collect::<Vec<_>>();
+                (
+                    storage::encode_entries(written.len(), written.into_iter()),
+                    storage::encode_entries(
+                        removed.len(),
+                        removed.iter().map(|key| (key.as_slice(), &[] as &[u8])),
+                    ),
+                )
+            }
+            None => (
+                storage::encode_entries(
+                    values.len(),
+                    values
+                        .iter()
+                        .map(|(key, value)| (key.as_slice(), value.as_slice())),
+                ),
+                storage::encode_entries(0, std::iter::empty()),
+            ),
+        };
+
+        let mut new_marker = session.to_be_bytes().to_vec();
+        new_marker.extend_from_slice(&version.to_be_bytes());
+
+        Ok(StorageDelta {
+            entries,
+            removed,
+            marker: new_marker,
+            full: since.is_none(),
+        })
+    }
+
+    /// Remove the entries with the keys in `removed_keys`, see
+    /// `removeStorageEntries`.
+    pub(crate) fn remove_storage_entries_native(
+        &self,
+        removed_keys: &[u8],
+    ) -> Result<(), StorageDeltaError> {
+        let removed = storage::decode_entries(removed_keys).map_err(StorageDeltaError::Format)?;
+        self.0
+            .storage()
+            .write_entries(removed.into_iter().map(|(key, _)| (key, None)))
+            .map_err(StorageDeltaError::Storage)
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Export the storage entries that changed since the delta export that
+    /// returned `marker`, for continuous backup.
+    ///
+    /// Apply the delta to the backup with `importStorage`, which merges the
+    /// written entries into the existing ones, and `removeStorageEntries`
+    /// for the removed ones, then keep the new marker for the next call.
+    /// Pass an empty marker for the first export. If the marker wasn't
+    /// issued by this provider, e.g. because the app was reloaded, the
+    /// delta is the whole storage and `full` is set.
+    ///
+    /// The storage versions its entries as they are written, so a delta
+    /// costs as much as the changes in it, not the whole storage.
+    #[wasm_bindgen(js_name = exportStorageDelta)]
+    pub fn export_storage_delta(&self, marker: &[u8]) -> Result<StorageDelta, JsError> {
+        Ok(self.storage_delta(marker)?)
+    }
+
+    /// Remove the entries with the keys in `removed_keys`, the
+    /// `removedKeys` of a `StorageDelta`.
+    #[wasm_bindgen(js_name = removeStorageEntries)]
+    pub fn remove_storage_entries(&self, removed_keys: &[u8]) -> Result<(), JsError> {
+        Ok(self.remove_storage_entries_native(removed_keys)?)
+    }
+}
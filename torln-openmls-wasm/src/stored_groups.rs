@@ -0,0 +1,150 @@
+//! Loading all groups in the provider storage at once, e.g. after restoring
+//! a backup with `importStorage`, and checking a group's storage entries.
+//!
+//! openmls has no index of the groups it stores, so the torln storage lists
+//! them from their group contexts. Which entries a group needs is up to
+//! openmls, see `MlsGroup::missing_storage_entries`.
+
+use openmls::group::{GroupId, MlsGroup};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::OpenMlsProvider;
+use wasm_bindgen::prelude::*;
+
+use crate::{utils::log_warning, Group, Provider};
+
+/// A group in the storage that couldn't be loaded.
+#[derive(Debug)]
+pub(crate) struct GroupLoadError {
+    pub(crate) group_id: GroupId,
+    /// `None` if parts of the group are missing from the storage.
+    pub(crate) error: Option<TorlnStorageError>,
+}
+
+impl std::fmt::Display for GroupLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let group_id = String::from_utf8_lossy(self.group_id.as_slice());
+        match &self.error {
+            Some(e) => write!(f, "failed to load group {group_id}: {e}"),
+            None => write!(f, "failed to load group {group_id}: incomplete group state"),
+        }
+    }
+}
+
+impl std::error::Error for GroupLoadError {}
+
+/// The ids of the groups in the storage of `provider`, in ascending byte
+/// order.
+fn stored_group_ids(provider: &Provider) -> Vec<GroupId> {
+    let mut group_ids = provider.0.storage().group_ids::<GroupId>();
+    group_ids.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+
+    group_ids
+}
+
+/// The result of checking the storage of a group, see
+/// `Provider.checkGroup`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupHealth {
+    missing: Vec<String>,
+    loadable: bool,
+    own_leaf_in_tree: bool,
+}
+
+#[wasm_bindgen]
+impl GroupHealth {
+    /// The storage labels of the entries that are missing, e.g.
+    /// `"EpochSecrets"`, or `"EncryptionKeyPair"` for the private key of
+    /// the own leaf.
+    #[wasm_bindgen(getter)]
+    pub fn missing(&self) -> Vec<String> {
+        self.missing.clone()
+    }
+    /// Whether the group can be loaded with `Group.loadFromStorage`.
+    #[wasm_bindgen(getter)]
+    pub fn loadable(&self) -> bool {
+        self.loadable
+    }
+    /// Whether the stored own leaf index points to our leaf in the stored
+    /// tree.
+    #[wasm_bindgen(getter, js_name = ownLeafInTree)]
+    pub fn own_leaf_in_tree(&self) -> bool {
+        self.own_leaf_in_tree
+    }
+    /// Whether nothing is missing and the group is consistent.
+    #[wasm_bindgen(getter)]
+    pub fn healthy(&self) -> bool {
+        self.missing.is_empty() && self.loadable && self.own_leaf_in_tree
+    }
+}
+
+impl Provider {
+    /// Load every group in the storage, see `loadAllGroups`.
+    pub(crate) fn load_all_groups_native(&self) -> Vec<Result<Group, GroupLoadError>> {
+        stored_group_ids(self)
+            .into_iter()
+            .map(
+                |group_id| match MlsGroup::load(self.0.storage(), &group_id) {
+                    Ok(Some(mls_group)) => Ok(mls_group.into()),
+                    Ok(None) => Err(GroupLoadError {
+                        group_id,
+                        error: None,
+                    }),
+                    Err(e) => Err(GroupLoadError {
+                        group_id,
+                        error: Some(e),
+                    }),
+                },
+            )
+            .collect()
+    }
+
+    /// Check the storage entries of the group `group_id`, see `checkGroup`.
+    pub(crate) fn check_group_native(&self, group_id: &GroupId) -> GroupHealth {
+        let missing = MlsGroup::missing_storage_entries(self.0.storage(), group_id)
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        // A load also fails if an entry can't be decoded.
+        let mls_group = MlsGroup::load(self.0.storage(), group_id).ok().flatten();
+        let own_leaf_in_tree = mls_group
+            .as_ref()
+            .and_then(MlsGroup::own_leaf_node)
+            .is_some();
+
+        GroupHealth {
+            missing,
+            loadable: mls_group.is_some(),
+            own_leaf_in_tree,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Load every group in the storage, in ascending group id order.
+    ///
+    /// Use this on startup after `importStorage` to restore the whole
+    /// session, instead of calling `Group.loadFromStorage` for each group id.
+    /// Groups that fail to load are skipped, with a warning on the console.
+    #[wasm_bindgen(js_name = loadAllGroups)]
+    pub fn load_all_groups(&self) -> Vec<Group> {
+        self.load_all_groups_native()
+            .into_iter()
+            .filter_map(|group| group.map_err(|e| log_warning(&e.to_string())).ok())
+            .collect()
+    }
+
+    /// Check that the group `group_id` is complete and consistent in the
+    /// storage, e.g. after a crash during a write.
+    ///
+    /// The report lists the missing entries and whether the group loads and
+    /// our leaf is in its tree, so that the app can offer recovery, e.g.
+    /// with `Group.recoverFromGroupInfo`, instead of failing on the next
+    /// operation.
+    #[wasm_bindgen(js_name = checkGroup)]
+    pub fn check_group(&self, group_id: &str) -> GroupHealth {
+        self.check_group_native(&GroupId::from_slice(group_id.as_bytes()))
+    }
+}
@@ -0,0 +1,74 @@
+//! Checking that two providers ended up with the same view of a group, e.g.
+//! two devices after a sync, or a client and the backup restored from it.
+//!
+//! Only the public state is compared: the epoch, the tree hash and the
+//! members. The secrets differ between members and are not compared, so
+//! two members of a group compare equal when they are in sync.
+
+use openmls::group::{GroupId, Member, MlsGroup};
+use wasm_bindgen::prelude::*;
+
+use crate::{stored_groups::GroupLoadError, Provider};
+
+/// The state of a group compared by `Provider.groupStateEquals`.
+#[derive(Debug, PartialEq, Eq)]
+struct PublicGroupState {
+    epoch: u64,
+    tree_hash: Vec<u8>,
+    members: Vec<Member>,
+}
+
+impl Provider {
+    /// The public state of the group `group_id` in the storage, or `None` if
+    /// the storage has no such group.
+    fn public_group_state(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<PublicGroupState>, GroupLoadError> {
+        let Some(mls_group) =
+            MlsGroup::load(self.0.storage(), group_id).map_err(|e| GroupLoadError {
+                group_id: group_id.clone(),
+                error: Some(e),
+            })?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(PublicGroupState {
+            epoch: mls_group.epoch().as_u64(),
+            tree_hash: mls_group.export_group_context().tree_hash().to_vec(),
+            members: mls_group.members().collect(),
+        }))
+    }
+
+    /// Whether this provider and `other` hold the same state of the group
+    /// `group_id`, see `groupStateEquals`.
+    pub(crate) fn group_state_equals_native(
+        &self,
+        other: &Provider,
+        group_id: &GroupId,
+    ) -> Result<bool, GroupLoadError> {
+        match (
+            self.public_group_state(group_id)?,
+            other.public_group_state(group_id)?,
+        ) {
+            (Some(ours), Some(theirs)) => Ok(ours == theirs),
+            _ => Ok(false),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Provider {
+    /// Whether this provider and `other` hold the same state of the group
+    /// `group_id`: the same epoch, tree hash and members.
+    ///
+    /// Secrets are not compared, so this also holds for two different
+    /// members of the group. Meant for testing sync logic. Returns `false`
+    /// if either storage has no such group, and fails if the group can't
+    /// be loaded from either storage.
+    #[wasm_bindgen(js_name = groupStateEquals)]
+    pub fn group_state_equals(&self, other: &Provider, group_id: &str) -> Result<bool, JsError> {
+        Ok(self.group_state_equals_native(other, &GroupId::from_slice(group_id.as_bytes()))?)
+    }
+}
@@ -86,8 +86,8 @@ mod tests {
     fn provider_with_seed() {
         let seed = [42u8; 32];
 
-        let provider1 = OpenMlsRustCrypto::with_seed(&seed);
-        let provider2 = OpenMlsRustCrypto::with_seed(&seed);
+        let provider1 = provider::TorlnProvider::with_seed(&seed);
+        let provider2 = provider::TorlnProvider::with_seed(&seed);
 
         use openmls_traits::random::OpenMlsRand;
         let buf1: [u8; 32] = provider1.rand().random_array().unwrap();
@@ -101,8 +101,8 @@ mod tests {
         let seed1 = [42u8; 32];
         let seed2 = [43u8; 32];
 
-        let provider1 = OpenMlsRustCrypto::with_seed(&seed1);
-        let provider2 = OpenMlsRustCrypto::with_seed(&seed2);
+        let provider1 = provider::TorlnProvider::with_seed(&seed1);
+        let provider2 = provider::TorlnProvider::with_seed(&seed2);
 
         use openmls_traits::random::OpenMlsRand;
         let buf1: [u8; 32] = provider1.rand().random_array().unwrap();
@@ -340,4 +340,5931 @@ mod tests {
             bob.get_public_key_bytes()
         );
     }
+
+    #[test]
+    fn group_name() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        assert_eq!(chess_club_alice.name(), None);
+        assert_eq!(chess_club_bob.name(), None);
+
+        let commit = chess_club_alice
+            .set_name(&alice_provider, &alice, "Chess Club ♞")
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        chess_club_bob
+            .process_message(&mut bob_provider, &commit)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert_eq!(chess_club_alice.name().as_deref(), Some("Chess Club ♞"));
+        assert_eq!(chess_club_bob.name().as_deref(), Some("Chess Club ♞"));
+        assert_eq!(chess_club_alice.get_epoch(), chess_club_bob.get_epoch());
+
+        // Only the types of the extensions in the group are required.
+        let required = chess_club_alice
+            .mls_group
+            .extensions()
+            .required_capabilities()
+            .unwrap()
+            .extension_types()
+            .to_vec();
+        assert_eq!(
+            required,
+            [openmls::extensions::ExtensionType::Unknown(
+                extensions::GROUP_NAME_EXTENSION_TYPE
+            )]
+        );
+    }
+
+    #[test]
+    fn members_are_ordered_by_leaf_index() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        for msg in [&add_msgs.proposal, &add_msgs.commit] {
+            chess_club_bob
+                .process_message(&mut bob_provider, msg)
+                .map_err(js_error_to_string)
+                .unwrap();
+        }
+
+        let alice_members = chess_club_alice
+            .members()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob_members = chess_club_bob
+            .members()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let leaf_indices = alice_members
+            .iter()
+            .map(GroupMember::leaf_index)
+            .collect::<Vec<_>>();
+        assert_eq!(leaf_indices, vec![0, 1, 2]);
+
+        assert_eq!(alice_members.len(), bob_members.len());
+        for (a, b) in alice_members.iter().zip(bob_members.iter()) {
+            assert_eq!(a.leaf_index(), b.leaf_index());
+            assert_eq!(a.credential(), b.credential());
+            assert_eq!(a.signature_key(), b.signature_key());
+        }
+        assert_eq!(
+            alice_members[0].signature_key(),
+            alice.get_public_key_bytes()
+        );
+    }
+
+    #[test]
+    fn inactive_after_removal() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        assert!(chess_club_alice.is_active());
+        assert!(chess_club_bob.is_active());
+
+        // Alice removes Bob
+        let bob_index = chess_club_bob.mls_group.own_leaf_index();
+        let (commit, _, _) = chess_club_alice
+            .mls_group
+            .remove_members(alice_provider.as_ref(), &alice.keypair, &[bob_index])
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert!(chess_club_bob.is_active());
+        chess_club_bob
+            .process_message(&mut bob_provider, &mls_message_to_u8vec(&commit))
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert!(chess_club_alice.is_active());
+        assert!(!chess_club_bob.is_active());
+    }
+
+    #[test]
+    fn test_storage_chunked_export_and_import() {
+        let (alice_provider, alice, chess_club_alice, _, _, _) = create_group_alice_and_bob();
+        let group_id = chess_club_alice.group_id();
+
+        let mut chunks = alice_provider
+            .export_storage_chunked(256)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let restored_provider = Provider::create(None).unwrap();
+        let mut chunk_count = 0;
+        while let Some(chunk) = chunks
+            .next_chunk(&alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap()
+        {
+            restored_provider
+                .import_storage_chunk(&chunk)
+                .map_err(js_error_to_string)
+                .unwrap();
+            chunk_count += 1;
+        }
+        assert!(chunk_count > 1, "Storage should span multiple chunks");
+
+        // The reassembled storage holds exactly the same entries
+        let original = alice_provider.0.storage().values().read().unwrap().clone();
+        let restored = restored_provider
+            .0
+            .storage()
+            .values()
+            .read()
+            .unwrap()
+            .clone();
+        assert_eq!(original, restored);
+
+        let restored_alice = Identity::create(
+            &restored_provider,
+            "alice",
+            Some(alice.export_keypair_bytes().unwrap()),
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        let mut restored_group = Group::load_from_storage(&restored_provider, &group_id)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(restored_group
+            .create_message(&restored_provider, &restored_alice, b"hello after restore")
+            .is_ok());
+    }
+
+    #[test]
+    fn rotate_signature_key() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let old_public_key = alice.get_public_key_bytes();
+        let rotation = chess_club_alice
+            .rotate_signature_key(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let new_public_key = rotation.public_key();
+        assert_ne!(old_public_key, new_public_key);
+
+        // The new keypair is stored with the pending commit
+        assert!(SignatureKeyPair::read(
+            alice_provider.0.storage(),
+            &new_public_key,
+            SignatureScheme::ED25519
+        )
+        .is_some());
+
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .process_message(&mut bob_provider, &rotation.commit())
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // The identity keeps its keypair, which stays in storage
+        assert_eq!(alice.get_public_key_bytes(), old_public_key);
+        assert!(SignatureKeyPair::read(
+            alice_provider.0.storage(),
+            &old_public_key,
+            SignatureScheme::ED25519
+        )
+        .is_some());
+
+        // Bob sees the new key in Alice's leaf and accepts messages signed with it
+        let alice_index = chess_club_alice.mls_group.own_leaf_index();
+        let alice_leaf = chess_club_bob.mls_group.member_at(alice_index).unwrap();
+        assert_eq!(alice_leaf.signature_key, new_public_key);
+
+        let msg_out = chess_club_alice
+            .create_message(&alice_provider, &alice, b"signed with the new key")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let received = chess_club_bob
+            .process_message(&mut bob_provider, &msg_out)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(received, b"signed with the new key");
+    }
+
+    #[test]
+    fn derive_group_key_packages() {
+        let provider = Provider::create(None).unwrap();
+        let mut alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let chess = alice
+            .derive_group_key_package(&provider, "chess club")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let go = alice
+            .derive_group_key_package(&provider, "go club")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let chess_again = alice
+            .derive_group_key_package(&provider, "chess club")
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Each group gets its own key package, stable across calls
+        assert_ne!(chess.to_bytes().unwrap(), go.to_bytes().unwrap());
+        assert_ne!(
+            chess.0.hpke_init_key().as_slice(),
+            go.0.hpke_init_key().as_slice()
+        );
+        assert_eq!(chess.to_bytes().unwrap(), chess_again.to_bytes().unwrap());
+
+        // Both share the identity's signature key
+        assert_eq!(
+            chess.0.leaf_node().signature_key().as_slice(),
+            go.0.leaf_node().signature_key().as_slice()
+        );
+
+        assert_eq!(alice.groups(), vec!["chess club", "go club"]);
+    }
+
+    #[test]
+    fn test_storage_ciphersuite_marker() {
+        let provider = Provider::create(None).unwrap();
+        let _alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let exported = provider.export_storage().unwrap();
+        assert_eq!(&exported[..4], b"TMLS");
+        assert!(storage::decode_entries(&exported).is_ok());
+
+        // A backup from a build using a different ciphersuite is rejected
+        let mut foreign = exported.clone();
+        let other_ciphersuite =
+            u16::from(Ciphersuite::MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519);
+        foreign[6..8].copy_from_slice(&other_ciphersuite.to_le_bytes());
+        assert_eq!(
+            storage::decode_entries(&foreign),
+            Err(storage::StorageFormatError::CiphersuiteMismatch {
+                expected: CIPHERSUITE.into(),
+                found: other_ciphersuite,
+            })
+        );
+
+        // Backups without a marker are still imported
+        let mut legacy = vec![];
+        legacy.extend_from_slice(&1u32.to_le_bytes());
+        legacy.extend_from_slice(&3u32.to_le_bytes());
+        legacy.extend_from_slice(&5u32.to_le_bytes());
+        legacy.extend_from_slice(b"key");
+        legacy.extend_from_slice(b"value");
+
+        let restored = Provider::create(None).unwrap();
+        restored.import_storage(&legacy).unwrap();
+        assert_eq!(
+            restored
+                .0
+                .storage()
+                .values()
+                .read()
+                .unwrap()
+                .get(b"key".as_slice()),
+            Some(&b"value".to_vec())
+        );
+    }
+
+    #[test]
+    fn create_message_buffered_reuses_buffer() {
+        let (alice_provider, alice, mut chess_club_alice, mut bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let first = chess_club_alice
+            .create_message_buffered(&alice_provider, &alice, b"first")
+            .map_err(js_error_to_string)
+            .unwrap()
+            .to_vec();
+        let buffer = chess_club_alice.message_buffer.as_ptr();
+        let received = chess_club_bob
+            .process_message(&mut bob_provider, &first)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(received, b"first");
+
+        // A message of the same size is serialized into the same allocation
+        let second = chess_club_alice
+            .create_message_buffered(&alice_provider, &alice, b"again")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(second.as_ptr(), buffer);
+        let second = second.to_vec();
+        let received = chess_club_bob
+            .process_message(&mut bob_provider, &second)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(received, b"again");
+    }
+
+    #[test]
+    fn message_group_id_routing() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, bob, _) =
+            create_group_alice_and_bob();
+        let group_id = b"chess club".to_vec();
+
+        let application = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(routing::group_id_of(&application), Ok(group_id.clone()));
+
+        let group_info = chess_club_alice
+            .mls_group
+            .export_group_info(alice_provider.as_ref().crypto(), &alice.keypair, false)
+            .unwrap();
+        assert_eq!(
+            routing::group_id_of(&mls_message_to_u8vec(&group_info)),
+            Ok(group_id.clone())
+        );
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            routing::group_id_of(&add_msgs.proposal),
+            Ok(group_id.clone())
+        );
+        assert_eq!(routing::group_id_of(&add_msgs.commit), Ok(group_id));
+
+        assert_eq!(
+            routing::group_id_of(&add_msgs.welcome),
+            Err(routing::RoutingError::NoGroupId("welcome"))
+        );
+        let key_package = bob.get_key_package(&bob_provider);
+        let key_package_msg = MlsMessageOut::from(key_package.0);
+        assert_eq!(
+            routing::group_id_of(&mls_message_to_u8vec(&key_package_msg)),
+            Err(routing::RoutingError::NoGroupId("key package"))
+        );
+        assert_eq!(
+            routing::group_id_of(&[0x00]),
+            Err(routing::RoutingError::Malformed)
+        );
+    }
+
+    #[test]
+    fn branch_subgroup() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        for name in ["charlie", "dave"] {
+            let provider = Provider::create(None).unwrap();
+            let identity = Identity::create(&provider, name, None)
+                .map_err(js_error_to_string)
+                .unwrap();
+            let add_msgs = chess_club_alice
+                .native_propose_and_commit_add(
+                    &alice_provider,
+                    &alice,
+                    &identity.get_key_package(&provider),
+                )
+                .map_err(js_error_to_string)
+                .unwrap();
+            chess_club_alice
+                .merge_pending_commit(&mut alice_provider)
+                .map_err(js_error_to_string)
+                .unwrap();
+            for msg in [&add_msgs.proposal, &add_msgs.commit] {
+                chess_club_bob
+                    .process_message(&mut bob_provider, msg)
+                    .map_err(js_error_to_string)
+                    .unwrap();
+            }
+        }
+        assert_eq!(chess_club_alice.mls_group.members().count(), 4);
+        let parent_epoch = chess_club_alice.get_epoch();
+
+        let subgroup = chess_club_alice
+            .branch_subgroup(
+                &alice_provider,
+                &alice,
+                "chess club breakout",
+                vec![0, 1],
+                vec![bob.get_key_package(&bob_provider)],
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        let ratchet_tree = subgroup.export_ratchet_tree();
+        let welcome = subgroup.welcome();
+        let mut breakout_alice = subgroup.into_group();
+
+        let mut breakout_bob =
+            Group::join_branch(&bob_provider, &chess_club_bob, &welcome, ratchet_tree)
+                .map_err(js_error_to_string)
+                .unwrap();
+
+        assert_eq!(breakout_bob.group_id(), "chess club breakout");
+        assert_eq!(breakout_alice.mls_group.members().count(), 2);
+        assert_eq!(breakout_bob.mls_group.members().count(), 2);
+        assert_eq!(
+            breakout_alice
+                .export_secret(&alice_provider, "breakout", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            breakout_bob
+                .export_secret(&bob_provider, "breakout", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+
+        // The subgroup advances on its own, the parent stays where it was.
+        let commit = breakout_bob
+            .set_name(&bob_provider, &bob, "endgames")
+            .map_err(js_error_to_string)
+            .unwrap();
+        breakout_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        breakout_alice
+            .process_message(&mut alice_provider, &commit)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert_eq!(breakout_alice.name(), Some("endgames".to_string()));
+        assert_eq!(breakout_alice.get_epoch(), breakout_bob.get_epoch());
+        assert_eq!(chess_club_alice.get_epoch(), parent_epoch);
+        assert_eq!(chess_club_bob.get_epoch(), parent_epoch);
+    }
+
+    #[test]
+    fn founder_info() {
+        let (mut alice_provider, alice, chess_club, bob_provider, bob, _) =
+            create_group_alice_and_bob();
+
+        // Only recorded on request, so that members needn't support it
+        assert!(chess_club.founder_info().is_none());
+        assert!(chess_club
+            .mls_group
+            .extensions()
+            .required_capabilities()
+            .is_none());
+
+        let mut config = GroupConfig::new();
+        config.set_record_founder(true);
+        let mut go_club_alice =
+            Group::create_new_with_config(&alice_provider, &alice, "go club", &config)
+                .map_err(js_error_to_string)
+                .unwrap();
+        let add_msgs = go_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        go_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let go_club_bob = Group::native_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            go_club_alice.export_ratchet_tree(),
+        );
+
+        let alice_view = go_club_alice.founder_info().unwrap();
+        let bob_view = go_club_bob.founder_info().unwrap();
+
+        assert_eq!(
+            alice_view.credential().map_err(js_error_to_string).unwrap(),
+            alice
+                .get_credential_bytes()
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+        assert!(alice_view.created_at() > 0);
+        assert_eq!(alice_view.0, bob_view.0);
+    }
+
+    #[test]
+    fn can_join_is_a_dry_run() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let charlie_provider = Provider::create(None).unwrap();
+
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let storage_before = bob_provider
+            .export_storage()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert!(Group::can_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+        assert!(!Group::can_join(
+            &charlie_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+        assert!(!Group::can_join(
+            &bob_provider,
+            &add_msgs.commit,
+            chess_club_alice.export_ratchet_tree()
+        ));
+
+        assert_eq!(
+            bob_provider
+                .export_storage()
+                .map_err(js_error_to_string)
+                .unwrap(),
+            storage_before
+        );
+
+        let chess_club_bob = Group::native_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn preview_welcome() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        for msg in [&add_msgs.proposal, &add_msgs.commit] {
+            chess_club_bob
+                .process_message(&mut bob_provider, msg)
+                .map_err(js_error_to_string)
+                .unwrap();
+        }
+
+        let storage_before = charlie_provider
+            .export_storage()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let preview = Group::preview_welcome(
+            &charlie_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        let members = chess_club_alice
+            .members()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert_eq!(preview.len(), 3);
+        for (previewed, member) in preview.iter().zip(members.iter()) {
+            assert_eq!(previewed.leaf_index(), member.leaf_index());
+            assert_eq!(previewed.credential(), member.credential());
+        }
+
+        assert_eq!(
+            charlie_provider
+                .export_storage()
+                .map_err(js_error_to_string)
+                .unwrap(),
+            storage_before
+        );
+        let chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_charlie.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn self_remove_committed_by_other_member() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let proposal = chess_club_bob
+            .propose_self_remove(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        // SelfRemove proposals are sent as public messages.
+        assert_eq!(proposal[2..4], [0, 1]);
+
+        chess_club_alice
+            .process_message(&mut alice_provider, &proposal)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let commit = chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap()
+            .commit();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        chess_club_bob
+            .process_message(&mut bob_provider, &commit)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert_eq!(chess_club_alice.mls_group.members().count(), 1);
+        assert!(chess_club_alice.is_active());
+        assert!(!chess_club_bob.is_active());
+    }
+
+    #[test]
+    fn current_generation_climbs_with_messages() {
+        let (alice_provider, alice, mut chess_club_alice, mut bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        chess_club_alice.set_generation_watermark(3);
+
+        assert_eq!(chess_club_alice.current_generation(0), Some(0));
+        for generation in 1..=3 {
+            assert!(!chess_club_alice.generation_watermark_reached());
+
+            let msg = chess_club_alice
+                .create_message(&alice_provider, &alice, b"check")
+                .map_err(js_error_to_string)
+                .unwrap();
+            assert_eq!(chess_club_alice.current_generation(0), Some(generation));
+
+            chess_club_bob
+                .process_message(&mut bob_provider, &msg)
+                .map_err(js_error_to_string)
+                .unwrap();
+            assert_eq!(chess_club_bob.current_generation(0), Some(generation));
+        }
+
+        assert!(chess_club_alice.generation_watermark_reached());
+        assert_eq!(chess_club_alice.current_generation(1), Some(0));
+        assert_eq!(chess_club_alice.current_generation(7), None);
+    }
+
+    #[test]
+    fn try_process_messages_isolates_failures() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let before_commit = chess_club_alice
+            .create_message(&alice_provider, &alice, b"before")
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let after_commit = chess_club_alice
+            .create_message(&alice_provider, &alice, b"after")
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let results = chess_club_bob.try_process_all(
+            &bob_provider,
+            &[
+                after_commit,
+                vec![0xde, 0xad],
+                add_msgs.commit,
+                before_commit,
+                add_msgs.proposal,
+            ],
+        );
+
+        assert_eq!(results.len(), 5);
+        let after = results[0].processed().unwrap();
+        assert_eq!(after.kind(), MessageKind::Application);
+        assert_eq!(after.application_data(), Some(b"after".to_vec()));
+        assert!(results[1].processed().is_none());
+        assert!(results[1].error().is_some());
+        assert_eq!(results[2].processed().unwrap().kind(), MessageKind::Commit);
+        let before = results[3].processed().unwrap();
+        assert_eq!(before.application_data(), Some(b"before".to_vec()));
+        assert_eq!(before.epoch() + 1, after.epoch());
+        assert_eq!(
+            results[4].processed().unwrap().kind(),
+            MessageKind::Proposal
+        );
+
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn custom_proposal_round_trip() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        assert_eq!(
+            proposals::check_app_proposal_type(0xf0ff),
+            Err(proposals::UnsupportedProposalType(0xf0ff))
+        );
+
+        let proposal = chess_club_alice
+            .propose_custom(
+                &alice_provider,
+                &alice,
+                extensions::PROMOTE_TO_ADMIN_PROPOSAL_TYPE,
+                b"bob".to_vec(),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let processed = chess_club_bob
+            .process_message_detailed(&bob_provider, &proposal)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(processed.kind(), MessageKind::Proposal);
+        assert_eq!(processed.sender_leaf_index(), Some(0));
+        let app_proposal = processed.app_proposal().unwrap();
+        assert_eq!(
+            app_proposal.proposal_type(),
+            extensions::PROMOTE_TO_ADMIN_PROPOSAL_TYPE
+        );
+        assert_eq!(app_proposal.payload(), b"bob".to_vec());
+
+        // The other members support the proposal type, so it can be committed.
+        let commit = chess_club_bob
+            .commit_pending_proposals(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap()
+            .commit();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process_message(&mut alice_provider, &commit)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club_alice.get_epoch(), chess_club_bob.get_epoch());
+    }
+
+    #[test]
+    fn pending_proposal_counts() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        assert_eq!(chess_club_alice.pending_proposal_counts().total(), 0);
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .mls_group
+            .propose_add_member(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &charlie.get_key_package(&charlie_provider).0,
+            )
+            .unwrap();
+
+        let proposal = chess_club_bob
+            .propose_self_remove(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process_message_detailed(&alice_provider, &proposal)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let counts = chess_club_alice.pending_proposal_counts();
+        assert_eq!(counts.add(), 1);
+        assert_eq!(counts.self_remove(), 1);
+        assert_eq!(counts.remove(), 0);
+        assert_eq!(counts.total(), 2);
+    }
+
+    #[test]
+    fn commit_pending_add_returns_welcome() {
+        let (mut alice_provider, alice, mut chess_club_alice, _, _, _) =
+            create_group_alice_and_bob();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .mls_group
+            .propose_add_member(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &charlie.get_key_package(&charlie_provider).0,
+            )
+            .unwrap();
+
+        // The welcome would be lost, so nothing is committed.
+        assert_eq!(
+            chess_club_alice
+                .commit_no_welcome(&alice_provider, &alice)
+                .map_err(js_error_to_string)
+                .unwrap_err(),
+            PendingAddsError(1).to_string()
+        );
+        assert!(chess_club_alice.mls_group.pending_commit().is_none());
+
+        let commit = chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &commit.welcome().unwrap(),
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_charlie.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn ciphersuite_params() {
+        let params = ciphersuite::params_of(None).unwrap();
+        assert_eq!(params.ciphersuite(), 0x0003);
+        assert_eq!(params.hash_length(), 32);
+        assert_eq!(params.aead_key_length(), 32);
+        assert_eq!(params.aead_nonce_length(), 12);
+        assert_eq!(params.signature_scheme(), 0x0807);
+
+        let params = ciphersuite::params_of(Some(0x0001)).unwrap();
+        assert_eq!(params.aead_key_length(), 16);
+
+        assert!(ciphersuite::params_of(Some(0xffff)).is_err());
+    }
+
+    #[test]
+    fn refresh_key_packages_after_credential_change() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let old_key_pkg = bob.get_key_package(&bob_provider);
+
+        // Bob changes his credential, keeping his keypair.
+        let keypair_bytes = bob
+            .export_keypair_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut robert = Identity::create(&bob_provider, "robert", Some(keypair_bytes))
+            .map_err(js_error_to_string)
+            .unwrap();
+        let new_key_pkgs = robert
+            .refresh_key_packages_native(&bob_provider, 2)
+            .unwrap();
+        assert_eq!(new_key_pkgs.len(), 2);
+        for key_pkg in &new_key_pkgs {
+            assert_eq!(key_pkg.leaf_node().credential(), &robert.credential);
+        }
+
+        // A welcome to the old key package can't be joined anymore.
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(&alice_provider, &alice, &old_key_pkg)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(!Group::can_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+
+        // A welcome to a new one can.
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "go club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &KeyPackage(new_key_pkgs[0].clone()),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(Group::can_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+    }
+
+    #[test]
+    fn join_with_mismatched_ciphersuite() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert_eq!(welcome::check_ciphersuite(&add_msgs.welcome), Ok(()));
+
+        // The ciphersuite follows the protocol version and the wire format.
+        let mut welcome = add_msgs.welcome.clone();
+        welcome[4..6].copy_from_slice(&0x0001u16.to_be_bytes());
+
+        assert_eq!(
+            welcome::check_ciphersuite(&welcome),
+            Err(welcome::CiphersuiteMismatch {
+                expected: 0x0003,
+                found: 0x0001,
+            })
+        );
+        assert!(matches!(
+            welcome::stage_welcome(
+                &bob_provider,
+                &welcome,
+                chess_club_alice.export_ratchet_tree()
+            ),
+            Err(welcome::WelcomePreviewError::CiphersuiteMismatch(_))
+        ));
+    }
+
+    #[cfg(feature = "debug-tools")]
+    #[test]
+    fn secret_tree_fingerprint() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let alice_fingerprint = chess_club_alice
+            .secret_tree_fingerprint(&alice_provider)
+            .unwrap();
+        let bob_fingerprint = chess_club_bob
+            .secret_tree_fingerprint(&bob_provider)
+            .unwrap();
+        assert_eq!(alice_fingerprint.len(), 32);
+        assert_eq!(alice_fingerprint, bob_fingerprint);
+
+        // Deriving message keys doesn't change the fingerprint.
+        let msg = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello, bob!")
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .process_message_detailed(&bob_provider, &msg)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            chess_club_bob
+                .secret_tree_fingerprint(&bob_provider)
+                .unwrap(),
+            alice_fingerprint
+        );
+    }
+
+    #[test]
+    fn verify_key_package_credential() {
+        let provider = Provider::create(None).unwrap();
+        let bob = Identity::create(&provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mallory = Identity::create(&provider, "mallory", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob_credential = bob
+            .get_credential_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mallory_credential = mallory
+            .get_credential_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let key_pkg = bob
+            .get_key_package(&provider)
+            .to_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            key_packages::key_package_has_credential(&key_pkg, &bob_credential),
+            Ok(true)
+        );
+        assert_eq!(
+            key_packages::key_package_has_credential(&key_pkg, &mallory_credential),
+            Ok(false)
+        );
+
+        // The signature comes last.
+        let mut tampered = key_pkg.clone();
+        *tampered.last_mut().unwrap() ^= 0xff;
+        assert_eq!(
+            key_packages::key_package_has_credential(&tampered, &bob_credential),
+            Ok(false)
+        );
+
+        assert!(key_packages::key_package_has_credential(&key_pkg[..8], &bob_credential).is_err());
+    }
+
+    #[test]
+    fn wrong_epoch() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let old_msg = chess_club_alice
+            .create_message(&alice_provider, &alice, b"epoch 1")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let commit = chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap()
+            .commit();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let new_msg = chess_club_alice
+            .create_message(&alice_provider, &alice, b"epoch 2")
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Bob hasn't seen the commit yet.
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &new_msg),
+            Err(processing::ProcessError::WrongEpoch {
+                message_epoch: 2,
+                current_epoch: 1,
+            })
+        ));
+
+        chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert_eq!(
+            chess_club_bob
+                .process(&bob_provider, &new_msg)
+                .unwrap()
+                .application_data(),
+            Some(b"epoch 2".to_vec())
+        );
+
+        // The message from before the commit is too old now.
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &old_msg),
+            Err(processing::ProcessError::WrongEpoch {
+                message_epoch: 1,
+                current_epoch: 2,
+            })
+        ));
+
+        // A message rejected for another reason isn't reported as being for
+        // another epoch, even if its epoch differs.
+        let mut go_club_alice = Group::create_new(&alice_provider, &alice, "go club");
+        let other_group_msg = go_club_alice
+            .create_message(&alice_provider, &alice, b"epoch 0")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &other_group_msg),
+            Err(processing::ProcessError::Process(_))
+        ));
+    }
+
+    #[test]
+    fn test_storage_binary_round_trip() {
+        let (alice_provider, ..) = create_group_alice_and_bob();
+
+        let exported = alice_provider
+            .export_storage()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let restored_provider = Provider::create_from_storage(None, &exported)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let reexported = restored_provider
+            .export_storage()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Entries are exported in hash map order, so compare them sorted.
+        let mut entries = storage::decode_entries(&exported).unwrap();
+        let mut reexported_entries = storage::decode_entries(&reexported).unwrap();
+        entries.sort();
+        reexported_entries.sort();
+        assert_eq!(entries, reexported_entries);
+        assert_eq!(exported.len(), reexported.len());
+
+        // No text encoding: the blob is the header plus the raw entries.
+        let entries_len = entries
+            .iter()
+            .map(|(key, value)| storage::encoded_entry_len(key, value))
+            .sum::<usize>();
+        assert_eq!(exported.len(), 12 + entries_len);
+    }
+
+    #[test]
+    fn audit_record_of_commit() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        chess_club_bob
+            .process(&bob_provider, &add_msgs.proposal)
+            .unwrap();
+        let processed = chess_club_bob
+            .process(&bob_provider, &add_msgs.commit)
+            .unwrap();
+        let mut record = processed.audit_record().unwrap();
+
+        assert_eq!(
+            record.actor(),
+            alice
+                .get_credential_bytes()
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+        assert_eq!(record.from_epoch(), 1);
+        assert_eq!(record.to_epoch(), 2);
+        let actions = record.actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].proposal_type(), 1);
+        assert_eq!(
+            actions[0].subject(),
+            charlie
+                .get_credential_bytes()
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+
+        record.set_timestamp(1_700_000_000);
+        let bytes = record.to_bytes().map_err(js_error_to_string).unwrap();
+        assert_eq!(
+            AuditRecord::from_bytes(&bytes)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            record
+        );
+
+        // Other messages carry no audit record.
+        let msg = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hi")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(chess_club_bob
+            .process(&bob_provider, &msg)
+            .unwrap()
+            .audit_record()
+            .is_none());
+    }
+
+    #[test]
+    fn create_with_initial_members() {
+        let alice_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let others = ["bob", "charlie", "dave"]
+            .into_iter()
+            .map(|name| {
+                let provider = Provider::create(None).unwrap();
+                let identity = Identity::create(&provider, name, None)
+                    .map_err(js_error_to_string)
+                    .unwrap();
+                (provider, identity)
+            })
+            .collect::<Vec<_>>();
+
+        let created = Group::create_with(
+            &alice_provider,
+            &alice,
+            "chess club",
+            others
+                .iter()
+                .map(|(provider, identity)| identity.get_key_package(provider))
+                .collect(),
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        let welcome = created.welcome();
+        let chess_club_alice = created.into_group();
+
+        assert_eq!(chess_club_alice.get_epoch(), 1);
+        assert_eq!(
+            chess_club_alice
+                .members()
+                .map_err(js_error_to_string)
+                .unwrap()
+                .len(),
+            4
+        );
+        let alice_key = chess_club_alice
+            .export_secret(&alice_provider, "chess_key", &[], 32)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        for (provider, _) in &others {
+            let chess_club =
+                Group::native_join(provider, &welcome, chess_club_alice.export_ratchet_tree());
+            assert_eq!(chess_club.get_epoch(), 1);
+            assert_eq!(
+                chess_club
+                    .export_secret(provider, "chess_key", &[], 32)
+                    .map_err(js_error_to_string)
+                    .unwrap(),
+                alice_key
+            );
+        }
+    }
+
+    #[test]
+    fn signed_roster() {
+        let (_, alice, chess_club_alice, _, bob, _) = create_group_alice_and_bob();
+
+        let signed = chess_club_alice.sign_roster_native(&alice).unwrap();
+        let alice_key = alice.get_public_key_bytes();
+        assert!(verify_roster(
+            &signed.attestation(),
+            &signed.signature(),
+            &alice_key
+        ));
+        assert!(!verify_roster(
+            &signed.attestation(),
+            &signed.signature(),
+            &bob.get_public_key_bytes()
+        ));
+
+        let roster = roster::Roster::tls_deserialize(&mut signed.attestation().as_slice()).unwrap();
+        assert_eq!(roster, chess_club_alice.roster());
+        assert_eq!(roster.epoch, 1);
+        let mut expected_keys = vec![alice_key.clone(), bob.get_public_key_bytes()];
+        expected_keys.sort();
+        assert_eq!(roster.signature_keys, expected_keys);
+
+        // A roster claiming a different epoch doesn't verify.
+        let forged = roster::Roster { epoch: 2, ..roster }
+            .tls_serialize_detached()
+            .unwrap();
+        assert!(!verify_roster(&forged, &signed.signature(), &alice_key));
+    }
+
+    #[test]
+    fn recover_from_group_info() {
+        let (alice_provider, alice, mut chess_club_alice, _, bob, _) = create_group_alice_and_bob();
+
+        let group_info = mls_message_to_u8vec(
+            &chess_club_alice
+                .mls_group
+                .export_group_info(alice_provider.0.crypto(), &alice.keypair, false)
+                .unwrap(),
+        );
+
+        // Charlie was never a member.
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(matches!(
+            Group::recover(
+                &charlie_provider,
+                &charlie,
+                &group_info,
+                chess_club_alice.export_ratchet_tree()
+            ),
+            Err(recovery::RecoveryError::NotAMember)
+        ));
+
+        // Bob lost his storage but kept his keypair.
+        let bob_provider = Provider::create(None).unwrap();
+        let keypair_bytes = bob
+            .export_keypair_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", Some(keypair_bytes))
+            .map_err(js_error_to_string)
+            .unwrap();
+        let recovery = Group::recover(
+            &bob_provider,
+            &bob,
+            &group_info,
+            chess_club_alice.export_ratchet_tree(),
+        )
+        .unwrap();
+        let commit = recovery.commit();
+        let chess_club_bob = recovery.into_group();
+
+        chess_club_alice.process(&alice_provider, &commit).unwrap();
+
+        assert_eq!(chess_club_alice.get_epoch(), 2);
+        assert_eq!(chess_club_bob.get_epoch(), 2);
+        assert_eq!(
+            chess_club_alice
+                .members()
+                .map_err(js_error_to_string)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            chess_club_bob
+                .export_secret(&bob_provider, "chess_key", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            chess_club_alice
+                .export_secret(&alice_provider, "chess_key", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn max_members() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut config = GroupConfig::new();
+        config.set_max_members(Some(2));
+        let mut chess_club_alice =
+            Group::create_new_with_config(&alice_provider, &alice, "chess club", &config)
+                .map_err(js_error_to_string)
+                .unwrap();
+        assert_eq!(chess_club_alice.max_members(), Some(2));
+
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Bob sees the same limit.
+        let chess_club_bob = Group::native_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_bob.max_members(), Some(2));
+
+        assert_eq!(
+            chess_club_alice.ensure_capacity(1),
+            Err(capacity::GroupFull {
+                max_members: 2,
+                members: 2,
+                adding: 1,
+            })
+        );
+
+        // Groups created without a limit have none.
+        let go_club = Group::create_new(&alice_provider, &alice, "go club");
+        assert_eq!(go_club.max_members(), None);
+        assert_eq!(go_club.ensure_capacity(100), Ok(()));
+    }
+
+    #[test]
+    fn pending_adds_count_against_max_members() {
+        let alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut config = GroupConfig::new();
+        config.set_max_members(Some(1));
+        let mut chess_club_alice =
+            Group::create_new_with_config(&alice_provider, &alice, "chess club", &config)
+                .map_err(js_error_to_string)
+                .unwrap();
+        let (_, proposal_ref) = chess_club_alice
+            .mls_group
+            .propose_add_member(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &bob.get_key_package(&bob_provider).0,
+            )
+            .unwrap();
+
+        let group_full = capacity::GroupFull {
+            max_members: 1,
+            members: 2,
+            adding: 0,
+        };
+        assert_eq!(
+            chess_club_alice
+                .commit_pending_proposals(&alice_provider, &alice)
+                .map_err(js_error_to_string)
+                .err(),
+            Some(group_full.to_string())
+        );
+        assert!(matches!(
+            chess_club_alice.commit_selected_proposals(
+                &alice_provider,
+                &alice,
+                &[proposal_ref.as_slice().to_vec()]
+            ),
+            Err(proposals::CommitProposalsError::GroupFull(e)) if e == group_full
+        ));
+        assert_eq!(
+            chess_club_alice
+                .set_name(&alice_provider, &alice, "go club")
+                .map_err(js_error_to_string)
+                .err(),
+            Some(group_full.to_string())
+        );
+        assert!(chess_club_alice.mls_group.pending_commit().is_none());
+    }
+
+    #[test]
+    fn sender_credential_tells_reused_leaves_apart() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let bob_msg = chess_club_bob
+            .create_message(&bob_provider, &bob, b"hi, I'm bob")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_alice.process(&alice_provider, &bob_msg).unwrap();
+        assert_eq!(processed.sender_leaf_index(), Some(1));
+        assert_eq!(
+            processed.sender_credential(),
+            bob.get_credential_bytes()
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+
+        // Alice removes Bob and adds Charlie, who gets Bob's leaf.
+        chess_club_alice
+            .mls_group
+            .remove_members(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &[openmls::prelude::LeafNodeIndex::new(1)],
+            )
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+
+        let charlie_msg = chess_club_charlie
+            .create_message(&charlie_provider, &charlie, b"hi, I'm charlie")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_alice
+            .process(&alice_provider, &charlie_msg)
+            .unwrap();
+        assert_eq!(processed.sender_leaf_index(), Some(1));
+        assert_eq!(
+            processed.sender_credential(),
+            charlie
+                .get_credential_bytes()
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn commit_remove_only_without_welcome() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let proposal = chess_club_bob
+            .leave_group(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process(&alice_provider, &proposal)
+            .unwrap();
+
+        let commit = chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap()
+            .commit();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club_alice.get_epoch(), 2);
+        assert_eq!(
+            chess_club_alice
+                .members()
+                .map_err(js_error_to_string)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert!(!chess_club_bob.is_active());
+    }
+
+    #[test]
+    fn stable_secret_survives_epochs() {
+        let (mut alice_provider, alice, mut chess_club_alice, ..) = create_group_alice_and_bob();
+
+        let stable = chess_club_alice
+            .stable_secret(&alice_provider, "search index", 32)
+            .unwrap();
+        let exported = chess_club_alice
+            .export_secret(&alice_provider, "search index", &[], 32)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(stable.len(), 32);
+
+        chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club_alice.get_epoch(), 2);
+
+        assert_eq!(
+            chess_club_alice
+                .stable_secret(&alice_provider, "search index", 32)
+                .unwrap(),
+            stable
+        );
+        assert_ne!(
+            chess_club_alice
+                .export_secret(&alice_provider, "search index", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            exported
+        );
+        assert_ne!(
+            chess_club_alice
+                .stable_secret(&alice_provider, "other label", 32)
+                .unwrap(),
+            stable
+        );
+    }
+
+    #[test]
+    fn prune_expired_key_packages() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let now = utils::unix_time_secs();
+        OpenMlsKeyPackage::builder()
+            .key_package_lifetime(openmls::key_packages::Lifetime::init(now - 120, now - 60))
+            .leaf_node_capabilities(extensions::capabilities())
+            .build(
+                CIPHERSUITE,
+                &bob_provider.0,
+                &bob.keypair,
+                bob.credential_with_key(),
+            )
+            .unwrap();
+        let bob_key_pkg = bob.get_key_package(&bob_provider);
+
+        assert_eq!(
+            bob_provider.prune_expired_key_packages_native(now).unwrap(),
+            1
+        );
+        assert_eq!(
+            bob_provider.prune_expired_key_packages_native(now).unwrap(),
+            0
+        );
+
+        // The valid key package is kept.
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(&alice_provider, &alice, &bob_key_pkg)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(Group::can_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+    }
+
+    #[test]
+    fn welcome_epoch_of_late_welcome() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let bob_key_pkg = bob.get_key_package(&bob_provider);
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(&alice_provider, &alice, &bob_key_pkg)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let ratchet_tree = chess_club_alice.export_ratchet_tree();
+
+        // The group advances before Bob gets the welcome.
+        chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let inspected = welcome::inspect_welcome(
+            &bob_provider,
+            &add_msgs.welcome,
+            RatchetTree(ratchet_tree.0.clone()),
+        )
+        .unwrap();
+        let welcome_epoch =
+            welcome::welcome_epoch(&bob_provider, &add_msgs.welcome, ratchet_tree).unwrap();
+        assert_eq!(welcome_epoch, 1);
+        assert!(welcome_epoch < chess_club_alice.get_epoch() as u64);
+
+        // Inspecting the welcome tells the same as joining it would.
+        assert_eq!(inspected.epoch, welcome_epoch);
+        assert_eq!(inspected.group_id, b"chess club");
+        assert_eq!(inspected.own_leaf_index, Some(1));
+        assert_eq!(
+            inspected
+                .members
+                .iter()
+                .map(|member| member.index.u32())
+                .collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            inspected.key_package_ref.as_slice(),
+            bob_key_pkg.reference().map_err(js_error_to_string).unwrap()
+        );
+    }
+
+    #[test]
+    fn two_devices_of_one_user() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_phone_provider = Provider::create(None).unwrap();
+        let bob_laptop_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob_phone = Identity::create(&bob_phone_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob_laptop = bob_phone.new_device(&bob_laptop_provider).unwrap();
+        assert_ne!(
+            bob_laptop.get_public_key_bytes(),
+            bob_phone.get_public_key_bytes()
+        );
+
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        for key_pkg in [
+            bob_phone.get_key_package(&bob_phone_provider),
+            bob_laptop.get_key_package(&bob_laptop_provider),
+        ] {
+            assert_eq!(key_pkg.0.leaf_node().credential(), &bob_phone.credential);
+            chess_club_alice
+                .native_propose_and_commit_add(&alice_provider, &alice, &key_pkg)
+                .map_err(js_error_to_string)
+                .unwrap();
+            chess_club_alice
+                .merge_pending_commit(&mut alice_provider)
+                .map_err(js_error_to_string)
+                .unwrap();
+        }
+
+        let users = devices::members_by_user(&chess_club_alice.mls_group).unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(
+            users[0].credential(),
+            alice
+                .get_credential_bytes()
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+        assert_eq!(users[0].leaf_indices(), vec![0]);
+        assert_eq!(
+            users[1].credential(),
+            bob_phone
+                .get_credential_bytes()
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+        assert_eq!(users[1].leaf_indices(), vec![1, 2]);
+    }
+
+    #[test]
+    fn failed_join_rolls_back_storage() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let storage_before = bob_provider.0.storage().values().read().unwrap().clone();
+
+        // Staging deletes the key package, then `into_group` stores the
+        // keypairs of the epoch and fails to store the group.
+        let aborted = transaction::with_rollback(&bob_provider, || {
+            let welcome = welcome::deserialize_welcome(&bob_provider, &add_msgs.welcome).unwrap();
+            let staged_welcome = openmls::group::StagedWelcome::new_from_welcome(
+                &bob_provider.0,
+                &join_config(),
+                welcome,
+                Some(chess_club_alice.export_ratchet_tree().0),
+            )
+            .unwrap();
+            bob_provider.0.storage().fail_writes_after(Some(1));
+            staged_welcome.into_group(&bob_provider.0).map(|_| ())
+        })
+        .unwrap_err();
+        bob_provider.0.storage().fail_writes_after(None);
+        assert!(aborted.rolled_back);
+        assert!(aborted
+            .to_string()
+            .ends_with("; storage changes were rolled back"));
+        assert_eq!(
+            *bob_provider.0.storage().values().read().unwrap(),
+            storage_before
+        );
+
+        // Nothing is rolled back if nothing was written.
+        let aborted =
+            transaction::with_rollback(&bob_provider, || Err::<(), _>("offline")).unwrap_err();
+        assert!(!aborted.rolled_back);
+
+        // Entries of our own are rolled back too.
+        let chess_club = GroupId::from_slice(b"chess club");
+        let aborted = transaction::with_rollback(&bob_provider, || {
+            bob_provider
+                .set_min_decrypt_epoch_native(&chess_club, 2)
+                .unwrap();
+            Err::<(), _>("offline")
+        })
+        .unwrap_err();
+        assert!(aborted.rolled_back);
+        assert_eq!(bob_provider.min_decrypt_epoch(&chess_club), 0);
+
+        // The key package is still there, so the join can be retried.
+        let chess_club_bob = Group::native_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn wire_format_policy_after_reload() {
+        let alice_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+
+        let policy = chess_club_alice.wire_format_policy();
+        assert_eq!(policy.outgoing(), WireFormat::Ciphertext);
+        assert_eq!(policy.incoming(), WireFormat::Ciphertext);
+
+        let reloaded = Group::load_from_storage(&alice_provider, "chess club")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(reloaded.wire_format_policy(), policy);
+    }
+
+    #[cfg(feature = "debug-tools")]
+    #[test]
+    fn tree_dump() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        for msg in [&add_msgs.proposal, &add_msgs.commit] {
+            chess_club_bob
+                .process_message(&mut bob_provider, msg)
+                .map_err(js_error_to_string)
+                .unwrap();
+        }
+
+        let dump = chess_club_alice.tree_dump().unwrap();
+        assert_eq!(dump.len(), 5);
+        for (node_index, node) in dump.iter().enumerate() {
+            assert_eq!(node.node_index(), node_index as u32);
+        }
+
+        let leaves = dump
+            .iter()
+            .filter_map(|node| node.leaf_index().map(|leaf_index| (leaf_index, node)))
+            .collect::<Vec<_>>();
+        assert_eq!(leaves.len(), 3);
+        for ((leaf_index, node), identity) in leaves.iter().zip([&alice, &bob, &charlie]) {
+            assert_eq!(node.leaf_index(), Some(*leaf_index));
+            assert!(node.occupied());
+            assert_eq!(
+                node.credential(),
+                Some(
+                    identity
+                        .get_credential_bytes()
+                        .map_err(js_error_to_string)
+                        .unwrap()
+                )
+            );
+            assert_eq!(node.parent_hash(), None);
+        }
+
+        // Both members see the same tree.
+        let bob_dump = chess_club_bob.tree_dump().unwrap();
+        for (node, bob_node) in dump.iter().zip(&bob_dump) {
+            assert_eq!(node.occupied(), bob_node.occupied());
+            assert_eq!(node.credential(), bob_node.credential());
+            assert_eq!(node.parent_hash(), bob_node.parent_hash());
+        }
+    }
+
+    #[test]
+    fn commit_received_proposal_by_ref() {
+        let (
+            alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let proposals = [b"first".to_vec(), b"second".to_vec()].map(|payload| {
+            chess_club_alice
+                .propose_custom(&alice_provider, &alice, 0xf000, payload)
+                .map_err(js_error_to_string)
+                .unwrap()
+        });
+        let proposal_refs = proposals
+            .iter()
+            .map(|proposal| {
+                let processed = chess_club_bob.process(&bob_provider, proposal).unwrap();
+                assert_eq!(processed.kind(), MessageKind::Proposal);
+                processed.proposal_ref().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_ne!(proposal_refs[0], proposal_refs[1]);
+
+        let commit = chess_club_bob
+            .commit_selected_proposals(&bob_provider, &bob, &proposal_refs[..1])
+            .unwrap();
+        assert!(commit.welcome().is_none());
+        // The other proposal is still queued until the commit is merged.
+        assert_eq!(chess_club_bob.pending_proposal_counts().custom(), 2);
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club_bob.pending_proposal_counts().total(), 0);
+
+        let processed = chess_club_alice
+            .process(&alice_provider, &commit.commit())
+            .unwrap();
+        let actions = processed.audit_record().unwrap().actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].proposal_type(), 0xf000);
+        assert_eq!(chess_club_alice.get_epoch(), chess_club_bob.get_epoch());
+
+        assert!(matches!(
+            chess_club_bob.commit_selected_proposals(&bob_provider, &bob, &proposal_refs[1..]),
+            Err(proposals::CommitProposalsError::UnknownProposal(0))
+        ));
+    }
+
+    #[test]
+    fn commit_received_add_proposal_by_ref() {
+        let (
+            alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let (proposal, _) = chess_club_alice
+            .mls_group
+            .propose_add_member(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &charlie.get_key_package(&charlie_provider).0,
+            )
+            .unwrap();
+        let proposal_ref = chess_club_bob
+            .process(&bob_provider, &mls_message_to_u8vec(&proposal))
+            .unwrap()
+            .proposal_ref()
+            .unwrap();
+
+        let commit = chess_club_bob
+            .commit_selected_proposals(&bob_provider, &bob, &[proposal_ref])
+            .unwrap();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process(&alice_provider, &commit.commit())
+            .unwrap();
+
+        // Charlie joins with the welcome of the commit.
+        let chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &commit.welcome().unwrap(),
+            chess_club_bob.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_charlie.get_epoch(), chess_club_bob.get_epoch());
+        assert_eq!(chess_club_alice.get_epoch(), chess_club_bob.get_epoch());
+    }
+
+    #[test]
+    fn auto_merge_on_merges_commits() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let commit = chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap()
+            .commit();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let processed = chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert_eq!(processed.kind(), MessageKind::Commit);
+        assert!(!processed.staged());
+        assert!(!chess_club_bob.has_staged_commit());
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn auto_merge_off_stages_commits() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        chess_club_bob.set_auto_merge(false);
+        let epoch = chess_club_bob.get_epoch();
+
+        let commit = chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap()
+            .commit();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let processed = chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert_eq!(processed.kind(), MessageKind::Commit);
+        assert!(processed.staged());
+        assert!(chess_club_bob.has_staged_commit());
+        assert_eq!(chess_club_bob.get_epoch(), epoch);
+
+        chess_club_bob.merge_staged(&bob_provider).unwrap();
+        assert!(!chess_club_bob.has_staged_commit());
+        assert_eq!(chess_club_bob.get_epoch(), epoch + 1);
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+
+        assert!(matches!(
+            chess_club_bob.merge_staged(&bob_provider),
+            Err(processing::ProcessError::NoStagedCommit)
+        ));
+
+        // Messages of the new epoch can be read after merging.
+        let msg = chess_club_alice
+            .create_message(&alice_provider, &alice, b"check")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &msg).unwrap();
+        assert_eq!(processed.application_data(), Some(b"check".to_vec()));
+    }
+
+    #[test]
+    fn welcome_for_one_of_several_recipients() {
+        let alice_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let recipients = ["bob", "charlie", "dave"].map(|name| {
+            let provider = Provider::create(None).unwrap();
+            let identity = Identity::create(&provider, name, None)
+                .map_err(js_error_to_string)
+                .unwrap();
+            let key_pkg = identity.get_key_package(&provider);
+            (provider, key_pkg)
+        });
+
+        let created = Group::create_with(
+            &alice_provider,
+            &alice,
+            "chess club",
+            recipients
+                .iter()
+                .map(|(_, key_pkg)| KeyPackage(key_pkg.0.clone()))
+                .collect(),
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        let welcome = created.welcome();
+        let chess_club_alice = created.into_group();
+
+        let (charlie_provider, charlie_key_pkg) = &recipients[1];
+        let charlie_ref = charlie_key_pkg
+            .reference()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let charlie_welcome = routing::welcome_for_ref(&welcome, &charlie_ref)
+            .unwrap()
+            .unwrap();
+        assert!(charlie_welcome.len() < welcome.len());
+
+        // The slice only holds Charlie's secrets.
+        let (bob_provider, _) = &recipients[0];
+        assert!(!Group::can_join(
+            bob_provider,
+            &charlie_welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+        let chess_club_charlie = Group::join(
+            charlie_provider,
+            &charlie_welcome,
+            chess_club_alice.export_ratchet_tree(),
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        assert_eq!(chess_club_charlie.get_epoch(), chess_club_alice.get_epoch());
+
+        let alice_ref = alice
+            .get_key_package(&alice_provider)
+            .reference()
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(routing::welcome_for_ref(&welcome, &alice_ref), Ok(None));
+    }
+
+    #[test]
+    fn content_type_of_messages() {
+        let alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+
+        let application = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let proposal = chess_club_alice
+            .propose_custom(&alice_provider, &alice, 0xf000, vec![])
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert_eq!(
+            routing::content_type_of(&application),
+            Ok(MessageContentType::Application)
+        );
+        assert_eq!(
+            routing::content_type_of(&proposal),
+            Ok(MessageContentType::Proposal)
+        );
+        assert_eq!(
+            routing::content_type_of(&add_msgs.commit),
+            Ok(MessageContentType::Commit)
+        );
+        assert_eq!(
+            routing::content_type_of(&add_msgs.welcome),
+            Ok(MessageContentType::Welcome)
+        );
+        assert_eq!(
+            routing::content_type_of(&[0, 1]),
+            Err(routing::RoutingError::Malformed)
+        );
+    }
+
+    #[test]
+    fn re_add_removed_member() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let proposal = chess_club_bob
+            .leave_group(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process(&alice_provider, &proposal)
+            .unwrap();
+        chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(matches!(
+            chess_club_alice.re_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+                None,
+            ),
+            Err(readd::ReAddError::NotRemoved)
+        ));
+
+        let (_commit, welcome) = chess_club_alice
+            .re_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+                Some("appeal accepted".to_string()),
+            )
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut chess_club_bob = Group::native_join(
+            &bob_provider,
+            &welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+
+        let message = chess_club_alice
+            .create_message(&alice_provider, &alice, b"welcome back")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &message).unwrap();
+        assert_eq!(processed.application_data(), Some(b"welcome back".to_vec()));
+
+        // Once re-added, bob is no longer a removed member.
+        assert!(matches!(
+            chess_club_alice.re_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+                None,
+            ),
+            Err(readd::ReAddError::NotRemoved)
+        ));
+    }
+
+    #[test]
+    fn empty_commit_rotates_path_secrets() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let own_encryption_key = |group: &Group| {
+            group
+                .members()
+                .map_err(js_error_to_string)
+                .unwrap()
+                .into_iter()
+                .find(|member| member.leaf_index() == 0)
+                .unwrap()
+                .encryption_key()
+        };
+        let encryption_key_before = own_encryption_key(&chess_club_alice);
+        let exported_before = chess_club_alice
+            .export_secret(&alice_provider, "rekey", &[], 32)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let commit = chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club_alice.get_epoch(), 2);
+        assert_eq!(
+            chess_club_alice
+                .members()
+                .map_err(js_error_to_string)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_ne!(own_encryption_key(&chess_club_alice), encryption_key_before);
+        assert_ne!(
+            chess_club_alice
+                .export_secret(&alice_provider, "rekey", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            exported_before
+        );
+
+        chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert_eq!(chess_club_bob.get_epoch(), 2);
+        assert_eq!(
+            own_encryption_key(&chess_club_bob),
+            own_encryption_key(&chess_club_alice)
+        );
+    }
+
+    #[test]
+    fn member_encryption_key_changes_after_update() {
+        let (mut alice_provider, alice, mut chess_club_alice, _, _, chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let encryption_key = chess_club_bob.leaf_encryption_key(0).unwrap();
+        assert_eq!(
+            chess_club_alice.leaf_encryption_key(0),
+            Ok(encryption_key.clone())
+        );
+        assert_eq!(chess_club_alice.leaf_encryption_key(2), Err(BlankLeaf(2)));
+
+        chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert_ne!(
+            chess_club_alice.leaf_encryption_key(0).unwrap(),
+            encryption_key
+        );
+    }
+
+    #[test]
+    fn validate_welcome_tree_detects_forged_tree() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let ratchet_tree = chess_club_alice.export_ratchet_tree();
+
+        // A tree with the same members, but from a later epoch.
+        chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let forged_tree = chess_club_alice.export_ratchet_tree();
+
+        assert!(matches!(
+            welcome::validate_welcome_tree(&bob_provider, &add_msgs.welcome, forged_tree),
+            Err(welcome::WelcomePreviewError::TreeMismatch)
+        ));
+        welcome::validate_welcome_tree(
+            &bob_provider,
+            &add_msgs.welcome,
+            RatchetTree(ratchet_tree.0.clone()),
+        )
+        .unwrap();
+
+        // Validating doesn't use up the key package.
+        let chess_club_bob = Group::native_join(&bob_provider, &add_msgs.welcome, ratchet_tree);
+        assert_eq!(chess_club_bob.get_epoch(), 1);
+    }
+
+    #[test]
+    fn dry_run_commit_leaves_group_unchanged() {
+        let (
+            alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let proposal = chess_club_alice
+            .leave_group(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let proposal_ref = chess_club_bob
+            .process(&bob_provider, &proposal)
+            .unwrap()
+            .proposal_ref()
+            .unwrap();
+
+        let storage_before = bob_provider.0.storage().values().read().unwrap().clone();
+        let dry_run = chess_club_bob
+            .dry_run(&bob_provider, &bob, &[proposal_ref.clone()])
+            .unwrap();
+        assert!(dry_run.welcome().is_none());
+        use openmls::framing::{MlsMessageBodyIn, MlsMessageIn};
+        let group_info = match MlsMessageIn::tls_deserialize_exact(dry_run.group_info())
+            .unwrap()
+            .extract()
+        {
+            MlsMessageBodyIn::GroupInfo(group_info) => group_info,
+            _ => panic!("expected a group info"),
+        };
+        assert_eq!(group_info.epoch().as_u64(), 2);
+
+        assert_eq!(
+            *bob_provider.0.storage().values().read().unwrap(),
+            storage_before
+        );
+        assert_eq!(chess_club_bob.get_epoch(), 1);
+        assert!(chess_club_bob.mls_group.pending_commit().is_none());
+        assert_eq!(chess_club_bob.pending_proposal_counts().remove(), 1);
+
+        // The real commit still goes through.
+        chess_club_bob
+            .commit_selected_proposals(&bob_provider, &bob, &[proposal_ref])
+            .unwrap();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club_bob.get_epoch(), 2);
+
+        assert!(matches!(
+            chess_club_bob.dry_run(&bob_provider, &bob, &[b"unknown".to_vec()]),
+            Err(dry_run::DryRunError::UnknownProposal(0))
+        ));
+    }
+
+    #[test]
+    fn negotiate_ciphersuite_with_key_package() {
+        let provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let key_package = alice
+            .get_key_package(&provider)
+            .to_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let ours = u16::from(CIPHERSUITE);
+        let p256 = u16::from(Ciphersuite::MLS_128_DHKEMP256_AES128GCM_SHA256_P256);
+        let p384 = u16::from(Ciphersuite::MLS_256_DHKEMP384_AES256GCM_SHA384_P384);
+        let x448 = u16::from(Ciphersuite::MLS_256_DHKEMX448_CHACHA20POLY1305_SHA512_Ed448);
+
+        // Overlapping: our first preference the key package supports wins.
+        assert_eq!(
+            ciphersuite::negotiate(&[p384, ours, p256], &key_package).unwrap(),
+            Some(ours)
+        );
+        assert_eq!(
+            ciphersuite::negotiate(&[p256, ours], &key_package).unwrap(),
+            Some(p256)
+        );
+        // Disjoint.
+        assert_eq!(
+            ciphersuite::negotiate(&[p384, x448], &key_package).unwrap(),
+            None
+        );
+        assert_eq!(ciphersuite::negotiate(&[], &key_package).unwrap(), None);
+
+        assert!(matches!(
+            ciphersuite::negotiate(&[ours], &key_package[1..]),
+            Err(ciphersuite::NegotiationError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn load_all_groups_from_backup() {
+        let provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        Group::create_new(&provider, &alice, "go club");
+        Group::create_new(&provider, &alice, "chess club");
+        let backup = provider
+            .export_storage()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let restored = Provider::create(None).unwrap();
+        restored
+            .import_storage(&backup)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let groups = restored.load_all_groups();
+        assert_eq!(
+            groups.iter().map(Group::group_id).collect::<Vec<_>>(),
+            ["chess club", "go club"]
+        );
+
+        // A group whose state is incomplete is reported and skipped.
+        let mut key = b"GroupContext".to_vec();
+        key.extend_from_slice(&serde_json::to_vec(&GroupId::from_slice(b"broken")).unwrap());
+        key.extend_from_slice(&openmls_traits::storage::CURRENT_VERSION.to_be_bytes());
+        restored
+            .0
+            .storage()
+            .values()
+            .write()
+            .unwrap()
+            .insert(key, b"{}".to_vec());
+        let loaded = restored.load_all_groups_native();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.iter().filter(|group| group.is_ok()).count(), 2);
+        assert_eq!(restored.load_all_groups().len(), 2);
+    }
+
+    #[test]
+    fn ephemeral_message_ttl() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let message = chess_club_alice
+            .create_ephemeral_message(&alice_provider, &alice, b"gone soon", 3600)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &message).unwrap();
+        assert_eq!(processed.application_data(), Some(b"gone soon".to_vec()));
+        assert_eq!(processed.ttl_seconds(), Some(3600));
+
+        // Later messages don't inherit the time to live.
+        let message = chess_club_alice
+            .create_message(&alice_provider, &alice, b"here to stay")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &message).unwrap();
+        assert_eq!(processed.ttl_seconds(), None);
+
+        // A relay extending the time to live breaks the message.
+        let mut message = chess_club_alice
+            .create_ephemeral_message(&alice_provider, &alice, b"gone soon", 60)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let ttl_end = message
+            .windows(b"torln ttl".len())
+            .position(|window| window == b"torln ttl")
+            .unwrap()
+            + b"torln ttl".len()
+            + 4;
+        message[ttl_end - 1] = 0xff;
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &message),
+            Err(processing::ProcessError::Process(_))
+        ));
+    }
+
+    #[test]
+    fn processing_stats_count_messages() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let message = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello")
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob.process(&bob_provider, &message).unwrap();
+        // Alice can't decrypt her own message.
+        assert!(chess_club_alice.process(&alice_provider, &message).is_err());
+
+        let proposal = chess_club_bob
+            .propose_custom(&bob_provider, &bob, 0xf000, b"admin".to_vec())
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process(&alice_provider, &proposal)
+            .unwrap();
+        let commit = chess_club_alice
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap()
+            .commit();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob.set_auto_merge(false);
+        chess_club_bob.process(&bob_provider, &commit).unwrap();
+        chess_club_bob.merge_staged(&bob_provider).unwrap();
+
+        // A message of the old epoch can no longer be decrypted.
+        chess_club_bob.process(&bob_provider, &message).unwrap_err();
+
+        let bob_stats = chess_club_bob.processing_stats();
+        assert_eq!(bob_stats.messages_processed(), 2);
+        assert_eq!(bob_stats.commits_merged(), 1);
+        assert_eq!(bob_stats.proposals_queued(), 0);
+        assert_eq!(bob_stats.decryption_failures(), 1);
+
+        let alice_stats = chess_club_alice.processing_stats();
+        assert_eq!(alice_stats.messages_processed(), 1);
+        assert_eq!(alice_stats.proposals_queued(), 1);
+        assert_eq!(alice_stats.commits_merged(), 0);
+        #[cfg(feature = "processing-timing")]
+        assert!(alice_stats.time_ms() >= 0.0);
+
+        chess_club_bob.reset_processing_stats();
+        assert_eq!(
+            chess_club_bob.processing_stats(),
+            ProcessingStats::default()
+        );
+    }
+
+    #[test]
+    fn check_group_reports_missing_entries() {
+        let (alice_provider, _, chess_club_alice, bob_provider, _, chess_club_bob) =
+            create_group_alice_and_bob();
+        assert!(alice_provider.check_group("chess club").healthy());
+        assert!(bob_provider.check_group("chess club").healthy());
+        assert_eq!(alice_provider.check_group("go club").missing().len(), 10);
+
+        use openmls_traits::storage::StorageProvider;
+        alice_provider
+            .0
+            .storage()
+            .delete_group_epoch_secrets(chess_club_alice.mls_group.group_id())
+            .unwrap();
+        let health = alice_provider.check_group("chess club");
+        assert_eq!(health.missing(), ["EpochSecrets"]);
+        assert!(!health.loadable());
+        assert!(!health.healthy());
+
+        bob_provider
+            .0
+            .storage()
+            .delete_encryption_key_pair(
+                chess_club_bob
+                    .mls_group
+                    .own_leaf_node()
+                    .unwrap()
+                    .encryption_key(),
+            )
+            .unwrap();
+        let health = bob_provider.check_group("chess club");
+        assert_eq!(health.missing(), ["EncryptionKeyPair"]);
+        assert!(health.loadable());
+        assert!(health.own_leaf_in_tree());
+        assert!(!health.healthy());
+    }
+
+    #[test]
+    fn add_member_by_key_package_bytes() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+
+        let key_package_bytes = bob
+            .get_key_package(&bob_provider)
+            .to_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert!(matches!(
+            chess_club_alice.add_member_by_bytes_native(&alice_provider, &alice, b"bob"),
+            Err(add_by_bytes::AddByBytesError::Malformed(_))
+        ));
+        let mut tampered = key_package_bytes.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            chess_club_alice.add_member_by_bytes_native(&alice_provider, &alice, &tampered),
+            Err(add_by_bytes::AddByBytesError::InvalidKeyPackage(_))
+        ));
+        assert_eq!(chess_club_alice.mls_group.pending_proposals().count(), 0);
+
+        let (_proposal, _commit, welcome) = chess_club_alice
+            .add_member_by_bytes_native(&alice_provider, &alice, &key_package_bytes)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let chess_club_bob = Group::native_join(
+            &bob_provider,
+            &mls_message_to_u8vec(&welcome),
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_bob.mls_group.members().count(), 2);
+    }
+
+    #[test]
+    fn oversized_messages_rejected_before_parsing() {
+        let (alice_provider, alice, mut chess_club_alice, mut bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        // Not even a valid header: a parser would fail with a malformed
+        // message, the size check fails first.
+        let oversized = vec![0u8; bob_provider.max_message_bytes() + 1];
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &oversized),
+            Err(processing::ProcessError::TooLarge(_))
+        ));
+
+        let msg_out = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello, bob!")
+            .map_err(js_error_to_string)
+            .unwrap();
+        bob_provider.set_max_message_bytes(64);
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &msg_out),
+            Err(processing::ProcessError::TooLarge(
+                message_size::MessageTooLarge { max: 64, .. }
+            ))
+        ));
+
+        // The rejected message left no trace and can be processed under a
+        // higher limit.
+        bob_provider.set_max_message_bytes(msg_out.len());
+        let bob_msg = chess_club_bob
+            .process_message(&mut bob_provider, &msg_out)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(bob_msg, b"hello, bob!");
+    }
+
+    #[test]
+    fn member_leaf_node_of_committed_tree() {
+        let (mut alice_provider, alice, mut chess_club_alice, _, _, chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let bob_leaf = chess_club_bob
+            .mls_group
+            .own_leaf_node()
+            .unwrap()
+            .tls_serialize_detached()
+            .unwrap();
+        assert_eq!(chess_club_alice.leaf_node_bytes(1).unwrap(), bob_leaf);
+        assert!(matches!(
+            chess_club_alice.leaf_node_bytes(2),
+            Err(leaf_node::LeafNodeError::Blank(BlankLeaf(2)))
+        ));
+
+        let alice_leaf = chess_club_alice.leaf_node_bytes(0).unwrap();
+        chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club_alice.leaf_node_bytes(0).unwrap(), alice_leaf);
+
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_ne!(chess_club_alice.leaf_node_bytes(0).unwrap(), alice_leaf);
+    }
+
+    #[test]
+    fn min_decrypt_epoch_refuses_old_messages() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let msg_out = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello, bob!")
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Bob still has the secrets of the epoch, but the policy forbids
+        // reading it.
+        bob_provider
+            .set_min_decrypt_epoch("chess club", 2)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &msg_out),
+            Err(processing::ProcessError::BelowMinEpoch {
+                message_epoch: 1,
+                min_epoch: 2,
+            })
+        ));
+
+        // The floor only applies to its group, and can be lowered again.
+        assert_eq!(
+            bob_provider.min_decrypt_epoch(&GroupId::from_slice(b"go club")),
+            0
+        );
+        bob_provider
+            .set_min_decrypt_epoch("chess club", 1)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &msg_out).unwrap();
+        assert_eq!(processed.application_data().unwrap(), b"hello, bob!");
+
+        // Binary group ids take the bytes variant.
+        let binary_id = [0xff, 0x00, 0xfe];
+        bob_provider
+            .set_min_decrypt_epoch_bytes(&binary_id, 3)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            bob_provider.min_decrypt_epoch(&GroupId::from_slice(&binary_id)),
+            3
+        );
+    }
+
+    #[test]
+    fn ratchet_tree_diff_reports_changed_leaves() {
+        let (mut alice_provider, alice, mut chess_club_alice, _, _, _) =
+            create_group_alice_and_bob();
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let before = chess_club_alice.export_ratchet_tree();
+        assert_eq!(before.diff_leaves(&before).unwrap(), Vec::<u32>::new());
+
+        // Adding without a path changes the new leaf only, and grows the
+        // tree.
+        chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let after = chess_club_alice.export_ratchet_tree();
+
+        assert_eq!(before.diff_leaves(&after).unwrap(), vec![2]);
+        assert_eq!(after.diff_leaves(&before).unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn enrollment_psk_required_to_join() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut config = GroupConfig::new();
+        config.set_enrollment_psk(b"enrollment".to_vec(), vec![7; 32]);
+        let mut chess_club_alice =
+            Group::create_new_with_config(&alice_provider, &alice, "chess club", &config)
+                .map_err(js_error_to_string)
+                .unwrap();
+
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert!(welcome::join_group(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        )
+        .is_err());
+        assert!(Group::join_with_psk(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+            b"enrollment",
+            &[8; 32],
+        )
+        .is_err());
+
+        let chess_club_bob = Group::join_with_psk(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+            b"enrollment",
+            &[7; 32],
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        assert_eq!(
+            chess_club_bob
+                .export_secret(&bob_provider, "test", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            chess_club_alice
+                .export_secret(&alice_provider, "test", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn join_with_info_reports_epoch_and_roster() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let info = welcome::JoinInfo::try_from(
+            welcome::join_group_with_info(
+                &bob_provider,
+                &add_msgs.welcome,
+                chess_club_alice.export_ratchet_tree(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(info.epoch(), 1);
+        assert_eq!(info.own_leaf_index(), 1);
+        let members = info.members();
+        assert_eq!(
+            members
+                .iter()
+                .map(GroupMember::leaf_index)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            members[1].credential(),
+            bob.credential.tls_serialize_detached().unwrap()
+        );
+
+        let chess_club_bob = info.into_group();
+        assert_eq!(chess_club_bob.get_epoch(), 1);
+    }
+
+    #[test]
+    fn rekey_replaces_own_path() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        // Nodes 0 and 2 are the leaves of alice and bob, node 1 is the root
+        // on alice's path.
+        let nodes = |group: &Group| {
+            serde_json::from_value::<Vec<serde_json::Value>>(
+                serde_json::to_value(&group.export_ratchet_tree().0).unwrap(),
+            )
+            .unwrap()
+        };
+        let before = nodes(&chess_club_alice);
+        let secret_before = chess_club_alice
+            .export_secret(&alice_provider, "test", &[], 32)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Bob's Update proposal is committed along with alice's path.
+        let update = chess_club_bob
+            .propose_self_update(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice.process(&alice_provider, &update).unwrap();
+
+        let commit = chess_club_alice
+            .rekey(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob.process(&bob_provider, &commit).unwrap();
+
+        let after = nodes(&chess_club_alice);
+        assert_ne!(after[0], before[0]);
+        assert_ne!(after[1], before[1]);
+        assert_ne!(after[2], before[2]);
+        assert_eq!(nodes(&chess_club_bob), after);
+
+        let secret_after = chess_club_bob
+            .export_secret(&bob_provider, "test", &[], 32)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_ne!(secret_after, secret_before);
+        assert_eq!(
+            secret_after,
+            chess_club_alice
+                .export_secret(&alice_provider, "test", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn rekey_refuses_other_pending_proposals() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let proposal = chess_club_bob
+            .propose_self_remove(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process(&alice_provider, &proposal)
+            .unwrap();
+
+        assert_eq!(
+            chess_club_alice
+                .rekey(&alice_provider, &alice)
+                .map_err(js_error_to_string)
+                .unwrap_err(),
+            rekey::OtherProposalsPending(1).to_string()
+        );
+        assert!(chess_club_alice.mls_group.pending_commit().is_none());
+        assert_eq!(chess_club_alice.pending_proposal_counts().self_remove(), 1);
+    }
+
+    #[test]
+    fn public_only_key_package_stores_nothing() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let stored_before = bob_provider.0.storage().values().read().unwrap().clone();
+        let key_package = bob.public_only_key_package().unwrap();
+        assert_eq!(
+            *bob_provider.0.storage().values().read().unwrap(),
+            stored_before
+        );
+        assert_eq!(
+            key_package.leaf_node().signature_key().as_slice(),
+            bob.keypair.public()
+        );
+
+        // The key package is valid, but the welcome to it can't be joined.
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(&alice_provider, &alice, &KeyPackage(key_package))
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(!Group::can_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+    }
+
+    #[test]
+    fn commit_filtered_proposals_drops_remove() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let custom = chess_club_bob
+            .propose_custom(&bob_provider, &bob, 0xf000, b"bob".to_vec())
+            .map_err(js_error_to_string)
+            .unwrap();
+        let (remove, _) = chess_club_bob
+            .mls_group
+            .propose_remove_member(bob_provider.as_ref(), &bob.keypair, LeafNodeIndex::new(0))
+            .unwrap();
+        chess_club_alice.process(&alice_provider, &custom).unwrap();
+        chess_club_alice
+            .process(&alice_provider, &mls_message_to_u8vec(&remove))
+            .unwrap();
+
+        let mut seen = Vec::new();
+        let commit = chess_club_alice
+            .commit_filtered_proposals(&alice_provider, &alice, |proposal_type, actor| {
+                seen.push((proposal_type, actor));
+                Ok(proposal_type != 3)
+            })
+            .map_err(js_error_to_string)
+            .unwrap();
+        seen.sort();
+        assert_eq!(seen, vec![(3, Some(1)), (0xf000, Some(1))]);
+
+        assert!(commit.welcome().is_none());
+
+        // Only the custom proposal was committed; bob is still a member.
+        let processed = chess_club_bob
+            .process(&bob_provider, &commit.commit())
+            .unwrap();
+        assert_eq!(processed.kind(), processing::MessageKind::Commit);
+        assert_eq!(chess_club_bob.mls_group.members().count(), 2);
+        assert_eq!(chess_club_alice.pending_proposal_counts().total(), 0);
+    }
+
+    #[test]
+    fn commit_filtered_proposals_returns_welcome() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let (add, _) = chess_club_bob
+            .mls_group
+            .propose_add_member(
+                bob_provider.as_ref(),
+                &bob.keypair,
+                &charlie.get_key_package(&charlie_provider).0,
+            )
+            .unwrap();
+        chess_club_alice
+            .process(&alice_provider, &mls_message_to_u8vec(&add))
+            .unwrap();
+
+        let commit = chess_club_alice
+            .commit_filtered_proposals(&alice_provider, &alice, |_, _| Ok(true))
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &commit.welcome().unwrap(),
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_charlie.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn own_signature_key_follows_rotation() {
+        let (mut alice_provider, alice, mut chess_club_alice, _, _, _) =
+            create_group_alice_and_bob();
+
+        let old_public_key = alice.get_public_key_bytes();
+        assert_eq!(
+            chess_club_alice.own_signature_key(),
+            Some(old_public_key.clone())
+        );
+
+        let rotation = chess_club_alice
+            .rotate_signature_key(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // The new key is only in the tree once the commit is merged
+        assert_eq!(chess_club_alice.own_signature_key(), Some(old_public_key));
+
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            chess_club_alice.own_signature_key(),
+            Some(rotation.public_key())
+        );
+    }
+
+    #[test]
+    fn pending_state_survives_reload() {
+        let (alice_provider, alice, mut chess_club_alice, mut bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        // The app persisted the storage before the commit was created
+        let committed_storage = alice_provider.export_storage().unwrap();
+
+        let commit = chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let pending_state = chess_club_alice
+            .export_pending_state(&alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        drop(chess_club_alice);
+
+        let mut reloaded_provider = Provider::create_from_storage(None, &committed_storage)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut reloaded_alice = Group::load_from_storage(&reloaded_provider, "chess club")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(reloaded_alice.mls_group.pending_commit().is_none());
+
+        reloaded_alice
+            .import_pending_state(&reloaded_provider, &pending_state)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(reloaded_alice.mls_group.pending_commit().is_some());
+        reloaded_alice
+            .merge_pending_commit(&mut reloaded_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        chess_club_bob
+            .process_message(&mut bob_provider, &commit)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(reloaded_alice.mls_group.epoch().as_u64(), 2);
+        assert_eq!(
+            reloaded_alice
+                .export_secret(&reloaded_provider, "pending", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            chess_club_bob
+                .export_secret(&bob_provider, "pending", &[], 32)
+                .map_err(js_error_to_string)
+                .unwrap()
+        );
+
+        // The pending state is of the previous epoch now
+        assert!(reloaded_alice
+            .import_pending_state(&reloaded_provider, &pending_state)
+            .is_err());
+    }
+
+    #[test]
+    fn unaccepted_credential_type_refuses_join() {
+        let mut alice_provider = Provider::default();
+        let bob_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Everyone has a basic credential, but bob requires X.509
+        let refused = welcome::join_group_accepting(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+            Some([2].as_slice()),
+        );
+        assert!(matches!(
+            refused.map(|_| ()).map_err(|aborted| aborted.error),
+            Err(welcome::WelcomePreviewError::UnacceptedCredentialType(
+                credential_policy::UnacceptedCredentialType(1)
+            ))
+        ));
+
+        // The key package was kept, so the welcome can still be joined
+        let mut chess_club_bob = Group::join_with_accepted_credential_types(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+            vec![1],
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+
+        chess_club_bob.set_accepted_credential_types(vec![2]);
+        let commit = chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &commit),
+            Err(processing::ProcessError::UnacceptedCredentialType(_))
+        ));
+        assert_eq!(chess_club_bob.get_epoch(), 1);
+    }
+
+    #[test]
+    fn key_package_bundle_round_trip() {
+        let provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let bundle = alice
+            .get_key_package_bundle(&provider, 5)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let key_packages = key_package_bundle::parse_bundle(&bundle).unwrap();
+        assert_eq!(key_packages.len(), 5);
+
+        let mut references = Vec::new();
+        for bundled in &key_packages {
+            let key_package =
+                openmls::key_packages::KeyPackageIn::tls_deserialize_exact(bundled.key_package())
+                    .unwrap()
+                    .validate(
+                        &RustCrypto::default(),
+                        openmls::versions::ProtocolVersion::Mls10,
+                    )
+                    .unwrap();
+            assert_eq!(
+                KeyPackage(key_package)
+                    .reference()
+                    .map_err(js_error_to_string)
+                    .unwrap(),
+                bundled.reference()
+            );
+            references.push(bundled.reference());
+        }
+        references.sort();
+        references.dedup();
+        assert_eq!(references.len(), 5);
+
+        assert!(matches!(
+            key_package_bundle::parse_bundle(&bundle[..bundle.len() - 1]),
+            Err(key_package_bundle::KeyPackageBundleError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn detect_fork_after_concurrent_commits() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+        let group_info_of = |group: &Group, provider: &Provider, identity: &Identity| {
+            mls_message_to_u8vec(
+                &group
+                    .mls_group
+                    .export_group_info(provider.0.crypto(), &identity.keypair, false)
+                    .unwrap(),
+            )
+        };
+        let epoch_1_group_info = group_info_of(&chess_club_alice, &alice_provider, &alice);
+
+        // Both commit in epoch 1 and merge their own commit.
+        chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .commit_empty(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // The delivery service accepted alice's commit.
+        let trusted_group_info = group_info_of(&chess_club_alice, &alice_provider, &alice);
+        assert_eq!(
+            chess_club_alice
+                .fork_status(&alice_provider, &trusted_group_info)
+                .unwrap(),
+            fork::ForkStatus::InSync
+        );
+        assert_eq!(
+            chess_club_bob
+                .fork_status(&bob_provider, &trusted_group_info)
+                .unwrap(),
+            fork::ForkStatus::Forked
+        );
+        assert_eq!(
+            chess_club_bob
+                .fork_status(&bob_provider, &epoch_1_group_info)
+                .unwrap(),
+            fork::ForkStatus::Ahead
+        );
+
+        let other_group = Group::create_new(&alice_provider, &alice, "go club");
+        assert!(matches!(
+            chess_club_bob.fork_status(
+                &bob_provider,
+                &group_info_of(&other_group, &alice_provider, &alice)
+            ),
+            Err(fork::ForkCheckError::OtherGroup(_))
+        ));
+    }
+
+    #[test]
+    fn unsupported_proposal_types_reported() {
+        use openmls::{
+            extensions::{Extension, RequiredCapabilitiesExtension},
+            messages::proposals::{CustomProposal, Proposal, ProposalType},
+            prelude::Capabilities,
+        };
+
+        // A proposal type of a newer version of the app
+        const NEWER_PROPOSAL_TYPE: u16 = 0xf0ff;
+
+        let mut alice_provider = Provider::default();
+        let bob_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+
+        // Both advertise the newer type, e.g. because the capabilities come
+        // from the newer version on another device.
+        let ours = extensions::capabilities();
+        let mut proposal_types = ours.proposals().to_vec();
+        proposal_types.push(ProposalType::Custom(NEWER_PROPOSAL_TYPE));
+        let capabilities = Capabilities::builder()
+            .extensions(ours.extensions().to_vec())
+            .proposals(proposal_types)
+            .build();
+        chess_club_alice
+            .mls_group
+            .self_update(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                LeafNodeParameters::builder()
+                    .with_capabilities(capabilities.clone())
+                    .build(),
+            )
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob_key_package = KeyPackage(
+            OpenMlsKeyPackage::builder()
+                .leaf_node_capabilities(capabilities)
+                .build(
+                    CIPHERSUITE,
+                    &bob_provider.0,
+                    &bob.keypair,
+                    bob.credential_with_key(),
+                )
+                .unwrap()
+                .key_package()
+                .clone(),
+        );
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(&alice_provider, &alice, &bob_key_package)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_bob = Group::native_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+
+        // Not required by the group: processed, and reported
+        let (proposal, _) = chess_club_alice
+            .mls_group
+            .propose_custom_proposal_by_reference(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                CustomProposal::new(NEWER_PROPOSAL_TYPE, vec![]),
+            )
+            .unwrap();
+        let processed = chess_club_bob
+            .process(&bob_provider, &mls_message_to_u8vec(&proposal))
+            .unwrap();
+        assert_eq!(
+            processed.unsupported_proposal_types(),
+            vec![NEWER_PROPOSAL_TYPE]
+        );
+        let (commit, _, _) = chess_club_alice
+            .mls_group
+            .commit_to_pending_proposals(alice_provider.as_ref(), &alice.keypair)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob
+            .process(&bob_provider, &mls_message_to_u8vec(&commit))
+            .unwrap();
+        assert_eq!(
+            processed.unsupported_proposal_types(),
+            vec![NEWER_PROPOSAL_TYPE]
+        );
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+
+        // Required by the group: fatal
+        let mut group_extensions = chess_club_alice.mls_group.extensions().clone();
+        let extension_types = group_extensions
+            .required_capabilities()
+            .map(|required| required.extension_types().to_vec())
+            .unwrap_or_default();
+        group_extensions
+            .add_or_replace(Extension::RequiredCapabilities(
+                RequiredCapabilitiesExtension::new(
+                    &extension_types,
+                    &[ProposalType::Custom(NEWER_PROPOSAL_TYPE)],
+                    &[],
+                ),
+            ))
+            .unwrap();
+        let (commit, _, _) = chess_club_alice
+            .mls_group
+            .update_group_context_extensions(
+                alice_provider.as_ref(),
+                group_extensions,
+                &alice.keypair,
+            )
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .process(&bob_provider, &mls_message_to_u8vec(&commit))
+            .unwrap();
+
+        let bundle = chess_club_alice
+            .mls_group
+            .commit_builder()
+            .add_proposal(Proposal::Custom(Box::new(CustomProposal::new(
+                NEWER_PROPOSAL_TYPE,
+                vec![],
+            ))))
+            .load_psks(alice_provider.0.storage())
+            .unwrap()
+            .build(
+                alice_provider.0.rand(),
+                alice_provider.0.crypto(),
+                &alice.keypair,
+                |_| true,
+            )
+            .unwrap()
+            .stage_commit(&alice_provider.0)
+            .unwrap();
+        let epoch = chess_club_bob.get_epoch();
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &mls_message_to_u8vec(bundle.commit())),
+            Err(processing::ProcessError::UnsupportedProposal(
+                proposals::UnsupportedProposalType(NEWER_PROPOSAL_TYPE)
+            ))
+        ));
+        assert_eq!(chess_club_bob.get_epoch(), epoch);
+    }
+
+    #[test]
+    fn derived_group_ids_are_stable_and_distinct() {
+        let alice_bob = derive_group_id("dm", b"alice\0bob")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(alice_bob.len(), 32);
+        assert_eq!(
+            derive_group_id("dm", b"alice\0bob")
+                .map_err(js_error_to_string)
+                .unwrap(),
+            alice_bob
+        );
+        assert_ne!(
+            derive_group_id("dm", b"alice\0carol")
+                .map_err(js_error_to_string)
+                .unwrap(),
+            alice_bob
+        );
+        // The namespace separates ids, even where the concatenation matches
+        assert_ne!(
+            derive_group_id("d", b"malice\0bob")
+                .map_err(js_error_to_string)
+                .unwrap(),
+            alice_bob
+        );
+
+        let provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let group = Group::create_new_from_bytes(&provider, &alice, &alice_bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(group.mls_group.group_id().as_slice(), alice_bob.as_slice());
+    }
+
+    #[test]
+    fn binary_group_id_round_trip() {
+        let group_id = [0xff, 0xfe, 0x00, 0x80, b'c'];
+        assert!(std::str::from_utf8(&group_id).is_err());
+
+        let provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let group = Group::create_new_from_bytes(&provider, &alice, &group_id)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(group.group_id_bytes(), group_id);
+        // The string form is lossy
+        assert_ne!(group.group_id().as_bytes(), group_id);
+
+        let storage = provider
+            .export_storage()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let reloaded_provider = Provider::create_from_storage(None, &storage)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let reloaded = Group::load_from_storage_bytes(&reloaded_provider, &group_id)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(reloaded.group_id_bytes(), group_id);
+        assert_eq!(reloaded.get_epoch(), group.get_epoch());
+        assert!(Group::load_from_storage(&reloaded_provider, &group.group_id()).is_err());
+    }
+
+    #[test]
+    fn retained_epochs_follow_max_past_epochs() {
+        let mut provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Groups keep no past epochs by default
+        let mut chess_club = Group::create_new(&provider, &alice, "chess club");
+        chess_club.commit_empty(&provider, &alice).unwrap();
+        chess_club.merge_pending_commit(&mut provider).unwrap();
+        assert_eq!(chess_club.retained_epochs(), vec![1]);
+
+        let mut go_club: Group = MlsGroup::builder()
+            .ciphersuite(CIPHERSUITE)
+            .with_capabilities(extensions::capabilities())
+            .max_past_epochs(2)
+            .with_group_id(GroupId::from_slice(b"go club"))
+            .build(&provider.0, &alice.keypair, alice.credential_with_key())
+            .unwrap()
+            .into();
+        assert_eq!(go_club.retained_epochs(), vec![0]);
+        go_club.commit_empty(&provider, &alice).unwrap();
+        go_club.merge_pending_commit(&mut provider).unwrap();
+        assert_eq!(go_club.retained_epochs(), vec![0, 1]);
+        for _ in 0..3 {
+            go_club.commit_empty(&provider, &alice).unwrap();
+            go_club.merge_pending_commit(&mut provider).unwrap();
+        }
+        assert_eq!(go_club.retained_epochs(), vec![2, 3, 4]);
+
+        // The past secrets are stored with the group
+        let reloaded = Group::load_from_storage(&provider, "go club").unwrap();
+        assert_eq!(reloaded.retained_epochs(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn storage_delta_has_only_changed_entries() {
+        let mut provider = Provider::default();
+        let backup = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club = Group::create_new(&provider, &alice, "chess club");
+
+        let delta = provider.storage_delta(&[]).unwrap();
+        assert!(delta.full());
+        backup.import_storage(&delta.entries()).unwrap();
+        let before = storage::decode_entries(&provider.export_storage().unwrap())
+            .unwrap()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        // Nothing changed
+        let delta = provider.storage_delta(&delta.marker()).unwrap();
+        assert!(!delta.full());
+        assert!(storage::decode_entries(&delta.entries())
+            .unwrap()
+            .is_empty());
+        assert!(storage::decode_entries(&delta.removed_keys())
+            .unwrap()
+            .is_empty());
+
+        chess_club.commit_empty(&provider, &alice).unwrap();
+        chess_club.merge_pending_commit(&mut provider).unwrap();
+        let after = storage::decode_entries(&provider.export_storage().unwrap())
+            .unwrap()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        let delta = provider.storage_delta(&delta.marker()).unwrap();
+        assert!(!delta.full());
+        let written = storage::decode_entries(&delta.entries()).unwrap();
+        let removed = storage::decode_entries(&delta.removed_keys()).unwrap();
+        let expected_written = after
+            .iter()
+            .filter(|(key, value)| before.get(*key) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<BTreeMap<_, _>>();
+        let expected_removed = before
+            .keys()
+            .filter(|key| !after.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(!expected_written.is_empty());
+        assert!(written.len() < after.len());
+        // Entries written since are in the delta even if they were written
+        // with the value they had.
+        let written = written.into_iter().collect::<BTreeMap<_, _>>();
+        assert!(expected_written
+            .iter()
+            .all(|(key, value)| written.get(key) == Some(value)));
+        assert!(written
+            .iter()
+            .all(|(key, value)| after.get(key) == Some(value)));
+        let removed = removed.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert!(expected_removed.iter().all(|key| removed.contains(key)));
+        assert!(removed.iter().all(|key| !after.contains_key(key)));
+
+        // Applying the deltas reproduces the storage
+        backup.import_storage(&delta.entries()).unwrap();
+        backup
+            .remove_storage_entries(&delta.removed_keys())
+            .unwrap();
+        assert_eq!(
+            storage::decode_entries(&backup.export_storage().unwrap())
+                .unwrap()
+                .into_iter()
+                .collect::<BTreeMap<_, _>>(),
+            after
+        );
+
+        // Entries written outside of openmls are versioned too
+        provider
+            .set_min_decrypt_epoch_native(&GroupId::from_slice(b"chess club"), 2)
+            .unwrap();
+        let delta = provider.storage_delta(&delta.marker()).unwrap();
+        assert_eq!(
+            storage::decode_entries(&delta.entries()).unwrap(),
+            vec![(
+                epoch_floor::MIN_DECRYPT_EPOCH_STORAGE_LABEL
+                    .iter()
+                    .chain(&10u32.to_be_bytes())
+                    .chain(b"chess club")
+                    .copied()
+                    .collect::<Vec<u8>>(),
+                2u64.to_be_bytes().to_vec()
+            )]
+        );
+
+        // Markers of another provider give a full export
+        let delta = Provider::default().storage_delta(&delta.marker()).unwrap();
+        assert!(delta.full());
+    }
+
+    #[test]
+    fn group_state_equals_detects_divergence() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+        assert!(alice_provider
+            .group_state_equals(&bob_provider, "chess club")
+            .unwrap());
+
+        let commit = chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .unwrap();
+        assert!(!alice_provider
+            .group_state_equals(&bob_provider, "chess club")
+            .unwrap());
+
+        chess_club_bob
+            .process_message(&mut bob_provider, &commit)
+            .unwrap();
+        assert!(alice_provider
+            .group_state_equals(&bob_provider, "chess club")
+            .unwrap());
+
+        // A group only one side has
+        assert!(!alice_provider
+            .group_state_equals(&Provider::default(), "chess club")
+            .unwrap());
+    }
+
+    #[test]
+    fn group_info_ratchet_tree_per_commit() {
+        use openmls::framing::{MlsMessageBodyIn, MlsMessageIn};
+
+        let has_ratchet_tree =
+            |group_info: &[u8]| match MlsMessageIn::tls_deserialize_exact(group_info)
+                .unwrap()
+                .extract()
+            {
+                MlsMessageBodyIn::GroupInfo(group_info) => {
+                    group_info.extensions().ratchet_tree().is_some()
+                }
+                _ => panic!("expected a group info"),
+            };
+
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+
+        let without_tree = chess_club_alice
+            .commit_pending_proposals_with_group_info(&alice_provider, &alice, false)
+            .unwrap();
+        assert!(without_tree.welcome().is_none());
+        assert!(!has_ratchet_tree(&without_tree.group_info()));
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .unwrap();
+        chess_club_bob
+            .process_message(&mut bob_provider, &without_tree.commit())
+            .unwrap();
+
+        let with_tree = chess_club_alice
+            .commit_pending_proposals_with_group_info(&alice_provider, &alice, true)
+            .unwrap();
+        assert!(has_ratchet_tree(&with_tree.group_info()));
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .unwrap();
+        chess_club_bob
+            .process_message(&mut bob_provider, &with_tree.commit())
+            .unwrap();
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn prune_orphaned_keypairs() {
+        let mut provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club = Group::create_new(&provider, &alice, "chess club");
+        // An identity that was replaced, and one that isn't in a group yet
+        let old_alice = Identity::create(&provider, "old alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let new_alice = Identity::create(&provider, "new alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let old_public_key = old_alice.keypair.public().to_vec();
+        let new_public_key = new_alice.keypair.public().to_vec();
+
+        let mut orphaned = vec![old_public_key.clone(), new_public_key.clone()];
+        orphaned.sort();
+        assert_eq!(provider.orphaned_public_keys(&[]).unwrap(), orphaned);
+        assert_eq!(
+            provider
+                .orphaned_public_keys(&[new_public_key.clone()])
+                .unwrap(),
+            vec![old_public_key.clone()]
+        );
+
+        assert_eq!(
+            provider
+                .prune_orphaned_keypairs_native(&[new_public_key.clone()])
+                .unwrap(),
+            1
+        );
+        assert!(provider
+            .orphaned_public_keys(&[new_public_key.clone()])
+            .unwrap()
+            .is_empty());
+        assert!(SignatureKeyPair::read(
+            provider.0.storage(),
+            &old_public_key,
+            SignatureScheme::ED25519
+        )
+        .is_none());
+        assert!(SignatureKeyPair::read(
+            provider.0.storage(),
+            &new_public_key,
+            SignatureScheme::ED25519
+        )
+        .is_some());
+
+        // The key of the group is kept
+        chess_club.commit_empty(&provider, &alice).unwrap();
+        chess_club.merge_pending_commit(&mut provider).unwrap();
+    }
+
+    #[test]
+    fn group_config_builder_options() {
+        let mut provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Without options, the group is configured like one of `createNew`
+        let config = GroupConfigBuilder::new().build_native().unwrap();
+        let chess_club =
+            Group::create_new_with_builder(&provider, &alice, "chess club", &config).unwrap();
+        let policy = chess_club.wire_format_policy();
+        assert_eq!(policy.outgoing(), WireFormat::Ciphertext);
+        assert_eq!(policy.incoming(), WireFormat::Ciphertext);
+        assert_eq!(chess_club.mls_group.configuration().padding_size(), 0);
+        assert!(chess_club.founder_info().is_none());
+
+        let config = GroupConfigBuilder::new()
+            .ciphersuite(u16::from(CIPHERSUITE))
+            .wire_format_policy(WireFormat::Plaintext, WireFormat::Plaintext)
+            .max_past_epochs(2)
+            .padding_size(64)
+            .use_ratchet_tree_extension(true)
+            .required_capabilities(vec![], vec![], vec![1])
+            .record_founder(true)
+            .extension(
+                extensions::MAX_MEMBERS_EXTENSION_TYPE,
+                3u32.to_be_bytes().to_vec(),
+            )
+            .extension(
+                extensions::MAX_MEMBERS_EXTENSION_TYPE,
+                5u32.to_be_bytes().to_vec(),
+            )
+            .build_native()
+            .unwrap();
+        let mut go_club =
+            Group::create_new_with_builder(&provider, &alice, "go club", &config).unwrap();
+        let policy = go_club.wire_format_policy();
+        assert_eq!(policy.outgoing(), WireFormat::Plaintext);
+        assert_eq!(policy.incoming(), WireFormat::Plaintext);
+        assert_eq!(go_club.mls_group.configuration().padding_size(), 64);
+        assert_eq!(go_club.max_members(), Some(5));
+        assert!(go_club.founder_info().is_some());
+        let required = go_club
+            .mls_group
+            .extensions()
+            .required_capabilities()
+            .unwrap();
+        assert_eq!(
+            required.credential_types(),
+            &[openmls::credentials::CredentialType::Basic]
+        );
+        assert!(required
+            .extension_types()
+            .contains(&openmls::extensions::ExtensionType::Unknown(
+                extensions::FOUNDER_INFO_EXTENSION_TYPE
+            )));
+
+        // With the ratchet tree extension, commits come with a group info
+        let (_, _, group_info) = go_club
+            .mls_group
+            .commit_to_pending_proposals(&provider.0, &alice.keypair)
+            .unwrap();
+        assert!(group_info.is_some());
+        go_club.merge_pending_commit(&mut provider).unwrap();
+        for _ in 0..3 {
+            go_club.commit_empty(&provider, &alice).unwrap();
+            go_club.merge_pending_commit(&mut provider).unwrap();
+        }
+        assert_eq!(go_club.retained_epochs(), vec![2, 3, 4]);
+
+        // Options openmls or this client don't support are refused
+        assert_eq!(
+            GroupConfigBuilder::new()
+                .ciphersuite(0x0001)
+                .build_native()
+                .unwrap_err(),
+            group_builder::GroupConfigError::UnsupportedCiphersuite(0x0001)
+        );
+        for (outgoing, incoming) in [
+            (WireFormat::Mixed, WireFormat::Mixed),
+            (WireFormat::Plaintext, WireFormat::Ciphertext),
+        ] {
+            assert_eq!(
+                GroupConfigBuilder::new()
+                    .wire_format_policy(outgoing, incoming)
+                    .build_native()
+                    .unwrap_err(),
+                group_builder::GroupConfigError::InvalidWireFormatPolicy { outgoing, incoming }
+            );
+        }
+    }
+
+    #[test]
+    fn processed_commits_report_path_updates() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            _bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Commits with only adds leave the path out
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let proposal = chess_club_bob
+            .process(&bob_provider, &add_msgs.proposal)
+            .unwrap();
+        assert!(!proposal.has_path_update());
+        let commit = chess_club_bob
+            .process(&bob_provider, &add_msgs.commit)
+            .unwrap();
+        assert_eq!(commit.kind(), MessageKind::Commit);
+        assert!(!commit.has_path_update());
+
+        let commit = chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let commit = chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert!(commit.has_path_update());
+    }
+
+    #[test]
+    fn staged_join_across_two_calls() {
+        let mut alice_provider = Provider::default();
+        let bob_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Staging writes nothing, so dropping the handle cancels the join
+        let storage_before = bob_provider.0.storage().values().read().unwrap().clone();
+        let staged = staged_join::stage_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        )
+        .unwrap();
+        assert_eq!(staged.epoch(), 1);
+        assert_eq!(staged.member_count(), 2);
+        drop(staged);
+        assert_eq!(
+            *bob_provider.0.storage().values().read().unwrap(),
+            storage_before
+        );
+
+        let staged = staged_join::stage_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        )
+        .unwrap();
+        let staged_again = staged_join::stage_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        )
+        .unwrap();
+        let mut chess_club_bob = staged_join::finish_join(&bob_provider, staged).unwrap();
+        assert_eq!(chess_club_bob.get_epoch(), 1);
+
+        let msg = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello, bob")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &msg).unwrap();
+        assert_eq!(processed.application_data(), Some(b"hello, bob".to_vec()));
+
+        // The key package is used up now
+        let storage_before = bob_provider.0.storage().values().read().unwrap().clone();
+        assert!(staged_join::finish_join(&bob_provider, staged_again).is_err());
+        assert_eq!(
+            *bob_provider.0.storage().values().read().unwrap(),
+            storage_before
+        );
+    }
+
+    #[test]
+    fn identity_bytes_of_identities_and_members() {
+        let (_alice_provider, alice, chess_club_alice, _bob_provider, bob, chess_club_bob) =
+            create_group_alice_and_bob();
+
+        assert_eq!(alice.identity_bytes().unwrap(), b"alice");
+        assert_eq!(bob.identity_bytes().unwrap(), b"bob");
+        for group in [&chess_club_alice, &chess_club_bob] {
+            assert_eq!(group.member_identity_native(0).unwrap(), b"alice");
+            assert_eq!(group.member_identity_native(1).unwrap(), b"bob");
+            assert!(matches!(
+                group.member_identity_native(2),
+                Err(identity_bytes::MemberIdentityError::BlankLeaf(BlankLeaf(2)))
+            ));
+        }
+    }
+
+    #[test]
+    fn reserialize_between_wire_formats() {
+        let mut provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let config = GroupConfigBuilder::new()
+            .wire_format_policy(WireFormat::Plaintext, WireFormat::Plaintext)
+            .build_native()
+            .unwrap();
+        let mut chess_club =
+            Group::create_new_with_builder(&provider, &alice, "chess club", &config).unwrap();
+
+        // Messages already in the target format are re-encoded as is
+        let commit = chess_club
+            .commit_empty(&provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club
+            .merge_pending_commit(&mut provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            reserialize::reserialize(&commit, WireFormat::Plaintext).unwrap(),
+            commit
+        );
+        let message = chess_club
+            .create_message(&provider, &alice, b"hello")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            reserialize::reserialize(&message, WireFormat::Ciphertext).unwrap(),
+            message
+        );
+
+        // Converting needs the sender
+        assert_eq!(
+            reserialize::reserialize(&commit, WireFormat::Ciphertext),
+            Err(reserialize::ReserializeError::NotConvertible {
+                from: WireFormat::Plaintext,
+                to: WireFormat::Ciphertext,
+            })
+        );
+        assert_eq!(
+            reserialize::reserialize(&message, WireFormat::Plaintext),
+            Err(reserialize::ReserializeError::NotConvertible {
+                from: WireFormat::Ciphertext,
+                to: WireFormat::Plaintext,
+            })
+        );
+        assert_eq!(
+            reserialize::reserialize(&message, WireFormat::Mixed),
+            Err(reserialize::ReserializeError::InvalidTarget)
+        );
+
+        let key_package =
+            mls_message_to_u8vec(&MlsMessageOut::from(alice.get_key_package(&provider).0));
+        assert_eq!(
+            reserialize::reserialize(&key_package, WireFormat::Plaintext),
+            Err(reserialize::ReserializeError::NotFramed("key package"))
+        );
+        let mut trailing = message.clone();
+        trailing.push(0);
+        assert!(matches!(
+            reserialize::reserialize(&trailing, WireFormat::Ciphertext),
+            Err(reserialize::ReserializeError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn validate_commit_chain_finds_gaps_and_forks() {
+        let mut provider = Provider::default();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club = Group::create_new(&provider, &alice, "chess club");
+        let follower = Group::load_from_storage(&provider, "chess club")
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut commits = Vec::new();
+        for _ in 0..3 {
+            commits.push(
+                chess_club
+                    .commit_empty(&provider, &alice)
+                    .map_err(js_error_to_string)
+                    .unwrap(),
+            );
+            chess_club
+                .merge_pending_commit(&mut provider)
+                .map_err(js_error_to_string)
+                .unwrap();
+        }
+
+        let check = follower.check_commit_chain(&commits);
+        assert!(check.contiguous());
+        assert_eq!(check.first_inconsistency(), None);
+        assert!(follower.check_commit_chain(&[]).contiguous());
+
+        // The commit for epoch 1 is missing
+        let gap = [commits[0].clone(), commits[2].clone()];
+        let check = follower.check_commit_chain(&gap);
+        assert!(!check.contiguous());
+        assert_eq!(check.first_inconsistency(), Some(1));
+        assert_eq!(
+            check.reason().unwrap(),
+            "missing commits: expected a commit for epoch 1, found epoch 2"
+        );
+
+        // Two commits for epoch 0
+        let fork = [commits[0].clone(), commits[0].clone()];
+        let check = follower.check_commit_chain(&fork);
+        assert_eq!(check.first_inconsistency(), Some(1));
+        assert_eq!(check.reason().unwrap(), "fork: another commit for epoch 0");
+
+        // Anything but commits of this group breaks the chain
+        let message = chess_club
+            .create_message(&provider, &alice, b"hello")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let check = follower.check_commit_chain(&[message]);
+        assert_eq!(check.reason().unwrap(), "message is not a commit");
+    }
+
+    #[test]
+    fn public_state_has_no_secrets() {
+        let (alice_provider, alice, chess_club_alice, _bob_provider, bob, chess_club_bob) =
+            create_group_alice_and_bob();
+
+        let state = chess_club_alice.public_state().unwrap();
+        assert_eq!(state.group_id, b"chess club");
+        assert_eq!(state.epoch, 1);
+        assert_eq!(state.tree_hash, chess_club_alice.mls_group.tree_hash());
+        let mut member_keys = chess_club_alice
+            .members()
+            .map_err(js_error_to_string)
+            .unwrap()
+            .iter()
+            .map(GroupMember::signature_key)
+            .collect::<Vec<_>>();
+        member_keys.sort();
+        assert_eq!(state.signature_keys, member_keys);
+        assert_eq!(
+            Extensions::<GroupContext>::tls_deserialize_exact(&state.extensions).unwrap(),
+            *chess_club_alice.mls_group.extensions()
+        );
+
+        // Every member exports the same state, and it round-trips
+        assert_eq!(chess_club_bob.public_state().unwrap(), state);
+        let bytes = state.to_bytes().map_err(js_error_to_string).unwrap();
+        assert_eq!(
+            GroupPublicState::from_bytes(&bytes)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            state
+        );
+        let roster = chess_club_bob.sign_roster_native(&bob).unwrap();
+        assert!(state.matches_roster(&roster.attestation()));
+
+        // Neither secrets of the group nor private keys are in it
+        let exporter_secret = chess_club_alice
+            .export_secret(&alice_provider, "public state", &[], 32)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let keypair = alice
+            .export_keypair_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let private_key = tls_codec::VLBytes::tls_deserialize(&mut keypair.as_slice()).unwrap();
+        for secret in [exporter_secret.as_slice(), private_key.as_slice()] {
+            assert!(!bytes.windows(secret.len()).any(|window| window == secret));
+        }
+    }
+
+    #[test]
+    fn key_packages_with_long_lifetimes_are_refused() {
+        let mut alice_provider = Provider::default();
+        let bob_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club = Group::create_new(&alice_provider, &alice, "chess club");
+
+        // Our key packages are valid for 12 weeks and an hour
+        alice_provider.set_key_package_max_lifetime(Some(7 * 24 * 60 * 60));
+        let key_package = bob.get_key_package(&bob_provider);
+        let error = chess_club
+            .native_propose_and_commit_add(&alice_provider, &alice, &key_package)
+            .map_err(js_error_to_string)
+            .unwrap_err();
+        assert!(error.contains("exceeds the limit of 604800 seconds"));
+        let bytes = key_package.to_bytes().map_err(js_error_to_string).unwrap();
+        assert!(matches!(
+            chess_club.add_member_by_bytes_native(&alice_provider, &alice, &bytes),
+            Err(add_by_bytes::AddByBytesError::LifetimeTooLong(
+                key_package_lifetime::KeyPackageLifetimeTooLong { max: 604800, .. }
+            ))
+        ));
+        assert_eq!(chess_club.get_epoch(), 0);
+        assert_eq!(chess_club.mls_group.pending_proposals().count(), 0);
+
+        alice_provider.set_key_package_max_lifetime(Some(365 * 24 * 60 * 60));
+        chess_club
+            .add_member_by_bytes_native(&alice_provider, &alice, &bytes)
+            .unwrap();
+        chess_club
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(chess_club.mls_group.members().count(), 2);
+    }
+
+    #[test]
+    fn pending_adds_with_long_lifetimes_are_refused() {
+        let mut alice_provider = Provider::default();
+        let bob_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club = Group::create_new(&alice_provider, &alice, "chess club");
+
+        // Queued like a join proposal of an external sender
+        chess_club
+            .mls_group
+            .propose_add_member(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &bob.get_key_package(&bob_provider).0,
+            )
+            .unwrap();
+        alice_provider.set_key_package_max_lifetime(Some(7 * 24 * 60 * 60));
+
+        let error = chess_club
+            .commit_pending_proposals(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .err()
+            .unwrap();
+        assert!(error.contains("exceeds the limit of 604800 seconds"));
+        let error = chess_club
+            .commit_pending_proposals_with_group_info(&alice_provider, &alice, false)
+            .map_err(js_error_to_string)
+            .err()
+            .unwrap();
+        assert!(error.contains("exceeds the limit of 604800 seconds"));
+        let error = chess_club
+            .commit_filtered_proposals(&alice_provider, &alice, |_, _| Ok(true))
+            .map_err(js_error_to_string)
+            .err()
+            .unwrap();
+        assert!(error.contains("exceeds the limit of 604800 seconds"));
+        assert!(chess_club.mls_group.pending_commit().is_none());
+        assert_eq!(chess_club.mls_group.pending_proposals().count(), 1);
+    }
+
+    #[test]
+    fn external_join_proposal_admits_requester() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            bob_provider,
+            _bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let group_info = chess_club_alice
+            .mls_group
+            .export_group_info(alice_provider.0.crypto(), &alice.keypair, false)
+            .unwrap();
+        let join_request = charlie
+            .external_join_proposal(&charlie_provider, &mls_message_to_u8vec(&group_info))
+            .unwrap();
+
+        // Members queue the request like any proposal
+        for (group, provider) in [
+            (&mut chess_club_alice, &alice_provider),
+            (&mut chess_club_bob, &bob_provider),
+        ] {
+            let processed = group.process(provider, &join_request).unwrap();
+            assert_eq!(processed.kind(), MessageKind::ExternalJoinProposal);
+            assert!(processed.proposal_ref().is_some());
+            assert_eq!(group.mls_group.pending_proposals().count(), 1);
+        }
+
+        let commit = chess_club_alice
+            .commit_pending_proposals_with_group_info(&alice_provider, &alice, false)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .process(&bob_provider, &commit.commit())
+            .unwrap();
+        let mut chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &commit.welcome().unwrap(),
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_charlie.mls_group.members().count(), 3);
+        assert_eq!(chess_club_bob.mls_group.members().count(), 3);
+
+        let message = chess_club_alice
+            .create_message(&alice_provider, &alice, b"welcome, charlie")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_charlie
+            .process(&charlie_provider, &message)
+            .unwrap();
+        assert_eq!(
+            processed.application_data(),
+            Some(b"welcome, charlie".to_vec())
+        );
+    }
+
+    #[test]
+    fn abandoned_self_updates_leave_no_keypairs() {
+        let (
+            alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            bob,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+        let stored = |provider: &Provider, encryption_key: &openmls::treesync::EncryptionKey| {
+            let key = openmls_torln_storage::TorlnStorage::encryption_key_pair_key(encryption_key)
+                .unwrap();
+            provider
+                .0
+                .storage()
+                .values()
+                .read()
+                .unwrap()
+                .contains_key(&key)
+        };
+
+        // Abandoned explicitly
+        chess_club_alice
+            .propose_self_update(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let key_pairs = chess_club_alice
+            .update_encryption_keys(&alice_provider)
+            .unwrap();
+        assert_eq!(key_pairs.len(), 1);
+        assert!(stored(&alice_provider, &key_pairs[0]));
+        chess_club_alice
+            .abandon_self_update(&alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(!stored(&alice_provider, &key_pairs[0]));
+        assert_eq!(chess_club_alice.mls_group.pending_proposals().count(), 0);
+        assert!(chess_club_alice
+            .update_encryption_keys(&alice_provider)
+            .unwrap()
+            .is_empty());
+
+        // Cleared with the other pending proposals
+        chess_club_alice
+            .propose_self_update(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let key_pairs = chess_club_alice
+            .update_encryption_keys(&alice_provider)
+            .unwrap();
+        chess_club_alice
+            .clear_pending_proposals(&alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(!stored(&alice_provider, &key_pairs[0]));
+
+        // Superseded by a commit of another member
+        chess_club_alice
+            .propose_self_update(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let key_pairs = chess_club_alice
+            .update_encryption_keys(&alice_provider)
+            .unwrap();
+        let commit = chess_club_bob
+            .commit_empty(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice.process(&alice_provider, &commit).unwrap();
+        assert!(!stored(&alice_provider, &key_pairs[0]));
+        assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 2);
+
+        // A self-update commit of our own goes with its keys
+        chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .abandon_self_update(&alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(chess_club_alice.mls_group.pending_commit().is_none());
+
+        let message = chess_club_alice
+            .create_message(&alice_provider, &alice, b"still here")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &message).unwrap();
+        assert_eq!(processed.application_data(), Some(b"still here".to_vec()));
+    }
+
+    #[test]
+    fn combined_add_messages_round_trip() {
+        let (mut alice_provider, alice, mut chess_club_alice, _, _, _) =
+            create_group_alice_and_bob();
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let combined = combined_messages::CombinedAddMessages {
+            proposal: add_msgs.proposal.clone(),
+            commit: add_msgs.commit.clone(),
+            welcome: add_msgs.welcome.clone(),
+        };
+        let bytes = combined.tls_serialize_detached().unwrap();
+
+        let split = combined_messages::CombinedAddMessages::tls_deserialize_exact(&bytes).unwrap();
+        assert_eq!(split, combined);
+        assert!(
+            combined_messages::CombinedAddMessages::tls_deserialize_exact(
+                &bytes[..bytes.len() - 1]
+            )
+            .is_err()
+        );
+
+        let chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &split.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_charlie.mls_group.members().count(), 3);
+    }
+
+    #[test]
+    fn removing_a_middle_member_keeps_leaf_indices() {
+        let (mut alice_provider, alice, mut chess_club_alice, _, _, chess_club_bob) =
+            create_group_alice_and_bob();
+        let charlie_provider = Provider::create(None).unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &charlie.get_key_package(&charlie_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_charlie = Group::native_join(
+            &charlie_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+        assert_eq!(chess_club_charlie.mls_group.own_leaf_index().u32(), 2);
+
+        // Alice removes Bob, in the middle of the tree
+        let (commit, _, _) = chess_club_alice
+            .mls_group
+            .remove_members(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &[chess_club_bob.mls_group.own_leaf_index()],
+            )
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_charlie
+            .process(&charlie_provider, &mls_message_to_u8vec(&commit))
+            .unwrap();
+
+        assert!(processed.leaf_index_changes().is_empty());
+        assert_eq!(chess_club_charlie.mls_group.own_leaf_index().u32(), 2);
+        let leaf_indices = chess_club_charlie
+            .mls_group
+            .members()
+            .map(|member| member.index.u32())
+            .collect::<Vec<_>>();
+        assert_eq!(leaf_indices, vec![0, 2]);
+    }
+
+    #[cfg(feature = "debug-tools")]
+    #[test]
+    fn debug_ratchet_state_follows_generations() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        let alice_index = chess_club_alice.mls_group.own_leaf_index().u32();
+        let ratchet_of = |group: &Group| {
+            group
+                .ratchet_state()
+                .into_iter()
+                .find(|state| state.leaf_index() == alice_index)
+                .unwrap()
+        };
+        assert_eq!(ratchet_of(&chess_club_bob).generation(), 0);
+
+        let messages = (0..3)
+            .map(|_| {
+                chess_club_alice
+                    .create_message(&alice_provider, &alice, b"move")
+                    .map_err(js_error_to_string)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let alice_ratchet = ratchet_of(&chess_club_alice);
+        assert!(alice_ratchet.own());
+        assert_eq!(alice_ratchet.generation(), 3);
+
+        chess_club_bob.process(&bob_provider, &messages[0]).unwrap();
+        let bob_ratchet = ratchet_of(&chess_club_bob);
+        assert!(!bob_ratchet.own());
+        assert_eq!(bob_ratchet.generation(), 1);
+        assert_eq!(bob_ratchet.retained_keys(), 0);
+
+        // Skipping a message keeps its key for later
+        chess_club_bob.process(&bob_provider, &messages[2]).unwrap();
+        let bob_ratchet = ratchet_of(&chess_club_bob);
+        assert_eq!(bob_ratchet.generation(), 3);
+        assert_eq!(bob_ratchet.retained_keys(), 1);
+
+        chess_club_bob.process(&bob_provider, &messages[1]).unwrap();
+        assert_eq!(ratchet_of(&chess_club_bob).retained_keys(), 0);
+        assert_eq!(chess_club_bob.ratchet_state().len(), 2);
+    }
+
+    #[test]
+    fn batch_add_admits_many_members() {
+        let alice_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut batch = Group::start_batch_add("tournament");
+
+        let members = (0..30)
+            .map(|i| {
+                let provider = Provider::create(None).unwrap();
+                let identity = Identity::create(&provider, &format!("player {i}"), None)
+                    .map_err(js_error_to_string)
+                    .unwrap();
+                let key_package = identity
+                    .get_key_package(&provider)
+                    .to_bytes()
+                    .map_err(js_error_to_string)
+                    .unwrap();
+                batch
+                    .push_key_package(&alice_provider, &key_package)
+                    .unwrap();
+                provider
+            })
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            batch.push_key_package(&alice_provider, b"not a key package"),
+            Err(batch_add::BatchAddError::KeyPackage { index: 30, .. })
+        ));
+        assert_eq!(batch.count(), 30);
+
+        let created = batch.commit_native(&alice_provider, &alice).unwrap();
+        let welcome = created.welcome();
+        let chess_club_alice = created.into_group();
+        assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 1);
+        assert_eq!(chess_club_alice.mls_group.members().count(), 31);
+
+        for provider in &members {
+            let group =
+                Group::native_join(provider, &welcome, chess_club_alice.export_ratchet_tree());
+            assert_eq!(group.mls_group.epoch().as_u64(), 1);
+            assert_eq!(group.mls_group.members().count(), 31);
+        }
+
+        let empty = Group::start_batch_add("empty");
+        assert!(matches!(
+            empty.commit_native(&alice_provider, &alice),
+            Err(transaction::Aborted {
+                error: batch_add::BatchAddError::Empty,
+                rolled_back: false,
+            })
+        ));
+    }
+
+    #[test]
+    fn failed_batch_add_leaves_no_group() {
+        let alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Two key packages of the same member are each valid, but adding
+        // both fails in the commit.
+        let mut batch = Group::start_batch_add("tournament");
+        for _ in 0..2 {
+            let key_package = bob
+                .get_key_package(&bob_provider)
+                .to_bytes()
+                .map_err(js_error_to_string)
+                .unwrap();
+            batch
+                .push_key_package(&alice_provider, &key_package)
+                .unwrap();
+        }
+        let storage_before = alice_provider.0.storage().values().read().unwrap().clone();
+        let aborted = batch.commit_native(&alice_provider, &alice).err().unwrap();
+        assert!(aborted.rolled_back);
+
+        assert_eq!(
+            *alice_provider.0.storage().values().read().unwrap(),
+            storage_before
+        );
+        assert!(Group::load_from_storage(&alice_provider, "tournament").is_err());
+    }
+
+    #[test]
+    fn verify_commit_confirmation_tag_without_merging() {
+        let (alice_provider, _, mut chess_club_alice, mut bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        let commit = chess_club_bob
+            .commit_empty(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Checking twice shows the first check left the group as it was
+        for _ in 0..2 {
+            assert!(chess_club_alice
+                .check_confirmation_tag(&alice_provider, &commit)
+                .unwrap());
+            assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 1);
+        }
+
+        let mut tampered = commit.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(!chess_club_alice
+            .check_confirmation_tag(&alice_provider, &tampered)
+            .unwrap());
+
+        chess_club_alice.process(&alice_provider, &commit).unwrap();
+        assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 2);
+        assert!(matches!(
+            chess_club_alice.check_confirmation_tag(&alice_provider, &commit),
+            Err(confirmation_tag::ConfirmationCheckError::WrongEpoch {
+                message_epoch: 1,
+                current_epoch: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn external_senders_are_readable_by_members() {
+        let mut alice_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob_provider = Provider::default();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let server = Identity::create(&Provider::default(), "relay", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let server_credential = server.credential.tls_serialize_detached().unwrap();
+
+        assert_eq!(
+            GroupConfigBuilder::new()
+                .external_sender(server.get_public_key_bytes(), b"not a credential".to_vec())
+                .build_native()
+                .unwrap_err(),
+            group_builder::GroupConfigError::InvalidExternalSender(0)
+        );
+
+        let config = GroupConfigBuilder::new()
+            .external_sender(server.get_public_key_bytes(), server_credential.clone())
+            .build_native()
+            .unwrap();
+        let mut chess_club_alice =
+            Group::create_new_with_builder(&alice_provider, &alice, "chess club", &config).unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let chess_club_bob = Group::native_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+
+        let external_senders = chess_club_bob.external_senders_native().unwrap();
+        assert_eq!(external_senders.len(), 1);
+        assert_eq!(
+            external_senders[0].signature_key(),
+            server.get_public_key_bytes()
+        );
+        assert_eq!(external_senders[0].credential(), server_credential);
+        assert_eq!(
+            chess_club_alice.external_senders_native().unwrap(),
+            external_senders
+        );
+
+        let go_club = Group::create_new(&alice_provider, &alice, "go club");
+        assert!(go_club.external_senders_native().unwrap().is_empty());
+    }
+
+    #[test]
+    fn process_message_dedup_skips_repeated_commit() {
+        let (alice_provider, _, mut chess_club_alice, mut bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        let commit = chess_club_bob
+            .commit_empty(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let first = chess_club_alice
+            .process_dedup(&alice_provider, &commit)
+            .unwrap();
+        assert!(!first.duplicate());
+        assert!(first.processed().is_some());
+        assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 2);
+
+        let second = chess_club_alice
+            .process_dedup(&alice_provider, &commit)
+            .unwrap();
+        assert!(second.duplicate());
+        assert!(second.processed().is_none());
+        assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 2);
+        assert_eq!(chess_club_alice.processing_stats().commits_merged(), 1);
+
+        // Without a window, the commit is processed again and fails
+        chess_club_alice.set_dedup_window(0);
+        assert!(matches!(
+            chess_club_alice.process_dedup(&alice_provider, &commit),
+            Err(dedup::DedupError::Process(_))
+        ));
+    }
+
+    #[test]
+    fn join_reports_consumed_key_package_ref() {
+        let mut alice_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob_provider = Provider::default();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let key_packages = (0..3)
+            .map(|_| bob.get_key_package(&bob_provider))
+            .collect::<Vec<_>>();
+        let references = key_packages
+            .iter()
+            .map(|key_package| key_package.0.hash_ref(bob_provider.0.crypto()).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut chess_club_alice = Group::create_new(&alice_provider, &alice, "chess club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(&alice_provider, &alice, &key_packages[1])
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let info = welcome::JoinInfo::try_from(
+            welcome::join_group_with_info(
+                &bob_provider,
+                &add_msgs.welcome,
+                chess_club_alice.export_ratchet_tree(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(info.consumed_key_package_ref(), references[1].as_slice());
+
+        // The other key packages are left for the app to prune
+        use openmls_traits::storage::StorageProvider;
+        let storage = bob_provider.0.storage();
+        let stored = |reference| {
+            storage
+                .key_package::<_, openmls::key_packages::KeyPackageBundle>(reference)
+                .unwrap()
+                .is_some()
+        };
+        assert!(stored(&references[0]));
+        assert!(!stored(&references[1]));
+        assert!(stored(&references[2]));
+    }
+
+    #[test]
+    fn echoed_own_messages_are_recognized() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        let message = chess_club_alice
+            .create_message(&alice_provider, &alice, b"hello")
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Without the setting, the echo fails like any undecryptable message
+        assert!(chess_club_alice.process(&alice_provider, &message).is_err());
+
+        chess_club_alice.set_ignore_own_messages(true);
+        let echoed = chess_club_alice.process(&alice_provider, &message).unwrap();
+        assert!(echoed.own_message());
+        assert_eq!(echoed.kind(), MessageKind::Application);
+        assert_eq!(echoed.epoch(), 1);
+        assert_eq!(echoed.sender_leaf_index(), Some(0));
+        assert_eq!(echoed.application_data(), None);
+
+        // An echoed own commit leaves it pending
+        let commit = chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let echoed = chess_club_alice.process(&alice_provider, &commit).unwrap();
+        assert!(echoed.own_message());
+        assert_eq!(echoed.kind(), MessageKind::Commit);
+        assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 1);
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let processed = chess_club_bob.process(&bob_provider, &message).unwrap();
+        assert!(!processed.own_message());
+        chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert_eq!(chess_club_bob.mls_group.epoch().as_u64(), 2);
+    }
+
+    #[test]
+    fn migrated_group_sends_and_receives() {
+        let (alice_provider, alice, _, mut bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        let mut destination = Provider::default();
+        let group_id = GroupId::from_slice(b"chess club");
+
+        let mut chess_club_alice =
+            Group::migrate_native(&alice_provider, &destination, &group_id, true).unwrap();
+        assert!(MlsGroup::load(alice_provider.0.storage(), &group_id)
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            Group::migrate_native(&alice_provider, &destination, &group_id, false),
+            Err(migrate::MigrateError::GroupNotFound)
+        ));
+
+        let message = chess_club_alice
+            .create_message(&destination, &alice, b"moved")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &message).unwrap();
+        assert_eq!(processed.application_data(), Some(b"moved".to_vec()));
+
+        let commit = chess_club_bob
+            .commit_empty(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .merge_pending_commit(&mut bob_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice.process(&destination, &commit).unwrap();
+
+        // A path update of our own uses the migrated keys
+        let commit = chess_club_alice
+            .commit_empty(&destination, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut destination)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob.process(&bob_provider, &commit).unwrap();
+        assert_eq!(chess_club_alice.mls_group.epoch().as_u64(), 3);
+        assert_eq!(chess_club_bob.mls_group.epoch().as_u64(), 3);
+    }
+
+    #[test]
+    fn migrated_enrollment_group_adds_members() {
+        let alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut config = GroupConfig::new();
+        config.set_enrollment_psk(b"enrollment".to_vec(), vec![7; 32]);
+        Group::create_new_with_config(&alice_provider, &alice, "chess club", &config)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // The enrollment PSK moves with the group.
+        let mut destination = Provider::default();
+        let mut chess_club_alice = Group::migrate_native(
+            &alice_provider,
+            &destination,
+            &GroupId::from_slice(b"chess club"),
+            true,
+        )
+        .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &destination,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut destination)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let chess_club_bob = Group::join_with_psk(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+            b"enrollment",
+            &[7; 32],
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        assert_eq!(chess_club_bob.get_epoch(), chess_club_alice.get_epoch());
+    }
+
+    #[test]
+    fn cleared_rotation_keeps_signing_with_old_key() {
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, _, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        let old_public_key = alice.get_public_key_bytes();
+
+        let rotation = chess_club_alice
+            .rotate_signature_key(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .clear_pending_commit_native(&alice_provider)
+            .unwrap();
+
+        assert_eq!(alice.get_public_key_bytes(), old_public_key);
+        assert!(SignatureKeyPair::read(
+            alice_provider.0.storage(),
+            &rotation.public_key(),
+            SignatureScheme::ED25519
+        )
+        .is_none());
+
+        let message = chess_club_alice
+            .create_message(&alice_provider, &alice, b"still the old key")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let processed = chess_club_bob.process(&bob_provider, &message).unwrap();
+        assert_eq!(
+            processed.application_data(),
+            Some(b"still the old key".to_vec())
+        );
+    }
+
+    #[test]
+    fn only_self_remove_is_accepted_as_public_handshake() {
+        let (mut alice_provider, _, mut chess_club_alice, bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+
+        // A public Remove proposal is refused by the pure-ciphertext policy
+        let plaintext_config = openmls::group::MlsGroupJoinConfig::builder()
+            .wire_format_policy(openmls::group::MIXED_PLAINTEXT_WIRE_FORMAT_POLICY)
+            .build();
+        chess_club_bob
+            .mls_group
+            .set_configuration(bob_provider.0.storage(), &plaintext_config)
+            .unwrap();
+        let remove = chess_club_bob
+            .leave_group(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(remove[2..4], [0, 1]);
+        assert!(matches!(
+            chess_club_alice.process(&alice_provider, &remove),
+            Err(processing::ProcessError::Process(
+                openmls::group::ProcessMessageError::IncompatibleWireFormat
+            ))
+        ));
+
+        // A public SelfRemove proposal is accepted, and the policy is back
+        // to pure-ciphertext afterwards
+        let self_remove = chess_club_bob
+            .propose_self_remove(&bob_provider, &bob)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .process_message(&mut alice_provider, &self_remove)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let policy = chess_club_alice.wire_format_policy();
+        assert_eq!(policy.outgoing(), WireFormat::Ciphertext);
+        assert_eq!(policy.incoming(), WireFormat::Ciphertext);
+        assert!(chess_club_alice.process(&alice_provider, &remove).is_err());
+    }
+
+    #[test]
+    fn branch_psk_deleted_after_branching() {
+        use openmls::{
+            prelude::{PreSharedKeyProposal, Proposal},
+            schedule::{ExternalPsk, PreSharedKeyId, Psk},
+        };
+
+        // Whether a commit in `group` finds the external PSK `psk_id`.
+        fn psk_stored(provider: &Provider, group: &mut Group, psk_id: &[u8]) -> bool {
+            let psk = Psk::External(ExternalPsk::new(psk_id.to_vec()));
+            let psk_id = PreSharedKeyId::new(CIPHERSUITE, provider.0.rand(), psk).unwrap();
+            group
+                .mls_group
+                .commit_builder()
+                .add_proposal(Proposal::PreSharedKey(Box::new(PreSharedKeyProposal::new(
+                    psk_id,
+                ))))
+                .load_psks(provider.0.storage())
+                .is_ok()
+        }
+
+        let (alice_provider, alice, mut chess_club_alice, bob_provider, bob, mut chess_club_bob) =
+            create_group_alice_and_bob();
+        let psk_id = branch::branch_psk_id(&chess_club_alice.mls_group);
+
+        let subgroup = chess_club_alice
+            .branch_subgroup(
+                &alice_provider,
+                &alice,
+                "chess club breakout",
+                vec![0, 1],
+                vec![bob.get_key_package(&bob_provider)],
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert!(!psk_stored(&alice_provider, &mut chess_club_alice, &psk_id));
+
+        Group::join_branch(
+            &bob_provider,
+            &chess_club_bob,
+            &subgroup.welcome(),
+            subgroup.export_ratchet_tree(),
+        )
+        .map_err(js_error_to_string)
+        .unwrap();
+        assert!(!psk_stored(&bob_provider, &mut chess_club_bob, &psk_id));
+
+        // The check does find the PSK while it's stored
+        enrollment::store_enrollment_psk(&bob_provider, &psk_id, &[7; 32]).unwrap();
+        assert!(psk_stored(&bob_provider, &mut chess_club_bob, &psk_id));
+    }
+
+    #[test]
+    fn can_join_checks_tree_and_capabilities() {
+        use crate::welcome::{check_joinable, WelcomePreviewError};
+
+        let mut alice_provider = Provider::default();
+        let bob_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let config = GroupConfigBuilder::new()
+            .record_founder(true)
+            .build_native()
+            .unwrap();
+        let mut chess_club_alice =
+            Group::create_new_with_builder(&alice_provider, &alice, "chess club", &config).unwrap();
+        let go_club_alice = Group::create_new(&alice_provider, &alice, "go club");
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Our key package has what the group requires
+        assert!(chess_club_alice
+            .mls_group
+            .extensions()
+            .required_capabilities()
+            .is_some());
+        assert!(check_joinable(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree()
+        )
+        .is_ok());
+
+        // The tree of another group is caught before staging
+        assert!(matches!(
+            check_joinable(
+                &bob_provider,
+                &add_msgs.welcome,
+                go_club_alice.export_ratchet_tree()
+            ),
+            Err(WelcomePreviewError::TreeMismatch)
+        ));
+        assert!(!Group::can_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            go_club_alice.export_ratchet_tree()
+        ));
+    }
+
+    #[test]
+    fn can_join_rejects_unsupported_required_capabilities() {
+        use crate::welcome::{check_joinable, WelcomePreviewError};
+        use openmls::treesync::errors::LeafNodeValidationError;
+
+        let mut alice_provider = Provider::default();
+        let bob_provider = Provider::default();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Bob's key package doesn't support the group name extension
+        let bob_key_package = OpenMlsKeyPackage::builder()
+            .build(
+                CIPHERSUITE,
+                &bob_provider.0,
+                &bob.keypair,
+                bob.credential_with_key(),
+            )
+            .unwrap()
+            .key_package()
+            .clone();
+        let mut chess_club_alice: Group = MlsGroup::builder()
+            .ciphersuite(CIPHERSUITE)
+            .with_capabilities(extensions::capabilities())
+            .with_group_id(GroupId::from_slice(b"chess club"))
+            .build(
+                &alice_provider.0,
+                &alice.keypair,
+                alice.credential_with_key(),
+            )
+            .unwrap()
+            .into();
+
+        // The commit adding Bob makes the group require it. Adds are only
+        // checked against the requirements from before the commit.
+        let group_name_extensions = extensions::with_app_extension(
+            chess_club_alice.mls_group.extensions(),
+            extensions::GROUP_NAME_EXTENSION_TYPE,
+            b"chess club".to_vec(),
+        )
+        .unwrap();
+        let welcome = chess_club_alice
+            .mls_group
+            .commit_builder()
+            .propose_adds([bob_key_package])
+            .propose_group_context_extensions(group_name_extensions)
+            .unwrap()
+            .load_psks(alice_provider.0.storage())
+            .unwrap()
+            .build(
+                alice_provider.0.rand(),
+                alice_provider.0.crypto(),
+                &alice.keypair,
+                |_| true,
+            )
+            .unwrap()
+            .stage_commit(&alice_provider.0)
+            .unwrap()
+            .to_welcome_msg()
+            .unwrap()
+            .tls_serialize_detached()
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        assert!(matches!(
+            check_joinable(
+                &bob_provider,
+                &welcome,
+                chess_club_alice.export_ratchet_tree()
+            ),
+            Err(WelcomePreviewError::UnsupportedCapabilities(
+                LeafNodeValidationError::UnsupportedExtensions
+            ))
+        ));
+        assert!(!Group::can_join(
+            &bob_provider,
+            &welcome,
+            chess_club_alice.export_ratchet_tree()
+        ));
+    }
+
+    #[test]
+    fn rekey_encrypted_storage() {
+        use crate::encrypted_storage::EncryptedStorageError;
+
+        let provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        Group::create_new(&provider, &alice, "chess club");
+        let encrypted = provider.export_storage_encrypted_native("old").unwrap();
+        let plain = provider
+            .export_storage()
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let rekeyed = provider
+            .rekey_storage_native(&encrypted, "old", "new")
+            .unwrap();
+        assert!(matches!(
+            provider.rekey_storage_native(&encrypted, "wrong", "new"),
+            Err(EncryptedStorageError::WrongPassphrase)
+        ));
+
+        let restored = Provider::create(None).unwrap();
+        assert!(matches!(
+            restored.import_storage_encrypted_native(&rekeyed, "old"),
+            Err(EncryptedStorageError::WrongPassphrase)
+        ));
+        assert!(restored.0.storage().values().read().unwrap().is_empty());
+        restored
+            .import_storage_encrypted_native(&rekeyed, "new")
+            .unwrap();
+        assert_eq!(
+            *restored.0.storage().values().read().unwrap(),
+            *provider.0.storage().values().read().unwrap()
+        );
+        assert_eq!(
+            restored
+                .load_all_groups()
+                .iter()
+                .map(Group::group_id)
+                .collect::<Vec<_>>(),
+            ["chess club"]
+        );
+
+        // The storage key changes on rekey, so the storage key in the old
+        // blob, which `old` still unwraps, does not decrypt the new storage.
+        let header_len = 106;
+        let mut spliced = encrypted[..header_len].to_vec();
+        spliced.extend_from_slice(&rekeyed[header_len..]);
+        assert!(matches!(
+            restored.import_storage_encrypted_native(&spliced, "old"),
+            Err(EncryptedStorageError::Crypto(_))
+        ));
+
+        assert!(matches!(
+            restored.import_storage_encrypted_native(&plain, "new"),
+            Err(EncryptedStorageError::NotEncrypted)
+        ));
+
+        // A blob asking for more memory than we ever use is refused before
+        // deriving the key.
+        let mut costly = rekeyed.clone();
+        costly[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            restored.import_storage_encrypted_native(&costly, "new"),
+            Err(EncryptedStorageError::KdfParamsTooHigh)
+        ));
+    }
+
+    #[test]
+    fn rotated_signature_key_is_per_group() {
+        let (
+            mut alice_provider,
+            alice,
+            mut chess_club_alice,
+            mut bob_provider,
+            _,
+            mut chess_club_bob,
+        ) = create_group_alice_and_bob();
+        let identity_key = alice.get_public_key_bytes();
+        let go_club = Group::create_new(&alice_provider, &alice, "go club");
+
+        let first = chess_club_alice
+            .rotate_signature_key(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .process_message(&mut bob_provider, &first.commit())
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        // Other groups of the identity keep its key
+        assert_eq!(go_club.own_signature_key(), Some(identity_key.clone()));
+        assert_eq!(
+            chess_club_alice.own_signature_key(),
+            Some(first.public_key())
+        );
+
+        // A second rotation deletes the keypair of the first, but never the
+        // one of the identity
+        let second = chess_club_alice
+            .rotate_signature_key(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_bob
+            .process_message(&mut bob_provider, &second.commit())
+            .map_err(js_error_to_string)
+            .unwrap();
+        let stored = |public_key: &[u8]| {
+            SignatureKeyPair::read(
+                alice_provider.0.storage(),
+                public_key,
+                SignatureScheme::ED25519,
+            )
+            .is_some()
+        };
+        assert!(!stored(&first.public_key()));
+        assert!(stored(&second.public_key()));
+        assert!(stored(&identity_key));
+
+        // A reloaded group finds its keypair in storage
+        let mut reloaded = Group::load_from_storage(&alice_provider, "chess club")
+            .map_err(js_error_to_string)
+            .unwrap();
+        let message = reloaded
+            .create_message(&alice_provider, &alice, b"rotated twice")
+            .map_err(js_error_to_string)
+            .unwrap();
+        assert_eq!(
+            chess_club_bob
+                .process_message(&mut bob_provider, &message)
+                .map_err(js_error_to_string)
+                .unwrap(),
+            b"rotated twice"
+        );
+    }
+
+    #[test]
+    fn oversized_inputs_rejected_before_parsing() {
+        let (mut alice_provider, alice, mut chess_club_alice, bob_provider, bob, _) =
+            create_group_alice_and_bob();
+        let oversized = vec![0u8; alice_provider.max_message_bytes() + 1];
+
+        // Without a provider, the default limit applies
+        assert!(matches!(
+            ciphersuite::negotiate(&[CIPHERSUITE.into()], &oversized),
+            Err(ciphersuite::NegotiationError::TooLarge(_))
+        ));
+        assert!(matches!(
+            reserialize::reserialize(&oversized, WireFormat::Plaintext),
+            Err(reserialize::ReserializeError::TooLarge(_))
+        ));
+        let bundle = key_package_bundle::encode_bundle(&[oversized]);
+        assert!(matches!(
+            key_package_bundle::parse_bundle(&bundle),
+            Err(key_package_bundle::KeyPackageBundleError::TooLarge { index: 0, .. })
+        ));
+
+        // With a provider, its limit applies
+        let bob_key_package = bob
+            .get_key_package(&bob_provider)
+            .to_bytes()
+            .map_err(js_error_to_string)
+            .unwrap();
+        let commit = chess_club_alice
+            .commit_empty(&alice_provider, &alice)
+            .map_err(js_error_to_string)
+            .unwrap();
+        alice_provider.set_max_message_bytes(64);
+        let mut batch = Group::start_batch_add("tournament");
+        assert!(matches!(
+            batch.push_key_package(&alice_provider, &bob_key_package),
+            Err(batch_add::BatchAddError::KeyPackage {
+                index: 0,
+                error: add_by_bytes::AddByBytesError::TooLarge(_),
+            })
+        ));
+        assert!(matches!(
+            chess_club_alice.check_confirmation_tag(&alice_provider, &commit),
+            Err(confirmation_tag::ConfirmationCheckError::TooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn received_commits_respect_max_members() {
+        let mut alice_provider = Provider::create(None).unwrap();
+        let bob_provider = Provider::create(None).unwrap();
+        let charlie_provider = Provider::create(None).unwrap();
+        let alice = Identity::create(&alice_provider, "alice", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let bob = Identity::create(&bob_provider, "bob", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let charlie = Identity::create(&charlie_provider, "charlie", None)
+            .map_err(js_error_to_string)
+            .unwrap();
+
+        let mut config = GroupConfig::new();
+        config.set_max_members(Some(2));
+        let mut chess_club_alice =
+            Group::create_new_with_config(&alice_provider, &alice, "chess club", &config)
+                .map_err(js_error_to_string)
+                .unwrap();
+        let add_msgs = chess_club_alice
+            .native_propose_and_commit_add(
+                &alice_provider,
+                &alice,
+                &bob.get_key_package(&bob_provider),
+            )
+            .map_err(js_error_to_string)
+            .unwrap();
+        chess_club_alice
+            .merge_pending_commit(&mut alice_provider)
+            .map_err(js_error_to_string)
+            .unwrap();
+        let mut chess_club_bob = Group::native_join(
+            &bob_provider,
+            &add_msgs.welcome,
+            chess_club_alice.export_ratchet_tree(),
+        );
+
+        // Removes in the same commit make room for adds
+        let bob_index = chess_club_alice.mls_group.members().last().unwrap().index;
+        chess_club_alice
+            .mls_group
+            .propose_remove_member(alice_provider.as_ref(), &alice.keypair, bob_index)
+            .unwrap();
+        chess_club_alice
+            .mls_group
+            .propose_add_member(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &charlie.get_key_package(&charlie_provider).0,
+            )
+            .unwrap();
+        assert_eq!(chess_club_alice.ensure_capacity(0), Ok(()));
+        chess_club_alice
+            .clear_pending_proposals_native(&alice_provider)
+            .unwrap();
+
+        // A commit beyond the limit, made without our checks, isn't merged
+        let (commit, _welcome, _group_info) = chess_club_alice
+            .mls_group
+            .add_members(
+                alice_provider.as_ref(),
+                &alice.keypair,
+                &[charlie.get_key_package(&charlie_provider).0],
+            )
+            .unwrap();
+        assert!(matches!(
+            chess_club_bob.process(&bob_provider, &mls_message_to_u8vec(&commit)),
+            Err(processing::ProcessError::GroupFull(capacity::GroupFull {
+                max_members: 2,
+                members: 3,
+                adding: 0,
+            }))
+        ));
+        assert_eq!(chess_club_bob.get_epoch(), 1);
+    }
 }
@@ -0,0 +1,63 @@
+//! Rolling back the provider storage when an operation fails half-way.
+//!
+//! openmls writes to the storage as it goes. An operation that fails after
+//! some of its writes, e.g. a join whose welcome was staged but whose group
+//! couldn't be stored, leaves key material behind that no group will ever
+//! use or delete. The storage journals the entries such operations write,
+//! and we restore them if the operations fail. Dry runs restore them in any
+//! case.
+//!
+//! A poisoned lock of the storage still holds consistent data: every write
+//! to the storage is a single map operation.
+
+use openmls_traits::OpenMlsProvider;
+
+use crate::Provider;
+
+/// An operation that failed, and whether its storage writes were undone.
+#[derive(Debug)]
+pub(crate) struct Aborted<E> {
+    pub(crate) error: E,
+    /// Whether the operation had written to the storage before failing, and
+    /// the storage was restored.
+    pub(crate) rolled_back: bool,
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for Aborted<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.rolled_back {
+            write!(f, "{}; storage changes were rolled back", self.error)
+        } else {
+            write!(f, "{}", self.error)
+        }
+    }
+}
+
+impl<E: std::fmt::Debug + std::fmt::Display> std::error::Error for Aborted<E> {}
+
+/// Run `operation` and restore the storage of `provider` to its previous
+/// state if it fails.
+pub(crate) fn with_rollback<T, E>(
+    provider: &Provider,
+    operation: impl FnOnce() -> Result<T, E>,
+) -> Result<T, Aborted<E>> {
+    let storage = provider.0.storage();
+    let (result, journal) = storage.journaled(operation);
+
+    result.map_err(|error| {
+        let rolled_back = !journal.is_empty();
+        storage.roll_back(journal);
+
+        Aborted { error, rolled_back }
+    })
+}
+
+/// Run `operation` and restore the storage of `provider` to its previous
+/// state afterwards, whether it succeeded or not.
+pub(crate) fn discarding_writes<T>(provider: &Provider, operation: impl FnOnce() -> T) -> T {
+    let storage = provider.0.storage();
+    let (result, journal) = storage.journaled(operation);
+    storage.roll_back(journal);
+
+    result
+}
@@ -0,0 +1,49 @@
+//! Comparing two ratchet trees leaf by leaf, e.g. to find where the tree of
+//! a client diverged from the one of the server.
+
+use wasm_bindgen::prelude::*;
+
+use crate::RatchetTree;
+
+impl RatchetTree {
+    /// The nodes of the tree in their serde encoding, blank nodes as `null`.
+    fn node_values(&self) -> Result<Vec<serde_json::Value>, serde_json::Error> {
+        // openmls doesn't give access to the nodes of a tree, but serializes
+        // it as the plain list of nodes, see `debugTreeDump`.
+        serde_json::from_value(serde_json::to_value(&self.0)?)
+    }
+
+    /// The indices of the leaves that differ between this tree and `other`,
+    /// see `diff`.
+    pub(crate) fn diff_leaves(&self, other: &RatchetTree) -> Result<Vec<u32>, serde_json::Error> {
+        let ours = self.node_values()?;
+        let theirs = other.node_values()?;
+
+        // Trees are trimmed of trailing blank nodes, so a leaf past the end
+        // of the smaller tree is blank.
+        let blank = serde_json::Value::Null;
+        let node_count = ours.len().max(theirs.len());
+        Ok((0..node_count)
+            .step_by(2)
+            .filter(|&node_index| {
+                ours.get(node_index).unwrap_or(&blank) != theirs.get(node_index).unwrap_or(&blank)
+            })
+            .map(|node_index| (node_index / 2) as u32)
+            .collect())
+    }
+}
+
+#[wasm_bindgen]
+impl RatchetTree {
+    /// The indices of the leaves that differ between this tree and `other`,
+    /// in ascending order.
+    ///
+    /// A leaf differs if it is blank in one tree and not in the other, or if
+    /// anything in it differs, e.g. after an update. The trees may have
+    /// different sizes: leaves past the end of the smaller tree count as
+    /// blank. Parent nodes are not compared; they follow from the leaves
+    /// and the commits that set them.
+    pub fn diff(&self, other: &RatchetTree) -> Result<Vec<u32>, JsError> {
+        Ok(self.diff_leaves(other)?)
+    }
+}
@@ -9,3 +9,24 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/// Log a warning to the console. Outside of wasm, the warning goes to stderr.
+pub fn log_warning(msg: &str) {
+    #[cfg(target_arch = "wasm32")]
+    crate::log(msg);
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{msg}");
+}
+
+/// The current Unix time in seconds.
+pub fn unix_time_secs() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    let secs = (js_sys::Date::now() / 1000.0) as u64;
+    #[cfg(not(target_arch = "wasm32"))]
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    secs
+}
@@ -0,0 +1,616 @@
+//! Inspecting welcomes and joining groups.
+//!
+//! Staging a welcome in openmls consumes the key package it was encrypted to.
+//! To look at a welcome without joining, we stage it while discarding the
+//! writes to the provider storage, so that it is left as it was.
+//! Joining stages the welcome against the real storage, and rolls back if
+//! the group can't be created.
+//!
+//! The ratchet tree for a welcome usually comes from the delivery service.
+//! `validateWelcomeTree` checks it against the tree hash the inviter signed
+//! in the group info, so that a forged tree is caught before joining.
+
+use openmls::{
+    framing::{MlsMessageBodyIn, MlsMessageIn},
+    group::{
+        Member, MlsGroup, ProcessedWelcome, ProposalStore, PublicGroup, StagedWelcome, WelcomeError,
+    },
+    key_packages::KeyPackageBundle,
+    messages::Welcome,
+    prelude::{CreationFromExternalError, KeyPackageRef},
+    treesync::errors::LeafNodeValidationError,
+};
+use openmls_torln_storage::TorlnStorageError;
+use openmls_traits::{storage::StorageProvider, OpenMlsProvider};
+use tls_codec::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    credential_policy::{self, UnacceptedCredentialType},
+    extensions, join_config,
+    message_size::MessageTooLarge,
+    routing,
+    transaction::{self, Aborted},
+    Group, GroupMember, Provider, RatchetTree, CIPHERSUITE,
+};
+
+/// A welcome for a group with a different ciphersuite than ours.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct CiphersuiteMismatch {
+    pub(crate) expected: u16,
+    pub(crate) found: u16,
+}
+
+impl std::fmt::Display for CiphersuiteMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "welcome uses ciphersuite {:#06x}, expected {:#06x}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for CiphersuiteMismatch {}
+
+/// Check the ciphersuite of a serialized welcome before processing it, so
+/// that a welcome from a differently configured client fails with a clear
+/// error instead of a decryption failure.
+///
+/// Anything that isn't a readable welcome passes; it is rejected when
+/// deserializing the welcome.
+pub(crate) fn check_ciphersuite(welcome: &[u8]) -> Result<(), CiphersuiteMismatch> {
+    let expected = u16::from(CIPHERSUITE);
+    match routing::welcome_ciphersuite_of(welcome) {
+        Ok(found) if found != expected => Err(CiphersuiteMismatch { expected, found }),
+        _ => Ok(()),
+    }
+}
+
+/// Errors when staging a welcome or joining its group.
+#[derive(Debug)]
+pub(crate) enum WelcomePreviewError {
+    TooLarge(MessageTooLarge),
+    Malformed(tls_codec::Error),
+    NotAWelcome,
+    CiphersuiteMismatch(CiphersuiteMismatch),
+    Welcome(WelcomeError<TorlnStorageError>),
+    /// The ratchet tree doesn't have the tree hash in the group info.
+    TreeMismatch,
+    InvalidTree(CreationFromExternalError<TorlnStorageError>),
+    /// A member of the group has a credential type we don't accept.
+    UnacceptedCredentialType(UnacceptedCredentialType),
+    /// Our key package lacks a capability the group requires.
+    UnsupportedCapabilities(LeafNodeValidationError),
+}
+
+impl std::fmt::Display for WelcomePreviewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed welcome: {e}"),
+            Self::NotAWelcome => write!(f, "expected a message of type welcome"),
+            Self::CiphersuiteMismatch(e) => write!(f, "{e}"),
+            Self::Welcome(e) => write!(f, "can't process welcome: {e}"),
+            Self::TreeMismatch => {
+                write!(
+                    f,
+                    "the ratchet tree doesn't match the group info of the welcome"
+                )
+            }
+            Self::InvalidTree(e) => write!(f, "invalid ratchet tree: {e}"),
+            Self::UnacceptedCredentialType(e) => write!(f, "{e}"),
+            Self::UnsupportedCapabilities(e) => {
+                write!(f, "the group requires unsupported capabilities: {e}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WelcomePreviewError {}
+
+/// Deserialize a welcome, checking its size and ciphersuite first.
+pub(crate) fn deserialize_welcome(
+    provider: &Provider,
+    mut welcome: &[u8],
+) -> Result<Welcome, WelcomePreviewError> {
+    provider
+        .check_message_size(welcome)
+        .map_err(WelcomePreviewError::TooLarge)?;
+    check_ciphersuite(welcome).map_err(WelcomePreviewError::CiphersuiteMismatch)?;
+
+    match MlsMessageIn::tls_deserialize(&mut welcome)
+        .map_err(WelcomePreviewError::Malformed)?
+        .extract()
+    {
+        MlsMessageBodyIn::Welcome(welcome) => Ok(welcome),
+        _ => Err(WelcomePreviewError::NotAWelcome),
+    }
+}
+
+/// Stage `welcome` without writing to the storage of `provider`.
+pub(crate) fn stage_welcome(
+    provider: &Provider,
+    welcome: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<StagedWelcome, WelcomePreviewError> {
+    let welcome = deserialize_welcome(provider, welcome)?;
+    let config = join_config();
+
+    transaction::discarding_writes(provider, || {
+        StagedWelcome::new_from_welcome(&provider.0, &config, welcome, Some(ratchet_tree.0))
+            .map_err(WelcomePreviewError::Welcome)
+    })
+}
+
+/// The reference of the key package in the storage of `provider` that
+/// `welcome` is encrypted to, found the same way openmls does when staging
+/// it: the first recipient we have a key package for.
+fn consumed_key_package_ref(
+    provider: &Provider,
+    welcome: &Welcome,
+) -> Result<KeyPackageRef, WelcomePreviewError> {
+    welcome
+        .secrets()
+        .iter()
+        .map(|secrets| secrets.new_member())
+        .find_map(|key_package_ref| {
+            provider
+                .0
+                .storage()
+                .key_package::<_, KeyPackageBundle>(&key_package_ref)
+                .map(|bundle| bundle.map(|_| key_package_ref.clone()))
+                .transpose()
+        })
+        .ok_or(WelcomePreviewError::Welcome(
+            WelcomeError::NoMatchingKeyPackage,
+        ))?
+        .map_err(|e| WelcomePreviewError::Welcome(WelcomeError::StorageError(e)))
+}
+
+/// A group joined from a welcome, with what the welcome told about it.
+pub(crate) struct JoinedGroup {
+    pub(crate) mls_group: MlsGroup,
+    pub(crate) epoch: u64,
+    /// The members, in ascending leaf index order.
+    pub(crate) members: Vec<Member>,
+    /// The key package the welcome was encrypted to.
+    pub(crate) consumed_key_package_ref: KeyPackageRef,
+}
+
+/// Join the group `welcome` invites to, see `Group.join`.
+///
+/// If staging the welcome or storing the group fails, the storage of
+/// `provider` is restored, so that a failed join doesn't leave the secrets
+/// of the welcome behind.
+pub(crate) fn join_group(
+    provider: &Provider,
+    welcome: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<MlsGroup, Aborted<WelcomePreviewError>> {
+    join_group_with_info(provider, welcome, ratchet_tree).map(|joined| joined.mls_group)
+}
+
+/// Like `join_group`, with the epoch and members read from the staged
+/// welcome, see `Group.joinWithInfo`.
+pub(crate) fn join_group_with_info(
+    provider: &Provider,
+    welcome: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<JoinedGroup, Aborted<WelcomePreviewError>> {
+    join_group_accepting(provider, welcome, ratchet_tree, None)
+}
+
+/// Like `join_group_with_info`, but refuses the group if a member has a
+/// credential type outside `accepted_credential_types`, see
+/// `Group.joinWithAcceptedCredentialTypes`.
+pub(crate) fn join_group_accepting(
+    provider: &Provider,
+    welcome: &[u8],
+    ratchet_tree: RatchetTree,
+    accepted_credential_types: Option<&[u16]>,
+) -> Result<JoinedGroup, Aborted<WelcomePreviewError>> {
+    transaction::with_rollback(provider, || {
+        let welcome = deserialize_welcome(provider, welcome)?;
+        // Looked up before staging, which deletes the key package.
+        let consumed_key_package_ref = consumed_key_package_ref(provider, &welcome)?;
+        let staged_welcome = StagedWelcome::new_from_welcome(
+            &provider.0,
+            &join_config(),
+            welcome,
+            Some(ratchet_tree.0),
+        )
+        .map_err(WelcomePreviewError::Welcome)?;
+
+        let epoch = staged_welcome.group_context().epoch().as_u64();
+        let mut members = staged_welcome.members().collect::<Vec<_>>();
+        members.sort_by_key(|member| member.index);
+        if let Some(accepted) = accepted_credential_types {
+            credential_policy::check_credential_types(
+                accepted,
+                members.iter().map(|member| &member.credential),
+            )
+            .map_err(WelcomePreviewError::UnacceptedCredentialType)?;
+        }
+        let mls_group = staged_welcome
+            .into_group(&provider.0)
+            .map_err(WelcomePreviewError::Welcome)?;
+
+        Ok(JoinedGroup {
+            mls_group,
+            epoch,
+            members,
+            consumed_key_package_ref,
+        })
+    })
+}
+
+/// The epoch of the group `welcome` invites to, see `welcomeEpoch`.
+pub(crate) fn welcome_epoch(
+    provider: &Provider,
+    welcome: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<u64, WelcomePreviewError> {
+    let staged_welcome = stage_welcome(provider, welcome, ratchet_tree)?;
+
+    Ok(staged_welcome.group_context().epoch().as_u64())
+}
+
+/// What a welcome tells about its group, see `Group.inspectWelcome`.
+pub(crate) struct InspectedWelcome {
+    pub(crate) group_id: Vec<u8>,
+    pub(crate) epoch: u64,
+    /// `None` if our leaf isn't in the tree, which openmls rejects on join.
+    pub(crate) own_leaf_index: Option<u32>,
+    /// The members, in ascending leaf index order.
+    pub(crate) members: Vec<Member>,
+    /// The key package the welcome is encrypted to.
+    pub(crate) key_package_ref: KeyPackageRef,
+}
+
+/// Read what `welcome` tells about its group without joining it, see
+/// `Group.inspectWelcome`.
+pub(crate) fn inspect_welcome(
+    provider: &Provider,
+    welcome_bytes: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<InspectedWelcome, WelcomePreviewError> {
+    let welcome = deserialize_welcome(provider, welcome_bytes)?;
+    let key_package_ref = consumed_key_package_ref(provider, &welcome)?;
+    let encryption_key = provider
+        .0
+        .storage()
+        .key_package::<_, KeyPackageBundle>(&key_package_ref)
+        .map_err(|e| WelcomePreviewError::Welcome(WelcomeError::StorageError(e)))?
+        .ok_or(WelcomePreviewError::Welcome(
+            WelcomeError::NoMatchingKeyPackage,
+        ))?
+        .key_package()
+        .leaf_node()
+        .encryption_key()
+        .as_slice()
+        .to_vec();
+    let staged_welcome = stage_welcome(provider, welcome_bytes, ratchet_tree)?;
+
+    let mut members = staged_welcome.members().collect::<Vec<_>>();
+    members.sort_by_key(|member| member.index);
+    let own_leaf_index = members
+        .iter()
+        .find(|member| member.encryption_key == encryption_key)
+        .map(|member| member.index.u32());
+
+    Ok(InspectedWelcome {
+        group_id: staged_welcome
+            .group_context()
+            .group_id()
+            .as_slice()
+            .to_vec(),
+        epoch: staged_welcome.group_context().epoch().as_u64(),
+        own_leaf_index,
+        members,
+        key_package_ref,
+    })
+}
+
+/// Check `ratchet_tree` against the group info in `welcome`, see
+/// `validateWelcomeTree`.
+pub(crate) fn validate_welcome_tree(
+    provider: &Provider,
+    welcome: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<(), WelcomePreviewError> {
+    let welcome = deserialize_welcome(provider, welcome)?;
+
+    transaction::discarding_writes(provider, || {
+        // Decrypting the group info needs the key package, but nothing else
+        // of the welcome; the group info is then checked like an external
+        // joiner would.
+        let processed_welcome =
+            ProcessedWelcome::new_from_welcome(&provider.0, &join_config(), welcome)
+                .map_err(WelcomePreviewError::Welcome)?;
+        match PublicGroup::from_external(
+            provider.0.crypto(),
+            provider.0.storage(),
+            ratchet_tree.0,
+            processed_welcome.unverified_group_info().clone(),
+            ProposalStore::new(),
+        ) {
+            Ok(_) => Ok(()),
+            Err(CreationFromExternalError::TreeHashMismatch) => {
+                Err(WelcomePreviewError::TreeMismatch)
+            }
+            Err(e) => Err(WelcomePreviewError::InvalidTree(e)),
+        }
+    })
+}
+
+/// Check that `welcome` can be joined with the key packages in `provider`,
+/// see `canJoin`: the ratchet tree matches the group info, the group
+/// secrets decrypt, and our key package has the capabilities the group
+/// requires.
+pub(crate) fn check_joinable(
+    provider: &Provider,
+    welcome_bytes: &[u8],
+    ratchet_tree: RatchetTree,
+) -> Result<(), WelcomePreviewError> {
+    validate_welcome_tree(provider, welcome_bytes, RatchetTree(ratchet_tree.0.clone()))?;
+
+    let welcome = deserialize_welcome(provider, welcome_bytes)?;
+    let key_package_ref = consumed_key_package_ref(provider, &welcome)?;
+    let bundle = provider
+        .0
+        .storage()
+        .key_package::<_, KeyPackageBundle>(&key_package_ref)
+        .map_err(|e| WelcomePreviewError::Welcome(WelcomeError::StorageError(e)))?
+        .ok_or(WelcomePreviewError::Welcome(
+            WelcomeError::NoMatchingKeyPackage,
+        ))?;
+    let staged_welcome = stage_welcome(provider, welcome_bytes, ratchet_tree)?;
+
+    match staged_welcome.group_context().required_capabilities() {
+        Some(required) => extensions::supports_required_capabilities(
+            bundle.key_package().leaf_node().capabilities(),
+            required,
+        )
+        .map_err(WelcomePreviewError::UnsupportedCapabilities),
+        None => Ok(()),
+    }
+}
+
+/// A joined group with its epoch and members, see `Group.joinWithInfo`.
+#[wasm_bindgen]
+pub struct JoinInfo {
+    group: Group,
+    epoch: u32,
+    own_leaf_index: u32,
+    members: Vec<GroupMember>,
+    consumed_key_package_ref: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl JoinInfo {
+    /// The epoch the group was joined in.
+    #[wasm_bindgen(getter)]
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+    /// The leaf index of this client in the group.
+    #[wasm_bindgen(getter, js_name = ownLeafIndex)]
+    pub fn own_leaf_index(&self) -> u32 {
+        self.own_leaf_index
+    }
+    /// The members in the joined epoch, in ascending leaf index order, as
+    /// returned by `Group.members`.
+    #[wasm_bindgen(getter)]
+    pub fn members(&self) -> Vec<GroupMember> {
+        self.members.clone()
+    }
+    /// The reference of the key package the welcome was encrypted to, as
+    /// returned by `KeyPackage.reference`. It was consumed by the join,
+    /// unless it is a last resort key package; the app can prune the other
+    /// key packages it published with it.
+    #[wasm_bindgen(getter, js_name = consumedKeyPackageRef)]
+    pub fn consumed_key_package_ref(&self) -> Vec<u8> {
+        self.consumed_key_package_ref.clone()
+    }
+    /// The joined group. Consumes this object.
+    #[wasm_bindgen(js_name = intoGroup)]
+    pub fn into_group(self) -> Group {
+        self.group
+    }
+}
+
+impl TryFrom<JoinedGroup> for JoinInfo {
+    type Error = tls_codec::Error;
+
+    fn try_from(joined: JoinedGroup) -> Result<Self, Self::Error> {
+        Ok(JoinInfo {
+            epoch: joined.epoch as u32,
+            own_leaf_index: joined.mls_group.own_leaf_index().u32(),
+            members: joined
+                .members
+                .into_iter()
+                .map(GroupMember::try_from)
+                .collect::<Result<_, _>>()?,
+            consumed_key_package_ref: joined.consumed_key_package_ref.as_slice().to_vec(),
+            group: joined.mls_group.into(),
+        })
+    }
+}
+
+/// What a welcome tells about its group, see `Group.inspectWelcome`.
+#[wasm_bindgen]
+pub struct WelcomeInfo {
+    group_id: Vec<u8>,
+    epoch: u32,
+    own_leaf_index: Option<u32>,
+    members: Vec<GroupMember>,
+    key_package_ref: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl WelcomeInfo {
+    /// The id of the group, as returned by `Group.groupIdBytes` once joined.
+    #[wasm_bindgen(getter, js_name = groupId)]
+    pub fn group_id(&self) -> Vec<u8> {
+        self.group_id.clone()
+    }
+    /// The epoch the group would be joined in, see `welcomeEpoch`.
+    #[wasm_bindgen(getter)]
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+    /// The leaf index this client would have in the group, or `undefined`
+    /// if our leaf isn't in the ratchet tree.
+    #[wasm_bindgen(getter, js_name = ownLeafIndex)]
+    pub fn own_leaf_index(&self) -> Option<u32> {
+        self.own_leaf_index
+    }
+    /// The members in the epoch of the welcome, in ascending leaf index
+    /// order.
+    #[wasm_bindgen(getter)]
+    pub fn members(&self) -> Vec<GroupMember> {
+        self.members.clone()
+    }
+    /// The reference of the key package the welcome is encrypted to, which
+    /// a join consumes, see `JoinInfo.consumedKeyPackageRef`.
+    #[wasm_bindgen(getter, js_name = keyPackageRef)]
+    pub fn key_package_ref(&self) -> Vec<u8> {
+        self.key_package_ref.clone()
+    }
+}
+
+impl TryFrom<InspectedWelcome> for WelcomeInfo {
+    type Error = tls_codec::Error;
+
+    fn try_from(inspected: InspectedWelcome) -> Result<Self, Self::Error> {
+        Ok(WelcomeInfo {
+            group_id: inspected.group_id,
+            epoch: inspected.epoch as u32,
+            own_leaf_index: inspected.own_leaf_index,
+            members: inspected
+                .members
+                .into_iter()
+                .map(GroupMember::try_from)
+                .collect::<Result<_, _>>()?,
+            key_package_ref: inspected.key_package_ref.as_slice().to_vec(),
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// Read what `welcome` tells about its group without joining it: the
+    /// group id, the epoch, our leaf index, the members and the key package
+    /// it is encrypted to, i.e. what `joinWithInfo` returns.
+    ///
+    /// Like `canJoin`, this doesn't write to the provider storage. Compare
+    /// the epoch with the current one, e.g. from the delivery service, to
+    /// tell a welcome that arrived late, see `welcomeEpoch`.
+    #[wasm_bindgen(js_name = inspectWelcome)]
+    pub fn inspect_welcome(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<WelcomeInfo, JsError> {
+        Ok(inspect_welcome(provider, welcome, ratchet_tree)?.try_into()?)
+    }
+
+    /// Like `join`, but also returns the epoch, the own leaf index and the
+    /// members of the joined group, e.g. to render the conversation right
+    /// away without calling `getEpoch` and `members` afterwards.
+    ///
+    /// The epoch and members are read from the welcome while joining.
+    #[wasm_bindgen(js_name = joinWithInfo)]
+    pub fn join_with_info(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<JoinInfo, JsError> {
+        Ok(join_group_with_info(provider, welcome, ratchet_tree)?.try_into()?)
+    }
+
+    /// Whether `welcome` can be joined with the key packages in `provider`,
+    /// without joining the group.
+    ///
+    /// Besides decrypting the group secrets, the ratchet tree is checked as
+    /// in `validateWelcomeTree`, and our key package against the
+    /// capabilities the group requires.
+    ///
+    /// Nothing is written to the provider storage; a later `join` with the
+    /// same welcome still succeeds.
+    #[wasm_bindgen(js_name = canJoin)]
+    pub fn can_join(provider: &Provider, welcome: &[u8], ratchet_tree: RatchetTree) -> bool {
+        check_joinable(provider, welcome, ratchet_tree).is_ok()
+    }
+
+    /// Check that `ratchet_tree` is the tree of the group `welcome` invites
+    /// to, without joining the group.
+    ///
+    /// The tree is compared to the tree hash in the signed group info of the
+    /// welcome. Fails with a tree mismatch error if a different tree was
+    /// supplied, e.g. a forged one from a malicious server. `join` performs
+    /// the same check, but doesn't tell a forged tree apart from other
+    /// failures.
+    #[wasm_bindgen(js_name = validateWelcomeTree)]
+    pub fn validate_welcome_tree(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<(), JsError> {
+        Ok(validate_welcome_tree(provider, welcome, ratchet_tree)?)
+    }
+
+    /// The members of the group `welcome` invites to, in ascending leaf
+    /// index order, without joining the group.
+    ///
+    /// Like `canJoin`, this doesn't write to the provider storage, so the
+    /// user can review the members before calling `join`.
+    #[wasm_bindgen(js_name = previewWelcome)]
+    pub fn preview_welcome(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<Vec<GroupMember>, JsError> {
+        let staged_welcome = stage_welcome(provider, welcome, ratchet_tree)?;
+
+        let mut members = staged_welcome.members().collect::<Vec<_>>();
+        members.sort_by_key(|member| member.index);
+
+        Ok(members
+            .into_iter()
+            .map(GroupMember::try_from)
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// The epoch of the group `welcome` invites to, without joining the
+    /// group.
+    ///
+    /// A welcome that arrives late joins the group in the epoch it was sent
+    /// in, and the messages of later epochs can't be decrypted. If the
+    /// group is known to be in a later epoch, e.g. from the delivery service,
+    /// fetch the current group info and join with an external commit instead.
+    #[wasm_bindgen(js_name = welcomeEpoch)]
+    pub fn welcome_epoch(
+        provider: &Provider,
+        welcome: &[u8],
+        ratchet_tree: RatchetTree,
+    ) -> Result<u32, JsError> {
+        Ok(welcome_epoch(provider, welcome, ratchet_tree)? as u32)
+    }
+
+    /// The welcome for the single new member with the key package reference
+    /// `key_package_ref`, cut out of a welcome for several new members, or
+    /// `undefined` if that key package isn't among them.
+    ///
+    /// Lets a delivery service hand each device only its own encrypted
+    /// secrets. The result is a complete welcome that the recipient joins
+    /// with `join` as usual.
+    #[wasm_bindgen(js_name = welcomeForRef)]
+    pub fn welcome_for_ref(
+        welcome: &[u8],
+        key_package_ref: &[u8],
+    ) -> Result<Option<Vec<u8>>, JsError> {
+        Ok(routing::welcome_for_ref(welcome, key_package_ref)?)
+    }
+}
@@ -0,0 +1,69 @@
+//! The wire format policy of a group.
+//!
+//! The policy is part of the group configuration that openmls persists with
+//! the group state, so a group loaded from storage still has the policy it
+//! was created or joined with.
+
+use openmls::group::{IncomingWireFormatPolicy, OutgoingWireFormatPolicy};
+use wasm_bindgen::prelude::*;
+
+use crate::Group;
+
+/// How handshake messages are sent or accepted.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// As private messages, encrypted to the group.
+    Ciphertext,
+    /// As public messages, signed but not encrypted.
+    Plaintext,
+    /// Both, only used for incoming messages.
+    Mixed,
+}
+
+/// The wire format policy of a group, see `Group.wireFormatPolicy`.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupWireFormatPolicy {
+    outgoing: WireFormat,
+    incoming: WireFormat,
+}
+
+#[wasm_bindgen]
+impl GroupWireFormatPolicy {
+    /// The format of the handshake messages this client sends, either
+    /// `Ciphertext` or `Plaintext`.
+    #[wasm_bindgen(getter)]
+    pub fn outgoing(&self) -> WireFormat {
+        self.outgoing
+    }
+    /// The format of the handshake messages this client accepts.
+    #[wasm_bindgen(getter)]
+    pub fn incoming(&self) -> WireFormat {
+        self.incoming
+    }
+}
+
+#[wasm_bindgen]
+impl Group {
+    /// The wire format policy the group was created or joined with.
+    ///
+    /// Application messages are always sent as private messages; the policy
+    /// applies to proposals and commits.
+    #[wasm_bindgen(js_name = wireFormatPolicy)]
+    pub fn wire_format_policy(&self) -> GroupWireFormatPolicy {
+        let policy = self.mls_group.configuration().wire_format_policy();
+
+        GroupWireFormatPolicy {
+            outgoing: match policy.outgoing() {
+                OutgoingWireFormatPolicy::AlwaysCiphertext => WireFormat::Ciphertext,
+                OutgoingWireFormatPolicy::AlwaysPlaintext => WireFormat::Plaintext,
+            },
+            incoming: match policy.incoming() {
+                IncomingWireFormatPolicy::AlwaysCiphertext => WireFormat::Ciphertext,
+                IncomingWireFormatPolicy::AlwaysPlaintext => WireFormat::Plaintext,
+                IncomingWireFormatPolicy::Mixed => WireFormat::Mixed,
+            },
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Versioning the entries as they are written.
+//!
+//! Apps that back up the storage continuously would otherwise have to
+//! compare all of it with the last backup to find what changed. Once
+//! [`TorlnStorage::track_changes`] is called, every write and delete gives
+//! its entry the next version, and [`TorlnStorage::changed_since`] lists
+//! the entries written after a version.
+
+use std::collections::HashMap;
+
+use crate::TorlnStorage;
+
+/// The version of the latest write of each entry written since tracking
+/// started.
+#[derive(Debug, Default)]
+pub(crate) struct Changes {
+    version: u64,
+    keys: HashMap<Vec<u8>, u64>,
+}
+
+impl Changes {
+    /// Give the entry `key`, which is about to be written, the next version.
+    pub(crate) fn bump(&mut self, key: &[u8]) {
+        self.version += 1;
+        self.keys.insert(key.to_vec(), self.version);
+    }
+}
+
+impl TorlnStorage {
+    /// Start versioning the entries written from now on. Does nothing if
+    /// versioning already started.
+    pub fn track_changes(&self) {
+        self.journals
+            .write()
+            .unwrap()
+            .changes
+            .get_or_insert_with(Changes::default);
+    }
+
+    /// The version of the latest write, `0` if nothing was written since
+    /// [`TorlnStorage::track_changes`] or it wasn't called.
+    pub fn change_version(&self) -> u64 {
+        self.journals
+            .read()
+            .unwrap()
+            .changes
+            .as_ref()
+            .map_or(0, |changes| changes.version)
+    }
+
+    /// The keys of the entries written or deleted after `version`, in no
+    /// particular order. Whether an entry was deleted is up to the caller to
+    /// look up.
+    pub fn changed_since(&self, version: u64) -> Vec<Vec<u8>> {
+        self.journals
+            .read()
+            .unwrap()
+            .changes
+            .iter()
+            .flat_map(|changes| &changes.keys)
+            .filter(|(_, written)| **written > version)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+}
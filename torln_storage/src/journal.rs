@@ -0,0 +1,138 @@
+//! Recording the entries an operation writes, to roll them back.
+//!
+//! Apps that undo a failed operation, e.g. a join that failed after some of
+//! its writes, would otherwise have to copy the whole storage before it.
+//! While an operation runs in [`TorlnStorage::journaled`], every write and
+//! delete records the previous value of its entries, once per entry, and
+//! [`TorlnStorage::roll_back`] restores them.
+
+use std::collections::HashMap;
+
+use crate::{changes::Changes, TorlnStorage, TorlnStorageError};
+
+/// The values of the entries written by an operation from before it wrote
+/// them, `None` for entries that didn't exist.
+#[derive(Debug, Default)]
+pub struct Journal(HashMap<Vec<u8>, Option<Vec<u8>>>);
+
+impl Journal {
+    /// Whether the operation didn't write at all.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The journals of the operations running, innermost last.
+#[derive(Debug, Default)]
+pub(crate) struct Journals {
+    stack: Vec<Journal>,
+    /// The number of writes that succeed before writes fail, see
+    /// [`TorlnStorage::fail_writes_after`].
+    #[cfg(feature = "test-utils")]
+    writes_until_failure: Option<usize>,
+    /// The versions of the written entries, see
+    /// [`TorlnStorage::track_changes`].
+    pub(crate) changes: Option<Changes>,
+}
+
+impl TorlnStorage {
+    /// Run `operation`, returning its result and the journal of the entries
+    /// it wrote.
+    ///
+    /// Operations may be nested; an entry written by the inner operation
+    /// is in the journal of both.
+    pub fn journaled<T>(&self, operation: impl FnOnce() -> T) -> (T, Journal) {
+        self.journals
+            .write()
+            .unwrap()
+            .stack
+            .push(Journal::default());
+        let result = operation();
+        let journal = self
+            .journals
+            .write()
+            .unwrap()
+            .stack
+            .pop()
+            .unwrap_or_default();
+
+        (result, journal)
+    }
+
+    /// Restore the entries in `journal` to the values they had before they
+    /// were written.
+    pub fn roll_back(&self, journal: Journal) {
+        // Restoring is a write of the operations around this one.
+        self.record(journal.0.keys().cloned()).ok();
+        self.write_values(journal.0);
+    }
+
+    /// Write the entries with the raw storage keys in `entries`, and delete
+    /// those whose value is `None`, for entries that aren't written through
+    /// the `StorageProvider` methods.
+    ///
+    /// Every entry is recorded like the writes of those methods, so that
+    /// [`TorlnStorage::roll_back`] restores it.
+    pub fn write_entries(
+        &self,
+        entries: impl IntoIterator<Item = (Vec<u8>, Option<Vec<u8>>)>,
+    ) -> Result<(), TorlnStorageError> {
+        let entries = entries.into_iter().collect::<Vec<_>>();
+        self.record(entries.iter().map(|(key, _)| key.clone()))?;
+        self.write_values(entries);
+
+        Ok(())
+    }
+
+    /// Write `entries` to the stored values, without recording them.
+    fn write_values(&self, entries: impl IntoIterator<Item = (Vec<u8>, Option<Vec<u8>>)>) {
+        let mut values = self.values().write().unwrap();
+        for (key, value) in entries {
+            match value {
+                Some(value) => values.insert(key, value),
+                None => values.remove(&key),
+            };
+        }
+    }
+
+    /// Let the next `writes` writes succeed and every write after them fail,
+    /// to test how failures half-way through an operation are handled.
+    /// `None` lets all writes succeed again.
+    #[cfg(feature = "test-utils")]
+    pub fn fail_writes_after(&self, writes: Option<usize>) {
+        self.journals.write().unwrap().writes_until_failure = writes;
+    }
+
+    /// Record the current values of the entries `keys`, which are about to
+    /// be written, in the journals of the operations running, and give the
+    /// entries new versions.
+    pub(crate) fn record(
+        &self,
+        keys: impl IntoIterator<Item = Vec<u8>>,
+    ) -> Result<(), TorlnStorageError> {
+        let mut journals = self.journals.write().unwrap();
+
+        #[cfg(feature = "test-utils")]
+        if let Some(writes) = &mut journals.writes_until_failure {
+            if *writes == 0 {
+                return Err(TorlnStorageError::InjectedFailure);
+            }
+            *writes -= 1;
+        }
+
+        let values = self.values().read().unwrap();
+        for key in keys {
+            for journal in &mut journals.stack {
+                journal
+                    .0
+                    .entry(key.clone())
+                    .or_insert_with(|| values.get(&key).cloned());
+            }
+            if let Some(changes) = &mut journals.changes {
+                changes.bump(&key);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+//! The keys under which the storage keeps single entries.
+//!
+//! Each key is built from a label naming the kind of entry, the JSON of the
+//! key passed to the `StorageProvider` method and the storage version, like
+//! in the memory storage of OpenMLS. The labels are copied from there, and
+//! must stay the same for exports to remain compatible.
+//!
+//! The public functions are for apps that copy some entries as they are,
+//! e.g. to checkpoint the uncommitted state of a group, without depending
+//! on how the keys are built. The values are read and written with the
+//! `StorageProvider` methods named below.
+
+use openmls_traits::storage::{traits, CURRENT_VERSION};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{TorlnStorage, TorlnStorageError};
+
+pub(crate) const KEY_PACKAGE_LABEL: &[u8] = b"KeyPackage";
+pub(crate) const PSK_LABEL: &[u8] = b"Psk";
+pub(crate) const ENCRYPTION_KEY_PAIR_LABEL: &[u8] = b"EncryptionKeyPair";
+pub(crate) const SIGNATURE_KEY_PAIR_LABEL: &[u8] = b"SignatureKeyPair";
+pub(crate) const EPOCH_KEY_PAIRS_LABEL: &[u8] = b"EpochKeyPairs";
+
+// related to PublicGroup
+pub(crate) const TREE_LABEL: &[u8] = b"Tree";
+pub(crate) const GROUP_CONTEXT_LABEL: &[u8] = b"GroupContext";
+#[cfg(feature = "extensions-draft-08")]
+pub(crate) const APPLICATION_EXPORT_TREE_LABEL: &[u8] = b"ApplicationExportTree";
+pub(crate) const INTERIM_TRANSCRIPT_HASH_LABEL: &[u8] = b"InterimTranscriptHash";
+pub(crate) const CONFIRMATION_TAG_LABEL: &[u8] = b"ConfirmationTag";
+
+// related to MlsGroup
+pub(crate) const JOIN_CONFIG_LABEL: &[u8] = b"MlsGroupJoinConfig";
+pub(crate) const OWN_LEAF_NODES_LABEL: &[u8] = b"OwnLeafNodes";
+pub(crate) const GROUP_STATE_LABEL: &[u8] = b"GroupState";
+pub(crate) const QUEUED_PROPOSAL_LABEL: &[u8] = b"QueuedProposal";
+pub(crate) const PROPOSAL_QUEUE_REFS_LABEL: &[u8] = b"ProposalQueueRefs";
+pub(crate) const OWN_LEAF_NODE_INDEX_LABEL: &[u8] = b"OwnLeafNodeIndex";
+pub(crate) const EPOCH_SECRETS_LABEL: &[u8] = b"EpochSecrets";
+pub(crate) const RESUMPTION_PSK_STORE_LABEL: &[u8] = b"ResumptionPsk";
+pub(crate) const MESSAGE_SECRETS_LABEL: &[u8] = b"MessageSecrets";
+
+/// The labels of the entries keyed by the group id alone.
+const GROUP_LABELS: &[&[u8]] = &[
+    TREE_LABEL,
+    GROUP_CONTEXT_LABEL,
+    #[cfg(feature = "extensions-draft-08")]
+    APPLICATION_EXPORT_TREE_LABEL,
+    INTERIM_TRANSCRIPT_HASH_LABEL,
+    CONFIRMATION_TAG_LABEL,
+    JOIN_CONFIG_LABEL,
+    OWN_LEAF_NODES_LABEL,
+    GROUP_STATE_LABEL,
+    PROPOSAL_QUEUE_REFS_LABEL,
+    OWN_LEAF_NODE_INDEX_LABEL,
+    EPOCH_SECRETS_LABEL,
+    RESUMPTION_PSK_STORE_LABEL,
+    MESSAGE_SECRETS_LABEL,
+];
+
+/// The key of the entry with `label` whose key is the JSON `key`.
+fn build_key(label: &[u8], key: &[u8]) -> Vec<u8> {
+    [label, key, &CURRENT_VERSION.to_be_bytes()].concat()
+}
+
+/// The key of the entry with `label` and `key`.
+pub(crate) fn entry_key(label: &[u8], key: &impl Serialize) -> Result<Vec<u8>, TorlnStorageError> {
+    Ok(build_key(label, &serde_json::to_vec(key)?))
+}
+
+/// The key of the keypairs of `leaf_index` in the epoch `epoch` of
+/// `group_id`, see `StorageProvider::write_encryption_epoch_key_pairs`.
+pub(crate) fn epoch_key_pairs_key(
+    group_id: &impl Serialize,
+    epoch: &impl Serialize,
+    leaf_index: u32,
+) -> Result<Vec<u8>, TorlnStorageError> {
+    let key = [
+        serde_json::to_vec(group_id)?,
+        serde_json::to_vec(epoch)?,
+        serde_json::to_vec(&leaf_index)?,
+    ]
+    .concat();
+    Ok(build_key(EPOCH_KEY_PAIRS_LABEL, &key))
+}
+
+impl TorlnStorage {
+    /// The ids of the groups with a group context in the storage, in no
+    /// particular order, see `StorageProvider::write_context`.
+    ///
+    /// Every group has exactly one group context, so this lists each
+    /// stored group once. Ids that can't be decoded are skipped.
+    pub fn group_ids<GroupId: traits::GroupId<CURRENT_VERSION> + DeserializeOwned>(
+        &self,
+    ) -> Vec<GroupId> {
+        let version = CURRENT_VERSION.to_be_bytes();
+        let values = self.values().read().unwrap();
+        values
+            .keys()
+            .filter_map(|key| {
+                key.strip_prefix(GROUP_CONTEXT_LABEL)?
+                    .strip_suffix(&version)
+            })
+            .filter_map(|group_id| serde_json::from_slice(group_id).ok())
+            .collect()
+    }
+
+    /// The keys of the entries of `group_id` that are keyed by the group id
+    /// alone, e.g. its tree, group context and epoch secrets.
+    ///
+    /// The queued proposals and the keypairs of past epochs have keys of
+    /// their own, see `is_queued_proposal_key` and `is_epoch_key_pairs_key`.
+    pub fn group_keys<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        group_id: &GroupId,
+    ) -> Result<Vec<Vec<u8>>, TorlnStorageError> {
+        let key = serde_json::to_vec(group_id)?;
+        Ok(GROUP_LABELS
+            .iter()
+            .map(|label| build_key(label, &key))
+            .collect())
+    }
+
+    /// Whether `key` is the key of the keypairs of a past epoch of
+    /// `group_id`, see `StorageProvider::write_encryption_epoch_key_pairs`.
+    pub fn is_epoch_key_pairs_key<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        key: &[u8],
+        group_id: &GroupId,
+    ) -> Result<bool, TorlnStorageError> {
+        // Keyed by the group id followed by the epoch and the leaf index.
+        let prefix = [EPOCH_KEY_PAIRS_LABEL, &serde_json::to_vec(group_id)?].concat();
+        Ok(key.starts_with(&prefix))
+    }
+
+    /// The key of the PSK with `psk_id`, see `StorageProvider::write_psk`.
+    pub fn psk_key<PskId: traits::PskId<CURRENT_VERSION>>(
+        psk_id: &PskId,
+    ) -> Result<Vec<u8>, TorlnStorageError> {
+        entry_key(PSK_LABEL, psk_id)
+    }
+
+    /// The key of the group state of `group_id`, see
+    /// `StorageProvider::write_group_state`.
+    pub fn group_state_key<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        group_id: &GroupId,
+    ) -> Result<Vec<u8>, TorlnStorageError> {
+        entry_key(GROUP_STATE_LABEL, group_id)
+    }
+
+    /// The key of the references of the queued proposals of `group_id`, see
+    /// `StorageProvider::queue_proposal`.
+    pub fn proposal_queue_refs_key<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        group_id: &GroupId,
+    ) -> Result<Vec<u8>, TorlnStorageError> {
+        entry_key(PROPOSAL_QUEUE_REFS_LABEL, group_id)
+    }
+
+    /// Whether `key` is the key of a queued proposal of `group_id`, see
+    /// `StorageProvider::queue_proposal`.
+    pub fn is_queued_proposal_key<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        key: &[u8],
+        group_id: &GroupId,
+    ) -> Result<bool, TorlnStorageError> {
+        // Queued proposals are keyed by the pair of the group id and the
+        // proposal reference.
+        let prefix = [
+            QUEUED_PROPOSAL_LABEL,
+            b"[",
+            &serde_json::to_vec(group_id)?,
+            b",",
+        ]
+        .concat();
+        Ok(key.starts_with(&prefix))
+    }
+
+    /// The key of the own leaf nodes of `group_id`, see
+    /// `StorageProvider::append_own_leaf_node`.
+    pub fn own_leaf_nodes_key<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        group_id: &GroupId,
+    ) -> Result<Vec<u8>, TorlnStorageError> {
+        entry_key(OWN_LEAF_NODES_LABEL, group_id)
+    }
+
+    /// The key of the encryption key pair of `public_key`, see
+    /// `StorageProvider::write_encryption_key_pair`.
+    pub fn encryption_key_pair_key<EncryptionKey: traits::EncryptionKey<CURRENT_VERSION>>(
+        public_key: &EncryptionKey,
+    ) -> Result<Vec<u8>, TorlnStorageError> {
+        entry_key(ENCRYPTION_KEY_PAIR_LABEL, public_key)
+    }
+}
@@ -0,0 +1,751 @@
+//! # OpenMLS Torln Storage
+//!
+//! The storage of the torln wasm bindings. Like the memory storage of
+//! OpenMLS it keeps all entries in a map, under the same keys, so exports of
+//! either storage can be imported into the other. In addition, the writes of
+//! an operation can be rolled back, see [`TorlnStorage::journaled`], and the
+//! entries written since a backup can be listed, see
+//! [`TorlnStorage::track_changes`].
+
+use std::collections::HashMap;
+
+use openmls_traits::storage::{traits, StorageProvider, CURRENT_VERSION};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "unsync")]
+mod unsync;
+
+mod changes;
+
+mod journal;
+pub use journal::Journal;
+
+mod keys;
+#[cfg(feature = "extensions-draft-08")]
+use keys::APPLICATION_EXPORT_TREE_LABEL;
+use keys::{
+    entry_key, epoch_key_pairs_key, CONFIRMATION_TAG_LABEL, ENCRYPTION_KEY_PAIR_LABEL,
+    EPOCH_SECRETS_LABEL, GROUP_CONTEXT_LABEL, GROUP_STATE_LABEL, INTERIM_TRANSCRIPT_HASH_LABEL,
+    JOIN_CONFIG_LABEL, KEY_PACKAGE_LABEL, MESSAGE_SECRETS_LABEL, OWN_LEAF_NODES_LABEL,
+    OWN_LEAF_NODE_INDEX_LABEL, PROPOSAL_QUEUE_REFS_LABEL, PSK_LABEL, QUEUED_PROPOSAL_LABEL,
+    RESUMPTION_PSK_STORE_LABEL, SIGNATURE_KEY_PAIR_LABEL, TREE_LABEL,
+};
+
+/// The lock around the stored values.
+#[cfg(not(feature = "unsync"))]
+pub type StorageLock<T> = std::sync::RwLock<T>;
+/// The lock around the stored values.
+#[cfg(feature = "unsync")]
+pub type StorageLock<T> = unsync::UnsyncLock<T>;
+
+/// An in-memory storage with its writes journaled and versioned.
+#[derive(Debug, Default)]
+pub struct TorlnStorage {
+    values: StorageLock<HashMap<Vec<u8>, Vec<u8>>>,
+    journals: StorageLock<journal::Journals>,
+}
+
+impl TorlnStorage {
+    /// The stored values by their keys.
+    ///
+    /// Write them with [`TorlnStorage::write_entries`] rather than through
+    /// the lock, so that the writes are journaled.
+    pub fn values(&self) -> &StorageLock<HashMap<Vec<u8>, Vec<u8>>> {
+        &self.values
+    }
+
+    /// Write `value` to the entry `key`.
+    fn write(&self, key: Vec<u8>, value: &impl Serialize) -> Result<(), TorlnStorageError> {
+        let value = serde_json::to_vec(value)?;
+        self.record([key.clone()])?;
+        self.values.write().unwrap().insert(key, value);
+
+        Ok(())
+    }
+
+    /// Append `item` to the list in the entry `key`, which is created if it
+    /// doesn't exist.
+    fn append(&self, key: Vec<u8>, item: &impl Serialize) -> Result<(), TorlnStorageError> {
+        let item = serde_json::to_vec(item)?;
+        self.record([key.clone()])?;
+        let mut values = self.values.write().unwrap();
+        let list_bytes = values.entry(key).or_insert(b"[]".to_vec());
+
+        let mut list: Vec<Vec<u8>> = serde_json::from_slice(list_bytes)?;
+        list.push(item);
+        *list_bytes = serde_json::to_vec(&list)?;
+
+        Ok(())
+    }
+
+    /// Remove `item` from the list in the entry `key`.
+    fn remove_item(&self, key: Vec<u8>, item: &impl Serialize) -> Result<(), TorlnStorageError> {
+        let item = serde_json::to_vec(item)?;
+        self.record([key.clone()])?;
+        let mut values = self.values.write().unwrap();
+        let list_bytes = values.entry(key).or_insert(b"[]".to_vec());
+
+        let mut list: Vec<Vec<u8>> = serde_json::from_slice(list_bytes)?;
+        if let Some(position) = list.iter().position(|stored_item| *stored_item == item) {
+            list.remove(position);
+        }
+        *list_bytes = serde_json::to_vec(&list)?;
+
+        Ok(())
+    }
+
+    /// Delete the entry `key`.
+    fn delete(&self, key: Vec<u8>) -> Result<(), TorlnStorageError> {
+        self.record([key.clone()])?;
+        self.values.write().unwrap().remove(&key);
+
+        Ok(())
+    }
+
+    /// Read the value of the entry `key`.
+    fn read<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<V>, TorlnStorageError> {
+        let values = self.values.read().unwrap();
+        let Some(value) = values.get(key) else {
+            return Ok(None);
+        };
+
+        Ok(Some(serde_json::from_slice(value)?))
+    }
+
+    /// Read the items of the list in the entry `key`, none if it doesn't
+    /// exist.
+    fn read_list<V: DeserializeOwned>(&self, key: &[u8]) -> Result<Vec<V>, TorlnStorageError> {
+        let values = self.values.read().unwrap();
+        let Some(list_bytes) = values.get(key) else {
+            return Ok(vec![]);
+        };
+
+        let list: Vec<Vec<u8>> = serde_json::from_slice(list_bytes)?;
+        Ok(list
+            .iter()
+            .map(|item| serde_json::from_slice(item))
+            .collect::<Result<_, _>>()?)
+    }
+}
+
+/// Errors thrown by the torln storage.
+#[derive(thiserror::Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TorlnStorageError {
+    #[error("Error serializing value.")]
+    SerializationError,
+    #[cfg(feature = "test-utils")]
+    #[error("Write failed on purpose.")]
+    InjectedFailure,
+}
+
+impl From<serde_json::Error> for TorlnStorageError {
+    fn from(_: serde_json::Error) -> Self {
+        Self::SerializationError
+    }
+}
+
+/// Every write records the entries it is about to change, see
+/// [`TorlnStorage::journaled`].
+impl StorageProvider<CURRENT_VERSION> for TorlnStorage {
+    type Error = TorlnStorageError;
+
+    fn write_mls_join_config<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        MlsGroupJoinConfig: traits::MlsGroupJoinConfig<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        config: &MlsGroupJoinConfig,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(JOIN_CONFIG_LABEL, group_id)?, config)
+    }
+
+    fn append_own_leaf_node<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        LeafNode: traits::LeafNode<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        leaf_node: &LeafNode,
+    ) -> Result<(), Self::Error> {
+        self.append(entry_key(OWN_LEAF_NODES_LABEL, group_id)?, leaf_node)
+    }
+
+    fn queue_proposal<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ProposalRef: traits::ProposalRef<CURRENT_VERSION>,
+        QueuedProposal: traits::QueuedProposal<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        proposal_ref: &ProposalRef,
+        proposal: &QueuedProposal,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(QUEUED_PROPOSAL_LABEL, &(group_id, proposal_ref))?,
+            proposal,
+        )?;
+        self.append(
+            entry_key(PROPOSAL_QUEUE_REFS_LABEL, group_id)?,
+            proposal_ref,
+        )
+    }
+
+    fn write_tree<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        TreeSync: traits::TreeSync<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        tree: &TreeSync,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(TREE_LABEL, group_id)?, tree)
+    }
+
+    fn write_interim_transcript_hash<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        InterimTranscriptHash: traits::InterimTranscriptHash<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        interim_transcript_hash: &InterimTranscriptHash,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(INTERIM_TRANSCRIPT_HASH_LABEL, group_id)?,
+            interim_transcript_hash,
+        )
+    }
+
+    fn write_context<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        GroupContext: traits::GroupContext<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        group_context: &GroupContext,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(GROUP_CONTEXT_LABEL, group_id)?, group_context)
+    }
+
+    fn write_confirmation_tag<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ConfirmationTag: traits::ConfirmationTag<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        confirmation_tag: &ConfirmationTag,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(CONFIRMATION_TAG_LABEL, group_id)?,
+            confirmation_tag,
+        )
+    }
+
+    fn write_group_state<
+        GroupState: traits::GroupState<CURRENT_VERSION>,
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        group_state: &GroupState,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(GROUP_STATE_LABEL, group_id)?, group_state)
+    }
+
+    fn write_message_secrets<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        MessageSecrets: traits::MessageSecrets<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        message_secrets: &MessageSecrets,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(MESSAGE_SECRETS_LABEL, group_id)?, message_secrets)
+    }
+
+    fn write_resumption_psk_store<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ResumptionPskStore: traits::ResumptionPskStore<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        resumption_psk_store: &ResumptionPskStore,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(RESUMPTION_PSK_STORE_LABEL, group_id)?,
+            resumption_psk_store,
+        )
+    }
+
+    fn write_own_leaf_index<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        LeafNodeIndex: traits::LeafNodeIndex<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        own_leaf_index: &LeafNodeIndex,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(OWN_LEAF_NODE_INDEX_LABEL, group_id)?,
+            own_leaf_index,
+        )
+    }
+
+    fn write_group_epoch_secrets<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        GroupEpochSecrets: traits::GroupEpochSecrets<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        group_epoch_secrets: &GroupEpochSecrets,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(EPOCH_SECRETS_LABEL, group_id)?,
+            group_epoch_secrets,
+        )
+    }
+
+    #[cfg(feature = "extensions-draft-08")]
+    fn write_application_export_tree<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ApplicationExportTree: traits::ApplicationExportTree<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        application_export_tree: &ApplicationExportTree,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(APPLICATION_EXPORT_TREE_LABEL, group_id)?,
+            application_export_tree,
+        )
+    }
+
+    fn write_signature_key_pair<
+        SignaturePublicKey: traits::SignaturePublicKey<CURRENT_VERSION>,
+        SignatureKeyPair: traits::SignatureKeyPair<CURRENT_VERSION>,
+    >(
+        &self,
+        public_key: &SignaturePublicKey,
+        signature_key_pair: &SignatureKeyPair,
+    ) -> Result<(), Self::Error> {
+        self.write(
+            entry_key(SIGNATURE_KEY_PAIR_LABEL, public_key)?,
+            signature_key_pair,
+        )
+    }
+
+    fn write_encryption_key_pair<
+        EncryptionKey: traits::EncryptionKey<CURRENT_VERSION>,
+        HpkeKeyPair: traits::HpkeKeyPair<CURRENT_VERSION>,
+    >(
+        &self,
+        public_key: &EncryptionKey,
+        key_pair: &HpkeKeyPair,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(ENCRYPTION_KEY_PAIR_LABEL, public_key)?, key_pair)
+    }
+
+    fn write_encryption_epoch_key_pairs<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        EpochKey: traits::EpochKey<CURRENT_VERSION>,
+        HpkeKeyPair: traits::HpkeKeyPair<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        epoch: &EpochKey,
+        leaf_index: u32,
+        key_pairs: &[HpkeKeyPair],
+    ) -> Result<(), Self::Error> {
+        self.write(epoch_key_pairs_key(group_id, epoch, leaf_index)?, key_pairs)
+    }
+
+    fn write_key_package<
+        HashReference: traits::HashReference<CURRENT_VERSION>,
+        KeyPackage: traits::KeyPackage<CURRENT_VERSION>,
+    >(
+        &self,
+        hash_ref: &HashReference,
+        key_package: &KeyPackage,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(KEY_PACKAGE_LABEL, hash_ref)?, key_package)
+    }
+
+    fn write_psk<
+        PskId: traits::PskId<CURRENT_VERSION>,
+        PskBundle: traits::PskBundle<CURRENT_VERSION>,
+    >(
+        &self,
+        psk_id: &PskId,
+        psk: &PskBundle,
+    ) -> Result<(), Self::Error> {
+        self.write(entry_key(PSK_LABEL, psk_id)?, psk)
+    }
+
+    fn mls_group_join_config<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        MlsGroupJoinConfig: traits::MlsGroupJoinConfig<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<MlsGroupJoinConfig>, Self::Error> {
+        self.read(&entry_key(JOIN_CONFIG_LABEL, group_id)?)
+    }
+
+    fn own_leaf_nodes<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        LeafNode: traits::LeafNode<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Vec<LeafNode>, Self::Error> {
+        self.read_list(&entry_key(OWN_LEAF_NODES_LABEL, group_id)?)
+    }
+
+    fn queued_proposal_refs<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ProposalRef: traits::ProposalRef<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Vec<ProposalRef>, Self::Error> {
+        self.read_list(&entry_key(PROPOSAL_QUEUE_REFS_LABEL, group_id)?)
+    }
+
+    fn queued_proposals<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ProposalRef: traits::ProposalRef<CURRENT_VERSION>,
+        QueuedProposal: traits::QueuedProposal<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Vec<(ProposalRef, QueuedProposal)>, Self::Error> {
+        let proposal_refs: Vec<ProposalRef> =
+            self.read_list(&entry_key(PROPOSAL_QUEUE_REFS_LABEL, group_id)?)?;
+
+        proposal_refs
+            .into_iter()
+            .map(|proposal_ref| -> Result<_, Self::Error> {
+                let key = entry_key(QUEUED_PROPOSAL_LABEL, &(group_id, &proposal_ref))?;
+                let proposal = self.read(&key)?.unwrap();
+                Ok((proposal_ref, proposal))
+            })
+            .collect()
+    }
+
+    fn tree<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        TreeSync: traits::TreeSync<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<TreeSync>, Self::Error> {
+        self.read(&entry_key(TREE_LABEL, group_id)?)
+    }
+
+    fn group_context<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        GroupContext: traits::GroupContext<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<GroupContext>, Self::Error> {
+        self.read(&entry_key(GROUP_CONTEXT_LABEL, group_id)?)
+    }
+
+    fn interim_transcript_hash<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        InterimTranscriptHash: traits::InterimTranscriptHash<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<InterimTranscriptHash>, Self::Error> {
+        self.read(&entry_key(INTERIM_TRANSCRIPT_HASH_LABEL, group_id)?)
+    }
+
+    fn confirmation_tag<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ConfirmationTag: traits::ConfirmationTag<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<ConfirmationTag>, Self::Error> {
+        self.read(&entry_key(CONFIRMATION_TAG_LABEL, group_id)?)
+    }
+
+    fn group_state<
+        GroupState: traits::GroupState<CURRENT_VERSION>,
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<GroupState>, Self::Error> {
+        self.read(&entry_key(GROUP_STATE_LABEL, group_id)?)
+    }
+
+    fn message_secrets<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        MessageSecrets: traits::MessageSecrets<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<MessageSecrets>, Self::Error> {
+        self.read(&entry_key(MESSAGE_SECRETS_LABEL, group_id)?)
+    }
+
+    fn resumption_psk_store<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ResumptionPskStore: traits::ResumptionPskStore<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<ResumptionPskStore>, Self::Error> {
+        self.read(&entry_key(RESUMPTION_PSK_STORE_LABEL, group_id)?)
+    }
+
+    fn own_leaf_index<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        LeafNodeIndex: traits::LeafNodeIndex<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<LeafNodeIndex>, Self::Error> {
+        self.read(&entry_key(OWN_LEAF_NODE_INDEX_LABEL, group_id)?)
+    }
+
+    fn group_epoch_secrets<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        GroupEpochSecrets: traits::GroupEpochSecrets<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<GroupEpochSecrets>, Self::Error> {
+        self.read(&entry_key(EPOCH_SECRETS_LABEL, group_id)?)
+    }
+
+    fn signature_key_pair<
+        SignaturePublicKey: traits::SignaturePublicKey<CURRENT_VERSION>,
+        SignatureKeyPair: traits::SignatureKeyPair<CURRENT_VERSION>,
+    >(
+        &self,
+        public_key: &SignaturePublicKey,
+    ) -> Result<Option<SignatureKeyPair>, Self::Error> {
+        self.read(&entry_key(SIGNATURE_KEY_PAIR_LABEL, public_key)?)
+    }
+
+    fn encryption_key_pair<
+        HpkeKeyPair: traits::HpkeKeyPair<CURRENT_VERSION>,
+        EncryptionKey: traits::EncryptionKey<CURRENT_VERSION>,
+    >(
+        &self,
+        public_key: &EncryptionKey,
+    ) -> Result<Option<HpkeKeyPair>, Self::Error> {
+        self.read(&entry_key(ENCRYPTION_KEY_PAIR_LABEL, public_key)?)
+    }
+
+    fn encryption_epoch_key_pairs<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        EpochKey: traits::EpochKey<CURRENT_VERSION>,
+        HpkeKeyPair: traits::HpkeKeyPair<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        epoch: &EpochKey,
+        leaf_index: u32,
+    ) -> Result<Vec<HpkeKeyPair>, Self::Error> {
+        Ok(self
+            .read(&epoch_key_pairs_key(group_id, epoch, leaf_index)?)?
+            .unwrap_or_default())
+    }
+
+    fn key_package<
+        KeyPackageRef: traits::HashReference<CURRENT_VERSION>,
+        KeyPackage: traits::KeyPackage<CURRENT_VERSION>,
+    >(
+        &self,
+        hash_ref: &KeyPackageRef,
+    ) -> Result<Option<KeyPackage>, Self::Error> {
+        self.read(&entry_key(KEY_PACKAGE_LABEL, hash_ref)?)
+    }
+
+    fn psk<PskBundle: traits::PskBundle<CURRENT_VERSION>, PskId: traits::PskId<CURRENT_VERSION>>(
+        &self,
+        psk_id: &PskId,
+    ) -> Result<Option<PskBundle>, Self::Error> {
+        self.read(&entry_key(PSK_LABEL, psk_id)?)
+    }
+
+    #[cfg(feature = "extensions-draft-08")]
+    fn application_export_tree<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ApplicationExportTree: traits::ApplicationExportTree<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<Option<ApplicationExportTree>, Self::Error> {
+        self.read(&entry_key(APPLICATION_EXPORT_TREE_LABEL, group_id)?)
+    }
+
+    fn remove_proposal<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ProposalRef: traits::ProposalRef<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        proposal_ref: &ProposalRef,
+    ) -> Result<(), Self::Error> {
+        self.remove_item(
+            entry_key(PROPOSAL_QUEUE_REFS_LABEL, group_id)?,
+            proposal_ref,
+        )?;
+        self.delete(entry_key(QUEUED_PROPOSAL_LABEL, &(group_id, proposal_ref))?)
+    }
+
+    fn delete_own_leaf_nodes<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(OWN_LEAF_NODES_LABEL, group_id)?)
+    }
+
+    fn delete_group_config<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(JOIN_CONFIG_LABEL, group_id)?)
+    }
+
+    fn delete_tree<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(TREE_LABEL, group_id)?)
+    }
+
+    fn delete_confirmation_tag<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(CONFIRMATION_TAG_LABEL, group_id)?)
+    }
+
+    fn delete_group_state<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(GROUP_STATE_LABEL, group_id)?)
+    }
+
+    fn delete_context<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(GROUP_CONTEXT_LABEL, group_id)?)
+    }
+
+    fn delete_interim_transcript_hash<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(INTERIM_TRANSCRIPT_HASH_LABEL, group_id)?)
+    }
+
+    fn delete_message_secrets<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(MESSAGE_SECRETS_LABEL, group_id)?)
+    }
+
+    fn delete_all_resumption_psk_secrets<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(RESUMPTION_PSK_STORE_LABEL, group_id)?)
+    }
+
+    fn delete_own_leaf_index<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(OWN_LEAF_NODE_INDEX_LABEL, group_id)?)
+    }
+
+    fn delete_group_epoch_secrets<GroupId: traits::GroupId<CURRENT_VERSION>>(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(EPOCH_SECRETS_LABEL, group_id)?)
+    }
+
+    fn clear_proposal_queue<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ProposalRef: traits::ProposalRef<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        let proposal_refs: Vec<ProposalRef> =
+            self.read_list(&entry_key(PROPOSAL_QUEUE_REFS_LABEL, group_id)?)?;
+        // Like the memory storage, the proposals are deleted under their key
+        // without the label and version, so that exports stay the same. openmls
+        // only finds them through the references, which are deleted below.
+        for proposal_ref in proposal_refs {
+            self.delete(serde_json::to_vec(&(group_id, proposal_ref))?)?;
+        }
+
+        self.delete(entry_key(PROPOSAL_QUEUE_REFS_LABEL, group_id)?)
+    }
+
+    fn delete_signature_key_pair<
+        SignaturePublicKey: traits::SignaturePublicKey<CURRENT_VERSION>,
+    >(
+        &self,
+        public_key: &SignaturePublicKey,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(SIGNATURE_KEY_PAIR_LABEL, public_key)?)
+    }
+
+    fn delete_encryption_key_pair<EncryptionKey: traits::EncryptionKey<CURRENT_VERSION>>(
+        &self,
+        public_key: &EncryptionKey,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(ENCRYPTION_KEY_PAIR_LABEL, public_key)?)
+    }
+
+    fn delete_encryption_epoch_key_pairs<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        EpochKey: traits::EpochKey<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+        epoch: &EpochKey,
+        leaf_index: u32,
+    ) -> Result<(), Self::Error> {
+        self.delete(epoch_key_pairs_key(group_id, epoch, leaf_index)?)
+    }
+
+    fn delete_key_package<KeyPackageRef: traits::HashReference<CURRENT_VERSION>>(
+        &self,
+        hash_ref: &KeyPackageRef,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(KEY_PACKAGE_LABEL, hash_ref)?)
+    }
+
+    fn delete_psk<PskKey: traits::PskId<CURRENT_VERSION>>(
+        &self,
+        psk_id: &PskKey,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(PSK_LABEL, psk_id)?)
+    }
+
+    #[cfg(feature = "extensions-draft-08")]
+    fn delete_application_export_tree<
+        GroupId: traits::GroupId<CURRENT_VERSION>,
+        ApplicationExportTree: traits::ApplicationExportTree<CURRENT_VERSION>,
+    >(
+        &self,
+        group_id: &GroupId,
+    ) -> Result<(), Self::Error> {
+        self.delete(entry_key(APPLICATION_EXPORT_TREE_LABEL, group_id)?)
+    }
+}
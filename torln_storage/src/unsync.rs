@@ -0,0 +1,39 @@
+//! A lock for single-threaded targets such as wasm without threads.
+//!
+//! It has the `read`/`write` interface of [`std::sync::RwLock`], so that the
+//! storage works the same with either lock, but it neither synchronizes nor
+//! gets poisoned. Both methods always return `Ok`.
+
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    sync::LockResult,
+};
+
+/// A [`RefCell`] with the interface of [`std::sync::RwLock`].
+#[derive(Debug, Default)]
+pub struct UnsyncLock<T>(RefCell<T>);
+
+impl<T> UnsyncLock<T> {
+    pub fn new(value: T) -> Self {
+        Self(RefCell::new(value))
+    }
+
+    /// Borrow the value immutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is borrowed mutably, where an `RwLock` would
+    /// deadlock.
+    pub fn read(&self) -> LockResult<Ref<'_, T>> {
+        Ok(self.0.borrow())
+    }
+
+    /// Borrow the value mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is borrowed, where an `RwLock` would deadlock.
+    pub fn write(&self) -> LockResult<RefMut<'_, T>> {
+        Ok(self.0.borrow_mut())
+    }
+}